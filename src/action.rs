@@ -0,0 +1,580 @@
+use crate::api::{ChatEdit, ChatRight, ClearHistory, NewPoll, PasswordEdit, PrivacyKey, PrivacyValue, ProfileEdit};
+use crate::app::Mode;
+use crate::compose::PostOption;
+use crate::help::Topic;
+use crate::popup::{Motion, Popup};
+use crate::render::TextFormat;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use grammers_session::PackedChat;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Everything the user can ask the app to do. Keys and `:` commands are
+/// translated into actions, which are then applied by `App::dispatch`.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Quit,
+    /// Move down/up in the focused pane
+    Down,
+    Up,
+    FocusChat,
+    FocusDialogs,
+    /// Select the message the selected one replies to, loading the history
+    /// up to it if needed
+    JumpToReply,
+    /// Focus the chat, or in it open where the selected message was
+    /// forwarded from
+    Open,
+    /// Make the dialog list wider by this many percent of the screen
+    ResizeDialogs(i16),
+    /// Open the current chat in a second window, or close the other window
+    ToggleSplit,
+    /// Close the other window
+    CloseSplit,
+    /// Move to the other window of the split view
+    OtherWindow,
+    /// Add the current chat to the tabline
+    OpenTab,
+    CloseTab,
+    /// Take back the last local change, like closing a tab
+    Undo,
+    /// Leave the selected dialog out of the list
+    HideDialog,
+    /// List the hidden dialogs again
+    UnhideDialogs,
+    /// Empty the compose box of the current chat, with its attachments
+    ClearDraft,
+    NextTab,
+    PrevTab,
+    /// Go back/forward in the list of opened chats
+    JumpBack,
+    JumpForward,
+    NextDialog,
+    PrevDialog,
+    /// List only dialogs with unread messages, or all again
+    ToggleUnreadOnly,
+    /// Mark every listed dialog as read, once confirmed
+    CatchUp,
+    MarkRead(Vec<PackedChat>),
+    /// `:` command which isn't built in, for the plugin adding it
+    PluginCommand { name: String, args: String },
+    /// Delete the history of the current chat, once confirmed
+    ClearHistory(ClearHistory),
+    DeleteHistory(ClearHistory),
+    /// Select a newer/older message in the chat view
+    NextMessage,
+    PrevMessage,
+    SetMode(Mode),
+    /// Add the selected message to the visual selection, or remove it
+    ToggleMark,
+    /// Pick a chat to forward the messages selected in visual mode to
+    ForwardSelected,
+    /// Delete the messages selected in visual mode, once confirmed
+    DeleteSelected,
+    /// Type where to save the file of the selected message on the command
+    /// line, its chat's download directory to begin with
+    Save,
+    /// Save the file of the selected message, into its chat's download
+    /// directory unless given a path
+    SaveFile(Option<PathBuf>),
+    /// Open the saved file of the selected message with the program for
+    /// its type, or the directory it's in
+    OpenFile { reveal: bool },
+    /// Complete the path typed on the command line
+    CompletePath,
+    /// Copy the photo of the selected message to the clipboard, downloading
+    /// it first if needed
+    CopyImage,
+    /// Copy the messages selected in visual mode as text
+    CopySelected(TextFormat),
+    /// Copy the code blocks of the selected message
+    CopyCode,
+    /// Show a page of the built-in help
+    Help(Topic),
+    /// Scroll a pager popup by this many lines, down if positive
+    PopupScroll(i32),
+    /// Type the file or directory to apply the diff of the selected message
+    /// to on the command line
+    ApplyPatch,
+    /// Apply the diff of the selected message, its text or a patch file,
+    /// to a file or the files under a directory
+    ApplyPatchTo(PathBuf),
+    /// Export the video note of the selected message as MP4 to a file or
+    /// directory, or else its chat's download directory
+    ExportVideoNote(Option<PathBuf>),
+    /// Type the file to write the messages selected in visual mode to on
+    /// the command line
+    StartExport,
+    /// Write the messages picked with `StartExport` to a file, as Markdown
+    /// if it ends with `.md`
+    Export(PathBuf),
+    ForwardMessages { from: PackedChat, ids: Vec<i32>, to: PackedChat },
+    DeleteMessages { chat: PackedChat, ids: Vec<i32> },
+
+    /// Text editing in the compose box or the command line
+    Insert(char),
+    DeleteBack,
+    CursorLeft,
+    CursorRight,
+    /// Enter: send the draft or run the command line
+    Submit,
+
+    /// Send the draft of the current chat
+    Send,
+    /// Change how the draft is posted to a channel
+    PostOption(PostOption),
+    /// Stage a file in the attachment tray of the current chat
+    Attach(PathBuf),
+    /// Text pasted into the terminal
+    Paste(String),
+    /// Offer to attach the image in the clipboard as a photo
+    PasteImage,
+    /// Remove an attachment from the tray by index
+    Detach(usize),
+    /// Set the caption of an attachment by index
+    Caption(usize, String),
+    /// Reply to the selected message
+    Reply,
+    /// Select a part of the selected message to quote in a reply to it
+    QuoteReply,
+    /// Move the cursor of the quote being selected
+    QuoteMotion(Motion),
+    /// Stop replying with the draft of the current chat
+    CancelReply,
+    /// Toggle the link preview of the draft in the current chat
+    ToggleLinkPreview,
+    /// Send a static location to the current chat
+    SendLocation { lat: f64, lon: f64 },
+    /// Send a poll to the current chat
+    SendPoll(NewPoll),
+    /// Play the selected video message with the external player
+    Play,
+    /// Translate the selected message, to the configured language unless given
+    Translate(Option<String>),
+    /// Open the discussion group of the current channel, or its channel
+    LinkedChat,
+    /// Show the comments on the selected channel post, or the replies to
+    /// the selected message
+    OpenComments,
+    /// Mark the current dialog as unread, or clear the mark
+    ToggleUnreadMark,
+    /// Show my profile
+    Profile,
+    /// Change my name, bio or username
+    EditProfile(ProfileEdit),
+    /// Show my privacy settings
+    Privacy,
+    /// Change who a privacy setting allows
+    SetPrivacy { key: PrivacyKey, value: PrivacyValue },
+    /// Show my 2FA settings
+    Password,
+    /// Change my 2FA settings
+    EditPassword(PasswordEdit),
+    /// Show the profile of the sender of the selected message, or of who
+    /// it was forwarded from
+    Whois,
+    /// Open the chat with a username, without the @
+    OpenUsername(String),
+    /// Show my active sessions and their policy
+    Sessions,
+    /// Change after how many days of inactivity sessions are terminated
+    SetSessionTtl(i32),
+    /// Log out another session of my account, by its hash
+    TerminateSession(i64),
+    /// Report the selected message as spam, or the user of a private
+    /// chat if none is selected
+    ReportSpam,
+    /// Ask to ban the sender of the selected message from the current
+    /// supergroup and delete all their messages
+    Ban,
+    /// Ban a user from the current supergroup and delete their messages
+    DeleteAndBan(PackedChat),
+    /// Change the title or description of the current group or channel
+    EditChat(ChatEdit),
+    /// Upload the photo of the current group or channel
+    SetChatPhoto(PathBuf),
+    /// Allow or deny the members of the current group something
+    SetChatRight { right: ChatRight, allowed: bool },
+    /// Leave the current group or channel, once confirmed
+    LeaveChat,
+    Leave(PackedChat),
+    /// Show what the members of the current group may do
+    ChatPermissions,
+    /// Delete the messages of the current chat this long after they're
+    /// sent from now on, or never
+    SetAutoDelete(Option<Duration>),
+    /// List the pending requests to join the current chat
+    JoinRequests,
+    /// Start recording a macro into the register named next, or stop
+    /// recording
+    RecordMacro,
+    /// Replay the macro in the register named next
+    ReplayMacro,
+    /// The register named after `q` or `@`, none if another key was pressed
+    MacroRegister(Option<char>),
+    /// Approve or decline the request selected in the join requests
+    HideJoinRequest { approve: bool },
+    /// Pin the selected message, notifying the members if `notify`
+    Pin { notify: bool },
+    /// Unpin the selected message, or the pinned one if none is selected
+    Unpin,
+    /// Expand or collapse the replies to the selected message under it
+    ToggleThread,
+    /// Show the raw details of the selected message
+    MessageInfo,
+    /// Show message counts by sender, hour and kind of the current chat
+    ChatStats,
+    /// Show the recent admin log of the current channel
+    AdminLog,
+    /// Show the size of the media cache
+    CacheStats,
+    /// Delete everything in the media cache
+    CacheClear,
+    /// Turn do not disturb on until turned off, or off if it's on
+    ToggleDnd,
+    /// Turn do not disturb on, for a while if given
+    Dnd(Option<Duration>),
+    DndOff,
+    /// Hide or show the text of messages, before sharing the screen
+    ToggleRedact,
+    /// Show or hide the debug overlay with frame times, queues and memory
+    ToggleMetrics,
+    /// Abort the most recently started upload or download
+    CancelTransfer,
+    /// Hide the messages of the current chat not matching, or show them all
+    /// again
+    Filter(Option<String>),
+    /// Type a filter on the command line
+    StartFilter,
+    /// Load the dialog list again, keeping the open chats
+    RefreshDialogs,
+    /// Send a message queued in the outbox of the current chat again, by
+    /// index, or all of them
+    RetryQueued(Option<usize>),
+    /// Drop a message queued in the outbox of the current chat, by index,
+    /// or all of them
+    DiscardQueued(Option<usize>),
+    /// Hide the new story indicator of the selected dialog
+    DismissStory,
+    /// Open the emoji picker to insert one into the compose box
+    EmojiPicker,
+    /// Pick a chat to open by name, the most used ones first
+    SwitchChat,
+    /// Open the picker of my contacts to share one into the current chat
+    ShareContact,
+
+    PopupUp,
+    PopupDown,
+    /// Enter in a popup: confirm the selected item
+    PopupSelect,
+    PopupClose,
+}
+
+impl Action {
+    /// Whether the action changes something on Telegram, which read-only
+    /// mode refuses.
+    pub fn mutates(&self) -> bool {
+        matches!(
+            self,
+            Action::Send
+                | Action::SendLocation { .. }
+                | Action::SendPoll(_)
+                | Action::ShareContact
+                | Action::ToggleUnreadMark
+                | Action::CatchUp
+                | Action::MarkRead(_)
+                | Action::EditProfile(_)
+                | Action::SetPrivacy { .. }
+                | Action::EditPassword(_)
+                | Action::SetSessionTtl(_)
+                | Action::TerminateSession(_)
+                | Action::ReportSpam
+                | Action::Ban
+                | Action::DeleteAndBan(_)
+                | Action::EditChat(_)
+                | Action::SetChatPhoto(_)
+                | Action::SetChatRight { .. }
+                | Action::HideJoinRequest { .. }
+                | Action::LeaveChat
+                | Action::SetAutoDelete(_)
+                | Action::Leave(_)
+                | Action::Pin { .. }
+                | Action::Unpin
+                | Action::ClearHistory(_)
+                | Action::DeleteHistory(_)
+                | Action::ForwardSelected
+                | Action::DeleteSelected
+                | Action::ForwardMessages { .. }
+                | Action::DeleteMessages { .. }
+                | Action::RetryQueued(_)
+        )
+    }
+}
+
+/// Lines a pager scrolls by a page
+const PAGE_SCROLL: i32 = 10;
+
+/// A key of some modes and what it does. [`from_key`] looks keys up here
+/// and `:help modes` lists them, so the two can't drift apart.
+pub struct Binding {
+    pub modes: &'static [Mode],
+    /// Key presses doing it, with the modifiers they need or none for any
+    pub keys: &'static [(Option<KeyModifiers>, KeyCode)],
+    action: Action,
+    pub about: &'static str,
+}
+
+const fn bind(
+    modes: &'static [Mode],
+    keys: &'static [(Option<KeyModifiers>, KeyCode)],
+    action: Action,
+    about: &'static str,
+) -> Binding {
+    Binding { modes, keys, action, about }
+}
+
+const NONE: Option<KeyModifiers> = Some(KeyModifiers::NONE);
+const CTRL: Option<KeyModifiers> = Some(KeyModifiers::CONTROL);
+const ANY: Option<KeyModifiers> = None;
+
+const NORMAL: &[Mode] = &[Mode::Normal];
+const VISUAL: &[Mode] = &[Mode::Visual];
+const INSERT: &[Mode] = &[Mode::Insert];
+const COMMAND: &[Mode] = &[Mode::Command];
+const TYPING: &[Mode] = &[Mode::Insert, Mode::Command];
+
+/// Keys of the modes, the first binding of a key in a mode wins. Ctrl-c
+/// quits in every mode, and in insert and command mode other keys type.
+pub const BINDINGS: &[Binding] = &[
+    bind(NORMAL, &[(NONE, KeyCode::Char('q'))], Action::RecordMacro, "record a macro into a register, or stop"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('@'))], Action::ReplayMacro, "play the macro in a register"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('j')), (NONE, KeyCode::Down)], Action::Down, "move down"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('k')), (NONE, KeyCode::Up)], Action::Up, "move up"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('l'))], Action::FocusChat, "focus the chat"),
+    bind(NORMAL, &[(NONE, KeyCode::Enter)], Action::Open, "open the chat, or where the message was forwarded from"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('h')), (NONE, KeyCode::Esc)], Action::FocusDialogs, "focus the dialogs"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('p'))], Action::Play, "play the video of the selected message"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('t'))], Action::Translate(None), "translate the selected message"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('i'))], Action::SetMode(Mode::Insert), "write the draft"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('v'))], Action::SetMode(Mode::Visual), "select messages"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('r'))], Action::Reply, "reply to the selected message"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('Q'))], Action::QuoteReply, "reply quoting part of the selected message"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('c'))], Action::OpenComments, "show the comments on the selected post"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('o'))], Action::JumpToReply, "go to the message the selected one replies to"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('e'))], Action::ToggleThread, "show the replies to the message, or hide them"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('u'))], Action::Undo, "take back the last local change"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('D'))], Action::LinkedChat, "open the discussion group of the channel"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('L'))], Action::AdminLog, "show the recent actions of the admins"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('I'))], Action::MessageInfo, "show the details of the selected message"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('|'))], Action::ToggleSplit, "show another chat next to this one, or stop"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('>'))], Action::ResizeDialogs(5), "make the dialogs wider"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('<'))], Action::ResizeDialogs(-5), "make the dialogs narrower"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('T'))], Action::OpenTab, "open the current chat in a tab"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('x'))], Action::CloseTab, "close the current tab"),
+    bind(NORMAL, &[(ANY, KeyCode::Char(']'))], Action::NextTab, "go to the next tab"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('['))], Action::PrevTab, "go to the previous tab"),
+    bind(NORMAL, &[(CTRL, KeyCode::Char('w'))], Action::OtherWindow, "move to the other window"),
+    bind(NORMAL, &[(CTRL, KeyCode::Char('o'))], Action::JumpBack, "go back in the opened chats"),
+    bind(NORMAL, &[(CTRL, KeyCode::Char('p'))], Action::SwitchChat, "jump to a chat by typing part of its name"),
+    bind(
+        NORMAL,
+        &[(CTRL, KeyCode::Char('i')), (ANY, KeyCode::Tab)],
+        Action::JumpForward,
+        "go forward in the opened chats",
+    ),
+    bind(NORMAL, &[(ANY, KeyCode::Char('S'))], Action::DismissStory, "dismiss the story of the selected chat"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('X'))], Action::CancelTransfer, "cancel the newest upload or download"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('N'))], Action::ToggleDnd, "don't disturb, or stop"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('R'))], Action::ToggleRedact, "hide the text of messages, or show it again"),
+    bind(NORMAL, &[(ANY, KeyCode::F(12))], Action::ToggleMetrics, "show the debug overlay, or hide it"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('U'))], Action::ToggleUnreadMark, "set or clear the unread mark of the dialog"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('P'))], Action::Pin { notify: false }, "pin the selected message"),
+    bind(NORMAL, &[(ANY, KeyCode::Char(':'))], Action::SetMode(Mode::Command), "run a command"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('/'))], Action::StartFilter, "show only the messages matching a pattern"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('Y'))], Action::CopyImage, "copy the photo of the selected message"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('y'))], Action::CopyCode, "copy the code blocks of the selected message"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('A'))], Action::ApplyPatch, "apply the diff of the selected message"),
+    bind(NORMAL, &[(NONE, KeyCode::Char('s'))], Action::Save, "save the file of the selected message"),
+    bind(NORMAL, &[(ANY, KeyCode::Char('O'))], Action::OpenFile { reveal: false }, "open the saved file"),
+    bind(VISUAL, &[(ANY, KeyCode::Esc), (ANY, KeyCode::Char('v'))], Action::SetMode(Mode::Normal), "go back"),
+    bind(VISUAL, &[(NONE, KeyCode::Char('j')), (NONE, KeyCode::Down)], Action::Down, "extend the selection down"),
+    bind(VISUAL, &[(NONE, KeyCode::Char('k')), (NONE, KeyCode::Up)], Action::Up, "extend the selection up"),
+    bind(VISUAL, &[(NONE, KeyCode::Char(' '))], Action::ToggleMark, "mark the selected message, or unmark it"),
+    bind(VISUAL, &[(NONE, KeyCode::Char('f'))], Action::ForwardSelected, "forward the messages"),
+    bind(VISUAL, &[(NONE, KeyCode::Char('d'))], Action::DeleteSelected, "delete the messages for everyone"),
+    bind(VISUAL, &[(NONE, KeyCode::Char('y'))], Action::CopySelected(TextFormat::Plain), "copy the messages"),
+    bind(VISUAL, &[(ANY, KeyCode::Char('Y'))], Action::CopySelected(TextFormat::Markdown), "copy them as Markdown"),
+    bind(VISUAL, &[(NONE, KeyCode::Char('w'))], Action::StartExport, "write the messages to a file"),
+    bind(INSERT, &[(CTRL, KeyCode::Char('l'))], Action::ToggleLinkPreview, "turn the link preview off or on"),
+    bind(INSERT, &[(CTRL, KeyCode::Char('e'))], Action::EmojiPicker, "pick an emoji to type"),
+    bind(INSERT, &[(CTRL, KeyCode::Char('x'))], Action::CancelReply, "cancel the reply"),
+    bind(INSERT, &[(CTRL, KeyCode::Char('v'))], Action::PasteImage, "paste an image"),
+    bind(INSERT, &[(CTRL, KeyCode::Char('u'))], Action::ClearDraft, "clear the draft"),
+    bind(COMMAND, &[(ANY, KeyCode::Tab)], Action::CompletePath, "complete a path"),
+    bind(TYPING, &[(ANY, KeyCode::Esc)], Action::SetMode(Mode::Normal), "go back"),
+    bind(TYPING, &[(ANY, KeyCode::Enter)], Action::Submit, "send the draft, or run the command"),
+    bind(TYPING, &[(ANY, KeyCode::Backspace)], Action::DeleteBack, "delete the character before the cursor"),
+    bind(TYPING, &[(ANY, KeyCode::Left)], Action::CursorLeft, "move the cursor left"),
+    bind(TYPING, &[(ANY, KeyCode::Right)], Action::CursorRight, "move the cursor right"),
+];
+
+impl Binding {
+    fn matches(&self, mode: Mode, key: KeyEvent) -> bool {
+        self.modes.contains(&mode)
+            && self.keys.iter().any(|&(modifiers, code)| {
+                code == key.code && modifiers.is_none_or(|modifiers| modifiers == key.modifiers)
+            })
+    }
+
+    /// The keys as `:help modes` shows them, like `k, Up`.
+    pub fn key_names(&self) -> String {
+        let names: Vec<_> = self
+            .keys
+            .iter()
+            .map(|&(modifiers, code)| {
+                let name = match code {
+                    KeyCode::Char(' ') => "Space".to_string(),
+                    KeyCode::Char(c) => c.to_string(),
+                    KeyCode::F(n) => format!("F{}", n),
+                    code => format!("{:?}", code),
+                };
+                match modifiers {
+                    Some(KeyModifiers::CONTROL) => format!("Ctrl-{}", name),
+                    _ => name,
+                }
+            })
+            .collect();
+        names.join(", ")
+    }
+}
+
+/// Map a key press to an action according to the current mode.
+pub fn from_key(mode: Mode, key: KeyEvent) -> Option<Action> {
+    if let (KeyModifiers::CONTROL, KeyCode::Char('c')) = (key.modifiers, key.code) {
+        return Some(Action::Quit);
+    }
+    if let Some(binding) = BINDINGS.iter().find(|binding| binding.matches(mode, key)) {
+        return Some(binding.action.clone());
+    }
+    match (mode, key.modifiers, key.code) {
+        (Mode::Insert | Mode::Command, KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            Some(Action::Insert(c))
+        }
+        _ => None,
+    }
+}
+
+/// Map the key naming a register after `q` or `@` to an action.
+pub fn from_register_key(key: KeyEvent) -> Option<Action> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => Some(Action::MacroRegister(Some(c))),
+        _ => Some(Action::MacroRegister(None)),
+    }
+}
+
+/// Map a key press to an action while a popup is open.
+pub fn from_popup_key(popup: &Popup, key: KeyEvent) -> Option<Action> {
+    match popup {
+        Popup::ShareContact(_)
+        | Popup::Comments(_)
+        | Popup::AdminLog(_)
+        | Popup::Mention(_)
+        | Popup::Emoji(_)
+        | Popup::Forward(_)
+        | Popup::Switch(_) => from_picker_key(key),
+        Popup::Privacy(_) | Popup::Password(_) | Popup::Sessions(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Esc | KeyCode::Char('q')) => Some(Action::PopupClose),
+            (_, KeyCode::Enter) => Some(Action::PopupSelect),
+            (_, KeyCode::Up | KeyCode::Char('k')) => Some(Action::PopupUp),
+            (_, KeyCode::Down | KeyCode::Char('j')) => Some(Action::PopupDown),
+            _ => None,
+        },
+        Popup::JoinRequests(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Esc | KeyCode::Char('q')) => Some(Action::PopupClose),
+            (_, KeyCode::Enter | KeyCode::Char('a')) => Some(Action::HideJoinRequest { approve: true }),
+            (_, KeyCode::Char('d')) => Some(Action::HideJoinRequest { approve: false }),
+            (_, KeyCode::Up | KeyCode::Char('k')) => Some(Action::PopupUp),
+            (_, KeyCode::Down | KeyCode::Char('j')) => Some(Action::PopupDown),
+            _ => None,
+        },
+        Popup::Quote(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Esc | KeyCode::Char('q')) => Some(Action::PopupClose),
+            (_, KeyCode::Enter) => Some(Action::PopupSelect),
+            (_, KeyCode::Left | KeyCode::Char('h')) => Some(Action::QuoteMotion(Motion::Left)),
+            (_, KeyCode::Right | KeyCode::Char('l')) => Some(Action::QuoteMotion(Motion::Right)),
+            (_, KeyCode::Char('b')) => Some(Action::QuoteMotion(Motion::WordLeft)),
+            (_, KeyCode::Char('w')) => Some(Action::QuoteMotion(Motion::WordRight)),
+            (_, KeyCode::Char('0') | KeyCode::Home) => Some(Action::QuoteMotion(Motion::LineStart)),
+            (_, KeyCode::Char('$') | KeyCode::End) => Some(Action::QuoteMotion(Motion::LineEnd)),
+            (_, KeyCode::Char('o')) => Some(Action::QuoteMotion(Motion::SwapEnds)),
+            _ => None,
+        },
+        Popup::Stats(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) => Some(Action::PopupClose),
+            _ => None,
+        },
+        Popup::Help(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (KeyModifiers::CONTROL, KeyCode::Char('d')) | (_, KeyCode::PageDown | KeyCode::Char(' ')) => {
+                Some(Action::PopupScroll(PAGE_SCROLL))
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('u')) | (_, KeyCode::PageUp) => {
+                Some(Action::PopupScroll(-PAGE_SCROLL))
+            }
+            (_, KeyCode::Char('j') | KeyCode::Down) => Some(Action::PopupScroll(1)),
+            (_, KeyCode::Char('k') | KeyCode::Up) => Some(Action::PopupScroll(-1)),
+            (_, KeyCode::Char('g') | KeyCode::Home) => Some(Action::PopupScroll(i32::MIN)),
+            (_, KeyCode::Char('G') | KeyCode::End) => Some(Action::PopupScroll(i32::MAX)),
+            (_, KeyCode::Esc | KeyCode::Char('q')) => Some(Action::PopupClose),
+            _ => None,
+        },
+        Popup::Info(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('I')) => {
+                Some(Action::PopupClose)
+            }
+            _ => None,
+        },
+        // can't be dismissed, nothing works until the session is unlocked
+        Popup::Passphrase(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Enter) => Some(Action::PopupSelect),
+            (_, KeyCode::Backspace) => Some(Action::DeleteBack),
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => Some(Action::Insert(c)),
+            _ => None,
+        },
+        Popup::PasswordForm(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Esc) => Some(Action::PopupClose),
+            (_, KeyCode::Enter) => Some(Action::PopupSelect),
+            (_, KeyCode::Backspace) => Some(Action::DeleteBack),
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => Some(Action::Insert(c)),
+            _ => None,
+        },
+        Popup::Confirm(_) => match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+            (_, KeyCode::Char('y') | KeyCode::Enter) => Some(Action::PopupSelect),
+            (_, KeyCode::Char('n') | KeyCode::Esc) => Some(Action::PopupClose),
+            _ => None,
+        },
+    }
+}
+
+/// Keys of filterable pickers: typing filters, arrows move.
+fn from_picker_key(key: KeyEvent) -> Option<Action> {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('c')) => Some(Action::Quit),
+        (_, KeyCode::Esc) => Some(Action::PopupClose),
+        (_, KeyCode::Enter) => Some(Action::PopupSelect),
+        (_, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => Some(Action::PopupUp),
+        (_, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => Some(Action::PopupDown),
+        (_, KeyCode::Backspace) => Some(Action::DeleteBack),
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => Some(Action::Insert(c)),
+        _ => None,
+    }
+}
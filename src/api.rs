@@ -1,56 +1,2875 @@
-use anyhow::Result;
-use grammers_client::{Client, Config, SignInError};
-use grammers_session::Session;
+use crate::compose::{Attachment, PostOptions, ReplyTo};
+use crate::credentials::Credentials;
+use crate::transfer::{Progress, ProgressReader, TransferId};
+use crate::error::{Result, Tg9Error};
+use crate::events::EventSender;
+use crate::folders::Folder;
+use crate::metrics;
+use crate::outbox::OutgoingMessage;
+use crate::secrets::{self, Secret};
+use crate::session;
+use crate::two_factor;
+use crate::usernames::UsernameCache;
+use grammers_client::grammers_tl_types as tl;
+use grammers_client::types::{Dialog, Downloadable, Media, Message, MessageDeletion, MessageIter};
+use grammers_client::{Client, Config, InputMedia, InputMessage, InvocationError, Update};
+use grammers_session::{PackedChat, Session};
+use chrono::{DateTime, Utc};
+use futures::future::{AbortHandle, AbortRegistration, Abortable};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
 
-use std::io::{self, BufRead};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
 
-static API_ID: &str = env!("TG9_API_ID");
-static API_HASH: &str = env!("TG9_API_HASH");
+/// Built in unless it's typed into the setup wizard instead
+static API_ID: Option<&str> = option_env!("TG9_API_ID");
+/// Built in unless it's kept in the keyring or typed into the setup
+/// wizard instead
+static API_HASH: Option<&str> = option_env!("TG9_API_HASH");
 
-fn read_prompt(prompt: &str) -> String {
-    println!("{}", prompt);
-    let mut line = String::new();
-    let stdin = io::stdin();
-    stdin.lock().read_line(&mut line).unwrap();
-    line
+pub fn api_id() -> Result<i32> {
+    match API_ID {
+        Some(id) => id
+            .parse()
+            .map_err(|_| Tg9Error::Auth(format!("the built in API ID {:?} isn't a number", id))),
+        None => Credentials::load()
+            .api_id
+            .ok_or_else(|| Tg9Error::Auth("no API ID, run tg9 to set it up".into())),
+    }
+}
+
+pub fn api_hash() -> Result<String> {
+    secrets::get(Secret::ApiHash)
+        .or_else(|| API_HASH.map(String::from))
+        .or_else(|| Credentials::load().api_hash)
+        .ok_or_else(|| Tg9Error::Auth("no API hash, run tg9 to set it up".into()))
+}
+
+/// Where older versions saved the session, in the working directory
+const SESSION_FILE_OLD: &str = "hello-world.session";
+
+/// The session file, in the platform data directory (`%LOCALAPPDATA%` on
+/// Windows). One left in the working directory by older versions is moved
+/// there.
+pub fn session_path() -> PathBuf {
+    let Some(dir) = dirs::data_local_dir().map(|d| d.join("tg9")) else {
+        return PathBuf::from(SESSION_FILE_OLD);
+    };
+    let path = dir.join("session");
+    let old = Path::new(SESSION_FILE_OLD);
+    if !path.exists() && old.exists() {
+        // renaming fails across file systems, the old one keeps working then
+        if std::fs::create_dir_all(&dir).and_then(|_| std::fs::rename(old, &path)).is_err() {
+            return old.to_path_buf();
+        }
+    }
+    path
 }
 
-pub async fn login() -> Result<Client> {
-    let client = Client::connect(Config {
-        session: Session::load_file_or_create("hello-world.session").unwrap(),
-        api_id: API_ID.parse().expect("API ID should be valid i32"),
-        api_hash: API_HASH.to_string(),
+/// Whether a previous login saved its session, so connecting needs no prompts.
+pub fn has_session() -> bool {
+    session_path().exists()
+}
+
+pub async fn connect(session: Session) -> Result<Client> {
+    Client::connect(Config {
+        session,
+        api_id: api_id()?,
+        api_hash: api_hash()?,
         params: Default::default(),
     })
-    .await?;
+    .await
+    .map_err(|e| Tg9Error::Network(e.to_string()))
+}
+
+/// Wait between tries to connect, or to get updates again, while offline
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Connect with a saved session, in the background while the UI is up.
+/// An encrypted session is unlocked with the passphrases typed into the UI
+/// until one is right. Without a network it keeps trying, none means the
+/// session can't be used at all.
+pub async fn connect_saved(
+    tx: &EventSender,
+    mut passphrases: mpsc::UnboundedReceiver<String>,
+) -> Option<Client> {
+    let path = &session_path();
+    let stored = secrets::get(Secret::Passphrase);
+    let mut passphrase = None;
+    let session = if session::is_encrypted(path) {
+        // the passphrase in the keyring, if any, is tried before asking
+        let mut next = stored;
+        loop {
+            let tried = match next.take() {
+                Some(passphrase) => passphrase,
+                None => {
+                    tx.send(ApiEvent::SessionLocked).unwrap();
+                    passphrases.recv().await?
+                }
+            };
+            match session::load(path, Some(&tried)) {
+                Ok(session) => {
+                    passphrase = Some(tried);
+                    break Ok(session);
+                }
+                Err(Tg9Error::WrongPassphrase) => {
+                    tx.send(ApiEvent::Notice("wrong passphrase".into())).unwrap();
+                }
+                Err(e) => break Err(e),
+            }
+        }
+    } else {
+        session::load(path, None)
+    };
+    let mut session = match session {
+        Ok(session) => session,
+        Err(error) => {
+            tx.send(ApiEvent::Error { doing: "load the session", error }).unwrap();
+            return None;
+        }
+    };
+    loop {
+        let connected = match connect(session).await {
+            Ok(client) => match client.is_authorized().await {
+                Ok(true) => Ok(client),
+                Ok(false) => Err(Tg9Error::Auth("the saved session was logged out".into())),
+                Err(e) => Err(e.into()),
+            },
+            Err(e) => Err(e),
+        };
+        match connected {
+            Ok(client) => return Some(client),
+            Err(Tg9Error::Network(e)) => {
+                let secs = RECONNECT_INTERVAL.as_secs();
+                tx.send(ApiEvent::Notice(format!("can't connect ({}), trying again in {}s", e, secs))).unwrap();
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+            }
+            Err(error) => {
+                tx.send(ApiEvent::Error { doing: "connect", error }).unwrap();
+                return None;
+            }
+        }
+        // connecting took the session, so it's read again
+        session = match session::load(path, passphrase.as_deref()) {
+            Ok(session) => session,
+            Err(error) => {
+                tx.send(ApiEvent::Error { doing: "load the session", error }).unwrap();
+                return None;
+            }
+        };
+    }
+}
+
+/// Contact as shared into a chat.
+#[derive(Debug, Clone)]
+pub struct SharedContact {
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+}
+
+impl SharedContact {
+    pub fn name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name).trim().to_string()
+    }
+}
+
+/// Member of a group who can be mentioned.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub user: PackedChat,
+    pub name: String,
+    pub username: Option<String>,
+}
+
+/// View and forward counters of a channel post.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Views {
+    pub views: Option<i32>,
+    pub forwards: Option<i32>,
+}
+
+/// Message of a comment thread, with its sender's name already resolved.
+#[derive(Debug, Clone)]
+pub struct ThreadMessage {
+    pub sender: String,
+    pub text: String,
+}
+
+/// Entry of a channel's admin log, with the name of the user who did it.
+#[derive(Debug, Clone)]
+pub struct AdminLogEntry {
+    pub date: DateTime<Utc>,
+    pub user: String,
+    pub action: tl::enums::ChannelAdminLogEventAction,
+}
+
+/// Someone asking to join a group or channel, with a profile to judge by.
+#[derive(Debug, Clone)]
+pub struct JoinRequest {
+    pub user: tl::types::InputUser,
+    pub name: String,
+    pub username: Option<String>,
+    pub date: DateTime<Utc>,
+    /// Why they want to join, if the link asked
+    pub about: Option<String>,
+}
+
+/// Jobs for api client worker to perform
+#[derive(Debug)]
+pub enum ApiJob {
+    /// Load a part of chat messages
+    LoadMessages(PackedChat),
+
+    /// Load a page of messages older than `offset_id`, which were dropped
+    /// to keep within `history_max`
+    LoadOlder { chat: PackedChat, offset_id: i32 },
+
+    /// Load messages older than `offset_id` until a bit past `message_id`
+    LoadUntil { chat: PackedChat, offset_id: i32, message_id: i32 },
+
+    /// Load messages replied to which aren't in the loaded history
+    LoadReplies { chat: PackedChat, ids: Vec<i32> },
+
+    /// Initial loading of all dialogs
+    LoadDialogs,
+
+    /// Load all dialogs again, to merge them with the ones shown
+    RefreshDialogs,
+
+    /// Load details of my own account
+    LoadAccount,
+
+    /// Load my chat folders
+    LoadFolders,
+
+    /// Load my profile, to show it
+    LoadProfile,
+
+    /// Change my profile, then load it again
+    EditProfile(ProfileEdit),
+
+    /// Load my privacy settings
+    LoadPrivacy,
+
+    /// Change a privacy setting keeping its exceptions, then load them again
+    SetPrivacy { key: PrivacyKey, value: PrivacyValue },
+
+    /// Load my 2FA settings
+    LoadPassword,
+
+    /// Change my 2FA settings, then load them again
+    EditPassword(PasswordEdit),
+
+    /// Load the profile of a user
+    LoadUserProfile(PackedChat),
 
-    if !client.is_authorized().await.unwrap() {
-        let phone = read_prompt("Phone number:");
+    /// Find the chat with a username, without the @
+    ResolveUsername(String),
 
-        let token = client.request_login_code(&phone).await.unwrap();
-        let code = read_prompt("Code: ");
+    /// Load my active sessions
+    LoadSessions,
+
+    /// Change after how many days of inactivity sessions are terminated
+    SetSessionTtl(i32),
+
+    /// Log out another session of my account, by its hash
+    TerminateSession(i64),
+
+    /// Send a message with optional attachments (sent as albums)
+    SendMessage {
+        id: TransferId,
+        chat: PackedChat,
+        message: OutgoingMessage,
+    },
+
+    /// Send media that isn't a file: locations, contacts, etc.
+    SendMedia(PackedChat, tl::enums::InputMedia),
+
+    /// Load my contacts list
+    LoadContacts,
+
+    /// Load the members of a group to mention them
+    LoadMembers(PackedChat),
+
+    /// Download media of a message to a file
+    Download {
+        id: TransferId,
+        media: Media,
+        path: PathBuf,
+        then: AfterDownload,
+    },
+
+    /// Abort an upload or download
+    CancelTransfer(TransferId),
+
+    /// Translate a message with Telegram, or the external command if given
+    Translate {
+        chat: PackedChat,
+        message_id: i32,
+        text: String,
+        to_lang: String,
+        command: Option<String>,
+    },
+
+    /// Find the discussion group of a channel, or the channel of a group
+    LoadLinkedChat(PackedChat),
+
+    /// Load the comments on a channel post
+    LoadComments { chat: PackedChat, message_id: i32 },
+
+    /// Mark chats as read, one after the other
+    MarkRead(Vec<PackedChat>),
+
+    /// Delete the history of a chat
+    ClearHistory { chat: PackedChat, scope: ClearHistory },
+
+    /// Forward messages of a chat to another, in batches
+    ForwardMessages { from: PackedChat, ids: Vec<i32>, to: PackedChat },
+
+    /// Delete messages of a chat for everyone, in batches
+    DeleteMessages { chat: PackedChat, ids: Vec<i32> },
+
+    /// Set or clear the unread mark of a dialog
+    MarkUnread { chat: PackedChat, unread: bool },
+
+    /// Load the slow mode settings of a group
+    LoadSlowMode(PackedChat),
+
+    /// Report a message as spam, or the user of a private chat without one
+    ReportSpam { chat: PackedChat, message_id: Option<i32> },
+
+    /// Delete all messages of a user in a supergroup and ban them
+    DeleteAndBan { chat: PackedChat, user: PackedChat },
+
+    /// Load the pinned message of a chat, for its pinned bar
+    LoadPinned(PackedChat),
+
+    /// Pin or unpin a message, pinning notifies the members if `notify`
+    Pin { chat: PackedChat, message_id: i32, notify: bool, unpin: bool },
+
+    /// Load recent admin log entries of a channel I administer
+    LoadAdminLog(PackedChat),
+
+    /// Refresh the view and forward counters of channel posts
+    LoadViews { chat: PackedChat, message_ids: Vec<i32> },
+
+    /// Change the title, description or permissions of a group or channel
+    EditChat { chat: PackedChat, edit: ChatEdit },
+
+    /// Upload a photo and make it the photo of a group or channel
+    SetChatPhoto { id: TransferId, chat: PackedChat, path: PathBuf },
+
+    /// Load the pending requests to join a chat I administer
+    LoadJoinRequests(PackedChat),
+
+    /// Let someone asking to join a chat in, or turn them away
+    HideJoinRequest { chat: PackedChat, user: tl::types::InputUser, approve: bool },
+
+    /// Leave a group or channel
+    LeaveChat(PackedChat),
+
+    /// Tell Telegram whether I'm online
+    UpdateStatus { offline: bool },
+
+    /// Delete the messages of a chat this many seconds after they're sent
+    /// from now on, 0 for never
+    SetTtlPeriod { chat: PackedChat, period: i32 },
+}
+
+impl ApiJob {
+    /// Kind of the job, to tell its timings apart.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiJob::LoadMessages(_) => "LoadMessages",
+            ApiJob::LoadOlder { .. } => "LoadOlder",
+            ApiJob::LoadUntil { .. } => "LoadUntil",
+            ApiJob::LoadReplies { .. } => "LoadReplies",
+            ApiJob::LoadDialogs => "LoadDialogs",
+            ApiJob::RefreshDialogs => "RefreshDialogs",
+            ApiJob::LoadAccount => "LoadAccount",
+            ApiJob::LoadFolders => "LoadFolders",
+            ApiJob::LoadProfile => "LoadProfile",
+            ApiJob::EditProfile(_) => "EditProfile",
+            ApiJob::LoadPrivacy => "LoadPrivacy",
+            ApiJob::SetPrivacy { .. } => "SetPrivacy",
+            ApiJob::LoadPassword => "LoadPassword",
+            ApiJob::EditPassword(_) => "EditPassword",
+            ApiJob::LoadUserProfile(_) => "LoadUserProfile",
+            ApiJob::ResolveUsername(_) => "ResolveUsername",
+            ApiJob::LoadSessions => "LoadSessions",
+            ApiJob::SetSessionTtl(_) => "SetSessionTtl",
+            ApiJob::TerminateSession(_) => "TerminateSession",
+            ApiJob::SendMessage { .. } => "SendMessage",
+            ApiJob::SendMedia(..) => "SendMedia",
+            ApiJob::LoadContacts => "LoadContacts",
+            ApiJob::LoadMembers(_) => "LoadMembers",
+            ApiJob::Download { .. } => "Download",
+            ApiJob::CancelTransfer(_) => "CancelTransfer",
+            ApiJob::Translate { .. } => "Translate",
+            ApiJob::LoadLinkedChat(_) => "LoadLinkedChat",
+            ApiJob::LoadComments { .. } => "LoadComments",
+            ApiJob::MarkRead(_) => "MarkRead",
+            ApiJob::ClearHistory { .. } => "ClearHistory",
+            ApiJob::ForwardMessages { .. } => "ForwardMessages",
+            ApiJob::DeleteMessages { .. } => "DeleteMessages",
+            ApiJob::MarkUnread { .. } => "MarkUnread",
+            ApiJob::LoadSlowMode(_) => "LoadSlowMode",
+            ApiJob::ReportSpam { .. } => "ReportSpam",
+            ApiJob::DeleteAndBan { .. } => "DeleteAndBan",
+            ApiJob::LoadPinned(_) => "LoadPinned",
+            ApiJob::Pin { .. } => "Pin",
+            ApiJob::LoadAdminLog(_) => "LoadAdminLog",
+            ApiJob::LoadViews { .. } => "LoadViews",
+            ApiJob::EditChat { .. } => "EditChat",
+            ApiJob::SetChatPhoto { .. } => "SetChatPhoto",
+            ApiJob::LoadJoinRequests(_) => "LoadJoinRequests",
+            ApiJob::HideJoinRequest { .. } => "HideJoinRequest",
+            ApiJob::LeaveChat(_) => "LeaveChat",
+            ApiJob::UpdateStatus { .. } => "UpdateStatus",
+            ApiJob::SetTtlPeriod { .. } => "SetTtlPeriod",
+        }
+    }
+}
+
+/// Which messages of a chat `:clearhistory` deletes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearHistory {
+    /// Delete them for the other members too, not only for me
+    pub for_everyone: bool,
+    /// Keep the messages of the last this many days
+    pub keep_days: Option<u32>,
+}
+
+impl ClearHistory {
+    /// Messages sent before then are deleted.
+    pub fn cutoff(&self) -> Option<DateTime<Utc>> {
+        self.keep_days
+            .map(|days| Utc::now() - chrono::Duration::days(days.into()))
+    }
+}
+
+/// My own profile, shown by `:profile`.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub first_name: String,
+    pub last_name: Option<String>,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub phone: Option<String>,
+}
+
+/// Change to my profile made with `:profile`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileEdit {
+    Name { first: String, last: String },
+    /// Empty to remove it
+    Bio(String),
+    Username(String),
+}
+
+/// Change to a group or channel made with `:chat`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatEdit {
+    Title(String),
+    /// Empty to remove it
+    About(String),
+    /// What members may do, unless they're allowed more
+    Permissions(tl::types::ChatBannedRights),
+}
+
+impl ChatEdit {
+    /// What changed, for telling it's done.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChatEdit::Title(_) => "title",
+            ChatEdit::About(_) => "description",
+            ChatEdit::Permissions(_) => "permissions",
+        }
+    }
+}
+
+/// Permissions of the members of a group, toggled with `:chat allow` and
+/// `:chat deny`. Some cover several rights Telegram tells apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChatRight {
+    Send,
+    /// Photos, videos, files, voice and video messages
+    Media,
+    /// Stickers and GIFs
+    Stickers,
+    Links,
+    Polls,
+    /// Add members
+    Invite,
+    Pin,
+    /// Change the title, photo and description
+    Info,
+}
 
-        let _user = match client.sign_in(&token, &code).await {
-            Ok(user) => {
-                client
-                    .session()
-                    .save_to_file("hello-world.session")
-                    .unwrap();
-                println!("{:?}", user);
+impl ChatRight {
+    pub const ALL: [ChatRight; 8] = [
+        ChatRight::Send,
+        ChatRight::Media,
+        ChatRight::Stickers,
+        ChatRight::Links,
+        ChatRight::Polls,
+        ChatRight::Invite,
+        ChatRight::Pin,
+        ChatRight::Info,
+    ];
+
+    pub fn parse(s: &str) -> Option<ChatRight> {
+        ChatRight::ALL.into_iter().find(|right| right.label() == s)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChatRight::Send => "send",
+            ChatRight::Media => "media",
+            ChatRight::Stickers => "stickers",
+            ChatRight::Links => "links",
+            ChatRight::Polls => "polls",
+            ChatRight::Invite => "invite",
+            ChatRight::Pin => "pin",
+            ChatRight::Info => "info",
+        }
+    }
+
+    /// Whether the members are denied it.
+    pub fn is_banned(self, rights: &tl::types::ChatBannedRights) -> bool {
+        match self {
+            ChatRight::Send => rights.send_messages || rights.send_plain,
+            ChatRight::Media => rights.send_media,
+            ChatRight::Stickers => rights.send_stickers,
+            ChatRight::Links => rights.embed_links,
+            ChatRight::Polls => rights.send_polls,
+            ChatRight::Invite => rights.invite_users,
+            ChatRight::Pin => rights.pin_messages,
+            ChatRight::Info => rights.change_info,
+        }
+    }
+
+    pub fn set_banned(self, rights: &mut tl::types::ChatBannedRights, banned: bool) {
+        match self {
+            ChatRight::Send => {
+                rights.send_messages = banned;
+                rights.send_plain = banned;
             }
-            Err(SignInError::PasswordRequired(_token)) => {
-                unimplemented!("Please provide a password");
+            ChatRight::Media => {
+                rights.send_media = banned;
+                rights.send_photos = banned;
+                rights.send_videos = banned;
+                rights.send_roundvideos = banned;
+                rights.send_audios = banned;
+                rights.send_voices = banned;
+                rights.send_docs = banned;
             }
-            Err(SignInError::SignUpRequired {
-                terms_of_service: _tos,
-            }) => {
-                unimplemented!("Sign up required");
+            ChatRight::Stickers => {
+                rights.send_stickers = banned;
+                rights.send_gifs = banned;
+                rights.send_games = banned;
+                rights.send_inline = banned;
             }
-            Err(err) => {
-                println!("Failed to sign in as a user :(\n{}", err);
-                return Err(err.into());
+            ChatRight::Links => rights.embed_links = banned,
+            ChatRight::Polls => rights.send_polls = banned,
+            ChatRight::Invite => rights.invite_users = banned,
+            ChatRight::Pin => rights.pin_messages = banned,
+            ChatRight::Info => rights.change_info = banned,
+        }
+    }
+}
+
+/// Rights with none of them denied, forever.
+pub fn no_banned_rights() -> tl::types::ChatBannedRights {
+    tl::types::ChatBannedRights {
+        view_messages: false,
+        send_messages: false,
+        send_media: false,
+        send_stickers: false,
+        send_gifs: false,
+        send_games: false,
+        send_inline: false,
+        embed_links: false,
+        send_polls: false,
+        change_info: false,
+        invite_users: false,
+        pin_messages: false,
+        manage_topics: false,
+        send_photos: false,
+        send_videos: false,
+        send_roundvideos: false,
+        send_audios: false,
+        send_voices: false,
+        send_docs: false,
+        send_plain: false,
+        until_date: 0,
+    }
+}
+
+/// Privacy settings managed by `:privacy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrivacyKey {
+    LastSeen,
+    ProfilePhoto,
+    /// Link to my account in messages forwarded from me
+    Forwards,
+    Calls,
+}
+
+impl PrivacyKey {
+    pub const ALL: [PrivacyKey; 4] = [
+        PrivacyKey::LastSeen,
+        PrivacyKey::ProfilePhoto,
+        PrivacyKey::Forwards,
+        PrivacyKey::Calls,
+    ];
+
+    pub fn parse(s: &str) -> Option<PrivacyKey> {
+        match s {
+            "lastseen" => Some(PrivacyKey::LastSeen),
+            "photo" => Some(PrivacyKey::ProfilePhoto),
+            "forwards" => Some(PrivacyKey::Forwards),
+            "calls" => Some(PrivacyKey::Calls),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PrivacyKey::LastSeen => "Last seen & online",
+            PrivacyKey::ProfilePhoto => "Profile photo",
+            PrivacyKey::Forwards => "Forwarded messages",
+            PrivacyKey::Calls => "Calls",
+        }
+    }
+
+    fn to_input(self) -> tl::enums::InputPrivacyKey {
+        match self {
+            PrivacyKey::LastSeen => tl::enums::InputPrivacyKey::StatusTimestamp,
+            PrivacyKey::ProfilePhoto => tl::enums::InputPrivacyKey::ProfilePhoto,
+            PrivacyKey::Forwards => tl::enums::InputPrivacyKey::Forwards,
+            PrivacyKey::Calls => tl::enums::InputPrivacyKey::PhoneCall,
+        }
+    }
+}
+
+/// Who a privacy setting allows, besides its exceptions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrivacyValue {
+    Everybody,
+    Contacts,
+    Nobody,
+}
+
+impl PrivacyValue {
+    pub fn parse(s: &str) -> Option<PrivacyValue> {
+        match s {
+            "everybody" => Some(PrivacyValue::Everybody),
+            "contacts" => Some(PrivacyValue::Contacts),
+            "nobody" => Some(PrivacyValue::Nobody),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PrivacyValue::Everybody => "everybody",
+            PrivacyValue::Contacts => "my contacts",
+            PrivacyValue::Nobody => "nobody",
+        }
+    }
+
+    /// The value selecting a setting in `:privacy` changes it to.
+    pub fn next(self) -> PrivacyValue {
+        match self {
+            PrivacyValue::Everybody => PrivacyValue::Contacts,
+            PrivacyValue::Contacts => PrivacyValue::Nobody,
+            PrivacyValue::Nobody => PrivacyValue::Everybody,
+        }
+    }
+
+    fn rule(self) -> tl::enums::InputPrivacyRule {
+        match self {
+            PrivacyValue::Everybody => tl::enums::InputPrivacyRule::ValueAllowAll,
+            PrivacyValue::Contacts => tl::enums::InputPrivacyRule::ValueAllowContacts,
+            PrivacyValue::Nobody => tl::enums::InputPrivacyRule::ValueDisallowAll,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivacySetting {
+    pub key: PrivacyKey,
+    pub value: PrivacyValue,
+    /// Users and groups always or never allowed, set in other apps
+    pub exceptions: usize,
+}
+
+/// My 2FA cloud password settings, shown by `:password`.
+#[derive(Debug, Clone)]
+pub struct PasswordState {
+    pub has_password: bool,
+    pub hint: Option<String>,
+    pub has_recovery: bool,
+    /// Recovery email waiting for its code, like `j***@e***.com`
+    pub unconfirmed_email: Option<String>,
+}
+
+impl PasswordState {
+    /// What can be done from the settings screen in this state.
+    pub fn options(&self) -> Vec<PasswordOption> {
+        let mut options = if self.has_password {
+            vec![PasswordOption::Change, PasswordOption::Disable, PasswordOption::Email]
+        } else {
+            vec![PasswordOption::Enable]
+        };
+        if self.unconfirmed_email.is_some() {
+            options.extend([
+                PasswordOption::ConfirmEmail,
+                PasswordOption::ResendEmail,
+                PasswordOption::CancelEmail,
+            ]);
+        }
+        options
+    }
+}
+
+/// Entry of the `:password` settings screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordOption {
+    Enable,
+    Change,
+    Disable,
+    /// Set or change the recovery email
+    Email,
+    /// Type the code sent to a new recovery email
+    ConfirmEmail,
+    ResendEmail,
+    CancelEmail,
+}
+
+/// Change to my 2FA settings. The current password is needed for all of
+/// them but the email confirmation ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordEdit {
+    Set {
+        /// None if there's no password yet
+        current: Option<String>,
+        new: String,
+        hint: String,
+        /// Recovery email, only when enabling the password
+        email: Option<String>,
+    },
+    Disable { current: String },
+    Email { current: String, email: String },
+    ConfirmEmail { code: String },
+    ResendEmail,
+    CancelEmail,
+}
+
+/// Inactivity after which Telegram terminates sessions, the choices its
+/// apps offer.
+pub const SESSION_TTL_DAYS: [i32; 5] = [7, 30, 90, 183, 365];
+
+/// My active sessions and their policy, shown by `:sessions`.
+#[derive(Debug, Clone)]
+pub struct Sessions {
+    /// Sessions inactive this many days are terminated
+    pub ttl_days: i32,
+    /// New sessions need the 2FA password to log in
+    pub password: bool,
+    pub sessions: Vec<ActiveSession>,
+}
+
+impl Sessions {
+    /// Entries of the session manager, the policy first.
+    pub fn items(self) -> Vec<SessionItem> {
+        let mut items = vec![SessionItem::Ttl(self.ttl_days), SessionItem::Password(self.password)];
+        items.extend(self.sessions.into_iter().map(SessionItem::Session));
+        items
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveSession {
+    pub hash: i64,
+    /// This session of tg9
+    pub current: bool,
+    pub device: String,
+    pub app: String,
+    pub location: String,
+    pub active: DateTime<Utc>,
+}
+
+/// Entry of the `:sessions` screen.
+#[derive(Debug, Clone)]
+pub enum SessionItem {
+    /// Selecting it changes the TTL to the next choice
+    Ttl(i32),
+    /// Selecting it opens the 2FA settings
+    Password(bool),
+    /// Selecting it asks to terminate the session
+    Session(ActiveSession),
+}
+
+/// Poll composed with `:poll`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewPoll {
+    pub question: String,
+    pub options: Vec<String>,
+    /// Show who voted for what, polls are anonymous by default
+    pub public_voters: bool,
+    pub multiple_choice: bool,
+}
+
+/// What to do with a file once its download completes
+#[derive(Debug, Clone)]
+pub enum AfterDownload {
+    /// Saved where asked to, from the message (by chat and message id)
+    Saved { chat: i64, message_id: i32 },
+    /// Open it with the configured video player
+    Play,
+    /// Play it inside the terminal, leaving the UI meanwhile
+    PlayInTerminal,
+    /// Downloaded automatically into the media cache
+    Cache,
+    /// Downloaded into the media cache, to copy the image to the clipboard
+    CopyImage,
+    /// Downloaded into the media cache, to apply the patch to the file or
+    /// directory
+    ApplyPatch(PathBuf),
+    /// Downloaded into the media cache, to export the video note as MP4
+    /// to the path
+    ExportVideoNote(PathBuf),
+}
+
+/// Events that update state from API messages
+#[derive(Debug)]
+pub enum ApiEvent {
+    /// connected and logged in, jobs are being done
+    Connected,
+
+    /// updates stopped coming, most likely the network is gone
+    Disconnected(Tg9Error),
+
+    /// the saved session is encrypted, its passphrase is needed to connect
+    SessionLocked,
+
+    /// new message
+    MessageNew(Message),
+
+    MessageDeleted(MessageDeletion),
+
+    MessageEdited(Message),
+
+    /// load a part of messages in chat
+    LoadedMessages(Message),
+
+    /// the history was loaded up to this message, if it was found
+    ReachedMessage { chat: PackedChat, message_id: i32 },
+
+    /// messages replied to, by id; none if deleted
+    LoadedReplies { chat: PackedChat, replies: Vec<(i32, Option<Message>)> },
+
+    /// initial loading of dialogs
+    LoadedDialog(Dialog),
+
+    /// every dialog, loaded again
+    RefreshedDialogs(Vec<Dialog>),
+
+    /// my account, whether it has Telegram Premium
+    LoadedAccount { premium: bool },
+
+    /// my chat folders, in order
+    LoadedFolders(Vec<Folder>),
+
+    /// chat folders were added, edited, removed or reordered elsewhere
+    FoldersChanged,
+
+    /// my profile, loaded or just edited
+    LoadedProfile(Profile),
+
+    /// profile of another user
+    LoadedUserProfile(Profile),
+
+    /// my privacy settings, loaded or just changed
+    LoadedPrivacy(Vec<PrivacySetting>),
+
+    /// my 2FA settings, loaded or after a change
+    LoadedPassword(PasswordState),
+
+    /// the chat with a username, none if nobody has it
+    ResolvedUsername { username: String, chat: Option<PackedChat> },
+
+    /// my active sessions, loaded or after a change
+    LoadedSessions(Sessions),
+
+    /// my contacts which have a visible phone number
+    LoadedContacts(Vec<SharedContact>),
+
+    /// members of a group, without me
+    LoadedMembers { chat: PackedChat, members: Vec<Member> },
+
+    /// a peer posted a story (by bare peer id)
+    StoryPosted(i64),
+
+    /// media finished downloading to the path
+    Downloaded { path: PathBuf, then: AfterDownload },
+
+    /// bytes uploaded or downloaded so far
+    TransferProgress { id: TransferId, done: u64 },
+
+    /// transfer is over, whether it succeeded or not
+    TransferDone(TransferId),
+
+    /// a message couldn't be sent, it's given back to try again later
+    SendFailed {
+        id: TransferId,
+        chat: PackedChat,
+        message: OutgoingMessage,
+        error: Tg9Error,
+    },
+
+    /// messages of the chat can't be read
+    ChatUnavailable { chat: PackedChat, reason: String },
+
+    /// translation of a message
+    Translated {
+        chat: PackedChat,
+        message_id: i32,
+        text: String,
+    },
+
+    /// the history of a chat was deleted
+    HistoryCleared { chat: PackedChat, scope: ClearHistory },
+
+    /// messages of a chat were deleted for everyone
+    MessagesDeleted { chat: PackedChat, ids: Vec<i32> },
+
+    /// the unread mark of a dialog was set or cleared
+    MarkedUnread { chat: PackedChat, unread: bool },
+
+    /// a user was banned from a supergroup and their messages deleted
+    Banned { chat: PackedChat, user_id: i64 },
+
+    /// the message pinned in a chat, if any
+    LoadedPinned { chat: PackedChat, message: Option<Message> },
+
+    /// a message was pinned or unpinned
+    Pinned { chat: PackedChat, message_id: i32, unpin: bool },
+
+    /// fresh view and forward counters of channel posts, by message id
+    LoadedViews { chat: PackedChat, views: Vec<(i32, Views)> },
+
+    /// the discussion group or channel linked to the chat, if any (by bare id)
+    LinkedChat { chat: PackedChat, linked: Option<i64> },
+
+    /// comments on a channel post, oldest first
+    LoadedComments {
+        chat: PackedChat,
+        message_id: i32,
+        comments: Vec<ThreadMessage>,
+    },
+
+    /// slow mode interval of a group, and how long until I can send again
+    SlowMode {
+        chat: PackedChat,
+        interval: Option<Duration>,
+        wait: Option<Duration>,
+    },
+
+    /// recent admin log entries, newest first
+    LoadedAdminLog(Vec<AdminLogEntry>),
+
+    /// the title, description, photo or permissions of a chat were changed
+    ChatEdited { chat: PackedChat, what: &'static str },
+
+    /// pending requests to join a chat, oldest first
+    LoadedJoinRequests { chat: PackedChat, requests: Vec<JoinRequest> },
+
+    /// a request to join a chat was approved or declined
+    JoinRequestHidden { chat: PackedChat, user_id: i64, approve: bool },
+
+    /// how many requests to join a chat are pending now (by bare chat id)
+    PendingJoinRequests { chat_id: i64, count: i32 },
+
+    /// I left a group or channel
+    LeftChat(PackedChat),
+
+    /// messages of a chat are deleted this many seconds after they're sent,
+    /// or not at all (by bare chat id)
+    TtlPeriod { chat_id: i64, period: Option<i32> },
+
+    /// something worth telling the user which isn't an error
+    Notice(String),
+
+    /// error invoking API
+    Error { doing: &'static str, error: Tg9Error },
+}
+
+/// Uploads and downloads in flight, so they can be cancelled. Each one
+/// removes itself once over.
+type Transfers = Arc<Mutex<HashMap<TransferId, AbortHandle>>>;
+
+/// Most messages forwarded or deleted by one request
+const BULK_MAX: usize = 100;
+
+/// Least time between two messages sent to the same chat
+const SEND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Message waiting in the queue of its chat
+struct Outgoing {
+    id: TransferId,
+    message: OutgoingMessage,
+    abort: AbortRegistration,
+}
+
+/// Perform API calls and receive updates.
+pub async fn api_worker(
+    client: Client,
+    mut rx: mpsc::UnboundedReceiver<ApiJob>,
+    tx: EventSender,
+) {
+    let transfers = Transfers::default();
+    // shared with the jobs that learn usernames in the background
+    let usernames = Arc::new(Mutex::new(UsernameCache::load()));
+    // messages are sent one at a time per chat, in order
+    let mut outboxes: HashMap<PackedChat, mpsc::UnboundedSender<Outgoing>> = HashMap::new();
+    let mut online = true;
+    loop {
+        tokio::select! {
+        job = rx.recv() => {
+            let Some(job) = job else { break; };
+            metrics::job_taken();
+            let tx = tx.traced(job.name());
+            let client = client.clone();
+            match job {
+                ApiJob::LoadDialogs => {
+                    let mut dialogs = client.iter_dialogs();
+                    loop {
+                        tx.ready().await;
+                        match dialogs.next().await {
+                            Ok(Some(dialog)) => {
+                                if let Some(username) = dialog.chat().username() {
+                                    usernames.lock().unwrap().insert(username, dialog.chat().pack());
+                                }
+                                tx.send(ApiEvent::LoadedDialog(dialog)).unwrap();
+                            }
+                            Ok(None) => {
+                                let _ = usernames.lock().unwrap().save();
+                                break;
+                            }
+                            Err(e) => {
+                                tx.send(ApiEvent::Error { doing: "load dialogs", error: e.into() }).unwrap();
+                                break;
+                            }
+                        }
+                    }
+                }
+                ApiJob::RefreshDialogs => {
+                    let usernames = usernames.clone();
+                    tokio::spawn(async move {
+                        match load_dialogs(&client).await {
+                            Ok(dialogs) => {
+                                let mut usernames = usernames.lock().unwrap();
+                                for dialog in &dialogs {
+                                    if let Some(username) = dialog.chat().username() {
+                                        usernames.insert(username, dialog.chat().pack());
+                                    }
+                                }
+                                let _ = usernames.save();
+                                drop(usernames);
+                                tx.send(ApiEvent::RefreshedDialogs(dialogs)).unwrap();
+                            }
+                            Err(e) => {
+                                tx.send(ApiEvent::Error { doing: "refresh dialogs", error: e }).unwrap();
+                            }
+                        }
+                    });
+                }
+                ApiJob::LoadAccount => {
+                    match client.get_me().await {
+                        Ok(me) => tx.send(ApiEvent::LoadedAccount { premium: me.raw.premium }).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load account", error: e.into() }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LoadFolders => {
+                    match client.invoke(&tl::functions::messages::GetDialogFilters {}).await {
+                        Ok(filters) => {
+                            let folders = filters.into_iter().filter_map(Folder::from_raw).collect();
+                            tx.send(ApiEvent::LoadedFolders(folders)).unwrap();
+                        }
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load chat folders", error: e.into() }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LoadProfile => {
+                    match load_profile(&client).await {
+                        Ok(profile) => tx.send(ApiEvent::LoadedProfile(profile)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the profile", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::EditProfile(edit) => {
+                    let result = match edit_profile(&client, edit).await {
+                        Ok(()) => load_profile(&client).await,
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(profile) => tx.send(ApiEvent::LoadedProfile(profile)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "edit the profile", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::LoadPrivacy => {
+                    match load_privacy(&client).await {
+                        Ok(settings) => tx.send(ApiEvent::LoadedPrivacy(settings)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the privacy settings", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::SetPrivacy { key, value } => {
+                    let result = match set_privacy(&client, key, value).await {
+                        Ok(()) => load_privacy(&client).await,
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(settings) => tx.send(ApiEvent::LoadedPrivacy(settings)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "change the privacy", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::LoadPassword => {
+                    match load_password(&client).await {
+                        Ok(state) => tx.send(ApiEvent::LoadedPassword(state)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the 2FA settings", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::EditPassword(edit) => {
+                    tokio::spawn(async move {
+                        // hashing the new password takes a moment
+                        match edit_password(&client, edit).await {
+                            Ok(()) => tx.send(ApiEvent::Notice("2FA settings changed".into())).unwrap(),
+                            Err(e) => tx.send(ApiEvent::Error { doing: "change the 2FA settings", error: e }).unwrap(),
+                        }
+                        // loaded again either way, so the settings screen stays current
+                        match load_password(&client).await {
+                            Ok(state) => tx.send(ApiEvent::LoadedPassword(state)).unwrap(),
+                            Err(e) => tx.send(ApiEvent::Error { doing: "load the 2FA settings", error: e }).unwrap(),
+                        }
+                    });
+                }
+                ApiJob::LoadUserProfile(user) => {
+                    match load_user_profile(&client, user).await {
+                        Ok(profile) => tx.send(ApiEvent::LoadedUserProfile(profile)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the profile", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::ResolveUsername(username) => {
+                    let cached = usernames.lock().unwrap().get(&username);
+                    let result = match cached {
+                        Some(chat) => Ok(Some(chat)),
+                        None => resolve_username(&client, &username).await,
+                    };
+                    match result {
+                        Ok(chat) => {
+                            if let (Some(chat), None) = (chat, cached) {
+                                let mut usernames = usernames.lock().unwrap();
+                                usernames.insert(&username, chat);
+                                let _ = usernames.save();
+                            }
+                            tx.send(ApiEvent::ResolvedUsername { username, chat }).unwrap();
+                        }
+                        Err(e) => tx.send(ApiEvent::Error { doing: "find the username", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::LoadSessions => {
+                    match load_sessions(&client).await {
+                        Ok(sessions) => tx.send(ApiEvent::LoadedSessions(sessions)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the sessions", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::SetSessionTtl(days) => {
+                    let request = tl::functions::account::SetAuthorizationTtl { authorization_ttl_days: days };
+                    if let Err(e) = client.invoke(&request).await {
+                        tx.send(ApiEvent::Error { doing: "change the session TTL", error: e.into() }).unwrap();
+                    }
+                    match load_sessions(&client).await {
+                        Ok(sessions) => tx.send(ApiEvent::LoadedSessions(sessions)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the sessions", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::TerminateSession(hash) => {
+                    match client.invoke(&tl::functions::account::ResetAuthorization { hash }).await {
+                        Ok(_) => tx.send(ApiEvent::Notice("session terminated".into())).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "terminate the session", error: e.into() }).unwrap();
+                        }
+                    }
+                    match load_sessions(&client).await {
+                        Ok(sessions) => tx.send(ApiEvent::LoadedSessions(sessions)).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the sessions", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::LoadMessages(c) => {
+                    // TODO: when scrolling up, load necessary messages. For now this is
+                    // just for initial loading of chats (and the view is not scrollable)
+                    let message_iter = client.iter_messages(c).limit(30);
+                    load_history(&client, &tx, c, message_iter, |_| false).await;
+                }
+                ApiJob::LoadOlder { chat, offset_id } => {
+                    let message_iter = client.iter_messages(chat).offset_id(offset_id).limit(HISTORY_PAGE);
+                    load_history(&client, &tx, chat, message_iter, |_| false).await;
+                }
+                ApiJob::LoadUntil { chat, offset_id, message_id } => {
+                    let message_iter = client.iter_messages(chat).offset_id(offset_id).limit(LOAD_UNTIL_MAX);
+                    let mut context = 0;
+                    load_history(&client, &tx, chat, message_iter, |message| {
+                        if message.id() <= message_id {
+                            context += 1;
+                        }
+                        context > CONTEXT_MESSAGES
+                    })
+                    .await;
+                    tx.send(ApiEvent::ReachedMessage { chat, message_id }).unwrap();
+                }
+                ApiJob::LoadReplies { chat, ids } => {
+                    match load_replies(&client, chat, &ids).await {
+                        Ok(replies) => tx.send(ApiEvent::LoadedReplies { chat, replies }).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "load the replied messages", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::SendMessage { id, chat, message } => {
+                    let (abort_handle, abort) = AbortHandle::new_pair();
+                    transfers.lock().unwrap().insert(id, abort_handle);
+                    // uploads can take a while, don't block receiving updates
+                    let outbox = outboxes.entry(chat).or_insert_with(|| {
+                        let (outbox, queue) = mpsc::unbounded_channel();
+                        tokio::spawn(send_queue(client, chat, queue, tx.untraced(), transfers.clone()));
+                        outbox
+                    });
+                    let _ = outbox.send(Outgoing { id, message, abort });
+                }
+                ApiJob::LoadContacts => {
+                    match load_contacts(&client).await {
+                        Ok(contacts) => tx.send(ApiEvent::LoadedContacts(contacts)).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load contacts", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::ClearHistory { chat, scope } => {
+                    tokio::spawn(async move {
+                        match clear_history(&client, chat, scope).await {
+                            Ok(()) => tx.send(ApiEvent::HistoryCleared { chat, scope }).unwrap(),
+                            Err(e) => {
+                                tx.send(ApiEvent::Error { doing: "clear the history", error: e }).unwrap();
+                            }
+                        }
+                    });
+                }
+                ApiJob::ForwardMessages { from, ids, to } => {
+                    tokio::spawn(async move {
+                        for batch in ids.chunks(BULK_MAX) {
+                            if let Err(e) = client.forward_messages(to, batch, from).await {
+                                tx.send(ApiEvent::Error { doing: "forward the messages", error: e.into() }).unwrap();
+                                return;
+                            }
+                        }
+                        tx.send(ApiEvent::Notice(format!("forwarded {} messages", ids.len()))).unwrap();
+                    });
+                }
+                ApiJob::DeleteMessages { chat, ids } => {
+                    tokio::spawn(async move {
+                        for batch in ids.chunks(BULK_MAX) {
+                            if let Err(e) = client.delete_messages(chat, batch).await {
+                                tx.send(ApiEvent::Error { doing: "delete the messages", error: e.into() }).unwrap();
+                                return;
+                            }
+                        }
+                        tx.send(ApiEvent::MessagesDeleted { chat, ids }).unwrap();
+                    });
+                }
+                ApiJob::LoadMembers(chat) => {
+                    let usernames = usernames.clone();
+                    tokio::spawn(async move {
+                        match load_members(&client, chat).await {
+                            Ok(members) => {
+                                let mut usernames = usernames.lock().unwrap();
+                                for member in &members {
+                                    if let Some(username) = &member.username {
+                                        usernames.insert(username, member.user);
+                                    }
+                                }
+                                let _ = usernames.save();
+                                drop(usernames);
+                                tx.send(ApiEvent::LoadedMembers { chat, members }).unwrap();
+                            }
+                            Err(e) => {
+                                tx.send(ApiEvent::Error { doing: "load members", error: e }).unwrap();
+                            }
+                        }
+                    });
+                }
+                ApiJob::Translate { chat, message_id, text, to_lang, command } => {
+                    tokio::spawn(async move {
+                        let translated = match command {
+                            Some(command) => translate_external(command, to_lang, text).await,
+                            None => translate(&client, chat, message_id, to_lang).await,
+                        };
+                        match translated {
+                            Ok(text) => tx.send(ApiEvent::Translated { chat, message_id, text }).unwrap(),
+                            Err(e) => {
+                                tx.send(ApiEvent::Error { doing: "translate", error: e }).unwrap();
+                            }
+                        }
+                    });
+                }
+                ApiJob::LoadLinkedChat(chat) => {
+                    match linked_chat(&client, chat).await {
+                        Ok(linked) => tx.send(ApiEvent::LinkedChat { chat, linked }).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "find the linked chat", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LoadComments { chat, message_id } => {
+                    match load_comments(&client, chat, message_id).await {
+                        Ok(comments) => {
+                            tx.send(ApiEvent::LoadedComments { chat, message_id, comments }).unwrap()
+                        }
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load comments", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::MarkRead(chats) => {
+                    tokio::spawn(async move {
+                        let count = chats.len();
+                        match mark_read(&client, chats, &tx).await {
+                            Ok(()) => {
+                                tx.send(ApiEvent::Notice(format!("marked {} chats as read", count))).unwrap();
+                            }
+                            Err(e) => {
+                                tx.send(ApiEvent::Error { doing: "mark as read", error: e }).unwrap();
+                            }
+                        }
+                    });
+                }
+                ApiJob::MarkUnread { chat, unread } => {
+                    let request = tl::functions::messages::MarkDialogUnread {
+                        unread,
+                        peer: tl::types::InputDialogPeer { peer: chat.to_input_peer() }.into(),
+                    };
+                    match client.invoke(&request).await {
+                        Ok(_) => tx.send(ApiEvent::MarkedUnread { chat, unread }).unwrap(),
+                        Err(e) => {
+                            let doing = if unread { "mark as unread" } else { "clear the unread mark" };
+                            tx.send(ApiEvent::Error { doing, error: e.into() }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::ReportSpam { chat, message_id } => {
+                    match report_spam(&client, chat, message_id).await {
+                        Ok(()) => tx.send(ApiEvent::Notice("reported as spam".into())).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "report spam", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::DeleteAndBan { chat, user } => {
+                    tokio::spawn(async move {
+                        match delete_and_ban(&client, chat, user).await {
+                            Ok(()) => tx.send(ApiEvent::Banned { chat, user_id: user.id }).unwrap(),
+                            Err(e) => tx.send(ApiEvent::Error { doing: "ban the user", error: e }).unwrap(),
+                        }
+                    });
+                }
+                ApiJob::LoadPinned(chat) => {
+                    // errors are left to loading the messages, which
+                    // explains why a chat is unavailable
+                    if let Ok(message) = client.get_pinned_message(chat).await {
+                        tx.send(ApiEvent::LoadedPinned { chat, message }).unwrap();
+                    }
+                }
+                ApiJob::Pin { chat, message_id, notify, unpin } => {
+                    let request = tl::functions::messages::UpdatePinnedMessage {
+                        silent: !notify,
+                        unpin,
+                        pm_oneside: false,
+                        peer: chat.to_input_peer(),
+                        id: message_id,
+                    };
+                    match client.invoke(&request).await {
+                        Ok(_) => tx.send(ApiEvent::Pinned { chat, message_id, unpin }).unwrap(),
+                        Err(e) => {
+                            let doing = if unpin { "unpin the message" } else { "pin the message" };
+                            tx.send(ApiEvent::Error { doing, error: e.into() }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LoadSlowMode(chat) => {
+                    match slow_mode(&client, chat).await {
+                        Ok((interval, wait)) => tx.send(ApiEvent::SlowMode { chat, interval, wait }).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load the slow mode", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LoadViews { chat, message_ids } => {
+                    match load_views(&client, chat, message_ids).await {
+                        Ok(views) => tx.send(ApiEvent::LoadedViews { chat, views }).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "refresh views", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::EditChat { chat, edit } => {
+                    let what = edit.label();
+                    match edit_chat(&client, chat, edit).await {
+                        Ok(()) => tx.send(ApiEvent::ChatEdited { chat, what }).unwrap(),
+                        Err(e) => tx.send(ApiEvent::Error { doing: "edit the chat", error: e }).unwrap(),
+                    }
+                }
+                ApiJob::SetChatPhoto { id, chat, path } => {
+                    let (abort_handle, abort) = AbortHandle::new_pair();
+                    transfers.lock().unwrap().insert(id, abort_handle);
+                    let transfers = transfers.clone();
+                    tokio::spawn(async move {
+                        let size = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+                        let progress = Progress::new(id, size, tx.clone());
+                        let result = Abortable::new(set_chat_photo(&client, chat, &path, progress), abort).await;
+                        transfers.lock().unwrap().remove(&id);
+                        match result {
+                            Err(_aborted) => {}
+                            Ok(Ok(())) => tx.send(ApiEvent::ChatEdited { chat, what: "photo" }).unwrap(),
+                            Ok(Err(e)) => {
+                                tx.send(ApiEvent::Error { doing: "set the chat photo", error: e }).unwrap();
+                            }
+                        }
+                        tx.send(ApiEvent::TransferDone(id)).unwrap();
+                    });
+                }
+                ApiJob::LoadJoinRequests(chat) => {
+                    match load_join_requests(&client, chat).await {
+                        Ok(requests) => tx.send(ApiEvent::LoadedJoinRequests { chat, requests }).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load the join requests", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::HideJoinRequest { chat, user, approve } => {
+                    let user_id = user.user_id;
+                    let result = client
+                        .invoke(&tl::functions::messages::HideChatJoinRequest {
+                            approved: approve,
+                            peer: chat.to_input_peer(),
+                            user_id: user.into(),
+                        })
+                        .await;
+                    match result {
+                        Ok(_) => tx.send(ApiEvent::JoinRequestHidden { chat, user_id, approve }).unwrap(),
+                        Err(e) => {
+                            let doing = if approve { "approve the request" } else { "decline the request" };
+                            tx.send(ApiEvent::Error { doing, error: e.into() }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LeaveChat(chat) => match client.delete_dialog(chat).await {
+                    Ok(()) => tx.send(ApiEvent::LeftChat(chat)).unwrap(),
+                    Err(e) => tx.send(ApiEvent::Error { doing: "leave the chat", error: e.into() }).unwrap(),
+                },
+                ApiJob::UpdateStatus { offline } => {
+                    if let Err(e) = client.invoke(&tl::functions::account::UpdateStatus { offline }).await {
+                        tx.send(ApiEvent::Error { doing: "update the online status", error: e.into() }).unwrap();
+                    }
+                }
+                ApiJob::SetTtlPeriod { chat, period } => {
+                    let request = tl::functions::messages::SetHistoryTtl { peer: chat.to_input_peer(), period };
+                    match client.invoke(&request).await {
+                        Ok(_) => {
+                            let period = (period > 0).then_some(period);
+                            tx.send(ApiEvent::TtlPeriod { chat_id: chat.id, period }).unwrap();
+                        }
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "set the auto-delete timer", error: e.into() }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::LoadAdminLog(chat) => {
+                    match load_admin_log(&client, chat).await {
+                        Ok(entries) => tx.send(ApiEvent::LoadedAdminLog(entries)).unwrap(),
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "load the admin log", error: e }).unwrap();
+                        }
+                    }
+                }
+                ApiJob::Download { id, media, path, then } => {
+                    let (abort_handle, abort) = AbortHandle::new_pair();
+                    transfers.lock().unwrap().insert(id, abort_handle);
+                    let transfers = transfers.clone();
+                    tokio::spawn(async move {
+                        let progress = Progress::new(id, crate::download::media_size(&media), tx.clone());
+                        let result = Abortable::new(download(&client, media, &path, progress), abort).await;
+                        transfers.lock().unwrap().remove(&id);
+                        match result {
+                            Err(_aborted) => {}
+                            Ok(Ok(())) => tx.send(ApiEvent::Downloaded { path, then }).unwrap(),
+                            Ok(Err(e)) => {
+                                tx.send(ApiEvent::Error { doing: "download", error: e }).unwrap();
+                            }
+                        }
+                        tx.send(ApiEvent::TransferDone(id)).unwrap();
+                    });
+                }
+                ApiJob::CancelTransfer(id) => {
+                    if let Some(abort_handle) = transfers.lock().unwrap().remove(&id) {
+                        abort_handle.abort();
+                    }
+                }
+                ApiJob::SendMedia(chat, media) => {
+                    match send_media(&client, chat, media).await {
+                        Ok(Some(message)) => tx.send(ApiEvent::MessageNew(message)).unwrap(),
+                        Ok(None) => {}
+                        Err(e) => {
+                            tx.send(ApiEvent::Error { doing: "send", error: e }).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+            // not taken before the UI has room for what it brings
+            update = async {
+                tx.ready().await;
+                client.next_update().await
+            } => {
+                // updates stop coming when the network goes, the worker
+                // stays for when it's back
+                let update = match update {
+                    Ok(update) => update,
+                    Err(e) => {
+                        if online {
+                            online = false;
+                            tx.send(ApiEvent::Disconnected(e.into())).unwrap();
+                        }
+                        tokio::time::sleep(RECONNECT_INTERVAL).await;
+                        continue;
+                    }
+                };
+                if !online {
+                    online = true;
+                    tx.send(ApiEvent::Connected).unwrap();
+                }
+                let Some(update) = update else { break; };
+                match update {
+                    Update::NewMessage(message) if !message.outgoing() => {
+                        tx.send(ApiEvent::MessageNew(message)).unwrap();
+                    }
+                    Update::MessageDeleted(_message_del) => {}
+                    Update::MessageEdited(message) => {
+                        tx.send(ApiEvent::MessageEdited(message)).unwrap();
+                    }
+                    Update::Raw(tl::enums::Update::PendingJoinRequests(update)) => {
+                        let chat_id = peer_id(&update.peer);
+                        tx.send(ApiEvent::PendingJoinRequests { chat_id, count: update.requests_pending }).unwrap();
+                    }
+                    Update::Raw(tl::enums::Update::PeerHistoryTtl(update)) => {
+                        let chat_id = peer_id(&update.peer);
+                        tx.send(ApiEvent::TtlPeriod { chat_id, period: update.ttl_period }).unwrap();
+                    }
+                    Update::Raw(
+                        tl::enums::Update::DialogFilter(_)
+                        | tl::enums::Update::DialogFilters
+                        | tl::enums::Update::DialogFilterOrder(_),
+                    ) => {
+                        tx.send(ApiEvent::FoldersChanged).unwrap();
+                    }
+                    Update::Raw(tl::enums::Update::Story(update)) => {
+                        if let tl::enums::StoryItem::Item(_) = update.story {
+                            tx.send(ApiEvent::StoryPosted(peer_id(&update.peer))).unwrap();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Every dialog, in the order of the list.
+async fn load_dialogs(client: &Client) -> Result<Vec<Dialog>> {
+    let mut dialogs = client.iter_dialogs();
+    let mut loaded = Vec::new();
+    while let Some(dialog) = dialogs.next().await? {
+        loaded.push(dialog);
+    }
+    Ok(loaded)
+}
+
+/// Telegram limits albums to 10 media
+const ALBUM_MAX: usize = 10;
+
+/// Send the messages queued for a chat in order, at most one per
+/// `SEND_INTERVAL`.
+async fn send_queue(
+    client: Client,
+    chat: PackedChat,
+    mut queue: mpsc::UnboundedReceiver<Outgoing>,
+    tx: EventSender,
+    transfers: Transfers,
+) {
+    let mut last_sent: Option<Instant> = None;
+    while let Some(outgoing) = queue.recv().await {
+        if let Some(last_sent) = last_sent {
+            tokio::time::sleep_until(last_sent + SEND_INTERVAL).await;
+        }
+        let id = outgoing.id;
+        let send = send_retrying(&client, chat, &outgoing.message, id, &tx);
+        let result = Abortable::new(send, outgoing.abort).await;
+        transfers.lock().unwrap().remove(&id);
+        match result {
+            Err(_aborted) => {}
+            Ok(Ok(_)) if outgoing.message.post.schedule.is_some() => {
+                last_sent = Some(Instant::now());
+                tx.send(ApiEvent::Notice("post scheduled".into())).unwrap();
+            }
+            Ok(Ok(sent)) => {
+                last_sent = Some(Instant::now());
+                for message in sent {
+                    tx.send(ApiEvent::MessageNew(message)).unwrap();
+                }
+            }
+            Ok(Err(e)) => {
+                if let Tg9Error::SlowMode(wait) = e {
+                    tx.send(ApiEvent::SlowMode { chat, interval: None, wait: Some(wait) }).unwrap();
+                }
+                let message = outgoing.message;
+                tx.send(ApiEvent::SendFailed { id, chat, message, error: e }).unwrap();
+            }
+        }
+        tx.send(ApiEvent::TransferDone(id)).unwrap();
+    }
+}
+
+/// Send a message, waiting and trying again as long as Telegram asks to
+/// wait because of flooding.
+async fn send_retrying(
+    client: &Client,
+    chat: PackedChat,
+    message: &OutgoingMessage,
+    id: TransferId,
+    tx: &EventSender,
+) -> Result<Vec<Message>> {
+    loop {
+        let progress = Progress::new(id, attachments_size(&message.attachments), tx.clone());
+        match send_message(client, chat, message.clone(), progress, tx).await {
+            Err(Tg9Error::FloodWait(wait)) => {
+                let _ = tx.send(ApiEvent::Notice(format!(
+                    "sending too fast, trying again in {}s",
+                    wait.as_secs()
+                )));
+                tokio::time::sleep(wait).await;
+            }
+            sent => return sent,
+        }
+    }
+}
+
+/// Least time between marking two chats as read
+const MARK_READ_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Mark the chats as read one at a time, waiting as long as Telegram asks
+/// to when flooding.
+async fn mark_read(
+    client: &Client,
+    chats: Vec<PackedChat>,
+    tx: &EventSender,
+) -> Result<()> {
+    for chat in chats {
+        loop {
+            match client.mark_as_read(chat).await.map_err(Tg9Error::from) {
+                Err(Tg9Error::FloodWait(wait)) => {
+                    let _ = tx.send(ApiEvent::Notice(format!(
+                        "marking as read too fast, going on in {}s",
+                        wait.as_secs()
+                    )));
+                    tokio::time::sleep(wait).await;
+                }
+                result => break result?,
+            }
+        }
+        tokio::time::sleep(MARK_READ_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// Delete the history of a chat, keeping the chat itself in the dialog
+/// list.
+async fn clear_history(client: &Client, chat: PackedChat, scope: ClearHistory) -> Result<()> {
+    let max_date = scope.cutoff().map(|cutoff| cutoff.timestamp() as i32);
+    if let Some(channel) = chat.try_to_input_channel() {
+        if max_date.is_some() {
+            return Err(Tg9Error::Other("supergroups can't keep recent messages when clearing".into()));
+        }
+        client
+            .invoke(&tl::functions::channels::DeleteHistory {
+                for_everyone: scope.for_everyone,
+                channel,
+                max_id: 0,
+            })
+            .await?;
+        return Ok(());
+    }
+    // deleted in batches, until nothing is left
+    loop {
+        let tl::enums::messages::AffectedHistory::History(affected) = client
+            .invoke(&tl::functions::messages::DeleteHistory {
+                just_clear: true,
+                revoke: scope.for_everyone,
+                peer: chat.to_input_peer(),
+                max_id: 0,
+                min_date: None,
+                max_date,
+            })
+            .await?;
+        if affected.offset <= 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Messages of a chat containing the query, newest first.
+pub async fn search(client: &Client, chat: PackedChat, query: &str, limit: usize) -> Result<Vec<Message>> {
+    let mut iter = client.search_messages(chat).query(query).limit(limit);
+    let mut messages = Vec::new();
+    while let Some(message) = iter.next().await? {
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// Most messages loaded to reach an old one
+const LOAD_UNTIL_MAX: usize = 1000;
+
+/// Older messages loaded at a time when scrolling back past the dropped ones
+const HISTORY_PAGE: usize = 50;
+
+/// Older messages loaded around one jumped to
+const CONTEXT_MESSAGES: usize = 5;
+
+/// Send the messages of the iterator as loaded, stopping after the one
+/// `stop` is true for, then the messages they reply to which aren't among
+/// them.
+async fn load_history(
+    client: &Client,
+    tx: &EventSender,
+    chat: PackedChat,
+    mut messages: MessageIter,
+    mut stop: impl FnMut(&Message) -> bool,
+) {
+    let mut loaded = HashSet::new();
+    let mut replied = Vec::new();
+    loop {
+        tx.ready().await;
+        match messages.next().await {
+            Ok(Some(message)) => {
+                loaded.insert(message.id());
+                if let (None, Some(id)) = (message.action(), message.reply_to_message_id()) {
+                    replied.push(id);
+                }
+                let stopped = stop(&message);
+                tx.send(ApiEvent::LoadedMessages(message)).unwrap();
+                if stopped {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                let event = match unavailable_reason(&e) {
+                    Some(reason) => ApiEvent::ChatUnavailable { chat, reason: reason.into() },
+                    None => ApiEvent::Error { doing: "load messages", error: e.into() },
+                };
+                tx.send(event).unwrap();
+                return;
             }
+        }
+    }
+    replied.retain(|id| !loaded.contains(id));
+    replied.sort_unstable();
+    replied.dedup();
+    if replied.is_empty() {
+        return;
+    }
+    match load_replies(client, chat, &replied).await {
+        Ok(replies) => tx.send(ApiEvent::LoadedReplies { chat, replies }).unwrap(),
+        Err(e) => tx.send(ApiEvent::Error { doing: "load the replied messages", error: e }).unwrap(),
+    }
+}
+
+async fn load_replies(client: &Client, chat: PackedChat, ids: &[i32]) -> Result<Vec<(i32, Option<Message>)>> {
+    let messages = client.get_messages_by_id(chat, ids).await?;
+    Ok(ids.iter().copied().zip(messages).collect())
+}
+
+/// Why a chat can't be read, if that's what the error means.
+fn unavailable_reason(error: &InvocationError) -> Option<&'static str> {
+    let InvocationError::Rpc(rpc) = error else {
+        return None;
+    };
+    match rpc.name.as_str() {
+        "CHANNEL_PRIVATE" => Some("this channel is private or you were banned from it"),
+        "CHANNEL_INVALID" | "CHAT_ID_INVALID" | "PEER_ID_INVALID" => Some("this chat no longer exists"),
+        "CHAT_FORBIDDEN" | "CHAT_RESTRICTED" => Some("you can't access this chat"),
+        "USER_BANNED_IN_CHANNEL" => Some("you were banned from this chat"),
+        "CHANNEL_PUBLIC_GROUP_NA" => Some("this group is unavailable"),
+        _ => None,
+    }
+}
+
+/// Send a message, signing it if asked to by turning the signatures of the
+/// channel on just for it, since they can't be set for each post.
+async fn send_message(
+    client: &Client,
+    chat: PackedChat,
+    message: OutgoingMessage,
+    progress: Progress,
+    tx: &EventSender,
+) -> Result<Vec<Message>> {
+    if !message.post.signed {
+        return send_unsigned(client, chat, message, progress).await;
+    }
+    // already on, by another admin or since we last looked, they stay on
+    if !toggle_signatures(client, chat, true).await? {
+        return send_unsigned(client, chat, message, progress).await;
+    }
+    let signatures = SignaturesOn { client: client.clone(), chat, tx: tx.clone(), restored: false };
+    let sent = send_unsigned(client, chat, message, progress).await;
+    signatures.restore().await;
+    sent
+}
+
+/// Signatures of a channel turned on for one post. They're turned back off
+/// after it, or in the background if the send is aborted before that.
+struct SignaturesOn {
+    client: Client,
+    chat: PackedChat,
+    tx: EventSender,
+    restored: bool,
+}
+
+impl SignaturesOn {
+    async fn restore(mut self) {
+        self.restored = true;
+        if let Err(error) = toggle_signatures(&self.client, self.chat, false).await {
+            let _ = self.tx.send(ApiEvent::Error { doing: "turn the channel signatures back off", error });
+        }
+    }
+}
+
+impl Drop for SignaturesOn {
+    fn drop(&mut self) {
+        if self.restored {
+            return;
+        }
+        let (client, chat, tx) = (self.client.clone(), self.chat, self.tx.clone());
+        tokio::spawn(async move {
+            if let Err(error) = toggle_signatures(&client, chat, false).await {
+                let _ = tx.send(ApiEvent::Error { doing: "turn the channel signatures back off", error });
+            }
+        });
+    }
+}
+
+/// Turn the signatures of a channel on or off, returns whether they
+/// weren't already.
+async fn toggle_signatures(client: &Client, chat: PackedChat, enabled: bool) -> Result<bool> {
+    let Some(channel) = chat.try_to_input_channel() else {
+        return Ok(false);
+    };
+    match client.invoke(&tl::functions::channels::ToggleSignatures { channel, enabled }).await {
+        Ok(_) => Ok(true),
+        Err(InvocationError::Rpc(rpc)) if rpc.name == "CHAT_NOT_MODIFIED" => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn send_unsigned(
+    client: &Client,
+    chat: PackedChat,
+    message: OutgoingMessage,
+    mut progress: Progress,
+) -> Result<Vec<Message>> {
+    let OutgoingMessage { text, entities, mut attachments, link_preview, mut reply, post } = message;
+    let mut sent = Vec::new();
+    // entities of the text if it becomes the caption of a lone attachment,
+    // album captions can't have any
+    let mut caption_entities = Vec::new();
+
+    match attachments.first_mut() {
+        None => {
+            sent.extend(send_text(client, chat, text, entities, link_preview, reply, post).await?);
+            return Ok(sent);
+        }
+        Some(first) if first.caption.is_empty() => {
+            first.caption = text;
+            caption_entities = entities;
+        }
+        Some(_) if !text.is_empty() => {
+            sent.extend(send_text(client, chat, text, entities, link_preview, reply.take(), post).await?);
+        }
+        Some(_) => {}
+    }
+
+    // albums can't mix photos with documents, so only compress if all are photos
+    let as_photos = attachments.iter().all(Attachment::is_photo);
+
+    let mut uploaded = Vec::with_capacity(attachments.len());
+    for attachment in attachments {
+        let file = tokio::fs::File::open(&attachment.path).await?;
+        let size = file.metadata().await?.len() as usize;
+        let mut reader = ProgressReader::new(file, &mut progress);
+        let file = client
+            .upload_stream(&mut reader, size, attachment.name())
+            .await?;
+        uploaded.push((file, attachment.caption));
+    }
+
+    let mut uploaded = uploaded.into_iter().peekable();
+    while uploaded.peek().is_some() {
+        let mut album: Vec<_> = uploaded.by_ref().take(ALBUM_MAX).collect();
+        let entities = std::mem::take(&mut caption_entities);
+        // only the first message sent replies, and only text can quote
+        let reply_to = reply.take().map(|r| r.message_id);
+        if album.len() == 1 {
+            let (file, caption) = album.remove(0);
+            let message = InputMessage::text(caption)
+                .fmt_entities(entities)
+                .reply_to(reply_to)
+                .silent(post.silent)
+                .schedule_date(post.schedule_date());
+            let message = if as_photos {
+                message.photo(file)
+            } else {
+                message.document(file)
+            };
+            sent.push(client.send_message(chat, message).await?);
+        } else {
+            let media = album
+                .into_iter()
+                .map(|(file, caption)| {
+                    let media = InputMedia::caption(caption).reply_to(reply_to);
+                    if as_photos {
+                        media.photo(file)
+                    } else {
+                        media.document(file)
+                    }
+                })
+                .collect();
+            sent.extend(client.send_album(chat, media).await?.into_iter().flatten());
+        }
+    }
+
+    Ok(sent)
+}
+
+/// Send a text message, with a raw call if it quotes the message it
+/// replies to since `InputMessage` can't, fetching the resulting message
+/// then as the raw call only returns updates. None for scheduled ones,
+/// which aren't in the history yet.
+async fn send_text(
+    client: &Client,
+    chat: PackedChat,
+    text: String,
+    entities: Vec<tl::enums::MessageEntity>,
+    link_preview: bool,
+    reply: Option<ReplyTo>,
+    post: PostOptions,
+) -> Result<Option<Message>> {
+    let Some(ReplyTo { message_id, quote: Some(quote) }) = reply else {
+        let message = InputMessage::text(text)
+            .link_preview(link_preview)
+            .fmt_entities(entities)
+            .reply_to(reply.map(|r| r.message_id))
+            .silent(post.silent)
+            .schedule_date(post.schedule_date());
+        return Ok(Some(client.send_message(chat, message).await?));
+    };
+    let random_id = random_id();
+    let updates = client
+        .invoke(&tl::functions::messages::SendMessage {
+            no_webpage: !link_preview,
+            silent: post.silent,
+            background: false,
+            clear_draft: false,
+            noforwards: false,
+            update_stickersets_order: false,
+            invert_media: false,
+            peer: chat.to_input_peer(),
+            reply_to: Some(
+                tl::types::InputReplyToMessage {
+                    reply_to_msg_id: message_id,
+                    top_msg_id: None,
+                    reply_to_peer_id: None,
+                    quote_text: Some(quote.text),
+                    quote_entities: Some(quote.entities).filter(|e| !e.is_empty()),
+                    quote_offset: Some(quote.offset),
+                }
+                .into(),
+            ),
+            message: text,
+            random_id,
+            reply_markup: None,
+            entities: Some(entities).filter(|e| !e.is_empty()),
+            schedule_date: post.schedule.map(|at| at as i32),
+            send_as: None,
+        })
+        .await?;
+    if post.schedule.is_some() {
+        return Ok(None);
+    }
+    sent_message(client, chat, &updates, random_id).await
+}
+
+/// Send raw input media, then fetch the resulting message since the raw call
+/// only returns updates.
+async fn send_media(
+    client: &Client,
+    chat: PackedChat,
+    media: tl::enums::InputMedia,
+) -> Result<Option<Message>> {
+    let random_id = random_id();
+    let updates = client
+        .invoke(&tl::functions::messages::SendMedia {
+            silent: false,
+            background: false,
+            clear_draft: false,
+            noforwards: false,
+            update_stickersets_order: false,
+            invert_media: false,
+            peer: chat.to_input_peer(),
+            reply_to: None,
+            media,
+            message: String::new(),
+            random_id,
+            reply_markup: None,
+            entities: None,
+            schedule_date: None,
+            send_as: None,
+        })
+        .await?;
+    sent_message(client, chat, &updates, random_id).await
+}
+
+/// The message sent with `random_id`, by the id the updates sending it
+/// returned give it. Not simply the newest in the chat, which may be
+/// someone else's by then.
+async fn sent_message(
+    client: &Client,
+    chat: PackedChat,
+    updates: &tl::enums::Updates,
+    random_id: i64,
+) -> Result<Option<Message>> {
+    let id = match updates {
+        tl::enums::Updates::UpdateShortSentMessage(sent) => Some(sent.id),
+        tl::enums::Updates::Updates(tl::types::Updates { updates, .. })
+        | tl::enums::Updates::Combined(tl::types::UpdatesCombined { updates, .. }) => {
+            updates.iter().find_map(|update| match update {
+                tl::enums::Update::MessageId(sent) if sent.random_id == random_id => Some(sent.id),
+                _ => None,
+            })
+        }
+        _ => None,
+    };
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    Ok(client.get_messages_by_id(chat, &[id]).await?.pop().flatten())
+}
+
+fn attachments_size(attachments: &[Attachment]) -> u64 {
+    attachments
+        .iter()
+        .filter_map(|a| std::fs::metadata(&a.path).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Size of the chunks media is downloaded in, resumed downloads start at a
+/// multiple of it.
+const DOWNLOAD_CHUNK_SIZE: u64 = 128 * 1024;
+
+/// Download into a `.part` file next to the destination, written chunk by
+/// chunk and renamed once complete, so a cancelled download never looks
+/// finished. An existing `.part` file (left by a cancelled download or a
+/// previous run) is resumed from its last whole chunk.
+async fn download(client: &Client, media: Media, path: &Path, mut progress: Progress) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+
+    // photo sizes are only known approximately
+    let expected = match &media {
+        Media::Document(document) => Some(document.size() as u64),
+        _ => None,
+    };
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&part)
+        .await?;
+    let existing = file.metadata().await?.len();
+    let skip = existing / DOWNLOAD_CHUNK_SIZE;
+    let offset = skip * DOWNLOAD_CHUNK_SIZE;
+    file.set_len(offset).await?;
+    progress.add(offset);
+
+    let mut chunks = client
+        .iter_download(&Downloadable::Media(media))
+        .chunk_size(DOWNLOAD_CHUNK_SIZE as i32)
+        .skip_chunks(skip as i32);
+    let mut written = offset;
+    while let Some(chunk) = chunks.next().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        progress.add(chunk.len() as u64);
+    }
+    file.flush().await?;
+
+    if let Some(expected) = expected.filter(|&expected| expected != written) {
+        tokio::fs::remove_file(&part).await?;
+        return Err(Tg9Error::Other(format!("expected {} bytes, got {}", expected, written)));
+    }
+    tokio::fs::rename(&part, path).await?;
+    Ok(())
+}
+
+async fn translate(client: &Client, chat: PackedChat, message_id: i32, to_lang: String) -> Result<String> {
+    let tl::enums::messages::TranslatedText::Result(result) = client
+        .invoke(&tl::functions::messages::TranslateText {
+            peer: Some(chat.to_input_peer()),
+            id: Some(vec![message_id]),
+            text: None,
+            to_lang,
+        })
+        .await?;
+    match result.result.into_iter().next() {
+        Some(tl::enums::TextWithEntities::Entities(text)) => Ok(text.text),
+        None => Err(Tg9Error::Other("empty translation".into())),
+    }
+}
+
+/// Run the configured translation command with the target language and
+/// the text appended, its output is the translation.
+async fn translate_external(command: String, to_lang: String, text: String) -> Result<String> {
+    let output = tokio::task::spawn_blocking(move || {
+        crate::external::output(&command, [to_lang, text])
+    })
+    .await?
+    .map_err(|e| Tg9Error::External(e.to_string()))?;
+    Ok(output.trim_end().to_string())
+}
+
+/// Most members loaded for mentions
+const MEMBERS_MAX: usize = 200;
+
+async fn load_members(client: &Client, chat: PackedChat) -> Result<Vec<Member>> {
+    let mut members = Vec::new();
+    let mut participants = client.iter_participants(chat).limit(MEMBERS_MAX);
+    while let Some(participant) = participants.next().await? {
+        let user = participant.user;
+        if user.is_self() || user.deleted() {
+            continue;
+        }
+        members.push(Member {
+            user: user.pack(),
+            name: user.full_name(),
+            username: user.username().map(str::to_string),
+        });
+    }
+    members.sort_by_key(|m| m.name.to_lowercase());
+    Ok(members)
+}
+
+async fn load_contacts(client: &Client) -> Result<Vec<SharedContact>> {
+    let tl::enums::contacts::Contacts::Contacts(contacts) = client
+        .invoke(&tl::functions::contacts::GetContacts { hash: 0 })
+        .await?
+    else {
+        return Ok(Vec::new());
+    };
+    let mut contacts: Vec<_> = contacts
+        .users
+        .into_iter()
+        .filter_map(|user| match user {
+            tl::enums::User::User(user) => Some(SharedContact {
+                phone: user.phone?,
+                first_name: user.first_name.unwrap_or_default(),
+                last_name: user.last_name.unwrap_or_default(),
+            }),
+            tl::enums::User::Empty(_) => None,
+        })
+        .collect();
+    contacts.sort_by_key(|c| c.name().to_lowercase());
+    Ok(contacts)
+}
+
+/// Bare id of the discussion group of a channel, or of the channel a
+/// discussion group belongs to.
+async fn linked_chat(client: &Client, chat: PackedChat) -> Result<Option<i64>> {
+    let Some(channel) = chat.try_to_input_channel() else {
+        return Ok(None);
+    };
+    let tl::enums::messages::ChatFull::Full(full) = client
+        .invoke(&tl::functions::channels::GetFullChannel { channel })
+        .await?;
+    match full.full_chat {
+        tl::enums::ChatFull::ChannelFull(full) => Ok(full.linked_chat_id),
+        tl::enums::ChatFull::Full(_) => Ok(None),
+    }
+}
+
+/// Slow mode interval of a group and the time left until I can send again.
+async fn slow_mode(client: &Client, chat: PackedChat) -> Result<(Option<Duration>, Option<Duration>)> {
+    let Some(channel) = chat.try_to_input_channel() else {
+        return Ok((None, None));
+    };
+    let tl::enums::messages::ChatFull::Full(full) = client
+        .invoke(&tl::functions::channels::GetFullChannel { channel })
+        .await?;
+    let tl::enums::ChatFull::ChannelFull(full) = full.full_chat else {
+        return Ok((None, None));
+    };
+    let interval = full
+        .slowmode_seconds
+        .map(|secs| Duration::from_secs(secs as u64));
+    let wait = full.slowmode_next_send_date.and_then(|date| {
+        let secs = i64::from(date) - Utc::now().timestamp();
+        (secs > 0).then(|| Duration::from_secs(secs as u64))
+    });
+    Ok((interval, wait))
+}
+
+/// Current view and forward counters of messages, without counting me as
+/// a viewer again.
+async fn load_views(client: &Client, chat: PackedChat, message_ids: Vec<i32>) -> Result<Vec<(i32, Views)>> {
+    let tl::enums::messages::MessageViews::Views(views) = client
+        .invoke(&tl::functions::messages::GetMessagesViews {
+            peer: chat.to_input_peer(),
+            id: message_ids.clone(),
+            increment: false,
+        })
+        .await?;
+    // counters come in the order of the requested ids
+    Ok(message_ids
+        .into_iter()
+        .zip(views.views)
+        .map(|(id, tl::enums::MessageViews::Views(views))| {
+            (id, Views { views: views.views, forwards: views.forwards })
+        })
+        .collect())
+}
+
+/// Most comments loaded for a post
+const COMMENTS_MAX: i32 = 100;
+
+async fn load_comments(client: &Client, chat: PackedChat, message_id: i32) -> Result<Vec<ThreadMessage>> {
+    use tl::enums::messages::Messages;
+
+    let (messages, users, chats) = match client
+        .invoke(&tl::functions::messages::GetReplies {
+            peer: chat.to_input_peer(),
+            msg_id: message_id,
+            offset_id: 0,
+            offset_date: 0,
+            add_offset: 0,
+            limit: COMMENTS_MAX,
+            max_id: 0,
+            min_id: 0,
+            hash: 0,
+        })
+        .await?
+    {
+        Messages::Messages(m) => (m.messages, m.users, m.chats),
+        Messages::Slice(m) => (m.messages, m.users, m.chats),
+        Messages::ChannelMessages(m) => (m.messages, m.users, m.chats),
+        Messages::NotModified(_) => return Ok(Vec::new()),
+    };
+
+    let mut names = user_names(users);
+    for chat in chats {
+        match chat {
+            tl::enums::Chat::Chat(chat) => names.insert(chat.id, chat.title),
+            tl::enums::Chat::Channel(channel) => names.insert(channel.id, channel.title),
+            _ => None,
         };
     }
-    Ok(client)
+
+    let mut comments: Vec<_> = messages
+        .into_iter()
+        .filter_map(|message| match message {
+            tl::enums::Message::Message(message) => {
+                // anonymous admins and posts sent as the channel have no sender
+                let sender = message.from_id.as_ref().unwrap_or(&message.peer_id);
+                Some(ThreadMessage {
+                    sender: names.get(&peer_id(sender)).cloned().unwrap_or_default(),
+                    text: message.message,
+                })
+            }
+            _ => None,
+        })
+        .collect();
+    // newest first from the API
+    comments.reverse();
+    Ok(comments)
+}
+
+async fn load_profile(client: &Client) -> Result<Profile> {
+    let me = client.get_me().await?;
+    let tl::enums::users::UserFull::Full(full) = client
+        .invoke(&tl::functions::users::GetFullUser {
+            id: tl::enums::InputUser::UserSelf,
+        })
+        .await?;
+    let tl::enums::UserFull::Full(full_user) = full.full_user;
+    Ok(Profile {
+        first_name: me.first_name().to_string(),
+        last_name: me.last_name().map(String::from),
+        username: me.username().map(String::from),
+        bio: full_user.about.filter(|bio| !bio.is_empty()),
+        phone: me.phone().map(String::from),
+    })
+}
+
+async fn load_user_profile(client: &Client, user: PackedChat) -> Result<Profile> {
+    let Some(id) = user.try_to_input_user() else {
+        return Err(Tg9Error::Other("only users have profiles".into()));
+    };
+    let tl::enums::users::UserFull::Full(full) = client.invoke(&tl::functions::users::GetFullUser { id }).await?;
+    let tl::enums::UserFull::Full(full_user) = full.full_user;
+    let user = full.users.into_iter().find_map(|u| match u {
+        tl::enums::User::User(u) if u.id == user.id => Some(u),
+        _ => None,
+    });
+    let Some(user) = user else {
+        return Err(Tg9Error::Other("Telegram didn't send the user".into()));
+    };
+    Ok(Profile {
+        first_name: user.first_name.unwrap_or_default(),
+        last_name: user.last_name,
+        username: user.username,
+        bio: full_user.about.filter(|bio| !bio.is_empty()),
+        phone: user.phone,
+    })
+}
+
+async fn edit_profile(client: &Client, edit: ProfileEdit) -> Result<()> {
+    match edit {
+        ProfileEdit::Name { first, last } => {
+            client
+                .invoke(&tl::functions::account::UpdateProfile {
+                    first_name: Some(first),
+                    last_name: Some(last),
+                    about: None,
+                })
+                .await?;
+        }
+        ProfileEdit::Bio(bio) => {
+            client
+                .invoke(&tl::functions::account::UpdateProfile {
+                    first_name: None,
+                    last_name: None,
+                    about: Some(bio),
+                })
+                .await?;
+        }
+        ProfileEdit::Username(username) => {
+            client
+                .invoke(&tl::functions::account::UpdateUsername { username })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn edit_chat(client: &Client, chat: PackedChat, edit: ChatEdit) -> Result<()> {
+    let result = match (edit, chat.try_to_input_channel()) {
+        (ChatEdit::Title(title), Some(channel)) => client
+            .invoke(&tl::functions::channels::EditTitle { channel, title })
+            .await
+            .map(drop),
+        (ChatEdit::Title(title), None) => client
+            .invoke(&tl::functions::messages::EditChatTitle { chat_id: chat.id, title })
+            .await
+            .map(drop),
+        (ChatEdit::About(about), _) => client
+            .invoke(&tl::functions::messages::EditChatAbout { peer: chat.to_input_peer(), about })
+            .await
+            .map(drop),
+        (ChatEdit::Permissions(rights), _) => client
+            .invoke(&tl::functions::messages::EditChatDefaultBannedRights {
+                peer: chat.to_input_peer(),
+                banned_rights: rights.into(),
+            })
+            .await
+            .map(drop),
+    };
+    match result {
+        Ok(()) => Ok(()),
+        // it was already
+        Err(InvocationError::Rpc(rpc)) if rpc.name.ends_with("_NOT_MODIFIED") => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Size of the parts files are uploaded in with raw calls
+const UPLOAD_PART_SIZE: usize = 512 * 1024;
+
+/// Upload a photo in parts with raw calls, since setting a chat photo takes
+/// the uploaded file itself rather than media built from it, then set it.
+async fn set_chat_photo(client: &Client, chat: PackedChat, path: &Path, mut progress: Progress) -> Result<()> {
+    let bytes = tokio::fs::read(path).await?;
+    let file_id = random_id();
+    let mut parts = 0;
+    for part in bytes.chunks(UPLOAD_PART_SIZE) {
+        client
+            .invoke(&tl::functions::upload::SaveFilePart {
+                file_id,
+                file_part: parts,
+                bytes: part.to_vec(),
+            })
+            .await?;
+        parts += 1;
+        progress.add(part.len() as u64);
+    }
+    let name = path.file_name().map_or_else(|| "photo.jpg".into(), |name| name.to_string_lossy().into_owned());
+    let file = tl::types::InputFile { id: file_id, parts, name, md5_checksum: String::new() };
+    let photo: tl::enums::InputChatPhoto = tl::types::InputChatUploadedPhoto {
+        file: Some(file.into()),
+        video: None,
+        video_start_ts: None,
+        video_emoji_markup: None,
+    }
+    .into();
+    match chat.try_to_input_channel() {
+        Some(channel) => client.invoke(&tl::functions::channels::EditPhoto { channel, photo }).await?,
+        None => client.invoke(&tl::functions::messages::EditChatPhoto { chat_id: chat.id, photo }).await?,
+    };
+    Ok(())
+}
+
+async fn load_privacy(client: &Client) -> Result<Vec<PrivacySetting>> {
+    let mut settings = Vec::new();
+    for key in PrivacyKey::ALL {
+        let tl::enums::account::PrivacyRules::Rules(rules) = client
+            .invoke(&tl::functions::account::GetPrivacy { key: key.to_input() })
+            .await?;
+        let mut value = PrivacyValue::Nobody;
+        let mut exceptions = 0;
+        for rule in &rules.rules {
+            use tl::enums::PrivacyRule as R;
+            match rule {
+                R::ValueAllowAll => value = PrivacyValue::Everybody,
+                R::ValueAllowContacts => value = PrivacyValue::Contacts,
+                R::ValueAllowUsers(users) => exceptions += users.users.len(),
+                R::ValueDisallowUsers(users) => exceptions += users.users.len(),
+                R::ValueAllowChatParticipants(chats) => exceptions += chats.chats.len(),
+                R::ValueDisallowChatParticipants(chats) => exceptions += chats.chats.len(),
+                _ => {}
+            }
+        }
+        settings.push(PrivacySetting { key, value, exceptions });
+    }
+    Ok(settings)
+}
+
+/// Replace the base rule of a privacy setting, keeping the exceptions
+/// which still mean something with the new one.
+async fn set_privacy(client: &Client, key: PrivacyKey, value: PrivacyValue) -> Result<()> {
+    let tl::enums::account::PrivacyRules::Rules(current) = client
+        .invoke(&tl::functions::account::GetPrivacy { key: key.to_input() })
+        .await?;
+    let access_hashes: HashMap<i64, i64> = current
+        .users
+        .iter()
+        .filter_map(|user| match user {
+            tl::enums::User::User(user) => Some((user.id, user.access_hash?)),
+            _ => None,
+        })
+        .collect();
+    let input_users = |ids: &[i64]| -> Vec<tl::enums::InputUser> {
+        ids.iter()
+            .filter_map(|&user_id| {
+                let access_hash = *access_hashes.get(&user_id)?;
+                Some(tl::types::InputUser { user_id, access_hash }.into())
+            })
+            .collect()
+    };
+    // allowing everybody leaves only users to disallow, and the other way
+    let allow = value != PrivacyValue::Everybody;
+    let disallow = value != PrivacyValue::Nobody;
+    let mut rules: Vec<tl::enums::InputPrivacyRule> = current
+        .rules
+        .iter()
+        .filter_map(|rule| {
+            use tl::enums::PrivacyRule as R;
+            match rule {
+                R::ValueAllowUsers(users) if allow => {
+                    Some(tl::types::InputPrivacyValueAllowUsers { users: input_users(&users.users) }.into())
+                }
+                R::ValueDisallowUsers(users) if disallow => {
+                    Some(tl::types::InputPrivacyValueDisallowUsers { users: input_users(&users.users) }.into())
+                }
+                R::ValueAllowChatParticipants(chats) if allow => {
+                    Some(tl::types::InputPrivacyValueAllowChatParticipants { chats: chats.chats.clone() }.into())
+                }
+                R::ValueDisallowChatParticipants(chats) if disallow => Some(
+                    tl::types::InputPrivacyValueDisallowChatParticipants { chats: chats.chats.clone() }.into(),
+                ),
+                _ => None,
+            }
+        })
+        .collect();
+    rules.push(value.rule());
+    client
+        .invoke(&tl::functions::account::SetPrivacy { key: key.to_input(), rules })
+        .await?;
+    Ok(())
+}
+
+async fn resolve_username(client: &Client, username: &str) -> Result<Option<PackedChat>> {
+    Ok(client.resolve_username(username).await?.map(|chat| chat.pack()))
+}
+
+async fn load_sessions(client: &Client) -> Result<Sessions> {
+    let tl::enums::account::Authorizations::Authorizations(authorizations) =
+        client.invoke(&tl::functions::account::GetAuthorizations {}).await?;
+    let mut sessions: Vec<_> = authorizations
+        .authorizations
+        .into_iter()
+        .map(|tl::enums::Authorization::Authorization(a)| ActiveSession {
+            hash: a.hash,
+            current: a.current,
+            device: format!("{} ({} {})", a.device_model, a.platform, a.system_version),
+            app: format!("{} {}", a.app_name, a.app_version),
+            location: format!("{}, {}", a.ip, a.country),
+            active: DateTime::from_timestamp(a.date_active.into(), 0).unwrap_or_default(),
+        })
+        .collect();
+    // this one first, then the most recently active
+    sessions.sort_by_key(|s| (!s.current, std::cmp::Reverse(s.active)));
+    Ok(Sessions {
+        ttl_days: authorizations.authorization_ttl_days,
+        password: get_password(client).await?.has_password,
+        sessions,
+    })
+}
+
+async fn get_password(client: &Client) -> Result<tl::types::account::Password> {
+    let tl::enums::account::Password::Password(info) = client.invoke(&tl::functions::account::GetPassword {}).await?;
+    Ok(info)
+}
+
+async fn load_password(client: &Client) -> Result<PasswordState> {
+    let info = get_password(client).await?;
+    Ok(PasswordState {
+        has_password: info.has_password,
+        hint: info.hint.filter(|hint| !hint.is_empty()),
+        has_recovery: info.has_recovery,
+        unconfirmed_email: info.email_unconfirmed_pattern.filter(|pattern| !pattern.is_empty()),
+    })
+}
+
+async fn edit_password(client: &Client, edit: PasswordEdit) -> Result<()> {
+    let (current, settings) = match edit {
+        PasswordEdit::ConfirmEmail { code } => {
+            client.invoke(&tl::functions::account::ConfirmPasswordEmail { code }).await?;
+            return Ok(());
+        }
+        PasswordEdit::ResendEmail => {
+            client.invoke(&tl::functions::account::ResendPasswordEmail {}).await?;
+            return Ok(());
+        }
+        PasswordEdit::CancelEmail => {
+            client.invoke(&tl::functions::account::CancelPasswordEmail {}).await?;
+            return Ok(());
+        }
+        PasswordEdit::Set { current, new, hint, email } => {
+            let info = get_password(client).await?;
+            let (new_algo, new_password_hash) = two_factor::new_password(&info, &new)?;
+            let settings = tl::types::account::PasswordInputSettings {
+                new_algo: Some(new_algo),
+                new_password_hash: Some(new_password_hash),
+                hint: Some(hint),
+                email,
+                new_secure_settings: None,
+            };
+            (current, settings)
+        }
+        PasswordEdit::Disable { current } => {
+            let settings = tl::types::account::PasswordInputSettings {
+                new_algo: Some(tl::enums::PasswordKdfAlgo::Unknown),
+                new_password_hash: Some(Vec::new()),
+                hint: Some(String::new()),
+                email: Some(String::new()),
+                new_secure_settings: None,
+            };
+            (Some(current), settings)
+        }
+        PasswordEdit::Email { current, email } => {
+            let settings = tl::types::account::PasswordInputSettings {
+                new_algo: None,
+                new_password_hash: None,
+                hint: None,
+                email: Some(email),
+                new_secure_settings: None,
+            };
+            (Some(current), settings)
+        }
+    };
+    // checked against fresh SRP parameters, they're good for one try
+    let info = get_password(client).await?;
+    let password = two_factor::check(&info, current.as_deref())?;
+    let result = client
+        .invoke(&tl::functions::account::UpdatePasswordSettings {
+            password,
+            new_settings: tl::enums::account::PasswordInputSettings::Settings(settings),
+        })
+        .await;
+    match result {
+        Ok(_) => Ok(()),
+        // done, but the recovery email waits for the code sent to it
+        Err(InvocationError::Rpc(rpc)) if rpc.name == "EMAIL_UNCONFIRMED" => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn report_spam(client: &Client, chat: PackedChat, message_id: Option<i32>) -> Result<()> {
+    match message_id {
+        Some(message_id) => {
+            client
+                .invoke(&tl::functions::messages::Report {
+                    peer: chat.to_input_peer(),
+                    id: vec![message_id],
+                    reason: tl::enums::InputReportReason::Spam,
+                    message: String::new(),
+                })
+                .await?;
+        }
+        None => {
+            client
+                .invoke(&tl::functions::messages::ReportSpam { peer: chat.to_input_peer() })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Delete the messages of a user in a supergroup, in batches until none
+/// is left, then ban them so they can't rejoin.
+async fn delete_and_ban(client: &Client, chat: PackedChat, user: PackedChat) -> Result<()> {
+    let Some(channel) = chat.try_to_input_channel() else {
+        return Err(Tg9Error::Other("only supergroups can ban".into()));
+    };
+    loop {
+        let tl::enums::messages::AffectedHistory::History(affected) = client
+            .invoke(&tl::functions::channels::DeleteParticipantHistory {
+                channel: channel.clone(),
+                participant: user.to_input_peer(),
+            })
+            .await?;
+        if affected.offset <= 0 {
+            break;
+        }
+    }
+    client
+        .invoke(&tl::functions::channels::EditBanned {
+            channel,
+            participant: user.to_input_peer(),
+            banned_rights: tl::types::ChatBannedRights {
+                view_messages: true,
+                ..no_banned_rights()
+            }
+            .into(),
+        })
+        .await?;
+    Ok(())
+}
+
+/// Most admin log entries loaded at once
+const ADMIN_LOG_MAX: i32 = 100;
+
+async fn load_admin_log(client: &Client, chat: PackedChat) -> Result<Vec<AdminLogEntry>> {
+    let Some(channel) = chat.try_to_input_channel() else {
+        return Ok(Vec::new());
+    };
+    let tl::enums::channels::AdminLogResults::Results(results) = client
+        .invoke(&tl::functions::channels::GetAdminLog {
+            channel,
+            q: String::new(),
+            events_filter: None,
+            admins: None,
+            max_id: 0,
+            min_id: 0,
+            limit: ADMIN_LOG_MAX,
+        })
+        .await?;
+
+    let names = user_names(results.users);
+    Ok(results
+        .events
+        .into_iter()
+        .map(|event| {
+            let tl::enums::ChannelAdminLogEvent::Event(event) = event;
+            AdminLogEntry {
+                date: DateTime::from_timestamp(event.date.into(), 0).unwrap_or_default(),
+                user: names.get(&event.user_id).cloned().unwrap_or_default(),
+                action: event.action,
+            }
+        })
+        .collect())
+}
+
+/// Most join requests loaded at once
+const JOIN_REQUESTS_MAX: i32 = 100;
+
+async fn load_join_requests(client: &Client, chat: PackedChat) -> Result<Vec<JoinRequest>> {
+    let tl::enums::messages::ChatInviteImporters::Importers(importers) = client
+        .invoke(&tl::functions::messages::GetChatInviteImporters {
+            requested: true,
+            subscription_expired: false,
+            peer: chat.to_input_peer(),
+            link: None,
+            q: None,
+            offset_date: 0,
+            offset_user: tl::enums::InputUser::Empty,
+            limit: JOIN_REQUESTS_MAX,
+        })
+        .await?;
+    let users: HashMap<i64, tl::types::User> = importers
+        .users
+        .into_iter()
+        .filter_map(|user| match user {
+            tl::enums::User::User(user) => Some((user.id, user)),
+            tl::enums::User::Empty(_) => None,
+        })
+        .collect();
+    let mut requests: Vec<_> = importers
+        .importers
+        .into_iter()
+        .filter_map(|importer| {
+            let tl::enums::ChatInviteImporter::Importer(importer) = importer;
+            let user = users.get(&importer.user_id)?;
+            let name = format!(
+                "{} {}",
+                user.first_name.as_deref().unwrap_or_default(),
+                user.last_name.as_deref().unwrap_or_default()
+            );
+            Some(JoinRequest {
+                user: tl::types::InputUser {
+                    user_id: user.id,
+                    access_hash: user.access_hash.unwrap_or_default(),
+                },
+                name: name.trim().to_string(),
+                username: user.username.clone(),
+                date: DateTime::from_timestamp(importer.date.into(), 0).unwrap_or_default(),
+                about: importer.about.filter(|about| !about.is_empty()),
+            })
+        })
+        .collect();
+    requests.sort_by_key(|request| request.date);
+    Ok(requests)
+}
+
+/// Full names of users by id.
+fn user_names(users: Vec<tl::enums::User>) -> HashMap<i64, String> {
+    users
+        .into_iter()
+        .filter_map(|user| match user {
+            tl::enums::User::User(user) => {
+                let name = format!(
+                    "{} {}",
+                    user.first_name.unwrap_or_default(),
+                    user.last_name.unwrap_or_default()
+                );
+                Some((user.id, name.trim().to_string()))
+            }
+            tl::enums::User::Empty(_) => None,
+        })
+        .collect()
+}
+
+pub fn contact(contact: &SharedContact) -> tl::enums::InputMedia {
+    tl::types::InputMediaContact {
+        phone_number: contact.phone.clone(),
+        first_name: contact.first_name.clone(),
+        last_name: contact.last_name.clone(),
+        vcard: String::new(),
+    }
+    .into()
+}
+
+pub fn poll(poll: &NewPoll) -> tl::enums::InputMedia {
+    let answers = poll
+        .options
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            tl::types::PollAnswer {
+                text: text.clone(),
+                // identifies the option in the votes
+                option: vec![i as u8],
+            }
+            .into()
+        })
+        .collect();
+    tl::types::InputMediaPoll {
+        poll: tl::types::Poll {
+            id: 0,
+            closed: false,
+            public_voters: poll.public_voters,
+            multiple_choice: poll.multiple_choice,
+            quiz: false,
+            question: poll.question.clone(),
+            answers,
+            close_period: None,
+            close_date: None,
+        }
+        .into(),
+        correct_answers: None,
+        solution: None,
+        solution_entities: None,
+    }
+    .into()
+}
+
+pub fn geo_point(lat: f64, lon: f64) -> tl::enums::InputMedia {
+    tl::types::InputMediaGeoPoint {
+        geo_point: tl::types::InputGeoPoint {
+            lat,
+            long: lon,
+            accuracy_radius: None,
+        }
+        .into(),
+    }
+    .into()
+}
+
+/// Bare id of a user, group or channel.
+pub fn peer_id(peer: &tl::enums::Peer) -> i64 {
+    match peer {
+        tl::enums::Peer::User(p) => p.user_id,
+        tl::enums::Peer::Chat(p) => p.chat_id,
+        tl::enums::Peer::Channel(p) => p.channel_id,
+    }
+}
+
+/// Random id Telegram uses to deduplicate sent messages.
+fn random_id() -> i64 {
+    RandomState::new().build_hasher().finish() as i64
 }
@@ -0,0 +1,3744 @@
+use crate::action::Action;
+use crate::api::{
+    self, AfterDownload, ApiEvent, ApiJob, ChatEdit, ChatRight, Member, ProfileEdit, SessionItem, ThreadMessage, Views,
+    SESSION_TTL_DAYS,
+};
+use crate::bidi;
+use crate::cache::{self, CacheKind, MediaCache};
+use crate::command;
+use crate::compose::{Attachment, Draft, Input, Mention, PostOption, PostOptions, ReplyTo};
+use crate::config::{self, Bell, Config, Presence};
+use crate::crash;
+use crate::diff;
+use crate::download;
+use crate::emoji;
+use crate::error::Tg9Error;
+use crate::external;
+use crate::highlight;
+use crate::help;
+use crate::filter::MessageFilter;
+use crate::folders::Folder;
+use crate::hooks::{self, HookEvent, HookMessage};
+use crate::limits::{self, Limits};
+use crate::macros::{Awaiting, Macros};
+use crate::metrics::{self, Metrics};
+use crate::notify;
+use crate::outbox::{OutgoingMessage, Outbox};
+use crate::peers::PeerStore;
+use crate::plugin::{self, Plugin};
+use crate::render::{self, TextFormat};
+use crate::state::State;
+use crate::stats::Stats;
+use crate::status::UnreadStatus;
+use crate::popup::{Confirm, Forward, Info, JoinRequests, Pager, PasswordForm, Picker, Popup, QuoteSelection};
+use crate::transfer::{Direction, Transfer, TransferId};
+use crate::usage::ChatUsage;
+use crate::video_note;
+use grammers_client::grammers_tl_types::enums::{Dialog as RawDialog, MessageAction, MessageFwdHeader};
+use grammers_client::grammers_tl_types::types::{
+    Channel as RawChannel, ChatAdminRights as RawAdminRights, ChatBannedRights as RawBannedRights,
+};
+use grammers_client::types::media::Document;
+use grammers_client::types::{Chat, Dialog, Media, Message};
+use grammers_session::PackedChat;
+use std::cmp;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+pub struct ChatState {
+    pub chat: PackedChat,
+    pub dialog: Dialog,
+    pub messages: VecDeque<Message>,
+    pub draft: Draft,
+    /// The peer posted a story which hasn't been dismissed yet
+    pub new_story: bool,
+    /// Selected message, an index into `messages` (0 is the newest)
+    pub selected: Option<usize>,
+    /// Translations shown under messages, by message id
+    pub translations: HashMap<i32, String>,
+    /// Why the chat can't be read or written to, if so
+    pub unavailable: Option<String>,
+    /// Least time between my messages, if the group has slow mode
+    pub slow_mode: Option<Duration>,
+    /// Slow mode allows sending again then
+    pub slow_mode_until: Option<Instant>,
+    /// Members of the group to mention, once loaded
+    pub members: Option<Vec<Member>>,
+    /// Messages not read yet
+    pub unread: i32,
+    /// Marked as unread by hand, to follow up on later
+    pub marked_unread: bool,
+    /// Unread messages mentioning me
+    pub mentions: i32,
+    /// Counters of channel posts refreshed since they were loaded, by
+    /// message id
+    pub views: HashMap<i32, Views>,
+    /// Counters of the posts around the selection were last refreshed then
+    views_refreshed: Option<Instant>,
+    /// Threads expanded under their message, by message id; `None` while
+    /// the replies load
+    pub threads: HashMap<i32, Option<Vec<ThreadMessage>>>,
+    /// Shown in the pinned bar above the messages
+    pub pinned: Option<Message>,
+    /// Requests to join the chat waiting for an admin, as last told
+    pub join_requests: i32,
+    /// Messages are deleted this many seconds after they're sent, if set
+    pub ttl_period: Option<i32>,
+    /// Messages replied to which aren't in the loaded history, by id;
+    /// `None` if deleted
+    pub replies: HashMap<i32, Option<Message>>,
+    /// Start of the range selected in visual mode, by message id
+    pub visual_anchor: Option<i32>,
+    /// Messages added to the visual selection one by one, by id
+    pub marked: BTreeSet<i32>,
+    /// Only the messages matching are shown
+    pub filter: Option<MessageFilter>,
+    /// Older messages were dropped to keep within `history_max`, so
+    /// reaching the oldest loaded one loads more
+    pub trimmed: bool,
+    /// Messages older than this id were asked for last, so they aren't
+    /// asked for again while they load
+    older_requested: Option<i32>,
+}
+
+impl ChatState {
+    pub fn new(dialog: Dialog) -> ChatState {
+        let chat = dialog.chat().pack();
+        let unavailable = unavailable_reason(dialog.chat());
+        let (unread, marked_unread, mentions) = unread_counts(&dialog);
+        let ttl_period = ttl_period(&dialog);
+        ChatState {
+            dialog,
+            messages: VecDeque::new(),
+            chat,
+            draft: Draft::default(),
+            new_story: false,
+            selected: None,
+            translations: HashMap::new(),
+            unavailable,
+            slow_mode: None,
+            slow_mode_until: None,
+            members: None,
+            unread,
+            marked_unread,
+            mentions,
+            views: HashMap::new(),
+            views_refreshed: None,
+            threads: HashMap::new(),
+            pinned: None,
+            join_requests: 0,
+            ttl_period,
+            replies: HashMap::new(),
+            visual_anchor: None,
+            marked: BTreeSet::new(),
+            filter: None,
+            trimmed: false,
+            older_requested: None,
+        }
+    }
+
+    /// Take the chat and counters of the dialog loaded again, keeping the
+    /// messages, draft and selection.
+    pub fn refresh_dialog(&mut self, dialog: Dialog) {
+        (self.unread, self.marked_unread, self.mentions) = unread_counts(&dialog);
+        self.ttl_period = ttl_period(&dialog);
+        if let Some(reason) = unavailable_reason(dialog.chat()) {
+            self.unavailable = Some(reason);
+        }
+        self.chat = dialog.chat().pack();
+        self.dialog = dialog;
+    }
+
+    /// Remove the loaded messages matching `remove`, with everything shown
+    /// under them.
+    pub fn remove_messages(&mut self, remove: impl Fn(&Message) -> bool) -> VecDeque<Message> {
+        let (removed, kept): (VecDeque<_>, VecDeque<_>) = self.messages.drain(..).partition(|m| remove(m));
+        self.messages = kept;
+        for message in &removed {
+            self.translations.remove(&message.id());
+            self.views.remove(&message.id());
+            self.threads.remove(&message.id());
+        }
+        if self.selected.is_some_and(|i| i >= self.messages.len()) {
+            self.selected = self.messages.len().checked_sub(1);
+        }
+        removed
+    }
+
+    /// Drop the oldest messages past `max`, except the selected one and
+    /// newer, with everything shown under them.
+    pub fn trim(&mut self, max: usize) {
+        let keep = max.max(self.selected.map_or(0, |i| i + 1));
+        if max == 0 || self.messages.len() <= keep {
+            return;
+        }
+        self.messages.truncate(keep);
+        self.trimmed = true;
+        let oldest = self.messages.back().map_or(i32::MAX, |m| m.id());
+        self.translations.retain(|&id, _| id >= oldest);
+        self.views.retain(|&id, _| id >= oldest);
+        self.threads.retain(|&id, _| id >= oldest);
+        let replied: HashSet<_> = self.messages.iter().filter_map(|m| m.reply_to_message_id()).collect();
+        self.replies.retain(|id, _| replied.contains(id));
+    }
+
+    /// Id of the oldest loaded message to load older ones than, if some
+    /// were dropped and they aren't being loaded already.
+    pub fn older_to_load(&mut self) -> Option<i32> {
+        let oldest = self.messages.back()?.id();
+        if !self.trimmed || self.older_requested == Some(oldest) {
+            return None;
+        }
+        self.older_requested = Some(oldest);
+        Some(oldest)
+    }
+
+    /// The message with this id, whether it's in the history or was
+    /// loaded as replied to. `None` if it isn't loaded, `Some(None)` if it
+    /// was deleted.
+    pub fn replied(&self, id: i32) -> Option<Option<&Message>> {
+        match self.messages.iter().find(|m| m.id() == id) {
+            Some(message) => Some(Some(message)),
+            None => self.replies.get(&id).map(Option::as_ref),
+        }
+    }
+
+    /// Byte ranges in the compose text of mentions which resolve to a
+    /// member: mentions by name and `@username`s of known members.
+    pub fn mention_highlights(&self) -> Vec<Range<usize>> {
+        let text = &self.draft.input.text;
+        let mut ranges: Vec<_> = self.draft.mention_ranges().into_iter().map(|(r, _)| r).collect();
+        let members = self.members.as_deref().unwrap_or_default();
+        for (start, _) in text.match_indices('@') {
+            let at_word_start = text[..start].chars().next_back().is_none_or(char::is_whitespace);
+            let len = text[start + 1..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(text.len() - start - 1);
+            let username = &text[start + 1..start + 1 + len];
+            let known = members.iter().any(|m| {
+                m.username.as_ref().is_some_and(|u| u.eq_ignore_ascii_case(username))
+            });
+            if at_word_start && len > 0 && known {
+                ranges.push(start..start + 1 + len);
+            }
+        }
+        ranges.sort_by_key(|r| r.start);
+        ranges
+    }
+
+    /// Time left until slow mode allows sending again.
+    pub fn slow_mode_wait(&self) -> Option<Duration> {
+        let left = self.slow_mode_until?.checked_duration_since(Instant::now())?;
+        (!left.is_zero()).then_some(left)
+    }
+
+    /// Whether the dialog has unread messages or is marked as unread.
+    pub fn is_unread(&self) -> bool {
+        self.unread > 0 || self.marked_unread
+    }
+
+    /// Whether the compose box is written right to left.
+    pub fn compose_rtl(&self, config: &Config) -> bool {
+        let chat_config = config.chat(self.dialog.chat().id());
+        chat_config
+            .and_then(|c| c.rtl)
+            .unwrap_or_else(|| bidi::is_rtl(&self.draft.input.text))
+    }
+
+    pub fn selected_message(&self) -> Option<&Message> {
+        self.selected.and_then(|i| self.messages.get(i))
+    }
+
+    /// Whether the filter, if any, lets the message through.
+    pub fn shown(&self, message: &Message) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(message))
+    }
+
+    /// Indices into `messages` of those shown, newest first.
+    pub fn shown_messages(&self) -> Vec<usize> {
+        (0..self.messages.len()).filter(|&i| self.shown(&self.messages[i])).collect()
+    }
+
+    /// Loaded messages among `ids`, oldest first.
+    pub fn messages_by_id(&self, ids: &[i32]) -> Vec<&Message> {
+        self.messages.iter().rev().filter(|m| ids.contains(&m.id())).collect()
+    }
+
+    /// Ids of the messages selected in visual mode, oldest first: those
+    /// from the anchor to the selected one, and the marked ones.
+    pub fn visual_selection(&self) -> Vec<i32> {
+        let mut ids = self.marked.clone();
+        let anchor = self.visual_anchor.and_then(|id| self.messages.iter().position(|m| m.id() == id));
+        if let Some(selected) = self.selected {
+            let anchor = anchor.unwrap_or(selected);
+            let range = cmp::min(anchor, selected)..=cmp::max(anchor, selected);
+            ids.extend(self.messages.range(range).filter(|m| self.shown(m)).map(|m| m.id()));
+        }
+        ids.into_iter().collect()
+    }
+
+    fn clear_visual(&mut self) {
+        self.visual_anchor = None;
+        self.marked.clear();
+    }
+
+    /// View and forward counters of a message, the refreshed ones if any.
+    pub fn views(&self, message: &Message) -> Views {
+        self.views.get(&message.id()).copied().unwrap_or(Views {
+            views: message.view_count(),
+            forwards: message.forward_count(),
+        })
+    }
+
+    /// Ids of the posts with counters around the selection (or the newest
+    /// ones), roughly those on screen.
+    fn visible_posts(&self) -> Vec<i32> {
+        let center = self.selected.unwrap_or(0);
+        let start = center.saturating_sub(VIEWS_AROUND);
+        self.messages
+            .iter()
+            .skip(start)
+            .take(2 * VIEWS_AROUND)
+            .filter(|message| message.view_count().is_some())
+            .map(|message| message.id())
+            .collect()
+    }
+}
+
+/// Whether the chat is a group or supergroup, rather than a private chat
+/// or a broadcast channel.
+fn is_group(chat: &Chat) -> bool {
+    match chat {
+        Chat::Group(_) => true,
+        Chat::Channel(channel) => channel.raw.megagroup,
+        Chat::User(_) => false,
+    }
+}
+
+/// Raw channel of a supergroup, which grammers gives as a group rather
+/// than a channel.
+fn supergroup(chat: &Chat) -> Option<&RawChannel> {
+    match chat {
+        Chat::Group(group) => match &group.raw {
+            grammers_client::grammers_tl_types::enums::Chat::Channel(raw) => Some(raw),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether I'm the creator or an admin of a group or channel.
+fn is_admin(chat: &Chat) -> bool {
+    match chat {
+        Chat::Channel(channel) => channel.raw.creator || channel.raw.admin_rights.is_some(),
+        Chat::Group(group) => match &group.raw {
+            grammers_client::grammers_tl_types::enums::Chat::Chat(raw) => {
+                raw.creator || raw.admin_rights.is_some()
+            }
+            grammers_client::grammers_tl_types::enums::Chat::Channel(raw) => {
+                raw.creator || raw.admin_rights.is_some()
+            }
+            _ => false,
+        },
+        Chat::User(_) => false,
+    }
+}
+
+/// Whether I'm the creator of a group or channel, or an admin `granted`
+/// tells has the right.
+fn has_admin_right(chat: &Chat, granted: fn(&RawAdminRights) -> bool) -> bool {
+    use grammers_client::grammers_tl_types::enums::{Chat as RawChat, ChatAdminRights};
+
+    let (creator, rights) = match chat {
+        Chat::Channel(channel) => (channel.raw.creator, &channel.raw.admin_rights),
+        Chat::Group(group) => match &group.raw {
+            RawChat::Chat(raw) => (raw.creator, &raw.admin_rights),
+            RawChat::Channel(raw) => (raw.creator, &raw.admin_rights),
+            _ => return false,
+        },
+        Chat::User(_) => return false,
+    };
+    creator || matches!(rights, Some(ChatAdminRights::Rights(rights)) if granted(rights))
+}
+
+/// What the members of a group may not do, unless they're allowed more.
+fn default_banned_rights(chat: &Chat) -> RawBannedRights {
+    use grammers_client::grammers_tl_types::enums::{Chat as RawChat, ChatBannedRights};
+
+    let rights = match chat {
+        Chat::Channel(channel) => channel.raw.default_banned_rights.clone(),
+        Chat::Group(group) => match &group.raw {
+            RawChat::Chat(raw) => raw.default_banned_rights.clone(),
+            RawChat::Channel(raw) => raw.default_banned_rights.clone(),
+            _ => None,
+        },
+        Chat::User(_) => None,
+    };
+    match rights {
+        Some(ChatBannedRights::Rights(rights)) => rights,
+        None => api::no_banned_rights(),
+    }
+}
+
+/// Why a dialog's chat can't be used, as far as is known from the chat itself.
+fn unavailable_reason(chat: &Chat) -> Option<String> {
+    use grammers_client::grammers_tl_types::enums::{ChatBannedRights, RestrictionReason};
+    use grammers_client::grammers_tl_types::enums::Chat as RawChat;
+
+    match chat {
+        Chat::User(user) if user.raw.deleted => Some("deleted account".into()),
+        Chat::User(user) if user.raw.restricted => Some("this account is restricted".into()),
+        Chat::User(_) => None,
+        Chat::Group(group) => match &group.raw {
+            RawChat::Forbidden(_) => Some("you were removed from this group".into()),
+            RawChat::Chat(raw) if raw.deactivated => {
+                Some("this group was deactivated or upgraded to a supergroup".into())
+            }
+            _ => None,
+        },
+        Chat::Channel(channel) => {
+            let raw = &channel.raw;
+            if raw.restricted {
+                let text = raw
+                    .restriction_reason
+                    .iter()
+                    .flatten()
+                    .map(|RestrictionReason::Reason(r)| r.text.clone())
+                    .next();
+                return Some(text.unwrap_or_else(|| "this channel is restricted".into()));
+            }
+            match &raw.banned_rights {
+                Some(ChatBannedRights::Rights(rights)) if rights.view_messages => {
+                    Some("you were banned from this chat".into())
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Pane that receives movement keys in normal mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Dialogs,
+    Chat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    /// Typing into the compose box of the current chat
+    Insert,
+    /// Typing a `:` command
+    Command,
+    /// Selecting messages in the chat view for a bulk action
+    Visual,
+}
+
+/// Queued messages are sent again this often while the connection is down
+const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Counters of posts on screen are refreshed this often
+const VIEWS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Posts on each side of the selection whose counters are refreshed
+const VIEWS_AROUND: usize = 20;
+
+/// Notification sounds are played at most this often, so a burst of
+/// messages doesn't play a burst of sounds
+const SOUND_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Being online is told to Telegram again this often, since it forgets
+/// after a few minutes
+const PRESENCE_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Longest history of opened chats kept for jumping back
+const JUMP_LIST_MAX: usize = 100;
+
+/// Do not disturb: notifications are suppressed while it lasts.
+#[derive(Debug, Clone, Copy)]
+pub struct Dnd {
+    /// Turns itself off at this time, if set
+    pub until: Option<Instant>,
+}
+
+impl Dnd {
+    /// Time left, or `None` if on until turned off.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.until.map(|until| until.saturating_duration_since(Instant::now()))
+    }
+
+    fn is_over(&self) -> bool {
+        self.until.is_some_and(|until| Instant::now() >= until)
+    }
+}
+
+/// Unread messages, the unread mark and unread mentions of a dialog.
+fn unread_counts(dialog: &Dialog) -> (i32, bool, i32) {
+    match &dialog.dialog {
+        RawDialog::Dialog(d) => (d.unread_count, d.unread_mark, d.unread_mentions_count),
+        RawDialog::Folder(_) => (0, false, 0),
+    }
+}
+
+/// Seconds after which the messages of a dialog are deleted, if they are.
+fn ttl_period(dialog: &Dialog) -> Option<i32> {
+    match &dialog.dialog {
+        RawDialog::Dialog(d) => d.ttl_period,
+        RawDialog::Folder(_) => None,
+    }
+}
+
+/// Second window showing a chat next to the current one
+#[derive(Debug, Clone, Copy)]
+pub struct Split {
+    /// Chat of the other window, an index into `chat_states`
+    pub dialog_idx: usize,
+    /// The current window is the right one
+    pub current_right: bool,
+}
+
+pub struct App {
+    pub quit: bool,
+    pub chat_states: VecDeque<ChatState>,
+    pub dialog_idx: Option<usize>,
+    /// Only dialogs with unread messages are listed
+    pub unread_only: bool,
+    pub split: Option<Split>,
+    /// Working set of chats shown in the tabline
+    pub tabs: Vec<PackedChat>,
+    /// Width of the dialog list in percent of the screen
+    pub dialogs_width: u16,
+    /// Emoji picked last, most recent first
+    recent_emoji: Vec<String>,
+    /// State of the last session, restored as its dialogs load
+    saved_state: State,
+    // chat_idxs: HashMap<usize, Option<usize>>,
+    /// Chats in the order they were opened, for jumping back and forth
+    jump_list: Vec<PackedChat>,
+    /// Position in `jump_list` of the current chat
+    jump_idx: usize,
+    pub mode: Mode,
+    pub focus: Focus,
+    pub command: Input,
+    /// Message shown in the status bar until the next one replaces it
+    pub status: Option<String>,
+    pub popup: Option<Popup>,
+    dnd: Option<Dnd>,
+    /// Status bar is highlighted until then, as a visual bell
+    pub flash_until: Option<Instant>,
+    /// When the last notification sound was played
+    sound_played: Option<Instant>,
+    /// Message text is hidden, e.g. while sharing the screen
+    pub redact: bool,
+    /// Started with `--read-only`, see also the `read_only` option
+    pub read_only: bool,
+    pub config: Config,
+    /// Command to run in the terminal in place of the UI until it exits
+    pub foreground: Option<std::process::Command>,
+    /// Connected to Telegram, until then jobs wait
+    pub connected: bool,
+    /// Whether my account has Telegram Premium, once known
+    pub premium: Option<bool>,
+    pub cache: MediaCache,
+    /// Files being downloaded into the media cache
+    pending_downloads: HashSet<PathBuf>,
+    /// Uploads and downloads in flight, oldest first
+    pub transfers: Vec<Transfer>,
+    next_transfer_id: TransferId,
+    jobs: mpsc::UnboundedSender<ApiJob>,
+    /// Passphrases typed to unlock an encrypted session while connecting
+    pub passphrases: Option<mpsc::UnboundedSender<String>>,
+    plugins: Vec<Box<dyn Plugin>>,
+    /// Unread counts last written for `tg9 status`
+    unread_status: Option<UnreadStatus>,
+    /// Peers seen so far, to reach them again
+    pub peers: PeerStore,
+    /// How much each chat is used, to rank the chat switcher
+    pub usage: ChatUsage,
+    /// Files saved from messages, by chat and message id
+    saved_files: HashMap<(i64, i32), PathBuf>,
+    /// Messages picked in visual mode to export, while the file is typed
+    exporting: Option<(PackedChat, Vec<i32>)>,
+    /// Messages that failed to send
+    pub outbox: Outbox,
+    /// When the queued messages were last sent again on their own
+    outbox_retried: Option<Instant>,
+    pub metrics: Metrics,
+    /// The debug overlay with the metrics is shown
+    pub show_metrics: bool,
+    pub macros: Macros,
+    /// Local changes to take back, the last one last
+    undo: Vec<Undo>,
+    /// Chats left out of the dialog list, by bare id
+    hidden: HashSet<i64>,
+    /// The terminal has the focus, as far as it tells
+    pub focused: bool,
+    /// Whether I was last told to be online, and when
+    presence_sent: Option<(bool, Instant)>,
+    /// My chat folders in order, for the summary bar
+    pub folders: Vec<Folder>,
+}
+
+/// Local changes kept to take back, past which the oldest are forgotten
+const UNDO_MAX: usize = 50;
+
+/// A local change `u` takes back.
+enum Undo {
+    CloseTab { idx: usize, chat: PackedChat },
+    ClearDraft { chat: PackedChat, draft: Draft },
+    /// The unread and mention counts of chats marked as read
+    MarkRead(Vec<(PackedChat, i32, i32)>),
+    HideDialog(i64),
+}
+
+impl Undo {
+    fn label(&self) -> &'static str {
+        match self {
+            Undo::CloseTab { .. } => "closing the tab",
+            Undo::ClearDraft { .. } => "clearing the draft",
+            Undo::MarkRead(_) => "marking as read",
+            Undo::HideDialog(_) => "hiding the dialog",
+        }
+    }
+}
+
+impl App {
+    pub fn new(config: Config, state: State, jobs: mpsc::UnboundedSender<ApiJob>) -> Self {
+        App {
+            dialogs_width: state.dialogs_width,
+            hidden: state.hidden.iter().copied().collect(),
+            recent_emoji: state.recent_emoji.clone(),
+            saved_state: state,
+            cache: MediaCache::new(config.media_cache_size),
+            quit: false,
+            chat_states: VecDeque::new(),
+            dialog_idx: None,
+            unread_only: false,
+            split: None,
+            tabs: Vec::new(),
+            jump_list: Vec::new(),
+            jump_idx: 0,
+            mode: Mode::Normal,
+            focus: Focus::Dialogs,
+            command: Input::default(),
+            status: None,
+            popup: None,
+            dnd: None,
+            flash_until: None,
+            sound_played: None,
+            redact: false,
+            read_only: false,
+            foreground: None,
+            connected: false,
+            focused: true,
+            presence_sent: None,
+            folders: Vec::new(),
+            premium: None,
+            config,
+            pending_downloads: HashSet::new(),
+            transfers: Vec::new(),
+            next_transfer_id: 0,
+            jobs,
+            passphrases: None,
+            plugins: plugin::plugins(),
+            unread_status: None,
+            peers: PeerStore::load(),
+            usage: ChatUsage::load(),
+            saved_files: HashMap::new(),
+            exporting: None,
+            outbox: Outbox::load(),
+            outbox_retried: None,
+            metrics: Metrics::default(),
+            show_metrics: false,
+            macros: Macros::default(),
+            undo: Vec::new(),
+        }
+    }
+
+    /// UI state to save on exit.
+    pub fn state(&self) -> State {
+        let chat_id = |idx: usize| self.chat_states.get(idx).map(|c| c.chat.id);
+        State {
+            // unless quitting before the dialogs loaded
+            chat: self.dialog_idx.and_then(chat_id).or(self.saved_state.chat),
+            split: self.split.and_then(|s| chat_id(s.dialog_idx)),
+            tabs: self.tabs.iter().map(|c| c.id).collect(),
+            dialogs_width: self.dialogs_width,
+            recent_emoji: self.recent_emoji.clone(),
+            hidden: self.hidden.iter().copied().collect(),
+        }
+    }
+
+    /// Reopen what was open in the last session if the dialog at `idx` was
+    /// part of it.
+    fn restore_dialog(&mut self, idx: usize) {
+        let chat = self.chat_states[idx].chat;
+        if self.saved_state.chat == Some(chat.id) && self.dialog_idx.is_none() {
+            self.select_dialog(idx);
+        }
+        if self.saved_state.split == Some(chat.id) && self.split.is_none() {
+            self.split = Some(Split {
+                dialog_idx: idx,
+                current_right: true,
+            });
+        }
+        if self.saved_state.tabs.contains(&chat.id) {
+            self.tabs.push(chat);
+            let saved_tabs = &self.saved_state.tabs;
+            self.tabs.sort_by_key(|c| saved_tabs.iter().position(|&id| id == c.id));
+        }
+    }
+
+    /// Replace the dialog list with one loaded again, keeping the state of
+    /// the chats still in it and which ones are open.
+    fn merge_dialogs(&mut self, dialogs: Vec<Dialog>) {
+        let current = self.current_chat().map(|c| c.chat);
+        let split = self.split.map(|s| self.chat_states[s.dialog_idx].chat);
+        let mut old: HashMap<i64, ChatState> = self.chat_states.drain(..).map(|c| (c.chat.id, c)).collect();
+        let mut added = 0;
+        for dialog in dialogs {
+            self.peers.record(dialog.chat());
+            let chat_state = match old.remove(&dialog.chat().id()) {
+                Some(mut chat_state) => {
+                    chat_state.refresh_dialog(dialog);
+                    chat_state
+                }
+                None => {
+                    added += 1;
+                    ChatState::new(dialog)
+                }
+            };
+            self.chat_states.push_back(chat_state);
+        }
+        // chats left, deleted or moved to the archive
+        let position = |chat: PackedChat| self.chat_states.iter().position(|c| c.chat.id == chat.id);
+        self.dialog_idx = current.and_then(position);
+        self.split = match (self.split, split.and_then(position)) {
+            (Some(split), Some(dialog_idx)) => Some(Split { dialog_idx, ..split }),
+            _ => None,
+        };
+        self.tabs.retain(|c| !old.contains_key(&c.id));
+        if current.is_some() && self.dialog_idx.is_none() {
+            self.focus = Focus::Dialogs;
+            self.mode = Mode::Normal;
+        }
+        self.status = Some(format!("dialogs refreshed: {} new, {} gone", added, old.len()));
+    }
+
+    /// What the UI is doing, for crash reports: counts and ids, never what
+    /// messages or drafts say.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "mode {:?}, focus {:?}, {}connected, {} chats, {} tabs, {} transfers, {} queued to send",
+            self.mode,
+            self.focus,
+            if self.connected { "" } else { "not " },
+            self.chat_states.len(),
+            self.tabs.len(),
+            self.transfers.len(),
+            self.outbox.messages.len(),
+        );
+        if let Some(chat_state) = self.current_chat() {
+            summary.push_str(&format!(
+                "\nchat {} ({:?}): {} messages loaded, selected {:?}, {}filtered, draft of {} chars",
+                chat_state.chat.id,
+                chat_state.chat.ty,
+                chat_state.messages.len(),
+                chat_state.selected,
+                if chat_state.filter.is_some() { "" } else { "not " },
+                chat_state.draft.input.text.chars().count(),
+            ));
+        }
+        if let Some(popup) = &self.popup {
+            summary.push_str(&format!("\npopup {}", popup.kind()));
+        }
+        summary
+    }
+
+    pub fn current_chat(&self) -> Option<&ChatState> {
+        self.dialog_idx.and_then(|i| self.chat_states.get(i))
+    }
+
+    pub fn current_chat_mut(&mut self) -> Option<&mut ChatState> {
+        self.dialog_idx.and_then(|i| self.chat_states.get_mut(i))
+    }
+
+    /// Do not disturb, if it's on.
+    pub fn dnd(&self) -> Option<Dnd> {
+        self.dnd.filter(|dnd| !dnd.is_over())
+    }
+
+    fn chat_state_mut(&mut self, chat: PackedChat) -> Option<&mut ChatState> {
+        self.chat_states.iter_mut().find(|c| c.chat == chat)
+    }
+
+    /// The text input receiving keys in the current mode.
+    fn input_mut(&mut self) -> Option<&mut Input> {
+        if self.popup.is_some() {
+            return self.popup.as_mut().and_then(Popup::filter_mut);
+        }
+        match self.mode {
+            Mode::Normal | Mode::Visual => None,
+            Mode::Insert => self.current_chat_mut().map(|c| &mut c.draft.input),
+            Mode::Command => Some(&mut self.command),
+        }
+    }
+
+    /// Limits on sending, the Premium ones until the account is loaded so
+    /// nothing valid is refused.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            premium: self.premium.unwrap_or(true),
+        }
+    }
+
+    /// Hand a job to the API worker, which is gone if the saved session
+    /// couldn't be used.
+    pub fn send_job(&mut self, job: ApiJob) {
+        metrics::job_sent();
+        if self.jobs.send(job).is_err() {
+            self.status = Some("not connected".into());
+        }
+    }
+
+    /// Open the command line with the start of a command typed.
+    fn prompt(&mut self, text: &str) {
+        self.mode = Mode::Command;
+        self.command = Input {
+            text: text.to_string(),
+            cursor: text.len(),
+        };
+    }
+
+    /// Send a message, tracked as an upload if it has attachments.
+    fn send_message(&mut self, chat: PackedChat, message: OutgoingMessage) -> TransferId {
+        self.usage.sent(chat.id);
+        let attachments = &message.attachments;
+        let id = match attachments.len() {
+            // nothing to upload, only needs an id
+            0 => self.transfer_id(),
+            n => {
+                let name = match n {
+                    1 => attachments[0].name(),
+                    n => format!("{} files", n),
+                };
+                let total = attachments
+                    .iter()
+                    .filter_map(|a| a.path.metadata().ok())
+                    .map(|m| m.len())
+                    .sum();
+                self.start_transfer(Direction::Upload, name, total, None)
+            }
+        };
+        self.send_job(ApiJob::SendMessage { id, chat, message });
+        id
+    }
+
+    /// Index into the outbox of the `n`th message queued for the current
+    /// chat, `Some(None)` for all of them.
+    fn queued_message(&mut self, n: Option<usize>) -> Option<Option<usize>> {
+        let Some(n) = n else {
+            return Some(None);
+        };
+        let queued = self.current_chat().map_or_else(Vec::new, |c| self.outbox.of_chat(c.chat));
+        match queued.get(n) {
+            Some(&idx) => Some(Some(idx)),
+            None => {
+                self.status = Some(format!("no queued message {}", n + 1));
+                None
+            }
+        }
+    }
+
+    fn transfer_id(&mut self) -> TransferId {
+        self.next_transfer_id += 1;
+        self.next_transfer_id
+    }
+
+    /// Track a transfer so it shows in the status bar and can be cancelled.
+    fn start_transfer(
+        &mut self,
+        direction: Direction,
+        name: String,
+        total: u64,
+        path: Option<PathBuf>,
+    ) -> TransferId {
+        let id = self.transfer_id();
+        self.transfers.push(Transfer {
+            id,
+            direction,
+            name,
+            done: 0,
+            total,
+            path,
+        });
+        id
+    }
+
+    /// Indices into `chat_states` of the dialogs listed, the current one
+    /// always is so it doesn't vanish once read.
+    pub fn visible_dialogs(&self) -> Vec<usize> {
+        (0..self.chat_states.len())
+            .filter(|&i| {
+                let chat_state = &self.chat_states[i];
+                let listed = !self.hidden.contains(&chat_state.chat.id)
+                    && (!self.unread_only || chat_state.is_unread());
+                listed || self.dialog_idx == Some(i)
+            })
+            .collect()
+    }
+
+    /// Keep a local change to take back with `u`.
+    fn push_undo(&mut self, undo: Undo) {
+        if self.undo.len() >= UNDO_MAX {
+            self.undo.remove(0);
+        }
+        self.undo.push(undo);
+    }
+
+    /// Ask before doing something that's hard to take back if `ask`, which
+    /// the `[confirm]` config sets for each kind, or else do it right away.
+    fn confirm(&mut self, ask: bool, prompt: String, action: Action) {
+        if ask {
+            self.popup = Some(Popup::Confirm(Confirm { prompt, action }));
+        } else {
+            self.dispatch(action);
+        }
+    }
+
+    /// Periodic work, called about every second.
+    pub fn tick(&mut self) {
+        self.refresh_views();
+        self.save_unread_status();
+        self.update_presence();
+        if self.outbox_retried.is_some_and(|at| at.elapsed() >= OUTBOX_RETRY_INTERVAL) {
+            self.retry_outbox();
+        }
+    }
+
+    /// Tell Telegram whether I'm online as the `presence` option says, when
+    /// that changes and again before being online runs out.
+    pub fn update_presence(&mut self) {
+        let online = match self.config.presence {
+            Presence::Auto => return,
+            Presence::Focus => self.focused,
+            Presence::Online => true,
+            Presence::Offline => false,
+        };
+        // offline stays offline, online has to be said again
+        let told = |(sent, at): (bool, Instant)| sent == online && (!online || at.elapsed() < PRESENCE_INTERVAL);
+        // being seen online changes the account too
+        if !self.connected || self.is_read_only() || self.presence_sent.is_some_and(told) {
+            return;
+        }
+        self.presence_sent = Some((online, Instant::now()));
+        self.send_job(ApiJob::UpdateStatus { offline: !online });
+    }
+
+    /// Send the messages queued for lack of a connection again.
+    fn retry_outbox(&mut self) {
+        self.outbox_retried = Some(Instant::now());
+        if !self.connected || self.is_read_only() {
+            return;
+        }
+        let queued: Vec<_> = (0..self.outbox.messages.len())
+            .filter(|&i| self.outbox.messages[i].retry && self.outbox.messages[i].sending.is_none())
+            .collect();
+        for i in queued {
+            self.resend(i);
+        }
+    }
+
+    /// Send a queued message again, it stays queued until it's sent.
+    fn resend(&mut self, idx: usize) {
+        let queued = &self.outbox.messages[idx];
+        let Some(chat) = queued.chat() else {
+            return;
+        };
+        let message = queued.message.clone();
+        let id = self.send_message(chat, message);
+        self.outbox.messages[idx].sending = Some(id);
+    }
+
+    fn save_outbox(&mut self) {
+        if let Err(e) = self.outbox.save() {
+            self.status = Some(format!("failed to save the outbox: {}", e));
+        }
+    }
+
+    /// Keep the unread counts for `tg9 status` up to date.
+    fn save_unread_status(&mut self) {
+        let status = UnreadStatus {
+            unread: self.chat_states.iter().map(|c| c.unread).sum(),
+            chats: self.chat_states.iter().filter(|c| c.is_unread()).count(),
+            mentions: self.chat_states.iter().map(|c| c.mentions).sum(),
+        };
+        if self.unread_status.as_ref() == Some(&status) {
+            return;
+        }
+        if let Err(e) = status.save() {
+            self.status = Some(format!("failed to save the unread status: {}", e));
+        }
+        self.unread_status = Some(status);
+    }
+
+    /// Ask for fresh counters of the posts on screen in the current chat,
+    /// if they're due.
+    fn refresh_views(&mut self) {
+        let Some(chat_state) = self.current_chat_mut() else {
+            return;
+        };
+        if !matches!(chat_state.dialog.chat(), Chat::Channel(_))
+            || chat_state.views_refreshed.is_some_and(|at| at.elapsed() < VIEWS_INTERVAL)
+        {
+            return;
+        }
+        let message_ids = chat_state.visible_posts();
+        if message_ids.is_empty() {
+            return;
+        }
+        chat_state.views_refreshed = Some(Instant::now());
+        let chat = chat_state.chat;
+        self.send_job(ApiJob::LoadViews { chat, message_ids });
+    }
+
+    fn select_dialog(&mut self, idx: usize) {
+        self.dialog_idx = Some(idx);
+        self.chat_states[idx].unread = 0;
+        self.chat_states[idx].mentions = 0;
+        let chat_state = &self.chat_states[idx];
+        let chat = chat_state.chat;
+        let event = HookEvent::chat_opened(chat_state.dialog.chat());
+        let marked_unread = chat_state.marked_unread;
+        // admins aren't limited by slow mode
+        let slow_mode = supergroup(chat_state.dialog.chat()).is_some_and(|raw| raw.slowmode_enabled)
+            && !is_admin(chat_state.dialog.chat());
+        // opening a chat clears its mark, like in the official apps
+        if marked_unread && !self.is_read_only() {
+            self.send_job(ApiJob::MarkUnread { chat, unread: false });
+        }
+        self.send_job(ApiJob::LoadMessages(chat));
+        self.send_job(ApiJob::LoadPinned(chat));
+        if slow_mode {
+            self.send_job(ApiJob::LoadSlowMode(chat));
+        }
+        if let Err(e) = hooks::fire(&self.config.hooks, &event) {
+            self.status = Some(format!("failed to run hook: {}", e));
+        }
+    }
+
+    /// Record opening a chat in the jump list, dropping the chats jumped
+    /// back from.
+    fn push_jump(&mut self, chat: PackedChat) {
+        if self.jump_list.get(self.jump_idx) == Some(&chat) {
+            return;
+        }
+        self.usage.opened(chat.id);
+        self.jump_list.truncate(self.jump_idx + 1);
+        self.jump_list.push(chat);
+        if self.jump_list.len() > JUMP_LIST_MAX {
+            self.jump_list.remove(0);
+        }
+        self.jump_idx = self.jump_list.len() - 1;
+    }
+
+    fn jump(&mut self, idx: usize) {
+        let Some(&chat) = self.jump_list.get(idx) else {
+            return;
+        };
+        if self.open_chat(chat) {
+            self.jump_idx = idx;
+        }
+    }
+
+    /// Select the chat and focus it, returns false if there's no such dialog.
+    fn open_chat(&mut self, chat: PackedChat) -> bool {
+        let Some(dialog_idx) = self.chat_states.iter().position(|c| c.chat == chat) else {
+            return false;
+        };
+        self.select_dialog(dialog_idx);
+        self.focus = Focus::Chat;
+        true
+    }
+
+    /// Index in `tabs` of the current chat.
+    pub fn current_tab(&self) -> Option<usize> {
+        let chat = self.current_chat()?.chat;
+        self.tabs.iter().position(|&c| c == chat)
+    }
+
+    /// Open the tab `offset` tabs away from the current one.
+    fn cycle_tab(&mut self, offset: isize) {
+        if self.tabs.is_empty() {
+            return;
+        }
+        let idx = match self.current_tab() {
+            Some(idx) => (idx as isize + offset).rem_euclid(self.tabs.len() as isize) as usize,
+            None => 0,
+        };
+        let chat = self.tabs[idx];
+        if self.open_chat(chat) {
+            self.push_jump(chat);
+        }
+    }
+
+    /// Whether actions changing the account are refused.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only || self.config.read_only
+    }
+
+    pub fn dispatch(&mut self, action: Action) {
+        if action.mutates() && self.is_read_only() {
+            self.status = Some("read-only mode".into());
+            return;
+        }
+        match action {
+            Action::Quit => self.quit = true,
+            Action::Down => match self.focus {
+                Focus::Dialogs => self.dispatch(Action::NextDialog),
+                Focus::Chat => self.dispatch(Action::NextMessage),
+            },
+            Action::Up => match self.focus {
+                Focus::Dialogs => self.dispatch(Action::PrevDialog),
+                Focus::Chat => self.dispatch(Action::PrevMessage),
+            },
+            Action::JumpToReply => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let Some(id) = message.reply_to_message_id().filter(|_| message.action().is_none()) else {
+                    self.status = Some("not a reply".into());
+                    return;
+                };
+                if let Some(i) = chat_state.messages.iter().position(|m| m.id() == id) {
+                    chat_state.selected = Some(i);
+                } else if chat_state.replies.get(&id).is_some_and(Option::is_none) {
+                    self.status = Some("the message was deleted".into());
+                } else {
+                    let Some(offset_id) = chat_state.messages.back().map(|m| m.id()) else {
+                        return;
+                    };
+                    let chat = chat_state.chat;
+                    self.status = Some("loading the history…".into());
+                    self.send_job(ApiJob::LoadUntil {
+                        chat,
+                        offset_id,
+                        message_id: id,
+                    });
+                }
+            }
+            Action::Open if self.focus != Focus::Chat => self.dispatch(Action::FocusChat),
+            Action::Open => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()) else {
+                    return;
+                };
+                let Some(MessageFwdHeader::Header(header)) = message.forward_header() else {
+                    return;
+                };
+                let Some(from) = header.from_id.as_ref().map(api::peer_id) else {
+                    self.status = Some("the original sender is hidden".into());
+                    return;
+                };
+                let source = self.chat_states.iter().find(|c| c.chat.id == from).map(|c| c.chat);
+                match (source, self.peers.get(from)) {
+                    (Some(chat), _) => {
+                        if self.open_chat(chat) {
+                            self.push_jump(chat);
+                        }
+                        // the original post, if it's loaded
+                        let post = header.channel_post;
+                        if let Some(chat_state) = self.current_chat_mut() {
+                            if let Some(i) = post.and_then(|id| chat_state.messages.iter().position(|m| m.id() == id)) {
+                                chat_state.selected = Some(i);
+                            }
+                        }
+                    }
+                    (None, Some(user)) if user.is_user() => {
+                        self.status = Some("loading the profile…".into());
+                        self.send_job(ApiJob::LoadUserProfile(user));
+                    }
+                    (None, _) => self.status = Some("the source isn't one of your chats".into()),
+                }
+            }
+            Action::FocusChat => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                if chat_state.selected.is_none() && !chat_state.messages.is_empty() {
+                    chat_state.selected = Some(0);
+                }
+                let chat = chat_state.chat;
+                self.push_jump(chat);
+                self.focus = Focus::Chat;
+            }
+            Action::FocusDialogs => self.focus = Focus::Dialogs,
+            Action::ResizeDialogs(delta) => {
+                self.dialogs_width = self.dialogs_width.saturating_add_signed(delta).clamp(10, 90);
+            }
+            Action::ToggleSplit => {
+                if self.split.is_some() {
+                    self.dispatch(Action::CloseSplit);
+                    return;
+                }
+                let Some(dialog_idx) = self.dialog_idx else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                self.split = Some(Split {
+                    dialog_idx,
+                    current_right: true,
+                });
+            }
+            Action::CloseSplit => self.split = None,
+            Action::OtherWindow => {
+                let (Some(split), Some(dialog_idx)) = (&mut self.split, &mut self.dialog_idx) else {
+                    return;
+                };
+                std::mem::swap(&mut split.dialog_idx, dialog_idx);
+                split.current_right = !split.current_right;
+            }
+            Action::OpenTab => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let chat = chat_state.chat;
+                if !self.tabs.contains(&chat) {
+                    self.tabs.push(chat);
+                }
+            }
+            Action::CloseTab => {
+                if let Some(idx) = self.current_tab() {
+                    let chat = self.tabs.remove(idx);
+                    self.push_undo(Undo::CloseTab { idx, chat });
+                }
+            }
+            Action::Undo => {
+                let Some(undo) = self.undo.pop() else {
+                    self.status = Some("nothing to undo".into());
+                    return;
+                };
+                self.status = Some(format!("undone {}", undo.label()));
+                match undo {
+                    Undo::CloseTab { idx, chat } => {
+                        if !self.tabs.contains(&chat) {
+                            self.tabs.insert(idx.min(self.tabs.len()), chat);
+                        }
+                        self.open_chat(chat);
+                    }
+                    Undo::ClearDraft { chat, draft } => {
+                        if let Some(chat_state) = self.chat_state_mut(chat) {
+                            // typed since, kept after what's restored
+                            let typed = std::mem::replace(&mut chat_state.draft, draft);
+                            chat_state.draft.input.insert_str(&typed.input.text);
+                            chat_state.draft.attachments.extend(typed.attachments);
+                        }
+                        self.open_chat(chat);
+                    }
+                    // the read receipts can't be taken back, only the counts here
+                    Undo::MarkRead(counts) => {
+                        for (chat, unread, mentions) in counts {
+                            if let Some(chat_state) = self.chat_state_mut(chat) {
+                                chat_state.unread = unread;
+                                chat_state.mentions = mentions;
+                            }
+                        }
+                    }
+                    Undo::HideDialog(id) => {
+                        self.hidden.remove(&id);
+                    }
+                }
+            }
+            Action::HideDialog => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let id = chat_state.chat.id;
+                self.status = Some(format!("hid {} (:unhide lists it again)", chat_state.dialog.chat().name()));
+                self.hidden.insert(id);
+                self.push_undo(Undo::HideDialog(id));
+                // on to the next one listed, or the one before if it was the last
+                let current = self.dialog_idx;
+                let visible: Vec<_> = self.visible_dialogs().into_iter().filter(|&i| Some(i) != current).collect();
+                if let Some(&next) = visible.iter().find(|&&i| Some(i) > current).or(visible.last()) {
+                    self.select_dialog(next);
+                }
+            }
+            Action::UnhideDialogs => {
+                self.status = Some(format!("listing {} hidden dialogs again", self.hidden.len()));
+                self.hidden.clear();
+            }
+            Action::ClearDraft => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                if chat_state.draft.is_empty() && chat_state.draft.reply.is_none() {
+                    return;
+                }
+                let chat = chat_state.chat;
+                let draft = std::mem::take(&mut chat_state.draft);
+                self.push_undo(Undo::ClearDraft { chat, draft });
+                self.status = Some("cleared the draft (u in normal mode undoes)".into());
+            }
+            Action::NextTab => self.cycle_tab(1),
+            Action::PrevTab => self.cycle_tab(-1),
+            Action::JumpBack => {
+                if self.jump_idx > 0 {
+                    self.jump(self.jump_idx - 1);
+                }
+            }
+            Action::JumpForward => self.jump(self.jump_idx + 1),
+            Action::NextMessage => {
+                if let Some(chat_state) = self.current_chat_mut() {
+                    let Some(selected) = chat_state.selected else {
+                        return;
+                    };
+                    let newer = (0..selected).rev().find(|&i| chat_state.shown(&chat_state.messages[i]));
+                    chat_state.selected = Some(newer.unwrap_or(selected));
+                }
+            }
+            Action::PrevMessage => {
+                if let Some(chat_state) = self.current_chat_mut() {
+                    let Some(selected) = chat_state.selected else {
+                        return;
+                    };
+                    let older =
+                        (selected + 1..chat_state.messages.len()).find(|&i| chat_state.shown(&chat_state.messages[i]));
+                    chat_state.selected = Some(older.unwrap_or(selected));
+                    // the dropped messages come back as they're scrolled to
+                    let chat = chat_state.chat;
+                    let offset_id = if older.is_none() { chat_state.older_to_load() } else { None };
+                    if let Some(offset_id) = offset_id {
+                        self.send_job(ApiJob::LoadOlder { chat, offset_id });
+                    }
+                }
+            }
+            Action::NextDialog => {
+                let visible = self.visible_dialogs();
+                if visible.is_empty() {
+                    return;
+                }
+                let pos = match self.dialog_idx.and_then(|i| visible.iter().position(|&v| v == i)) {
+                    Some(pos) => cmp::min(pos + 1, visible.len() - 1),
+                    None => 0,
+                };
+                self.select_dialog(visible[pos]);
+            }
+            Action::PrevDialog => {
+                let visible = self.visible_dialogs();
+                if visible.is_empty() {
+                    return;
+                }
+                let pos = match self.dialog_idx.and_then(|i| visible.iter().position(|&v| v == i)) {
+                    Some(pos) => pos.saturating_sub(1),
+                    None => 0,
+                };
+                self.select_dialog(visible[pos]);
+            }
+            Action::CatchUp => {
+                let chats: Vec<_> = self
+                    .visible_dialogs()
+                    .into_iter()
+                    .map(|i| &self.chat_states[i])
+                    .filter(|c| c.unread > 0)
+                    .map(|c| c.chat)
+                    .collect();
+                if chats.is_empty() {
+                    self.status = Some("nothing unread".into());
+                    return;
+                }
+                let prompt = format!("Mark {} chats as read?", chats.len());
+                self.confirm(self.config.confirm.mark_all_read, prompt, Action::MarkRead(chats));
+            }
+            Action::MarkRead(chats) => {
+                let mut counts = Vec::new();
+                for chat_state in self.chat_states.iter_mut().filter(|c| chats.contains(&c.chat)) {
+                    counts.push((chat_state.chat, chat_state.unread, chat_state.mentions));
+                    chat_state.unread = 0;
+                    chat_state.mentions = 0;
+                }
+                self.push_undo(Undo::MarkRead(counts));
+                self.status = Some(format!("marking {} chats as read…", chats.len()));
+                self.send_job(ApiJob::MarkRead(chats));
+            }
+            Action::PluginCommand { name, args } => {
+                let Some(plugin) = self
+                    .plugins
+                    .iter_mut()
+                    .find(|p| p.commands().contains(&name.as_str()))
+                else {
+                    self.status = Some(format!("unknown command: {}", name));
+                    return;
+                };
+                match plugin.run(&name, &args) {
+                    Ok(Some(action)) => self.dispatch(action),
+                    Ok(None) => {}
+                    Err(e) => self.status = Some(format!("{}: {}", plugin.name(), e)),
+                }
+            }
+            Action::ClearHistory(scope) => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let whose = if scope.for_everyone { "for everyone" } else { "for me" };
+                let which = match scope.keep_days {
+                    Some(days) => format!("messages older than {} days", days),
+                    None => "all messages".into(),
+                };
+                let prompt = format!("Delete {} in {} {}?", which, chat_state.dialog.chat().name(), whose);
+                self.confirm(self.config.confirm.clear_history, prompt, Action::DeleteHistory(scope));
+            }
+            Action::DeleteHistory(scope) => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let chat = chat_state.chat;
+                self.status = Some("clearing the history…".into());
+                self.send_job(ApiJob::ClearHistory { chat, scope });
+            }
+            Action::Profile => {
+                self.status = Some("loading the profile…".into());
+                self.send_job(ApiJob::LoadProfile);
+            }
+            Action::EditProfile(edit) => {
+                let checked = match &edit {
+                    ProfileEdit::Name { first, last } => limits::check_name(first, last),
+                    ProfileEdit::Bio(bio) => self.limits().check_bio(bio),
+                    ProfileEdit::Username(username) => limits::check_username(username),
+                };
+                if let Err(e) = checked {
+                    self.status = Some(e);
+                    return;
+                }
+                self.status = Some("saving the profile…".into());
+                self.send_job(ApiJob::EditProfile(edit));
+            }
+            Action::Privacy => {
+                self.popup = Some(Popup::Privacy(Picker::loading("Privacy (Enter changes)")));
+                self.send_job(ApiJob::LoadPrivacy);
+            }
+            Action::SetPrivacy { key, value } => {
+                self.status = Some(format!("{}: {}…", key.label(), value.label()));
+                self.send_job(ApiJob::SetPrivacy { key, value });
+            }
+            Action::Password => {
+                self.popup = Some(Popup::Password(Picker::loading("Two-step verification")));
+                self.send_job(ApiJob::LoadPassword);
+            }
+            Action::EditPassword(edit) => {
+                self.status = Some("changing the 2FA settings…".into());
+                self.popup = Some(Popup::Password(Picker::loading("Two-step verification")));
+                self.send_job(ApiJob::EditPassword(edit));
+            }
+            Action::Whois => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let forwarded_from = match message.forward_header() {
+                    Some(MessageFwdHeader::Header(header)) => Some(header.from_id.map(|p| api::peer_id(&p))),
+                    None => None,
+                };
+                let user = match forwarded_from {
+                    Some(Some(id)) => self.peers.get(id),
+                    Some(None) => {
+                        self.status = Some("the original sender is hidden".into());
+                        return;
+                    }
+                    None => message.sender().map(|s| s.pack()),
+                };
+                match user {
+                    Some(user) if user.is_user() => {
+                        self.status = Some("loading the profile…".into());
+                        self.send_job(ApiJob::LoadUserProfile(user));
+                    }
+                    Some(_) => self.status = Some("only users have profiles".into()),
+                    None => self.status = Some("tg9 hasn't seen who this is from yet".into()),
+                }
+            }
+            Action::OpenUsername(username) => {
+                let loaded = self
+                    .chat_states
+                    .iter()
+                    .find(|c| c.dialog.chat().username().is_some_and(|u| u.eq_ignore_ascii_case(&username)))
+                    .map(|c| c.chat);
+                match loaded {
+                    Some(chat) => {
+                        if self.open_chat(chat) {
+                            self.push_jump(chat);
+                        }
+                    }
+                    None => {
+                        self.status = Some(format!("looking up @{}…", username));
+                        self.send_job(ApiJob::ResolveUsername(username));
+                    }
+                }
+            }
+            Action::Sessions => {
+                self.popup = Some(Popup::Sessions(Picker::loading("Sessions")));
+                self.send_job(ApiJob::LoadSessions);
+            }
+            Action::SetSessionTtl(days) => {
+                self.status = Some(format!("terminating sessions inactive for {} days…", days));
+                if !matches!(self.popup, Some(Popup::Sessions(_))) {
+                    self.popup = Some(Popup::Sessions(Picker::loading("Sessions")));
+                }
+                self.send_job(ApiJob::SetSessionTtl(days));
+            }
+            Action::TerminateSession(hash) => {
+                self.status = Some("terminating the session…".into());
+                self.popup = Some(Popup::Sessions(Picker::loading("Sessions")));
+                self.send_job(ApiJob::TerminateSession(hash));
+            }
+            Action::ReportSpam => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let message_id = chat_state.selected_message().map(|m| m.id());
+                if message_id.is_none() && !matches!(chat_state.dialog.chat(), Chat::User(_)) {
+                    self.status = Some("no message selected".into());
+                    return;
+                }
+                let chat = chat_state.chat;
+                self.send_job(ApiJob::ReportSpam { chat, message_id });
+            }
+            Action::Ban => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                if supergroup(chat).is_none() || !has_admin_right(chat, |rights| rights.ban_users) {
+                    self.status = Some("only admins of supergroups can ban".into());
+                    return;
+                }
+                let Some(sender) = chat_state.selected_message().and_then(|m| m.sender()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                self.popup = Some(Popup::Confirm(Confirm {
+                    prompt: format!(
+                        "Ban {} from {} and delete all their messages?",
+                        sender.name(),
+                        chat_state.dialog.chat().name()
+                    ),
+                    action: Action::DeleteAndBan(sender.pack()),
+                }));
+            }
+            Action::EditChat(edit) => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                if !has_admin_right(chat_state.dialog.chat(), |rights| rights.change_info) {
+                    self.status = Some("only admins who can change its info can edit this chat".into());
+                    return;
+                }
+                let chat = chat_state.chat;
+                if let Err(e) = limits::check_chat_edit(&edit) {
+                    self.status = Some(e);
+                    return;
+                }
+                self.status = Some(format!("saving the {}…", edit.label()));
+                self.send_job(ApiJob::EditChat { chat, edit });
+            }
+            Action::SetChatPhoto(path) => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                if !has_admin_right(chat_state.dialog.chat(), |rights| rights.change_info) {
+                    self.status = Some("only admins who can change its info can edit this chat".into());
+                    return;
+                }
+                let chat = chat_state.chat;
+                let size = match std::fs::metadata(&path) {
+                    Ok(metadata) if metadata.is_file() => metadata.len(),
+                    Ok(_) => {
+                        self.status = Some(format!("{} is not a file", path.display()));
+                        return;
+                    }
+                    Err(e) => {
+                        self.status = Some(format!("{}: {}", path.display(), e));
+                        return;
+                    }
+                };
+                let attachment = Attachment::new(path.clone());
+                if !attachment.is_photo() {
+                    self.status = Some("the chat photo has to be a JPEG, PNG or WebP image".into());
+                    return;
+                }
+                if size > limits::CHAT_PHOTO_SIZE {
+                    let limit = render::human_size(limits::CHAT_PHOTO_SIZE);
+                    self.status = Some(format!("the chat photo is over {}", limit));
+                    return;
+                }
+                let name = attachment.name();
+                let id = self.start_transfer(Direction::Upload, name, size, None);
+                self.send_job(ApiJob::SetChatPhoto { id, chat, path });
+            }
+            Action::SetChatRight { right, allowed } => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                if !is_group(chat) {
+                    self.status = Some("only groups have permissions for their members".into());
+                    return;
+                }
+                if !has_admin_right(chat, |rights| rights.ban_users) {
+                    self.status = Some("only admins who can ban can change the permissions".into());
+                    return;
+                }
+                let mut rights = default_banned_rights(chat);
+                right.set_banned(&mut rights, !allowed);
+                // members who can't send messages can't send anything else either
+                if allowed && right != ChatRight::Send {
+                    ChatRight::Send.set_banned(&mut rights, false);
+                }
+                let chat = chat_state.chat;
+                self.status = Some(format!("{} {}…", if allowed { "allowing" } else { "denying" }, right.label()));
+                self.send_job(ApiJob::EditChat { chat, edit: ChatEdit::Permissions(rights) });
+            }
+            Action::LeaveChat => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                if matches!(chat_state.dialog.chat(), Chat::User(_)) {
+                    self.status = Some("only groups and channels can be left".into());
+                    return;
+                }
+                let prompt = format!("Leave {}?", chat_state.dialog.chat().name());
+                let chat = chat_state.chat;
+                self.confirm(self.config.confirm.leave_chat, prompt, Action::Leave(chat));
+            }
+            Action::Leave(chat) => {
+                self.status = Some("leaving the chat…".into());
+                self.send_job(ApiJob::LeaveChat(chat));
+            }
+            Action::SetAutoDelete(period) => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                if !matches!(chat, Chat::User(_)) && !has_admin_right(chat, |rights| rights.change_info) {
+                    self.status = Some("only admins who can change the chat info can set auto-delete".into());
+                    return;
+                }
+                let Ok(period) = period.map_or(Ok(0), |period| i32::try_from(period.as_secs())) else {
+                    self.status = Some("that's too long for auto-delete".into());
+                    return;
+                };
+                let chat = chat_state.chat;
+                self.status = Some("setting the auto-delete timer…".into());
+                self.send_job(ApiJob::SetTtlPeriod { chat, period });
+            }
+            Action::ChatPermissions => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                if !is_group(chat) {
+                    self.status = Some("only groups have permissions for their members".into());
+                    return;
+                }
+                let rights = default_banned_rights(chat);
+                let (denied, allowed): (Vec<_>, Vec<_>) =
+                    ChatRight::ALL.into_iter().partition(|right| right.is_banned(&rights));
+                let labels = |rights: Vec<ChatRight>| rights.iter().map(|r| r.label()).collect::<Vec<_>>().join(", ");
+                self.status = Some(format!(
+                    "members may: {}; may not: {} (:chat allow|deny <permission>)",
+                    labels(allowed),
+                    labels(denied)
+                ));
+            }
+            Action::DeleteAndBan(user) => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let chat = chat_state.chat;
+                self.status = Some("banning…".into());
+                self.send_job(ApiJob::DeleteAndBan { chat, user });
+            }
+            Action::ToggleUnreadMark => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let job = ApiJob::MarkUnread {
+                    chat: chat_state.chat,
+                    unread: !chat_state.marked_unread,
+                };
+                self.send_job(job);
+            }
+            Action::Pin { notify } => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let job = ApiJob::Pin {
+                    chat: chat_state.chat,
+                    message_id: message.id(),
+                    notify,
+                    unpin: false,
+                };
+                self.send_job(job);
+            }
+            Action::Unpin => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let Some(message) = chat_state.selected_message().or(chat_state.pinned.as_ref()) else {
+                    self.status = Some("no message pinned".into());
+                    return;
+                };
+                let job = ApiJob::Pin {
+                    chat: chat_state.chat,
+                    message_id: message.id(),
+                    notify: false,
+                    unpin: true,
+                };
+                self.send_job(job);
+            }
+            Action::ToggleUnreadOnly => {
+                self.unread_only = !self.unread_only;
+                if self.unread_only && self.visible_dialogs().is_empty() {
+                    self.status = Some("no unread dialogs".into());
+                }
+            }
+            Action::SetMode(Mode::Insert) if self.current_chat().is_none() => {
+                self.status = Some("no chat selected".into());
+            }
+            Action::SetMode(Mode::Insert)
+                if self.current_chat().is_some_and(|c| c.unavailable.is_some()) =>
+            {
+                self.status = Some("can't write to this chat".into());
+            }
+            Action::SetMode(Mode::Visual) => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let Some(id) = chat_state.selected_message().map(|m| m.id()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                chat_state.clear_visual();
+                chat_state.visual_anchor = Some(id);
+                self.focus = Focus::Chat;
+                self.mode = Mode::Visual;
+            }
+            Action::SetMode(mode) => {
+                if mode == Mode::Command {
+                    self.command.take();
+                }
+                if self.mode == Mode::Command {
+                    // the command line was left without running the export
+                    self.exporting = None;
+                }
+                if self.mode == Mode::Visual {
+                    if let Some(chat_state) = self.current_chat_mut() {
+                        chat_state.clear_visual();
+                    }
+                }
+                self.mode = mode;
+            }
+            Action::ToggleMark => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                if let Some(id) = chat_state.selected_message().map(|m| m.id()) {
+                    if !chat_state.marked.remove(&id) {
+                        chat_state.marked.insert(id);
+                    }
+                }
+            }
+            Action::ForwardSelected => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let ids = chat_state.visual_selection();
+                let from = chat_state.chat;
+                let items = self
+                    .chat_states
+                    .iter()
+                    .filter(|c| c.unavailable.is_none())
+                    .map(|c| (c.dialog.chat().name().to_string(), c.chat))
+                    .collect();
+                let title = format!("Forward {} messages to", ids.len());
+                self.popup = Some(Popup::Forward(Forward {
+                    from,
+                    ids,
+                    picker: Picker::new(title, items),
+                }));
+                self.dispatch(Action::SetMode(Mode::Normal));
+            }
+            Action::DeleteSelected => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let (chat, ids) = (chat_state.chat, chat_state.visual_selection());
+                let prompt = format!("Delete {} messages for everyone?", ids.len());
+                self.confirm(self.config.confirm.delete_for_everyone, prompt, Action::DeleteMessages { chat, ids });
+                self.dispatch(Action::SetMode(Mode::Normal));
+            }
+            Action::CopySelected(format) => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let ids = chat_state.visual_selection();
+                let text = render::transcript(&chat_state.messages_by_id(&ids), format);
+                self.dispatch(Action::SetMode(Mode::Normal));
+                let Some(command) = self.config.copy_command() else {
+                    self.status = Some("no clipboard command, set copy_command in the config".into());
+                    return;
+                };
+                match external::spawn_with_input(command, text.into_bytes()) {
+                    Ok(()) => self.status = Some(format!("copied {} messages", ids.len())),
+                    Err(e) => self.show_error("copy the messages", e.into()),
+                }
+            }
+            Action::CopyCode => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let text = message.text();
+                let entities = message.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+                let blocks = highlight::code_blocks(text, entities);
+                if blocks.is_empty() {
+                    self.status = Some("no code in the message".into());
+                    return;
+                }
+                let code: Vec<_> = blocks.iter().map(|block| &text[block.range.clone()]).collect();
+                let Some(command) = self.config.copy_command() else {
+                    self.status = Some("no clipboard command, set copy_command in the config".into());
+                    return;
+                };
+                match external::spawn_with_input(command, code.join("\n\n").into_bytes()) {
+                    Ok(()) if blocks.len() == 1 => self.status = Some("copied the code".into()),
+                    Ok(()) => self.status = Some(format!("copied {} code blocks", blocks.len())),
+                    Err(e) => self.show_error("copy the code", e.into()),
+                }
+            }
+            Action::ApplyPatch => {
+                if self.current_chat().and_then(|c| c.selected_message()).is_none() {
+                    self.status = Some("no message selected".into());
+                    return;
+                }
+                self.prompt("apply ");
+            }
+            Action::ApplyPatchTo(target) => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let text = message.text();
+                let entities = message.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+                let mut diffs = highlight::code_blocks(text, entities).into_iter().map(|b| &text[b.range]);
+                let diff_text = Some(text).filter(|text| diff::looks_like_diff(text));
+                if let Some(diff_text) = diff_text.or_else(|| diffs.find(|code| diff::looks_like_diff(code))) {
+                    let dir = config::temp_dir();
+                    let path = dir.join(format!("message-{}.patch", message.id()));
+                    match std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, diff_text)) {
+                        Ok(()) => self.apply_patch(&path, &target),
+                        Err(e) => self.show_error("apply the patch", e.into()),
+                    }
+                    return;
+                }
+                let media = match message.media() {
+                    Some(Media::Document(document)) if diff::is_patch(&document) => Media::Document(document),
+                    _ => {
+                        self.status = Some("no diff in the message".into());
+                        return;
+                    }
+                };
+                let Some(key) = cache::media_key(&media) else {
+                    return;
+                };
+                if let Some(path) = self.cache.get(CacheKind::File, &key) {
+                    self.apply_patch(&path, &target);
+                    return;
+                }
+                let path = self.cache.path(CacheKind::File, &key);
+                if !self.pending_downloads.insert(path.clone()) {
+                    self.status = Some("still downloading".into());
+                    return;
+                }
+                let size = download::media_size(&media);
+                let id = self.start_transfer(Direction::Download, key, size, Some(path.clone()));
+                self.send_job(ApiJob::Download {
+                    id,
+                    media,
+                    path,
+                    then: AfterDownload::ApplyPatch(target),
+                });
+            }
+            Action::ExportVideoNote(target) => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let document = match message.media() {
+                    Some(Media::Document(document)) if video_note::is_video_note(&document) => document,
+                    _ => {
+                        self.status = Some("not a video note".into());
+                        return;
+                    }
+                };
+                let dir = command::expand_tilde(&self.config.download_dir(chat_state.chat.id));
+                let target = match target {
+                    Some(target) if target.is_dir() => target.join(video_note::file_name(&document)),
+                    Some(target) => target,
+                    None => dir.join(video_note::file_name(&document)),
+                };
+                let media = Media::Document(document);
+                let Some(key) = cache::media_key(&media) else {
+                    return;
+                };
+                if let Some(path) = self.cache.get(CacheKind::File, &key) {
+                    self.export_video_note(&path, &target);
+                    return;
+                }
+                let path = self.cache.path(CacheKind::File, &key);
+                if !self.pending_downloads.insert(path.clone()) {
+                    self.status = Some("still downloading".into());
+                    return;
+                }
+                let size = download::media_size(&media);
+                let id = self.start_transfer(Direction::Download, key, size, Some(path.clone()));
+                self.send_job(ApiJob::Download {
+                    id,
+                    media,
+                    path,
+                    then: AfterDownload::ExportVideoNote(target),
+                });
+            }
+            Action::Save => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some((_, name)) = chat_state.selected_message().and_then(saved_file) else {
+                    self.status = Some("no file to save".into());
+                    return;
+                };
+                let dir = self.config.download_dir(chat_state.chat.id);
+                self.prompt(&format!("save {}/{}", dir.trim_end_matches('/'), name));
+            }
+            Action::SaveFile(path) => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some((media, name)) = chat_state.selected_message().and_then(saved_file) else {
+                    self.status = Some("no file to save".into());
+                    return;
+                };
+                let path = match path {
+                    Some(path) if path.is_dir() || path.to_string_lossy().ends_with('/') => path.join(&name),
+                    Some(path) => path,
+                    None => command::expand_tilde(&self.config.download_dir(chat_state.chat.id)).join(&name),
+                };
+                if path.exists() {
+                    self.status = Some(format!("{} already exists", path.display()));
+                    return;
+                }
+                let then = AfterDownload::Saved {
+                    chat: chat_state.chat.id,
+                    message_id: chat_state.selected_message().map_or(0, |m| m.id()),
+                };
+                let size = download::media_size(&media);
+                let id = self.start_transfer(Direction::Download, name, size, Some(path.clone()));
+                self.send_job(ApiJob::Download { id, media, path, then });
+            }
+            Action::OpenFile { reveal } => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let Some(path) = self.downloaded_file(message) else {
+                    self.status = Some("not downloaded, save it first with s".into());
+                    return;
+                };
+                let (opened, mime) = match path.parent() {
+                    Some(dir) if reveal => (dir.to_path_buf(), None),
+                    _ => (path.clone(), message.media().as_ref().and_then(download::media_mime)),
+                };
+                let Some(opener) = self.config.opener(mime.as_deref()) else {
+                    let path = path.display();
+                    self.status = Some(format!("{}, nothing to open it with (see `open` in the config)", path));
+                    return;
+                };
+                self.status = match external::spawn(opener, &opened) {
+                    Ok(()) => Some(path.display().to_string()),
+                    Err(e) => Some(format!("failed to open {}: {}", opened.display(), e)),
+                };
+            }
+            Action::CompletePath => {
+                let Some((name, typed)) = self.command.text.split_once(' ') else {
+                    return;
+                };
+                if !matches!(name, "save" | "attach" | "export" | "apply") {
+                    return;
+                }
+                let (completed, candidates) = command::complete_path(typed);
+                if candidates.len() > 1 {
+                    self.status = Some(candidates.join("  "));
+                }
+                let text = format!("{} {}", name, completed);
+                self.command = Input {
+                    cursor: text.len(),
+                    text,
+                };
+            }
+            Action::CopyImage => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let is_image = |d: &Document| d.mime_type().is_some_and(|m| m.starts_with("image/"));
+                let media = match message.media() {
+                    Some(media @ Media::Photo(_)) => media,
+                    Some(Media::Document(document)) if is_image(&document) => Media::Document(document),
+                    _ => {
+                        self.status = Some("not a photo".into());
+                        return;
+                    }
+                };
+                let Some(key) = cache::media_key(&media) else {
+                    return;
+                };
+                if let Some(path) = self.cache.get(CacheKind::File, &key) {
+                    self.copy_image(&path);
+                    return;
+                }
+                let path = self.cache.path(CacheKind::File, &key);
+                if !self.pending_downloads.insert(path.clone()) {
+                    self.status = Some("still downloading".into());
+                    return;
+                }
+                let size = download::media_size(&media);
+                let id = self.start_transfer(Direction::Download, key, size, Some(path.clone()));
+                self.send_job(ApiJob::Download {
+                    id,
+                    media,
+                    path,
+                    then: AfterDownload::CopyImage,
+                });
+            }
+            Action::StartExport => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                self.exporting = Some((chat_state.chat, chat_state.visual_selection()));
+                self.dispatch(Action::SetMode(Mode::Normal));
+                self.prompt("export ");
+            }
+            Action::Export(path) => {
+                let Some((chat, ids)) = self.exporting.take() else {
+                    self.status = Some("select the messages to export in visual mode first (v, then w)".into());
+                    return;
+                };
+                let Some(chat_state) = self.chat_states.iter().find(|c| c.chat == chat) else {
+                    return;
+                };
+                let format = match path.extension().and_then(|e| e.to_str()) {
+                    Some("md" | "markdown") => TextFormat::Markdown,
+                    _ => TextFormat::Plain,
+                };
+                let text = render::transcript(&chat_state.messages_by_id(&ids), format);
+                match std::fs::write(&path, text) {
+                    Ok(()) => self.status = Some(format!("exported {} messages to {}", ids.len(), path.display())),
+                    Err(e) => self.show_error("export the messages", e.into()),
+                }
+            }
+            Action::ForwardMessages { from, ids, to } => self.send_job(ApiJob::ForwardMessages { from, ids, to }),
+            Action::DeleteMessages { chat, ids } => self.send_job(ApiJob::DeleteMessages { chat, ids }),
+
+            Action::Insert(c) => {
+                if let Some(input) = self.input_mut() {
+                    input.insert(c);
+                }
+                if let Some(popup) = &mut self.popup {
+                    popup.filter_changed();
+                } else if c == '@' && self.mode == Mode::Insert {
+                    self.complete_mention();
+                }
+            }
+            Action::DeleteBack => {
+                if let Some(input) = self.input_mut() {
+                    input.delete_back();
+                }
+                if let Some(popup) = &mut self.popup {
+                    popup.filter_changed();
+                }
+            }
+            // the arrows move the way they point, which is backwards in
+            // text written right to left
+            Action::CursorLeft | Action::CursorRight => {
+                let rtl = self.popup.is_none()
+                    && self.mode == Mode::Insert
+                    && self.current_chat().is_some_and(|c| c.compose_rtl(&self.config));
+                if let Some(input) = self.input_mut() {
+                    if matches!(action, Action::CursorLeft) != rtl {
+                        input.left();
+                    } else {
+                        input.right();
+                    }
+                }
+            }
+            Action::Submit => match self.mode {
+                Mode::Normal | Mode::Visual => {}
+                Mode::Insert => self.dispatch(Action::Send),
+                Mode::Command => {
+                    let line = self.command.take();
+                    self.mode = Mode::Normal;
+                    match command::parse(&line) {
+                        Ok(action) => self.dispatch(action),
+                        Err(e) => self.status = Some(e),
+                    }
+                }
+            },
+
+            Action::Send => {
+                let limits = self.limits();
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                if chat_state.draft.is_empty() || chat_state.unavailable.is_some() {
+                    return;
+                }
+                if let Some(wait) = chat_state.slow_mode_wait() {
+                    self.status = Some(format!("slow mode, wait {}", render::human_duration(wait)));
+                    return;
+                }
+                if let Err(e) = limits.check(&chat_state.draft) {
+                    self.status = Some(e);
+                    return;
+                }
+                let post = chat_state.draft.post;
+                if (post.silent || post.schedule.is_some()) && chat_state.draft.attachments.len() > 1 {
+                    self.status = Some("albums can't be posted silently or scheduled, send one file at a time".into());
+                    return;
+                }
+                if post.schedule.is_some_and(|at| at <= chrono::Utc::now().timestamp() as u64) {
+                    self.status = Some("the time to post at has passed, schedule it again with :post".into());
+                    return;
+                }
+                let chat = chat_state.chat;
+                let link_preview = chat_state.draft.link_preview;
+                let post = std::mem::take(&mut chat_state.draft.post);
+                let entities = chat_state.draft.entities();
+                let reply = chat_state.draft.reply.take();
+                let (text, attachments) = chat_state.draft.take();
+                let message = OutgoingMessage {
+                    text,
+                    link_preview,
+                    entities,
+                    attachments,
+                    reply,
+                    post,
+                };
+                self.send_message(chat, message);
+            }
+            Action::RetryQueued(n) => {
+                let Some(idx) = self.queued_message(n) else {
+                    return;
+                };
+                let queued = match idx {
+                    Some(idx) => vec![idx],
+                    None => self.current_chat().map_or_else(Vec::new, |c| self.outbox.of_chat(c.chat)),
+                };
+                let queued: Vec<_> = queued
+                    .into_iter()
+                    .filter(|&i| self.outbox.messages[i].sending.is_none())
+                    .collect();
+                if queued.is_empty() {
+                    self.status = Some("nothing to send again".into());
+                    return;
+                }
+                self.status = Some(format!("sending {} queued message(s) again", queued.len()));
+                for i in queued {
+                    self.resend(i);
+                }
+            }
+            Action::DiscardQueued(n) => {
+                let Some(idx) = self.queued_message(n) else {
+                    return;
+                };
+                let mut discarded = match idx {
+                    Some(idx) => vec![idx],
+                    None => self.current_chat().map_or_else(Vec::new, |c| self.outbox.of_chat(c.chat)),
+                };
+                if discarded.is_empty() {
+                    self.status = Some("nothing queued".into());
+                    return;
+                }
+                discarded.sort_unstable();
+                for &i in discarded.iter().rev() {
+                    let queued = self.outbox.messages.remove(i);
+                    if let Some(id) = queued.sending {
+                        self.send_job(ApiJob::CancelTransfer(id));
+                        self.transfers.retain(|t| t.id != id);
+                    }
+                }
+                self.status = Some(format!("discarded {} queued message(s)", discarded.len()));
+                self.save_outbox();
+            }
+            Action::Reply => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                let Some(message_id) = chat_state.selected_message().map(|m| m.id()) else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                chat_state.draft.reply = Some(ReplyTo { message_id, quote: None });
+                self.dispatch(Action::SetMode(Mode::Insert));
+            }
+            Action::QuoteReply => {
+                let Some(message) = self.current_chat().and_then(|c| c.selected_message()).cloned() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                match QuoteSelection::new(message) {
+                    Some(selection) => self.popup = Some(Popup::Quote(selection)),
+                    None => self.status = Some("the message has no text to quote".into()),
+                }
+            }
+            Action::QuoteMotion(motion) => {
+                if let Some(Popup::Quote(selection)) = &mut self.popup {
+                    selection.motion(motion);
+                }
+            }
+            Action::CancelReply => {
+                if let Some(chat_state) = self.current_chat_mut() {
+                    chat_state.draft.reply = None;
+                }
+            }
+            Action::Attach(path) => {
+                let limits = self.limits();
+                if !path.is_file() {
+                    self.status = Some(format!("not a file: {}", path.display()));
+                    return;
+                }
+                let Some(chat_state) = self.current_chat_mut() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                chat_state.draft.attachments.push(Attachment::new(path));
+                // warn right away rather than on sending
+                if let Err(e) = limits.check(&chat_state.draft) {
+                    self.status = Some(e);
+                }
+            }
+            Action::Paste(text) => {
+                // an image in the clipboard is pasted as nothing, or as
+                // its bytes, by most terminals
+                let garbage = text.contains('\u{fffd}')
+                    || text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'));
+                if self.mode == Mode::Insert && (text.is_empty() || garbage) {
+                    self.dispatch(Action::PasteImage);
+                    return;
+                }
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                let composing = self.mode == Mode::Insert && self.popup.is_none();
+                if composing && text.chars().count() > limits::MESSAGE_LENGTH {
+                    self.offer_snippet(&text);
+                    return;
+                }
+                // popup filters and commands are a single line
+                let text = if composing { text } else { text.replace('\n', " ") };
+                if let Some(input) = self.input_mut() {
+                    input.insert_str(&text);
+                }
+                if let Some(popup) = &mut self.popup {
+                    popup.filter_changed();
+                }
+            }
+            Action::PasteImage => {
+                if self.current_chat().is_none() {
+                    self.status = Some("no chat selected".into());
+                    return;
+                }
+                match self.clipboard_image() {
+                    Ok(Some(path)) => {
+                        self.popup = Some(Popup::Confirm(Confirm {
+                            prompt: "Attach the image in the clipboard, to send it as a photo?".into(),
+                            action: Action::Attach(path),
+                        }))
+                    }
+                    Ok(None) => self.status = Some("no image in the clipboard".into()),
+                    Err(e) => self.show_error("paste the image", e.into()),
+                }
+            }
+            Action::Detach(idx) => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                if idx < chat_state.draft.attachments.len() {
+                    chat_state.draft.attachments.remove(idx);
+                } else {
+                    self.status = Some(format!("no attachment {}", idx + 1));
+                }
+            }
+            Action::Caption(idx, caption) => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                match chat_state.draft.attachments.get_mut(idx) {
+                    Some(attachment) => attachment.caption = caption,
+                    None => self.status = Some(format!("no attachment {}", idx + 1)),
+                }
+            }
+            Action::ToggleLinkPreview => {
+                if let Some(chat_state) = self.current_chat_mut() {
+                    chat_state.draft.link_preview = !chat_state.draft.link_preview;
+                }
+            }
+            Action::PostOption(option) => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let signs = match chat_state.dialog.chat() {
+                    Chat::Channel(channel) if !channel.raw.megagroup && is_admin(chat_state.dialog.chat()) => {
+                        channel.raw.signatures
+                    }
+                    _ => {
+                        self.status = Some("post options are for channels I post to".into());
+                        return;
+                    }
+                };
+                let post = &mut chat_state.draft.post;
+                match option {
+                    PostOption::Show => {}
+                    PostOption::Silent => post.silent = !post.silent,
+                    PostOption::Signed if signs => {
+                        self.status = Some("the channel signs all its posts already".into());
+                        return;
+                    }
+                    PostOption::Signed => post.signed = !post.signed,
+                    PostOption::Schedule(Some(at)) if at <= chrono::Utc::now().timestamp() as u64 => {
+                        self.status = Some("that time has passed".into());
+                        return;
+                    }
+                    PostOption::Schedule(at) => post.schedule = at,
+                    PostOption::Clear => *post = PostOptions::default(),
+                }
+                let summary = post.summary();
+                self.status = Some(if summary.is_empty() {
+                    "posting right away, with sound".into()
+                } else {
+                    format!("posting {}", summary)
+                });
+            }
+            Action::SendLocation { lat, lon } => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                self.send_job(ApiJob::SendMedia(chat_state.chat, api::geo_point(lat, lon)));
+            }
+            Action::SendPoll(poll) => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                if let Err(e) = limits::check_poll(&poll) {
+                    self.status = Some(e);
+                    return;
+                }
+                self.send_job(ApiJob::SendMedia(chat_state.chat, api::poll(&poll)));
+            }
+            Action::Play => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let Some(Media::Document(document)) = message.media() else {
+                    self.status = Some("not a video".into());
+                    return;
+                };
+                if !document.mime_type().is_some_and(|m| m.starts_with("video/")) {
+                    self.status = Some("not a video".into());
+                    return;
+                }
+                let in_terminal = document.is_animated()
+                    && !self.config.terminal_player.is_empty()
+                    && external::is_installed(&self.config.terminal_player);
+                let then = if in_terminal {
+                    AfterDownload::PlayInTerminal
+                } else {
+                    AfterDownload::Play
+                };
+                let name = file_name(document.name()).unwrap_or_else(|| document.id().to_string());
+                let path = config::temp_dir().join(format!("{}-{}-{}", chat_state.chat.id, message.id(), name));
+                if path.exists() {
+                    self.play(&path, then);
+                    return;
+                }
+                let id = self.start_transfer(
+                    Direction::Download,
+                    document.name().to_string(),
+                    document.size() as u64,
+                    Some(path.clone()),
+                );
+                self.send_job(ApiJob::Download {
+                    id,
+                    media: Media::Document(document),
+                    path,
+                    then,
+                });
+            }
+            Action::Translate(to_lang) => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                if message.text().is_empty() {
+                    self.status = Some("nothing to translate".into());
+                    return;
+                }
+                let job = ApiJob::Translate {
+                    chat: chat_state.chat,
+                    message_id: message.id(),
+                    text: message.text().to_string(),
+                    to_lang: to_lang.unwrap_or_else(|| self.config.translate.to.clone()),
+                    command: self.config.translate.command.clone(),
+                };
+                self.send_job(job);
+                self.status = Some("translating…".into());
+            }
+            Action::CacheStats => {
+                self.status = Some(match self.cache.stats() {
+                    Ok(stats) => {
+                        let total = stats.total();
+                        format!(
+                            "media cache: {} files, {} of {}",
+                            total.files,
+                            render::human_size(total.bytes),
+                            render::human_size(self.config.media_cache_size),
+                        )
+                    }
+                    Err(e) => return self.show_error("read the media cache", e),
+                });
+            }
+            Action::CacheClear => {
+                match self.cache.clear() {
+                    Ok(()) => self.status = Some("media cache cleared".into()),
+                    Err(e) => self.show_error("clear the media cache", e),
+                }
+            }
+            Action::ToggleDnd => {
+                if self.dnd().is_some() {
+                    self.dispatch(Action::DndOff);
+                } else {
+                    self.dispatch(Action::Dnd(None));
+                }
+            }
+            Action::Dnd(duration) => {
+                self.dnd = Some(Dnd {
+                    until: duration.map(|d| Instant::now() + d),
+                });
+                self.status = Some(match duration {
+                    Some(d) => format!("do not disturb for {}", render::human_duration(d)),
+                    None => "do not disturb".into(),
+                });
+            }
+            Action::ToggleMetrics => {
+                self.show_metrics = !self.show_metrics;
+                if self.show_metrics {
+                    self.metrics.media_cache = self.cache.stats().ok().map(|stats| stats.total().bytes);
+                }
+            }
+            Action::ToggleRedact => {
+                self.redact = !self.redact;
+                self.status = Some(if self.redact { "text redacted" } else { "text shown" }.into());
+            }
+            Action::DndOff => {
+                self.dnd = None;
+                self.status = Some("do not disturb off".into());
+            }
+            Action::Filter(pattern) => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let Some(pattern) = pattern else {
+                    chat_state.filter = None;
+                    self.status = Some("filter cleared".into());
+                    return;
+                };
+                match MessageFilter::new(&pattern) {
+                    Ok(filter) => {
+                        chat_state.filter = Some(filter);
+                        let shown = chat_state.shown_messages();
+                        chat_state.selected = shown.first().copied();
+                        let loaded = chat_state.messages.len();
+                        self.status = Some(format!("{} of {} loaded messages match", shown.len(), loaded));
+                    }
+                    Err(e) => self.status = Some(e),
+                }
+            }
+            Action::StartFilter => self.prompt("filter "),
+            Action::RefreshDialogs => {
+                self.status = Some("refreshing dialogs…".into());
+                self.send_job(ApiJob::RefreshDialogs);
+            }
+            Action::CancelTransfer => {
+                let Some(transfer) = self.transfers.pop() else {
+                    self.status = Some("no transfers".into());
+                    return;
+                };
+                self.send_job(ApiJob::CancelTransfer(transfer.id));
+                if let Some(path) = &transfer.path {
+                    self.pending_downloads.remove(path);
+                }
+                self.status = Some(format!("cancelled {}", transfer.name));
+            }
+            Action::LinkedChat => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                if !matches!(chat, Chat::Channel(_)) && supergroup(chat).is_none() {
+                    self.status = Some("not a channel or supergroup".into());
+                    return;
+                }
+                self.send_job(ApiJob::LoadLinkedChat(chat_state.chat));
+            }
+            Action::OpenComments => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let title = if render::comments(message).is_some() {
+                    "Comments"
+                } else if render::replies(message).is_some_and(|n| n > 0) {
+                    "Replies"
+                } else {
+                    self.status = Some("this message has no replies".into());
+                    return;
+                };
+                let job = ApiJob::LoadComments {
+                    chat: chat_state.chat,
+                    message_id: message.id(),
+                };
+                self.send_job(job);
+                self.popup = Some(Popup::Comments(Picker::loading(title)));
+            }
+            Action::ToggleThread => {
+                let Some(chat_state) = self.current_chat_mut() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                let message_id = message.id();
+                let has_replies =
+                    render::comments(message).is_some() || render::replies(message).is_some_and(|n| n > 0);
+                if chat_state.threads.remove(&message_id).is_some() {
+                    return;
+                }
+                if !has_replies {
+                    self.status = Some("this message has no replies".into());
+                    return;
+                }
+                chat_state.threads.insert(message_id, None);
+                let chat = chat_state.chat;
+                self.send_job(ApiJob::LoadComments { chat, message_id });
+            }
+            Action::MessageInfo => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let Some(message) = chat_state.selected_message() else {
+                    self.status = Some("no message selected".into());
+                    return;
+                };
+                self.popup = Some(Popup::Info(Info {
+                    title: "Message info".into(),
+                    fields: render::message_info(message, &self.peers),
+                }));
+            }
+            Action::ChatStats => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let stats = Stats::new(chat_state.dialog.chat().name(), &chat_state.messages);
+                self.popup = Some(Popup::Stats(stats));
+            }
+            Action::AdminLog => {
+                let Some(chat_state) = self.current_chat() else {
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                let is_channel = matches!(chat, Chat::Channel(_)) || supergroup(chat).is_some();
+                if !is_channel || !is_admin(chat) {
+                    self.status = Some("the admin log is only for channels and supergroups you administer".into());
+                    return;
+                }
+                self.send_job(ApiJob::LoadAdminLog(chat_state.chat));
+                self.popup = Some(Popup::AdminLog(Picker::loading("Admin log")));
+            }
+            Action::JoinRequests => {
+                let Some(chat_state) = self.current_chat() else {
+                    self.status = Some("no chat selected".into());
+                    return;
+                };
+                let chat = chat_state.dialog.chat();
+                if !has_admin_right(chat, |rights| rights.invite_users) {
+                    self.status = Some("only admins who can add members see the requests to join".into());
+                    return;
+                }
+                let title = format!("Asking to join {} (a/Enter approve, d decline)", chat.name());
+                let chat = chat_state.chat;
+                self.popup = Some(Popup::JoinRequests(JoinRequests {
+                    chat,
+                    picker: Picker::loading(title),
+                }));
+                self.send_job(ApiJob::LoadJoinRequests(chat));
+            }
+            Action::HideJoinRequest { approve } => {
+                let Some(Popup::JoinRequests(requests)) = &self.popup else {
+                    return;
+                };
+                let Some(request) = requests.picker.selected_item() else {
+                    return;
+                };
+                let job = ApiJob::HideJoinRequest {
+                    chat: requests.chat,
+                    user: request.user.clone(),
+                    approve,
+                };
+                self.status = Some(format!("{} {}…", if approve { "approving" } else { "declining" }, request.name));
+                self.send_job(job);
+            }
+            Action::RecordMacro => match self.macros.stop() {
+                Some((register, len)) => self.status = Some(format!("recorded {} actions into @{}", len, register)),
+                None => self.macros.awaiting = Some(Awaiting::Record),
+            },
+            Action::ReplayMacro => self.macros.awaiting = Some(Awaiting::Replay),
+            Action::MacroRegister(register) => {
+                let (Some(awaiting), Some(register)) = (self.macros.awaiting.take(), register) else {
+                    return;
+                };
+                let valid = Macros::is_register(register) || (awaiting == Awaiting::Replay && register == '@');
+                if !valid {
+                    self.status = Some("registers are letters and digits".into());
+                    return;
+                }
+                if awaiting == Awaiting::Record {
+                    self.macros.start(register);
+                    return;
+                }
+                let Some(actions) = self.macros.replay(register) else {
+                    self.status = Some(format!("nothing recorded in @{}", register));
+                    return;
+                };
+                for action in actions {
+                    if self.quit {
+                        break;
+                    }
+                    // replayed while recording another, it's recorded as what it does
+                    self.macros.record(&action);
+                    self.dispatch(action);
+                }
+            }
+            Action::DismissStory => {
+                if let Some(chat_state) = self.current_chat_mut() {
+                    chat_state.new_story = false;
+                }
+            }
+            Action::SwitchChat => {
+                let current = self.current_chat().map(|c| c.chat);
+                let mut chats: Vec<_> = self
+                    .chat_states
+                    .iter()
+                    .filter(|c| Some(c.chat) != current)
+                    .map(|c| (self.usage.score(c.chat.id), c.dialog.chat().name().to_string(), c.chat))
+                    .collect();
+                // the most used first, the rest in the order of the dialog list
+                chats.sort_by(|a, b| b.0.total_cmp(&a.0));
+                let items = chats.into_iter().map(|(_, name, chat)| (name, chat)).collect();
+                self.popup = Some(Popup::Switch(Picker::fuzzy("Switch to chat", items)));
+            }
+            Action::EmojiPicker => {
+                if self.current_chat().is_none() {
+                    self.status = Some("no chat selected".into());
+                    return;
+                }
+                let picker = Picker::new("Emoji", emoji::items(&self.recent_emoji));
+                self.popup = Some(Popup::Emoji(picker));
+            }
+            Action::ShareContact => {
+                if self.current_chat().is_none() {
+                    self.status = Some("no chat selected".into());
+                    return;
+                }
+                self.popup = Some(Popup::ShareContact(Picker::loading("Share contact")));
+                self.send_job(ApiJob::LoadContacts);
+            }
+
+            Action::Help(topic) => {
+                self.popup = Some(Popup::Help(Pager {
+                    title: format!("Help: {} (j/k to scroll, q to close)", topic.name()),
+                    rows: help::page(topic),
+                    scroll: 0,
+                }));
+            }
+            Action::PopupScroll(lines) => {
+                if let Some(Popup::Help(pager)) = &mut self.popup {
+                    pager.scroll_by(lines);
+                }
+            }
+            Action::PopupUp => match &mut self.popup {
+                Some(
+                    Popup::Confirm(_)
+                    | Popup::Info(_)
+                    | Popup::Help(_)
+                    | Popup::Stats(_)
+                    | Popup::Passphrase(_)
+                    | Popup::PasswordForm(_)
+                    | Popup::Quote(_),
+                ) => {}
+                Some(Popup::ShareContact(picker)) => picker.up(),
+                Some(Popup::Comments(picker)) => picker.up(),
+                Some(Popup::AdminLog(picker)) => picker.up(),
+                Some(Popup::Mention(picker)) => picker.up(),
+                Some(Popup::Emoji(picker)) => picker.up(),
+                Some(Popup::Privacy(picker)) => picker.up(),
+                Some(Popup::Password(picker)) => picker.up(),
+                Some(Popup::Sessions(picker)) => picker.up(),
+                Some(Popup::JoinRequests(requests)) => requests.picker.up(),
+                Some(Popup::Forward(forward)) => forward.picker.up(),
+                Some(Popup::Switch(picker)) => picker.up(),
+                None => {}
+            },
+            Action::PopupDown => match &mut self.popup {
+                Some(
+                    Popup::Confirm(_)
+                    | Popup::Info(_)
+                    | Popup::Help(_)
+                    | Popup::Stats(_)
+                    | Popup::Passphrase(_)
+                    | Popup::PasswordForm(_)
+                    | Popup::Quote(_),
+                ) => {}
+                Some(Popup::ShareContact(picker)) => picker.down(),
+                Some(Popup::Comments(picker)) => picker.down(),
+                Some(Popup::AdminLog(picker)) => picker.down(),
+                Some(Popup::Mention(picker)) => picker.down(),
+                Some(Popup::Emoji(picker)) => picker.down(),
+                Some(Popup::Privacy(picker)) => picker.down(),
+                Some(Popup::Password(picker)) => picker.down(),
+                Some(Popup::Sessions(picker)) => picker.down(),
+                Some(Popup::JoinRequests(requests)) => requests.picker.down(),
+                Some(Popup::Forward(forward)) => forward.picker.down(),
+                Some(Popup::Switch(picker)) => picker.down(),
+                None => {}
+            },
+            Action::PopupClose => {
+                // keep what was typed after the @
+                if let Some(Popup::Mention(picker)) = self.popup.take() {
+                    if let Some(chat_state) = self.current_chat_mut() {
+                        picker.filter.text.chars().for_each(|c| chat_state.draft.input.insert(c));
+                    }
+                }
+            }
+            Action::PopupSelect => match self.popup.take() {
+                Some(Popup::ShareContact(picker)) => {
+                    let (Some(contact), Some(chat_state)) =
+                        (picker.selected_item(), self.current_chat())
+                    else {
+                        return;
+                    };
+                    self.send_job(ApiJob::SendMedia(chat_state.chat, api::contact(contact)));
+                }
+                Some(Popup::Mention(picker)) => {
+                    let Some(member) = picker.selected_item().cloned() else {
+                        return;
+                    };
+                    let Some(chat_state) = self.current_chat_mut() else {
+                        return;
+                    };
+                    let input = &mut chat_state.draft.input;
+                    match &member.username {
+                        Some(username) => username.chars().for_each(|c| input.insert(c)),
+                        None => {
+                            // mentioned by name, replacing the @
+                            input.delete_back();
+                            member.name.chars().for_each(|c| input.insert(c));
+                            chat_state.draft.mentions.push(Mention {
+                                name: member.name,
+                                user: member.user,
+                            });
+                        }
+                    }
+                    chat_state.draft.input.insert(' ');
+                }
+                Some(Popup::Emoji(picker)) => {
+                    let Some(emoji) = picker.selected_item().cloned() else {
+                        return;
+                    };
+                    if let Some(chat_state) = self.current_chat_mut() {
+                        emoji.chars().for_each(|c| chat_state.draft.input.insert(c));
+                    }
+                    self.recent_emoji.retain(|e| *e != emoji);
+                    self.recent_emoji.insert(0, emoji);
+                    self.recent_emoji.truncate(emoji::RECENT_MAX);
+                }
+                Some(Popup::Forward(forward)) => {
+                    let Some(&to) = forward.picker.selected_item() else {
+                        return;
+                    };
+                    self.dispatch(Action::ForwardMessages {
+                        from: forward.from,
+                        ids: forward.ids,
+                        to,
+                    });
+                }
+                Some(Popup::Switch(picker)) => {
+                    let Some(&chat) = picker.selected_item() else {
+                        return;
+                    };
+                    if self.open_chat(chat) {
+                        self.push_jump(chat);
+                    }
+                }
+                Some(Popup::Quote(selection)) => {
+                    if let Some(chat_state) = self.current_chat_mut() {
+                        chat_state.draft.reply = Some(selection.reply());
+                        self.dispatch(Action::SetMode(Mode::Insert));
+                    }
+                }
+                Some(Popup::Confirm(confirm)) => self.dispatch(confirm.action),
+                Some(Popup::Privacy(mut picker)) => {
+                    let Some(setting) = picker.selected_item().cloned() else {
+                        return;
+                    };
+                    // stays open with the same setting selected
+                    picker.loading = true;
+                    self.popup = Some(Popup::Privacy(picker));
+                    self.dispatch(Action::SetPrivacy {
+                        key: setting.key,
+                        value: setting.value.next(),
+                    });
+                }
+                Some(Popup::Password(picker)) => {
+                    let Some(&option) = picker.selected_item() else {
+                        return;
+                    };
+                    self.popup = Some(Popup::Password(picker));
+                    self.fill_password_form(PasswordForm::new(option));
+                }
+                Some(Popup::Sessions(mut picker)) => match picker.selected_item().cloned() {
+                    Some(SessionItem::Ttl(days)) => {
+                        let next = SESSION_TTL_DAYS
+                            .iter()
+                            .position(|&d| d == days)
+                            .map_or(SESSION_TTL_DAYS[0], |i| SESSION_TTL_DAYS[(i + 1) % SESSION_TTL_DAYS.len()]);
+                        // stays open with the TTL selected
+                        picker.loading = true;
+                        self.popup = Some(Popup::Sessions(picker));
+                        self.dispatch(Action::SetSessionTtl(next));
+                    }
+                    Some(SessionItem::Password(_)) => self.dispatch(Action::Password),
+                    Some(SessionItem::Session(session)) if session.current => {
+                        self.popup = Some(Popup::Sessions(picker));
+                        self.status = Some("that's this session".into());
+                    }
+                    Some(SessionItem::Session(session)) => {
+                        self.popup = Some(Popup::Confirm(Confirm {
+                            prompt: format!("Terminate the session of {} on {}?", session.app, session.device),
+                            action: Action::TerminateSession(session.hash),
+                        }));
+                    }
+                    None => self.popup = Some(Popup::Sessions(picker)),
+                },
+                Some(Popup::PasswordForm(mut form)) => {
+                    form.answers.push(form.input.take());
+                    self.fill_password_form(form);
+                }
+                Some(Popup::Passphrase(mut input)) => {
+                    if let Some(passphrases) = &self.passphrases {
+                        // the worker asks again if it's wrong
+                        let _ = passphrases.send(input.take());
+                        self.status = Some("unlocking the session…".into());
+                    }
+                }
+                Some(popup @ Popup::JoinRequests(_)) => {
+                    self.popup = Some(popup);
+                    self.dispatch(Action::HideJoinRequest { approve: true });
+                }
+                Some(
+                    Popup::Comments(_) | Popup::AdminLog(_) | Popup::Info(_) | Popup::Help(_) | Popup::Stats(_),
+                )
+                | None => {}
+            },
+        }
+    }
+
+    pub fn handle_api_event(&mut self, event: ApiEvent) {
+        let actions: Vec<_> = self.plugins.iter_mut().filter_map(|p| p.on_event(&event)).collect();
+        for action in actions {
+            self.dispatch(action);
+        }
+        match event {
+            ApiEvent::LoadedDialog(dialog) => {
+                self.peers.record(dialog.chat());
+                let chat_state = ChatState::new(dialog);
+                self.chat_states.push_back(chat_state);
+                self.restore_dialog(self.chat_states.len() - 1);
+            }
+            ApiEvent::RefreshedDialogs(dialogs) => self.merge_dialogs(dialogs),
+            ApiEvent::Connected => {
+                crash::log("connected");
+                self.connected = true;
+                // whatever was left in the outbox last time
+                self.retry_outbox();
+                self.update_presence();
+            }
+            ApiEvent::Disconnected(error) => {
+                crash::log(format!("disconnected: {}", error));
+                self.connected = false;
+                self.status = Some(format!("disconnected: {}", error));
+            }
+            ApiEvent::SessionLocked => self.popup = Some(Popup::Passphrase(Input::default())),
+            ApiEvent::LoadedAccount { premium } => self.premium = Some(premium),
+            ApiEvent::LoadedFolders(folders) => self.folders = folders,
+            ApiEvent::FoldersChanged => self.send_job(ApiJob::LoadFolders),
+            ApiEvent::LoadedPrivacy(settings) => {
+                self.status = None;
+                let items = settings
+                    .into_iter()
+                    .map(|setting| (render::privacy_setting(&setting), setting))
+                    .collect();
+                match &mut self.popup {
+                    Some(Popup::Privacy(picker)) => {
+                        let selected = picker.selected;
+                        picker.set_items(items);
+                        picker.selected = selected;
+                    }
+                    _ => self.popup = Some(Popup::Privacy(Picker::new("Privacy (Enter changes)", items))),
+                }
+            }
+            ApiEvent::LoadedPassword(state) => {
+                self.status = None;
+                let title = render::password_state(&state);
+                let items = state
+                    .options()
+                    .into_iter()
+                    .map(|option| (render::password_option(option, &state), option))
+                    .collect();
+                match &mut self.popup {
+                    Some(Popup::Password(picker)) => {
+                        picker.title = title;
+                        picker.set_items(items);
+                    }
+                    Some(Popup::PasswordForm(_)) => {}
+                    _ => self.popup = Some(Popup::Password(Picker::new(title, items))),
+                }
+            }
+            ApiEvent::ResolvedUsername { username, chat } => {
+                self.status = None;
+                let loaded = chat.and_then(|chat| self.chat_states.iter().find(|c| c.chat.id == chat.id));
+                match (chat, loaded.map(|c| c.chat)) {
+                    (_, Some(loaded)) => {
+                        if self.open_chat(loaded) {
+                            self.push_jump(loaded);
+                        }
+                    }
+                    (Some(_), None) => self.status = Some(format!("@{} isn't one of your chats", username)),
+                    (None, _) => self.status = Some(format!("@{} doesn't exist", username)),
+                }
+            }
+            ApiEvent::LoadedSessions(sessions) => {
+                self.status = None;
+                let title = format!("Sessions ({} active)", sessions.sessions.len());
+                let items = sessions
+                    .items()
+                    .into_iter()
+                    .map(|item| (render::session_item(&item), item))
+                    .collect();
+                match &mut self.popup {
+                    Some(Popup::Sessions(picker)) => {
+                        let selected = picker.selected;
+                        picker.title = title;
+                        picker.set_items(items);
+                        picker.selected = selected.min(picker.items.len().saturating_sub(1));
+                    }
+                    _ => self.popup = Some(Popup::Sessions(Picker::new(title, items))),
+                }
+            }
+            ApiEvent::LoadedProfile(profile) => {
+                self.status = None;
+                let mut fields = render::profile(&profile);
+                fields.push(("Edit", ":profile name <first> [last] | bio [text] | username <name>".into()));
+                self.popup = Some(Popup::Info(Info {
+                    title: "Profile".into(),
+                    fields,
+                }));
+            }
+            ApiEvent::LoadedUserProfile(profile) => {
+                self.status = None;
+                self.popup = Some(Popup::Info(Info {
+                    title: format!("Profile of {}", profile.first_name),
+                    fields: render::profile(&profile),
+                }));
+            }
+            ApiEvent::LoadedMessages(message) => {
+                self.record_peers(&message);
+                self.auto_download(&message);
+                if let Some(chat_state) = self.chat_state_mut(message.chat().into()) {
+                    chat_state.messages.push_back(message);
+                }
+            }
+            ApiEvent::MessageNew(message) => {
+                self.record_peers(&message);
+                self.auto_download(&message);
+                self.notify(&message);
+                self.fire_hooks(&message);
+                let current = self.current_chat().map(|c| c.chat);
+                let history_max = self.config.history_max;
+                if let Some(chat_state) = self.chat_state_mut(message.chat().into()) {
+                    if !message.outgoing() && current != Some(chat_state.chat) {
+                        chat_state.unread += 1;
+                        if message.mentioned() {
+                            chat_state.mentions += 1;
+                        }
+                    }
+                    if let Some(interval) = chat_state.slow_mode.filter(|_| message.outgoing()) {
+                        chat_state.slow_mode_until = Some(Instant::now() + interval);
+                    }
+                    chat_state.dialog.last_message = Some(message.clone());
+                    // "pinned a message" by anyone, including my own pins
+                    // from other devices
+                    let pinned = match message.action() {
+                        Some(MessageAction::PinMessage) => message.reply_to_message_id(),
+                        _ => None,
+                    };
+                    let replied = match (message.action(), message.reply_to_message_id()) {
+                        (None, Some(id)) if chat_state.replied(id).is_none() => Some(id),
+                        _ => None,
+                    };
+                    chat_state.messages.push_front(message);
+                    // the selection is an index from the newest, so it stays on its message
+                    if let Some(selected) = &mut chat_state.selected {
+                        *selected += 1;
+                    }
+                    chat_state.trim(history_max);
+                    let chat = chat_state.chat;
+                    if let Some(id) = pinned {
+                        chat_state.pinned = chat_state.messages.iter().find(|m| m.id() == id).cloned();
+                        if chat_state.pinned.is_none() {
+                            self.send_job(ApiJob::LoadPinned(chat));
+                        }
+                    }
+                    if let Some(id) = replied {
+                        self.send_job(ApiJob::LoadReplies { chat, ids: vec![id] });
+                    }
+                }
+            }
+            ApiEvent::LoadedReplies { chat, replies } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.replies.extend(replies);
+                }
+            }
+            ApiEvent::ReachedMessage { chat, message_id } => {
+                self.status = None;
+                let Some(chat_state) = self.current_chat_mut().filter(|c| c.chat == chat) else {
+                    return;
+                };
+                match chat_state.messages.iter().position(|m| m.id() == message_id) {
+                    Some(i) => chat_state.selected = Some(i),
+                    None => self.status = Some("the message is too far back".into()),
+                }
+            }
+            ApiEvent::LoadedMembers { chat, members } => {
+                for member in &members {
+                    self.peers.record_packed(member.user, &member.name);
+                }
+                let current = self.current_chat().is_some_and(|c| c.chat == chat);
+                if let (Some(Popup::Mention(picker)), true) = (&mut self.popup, current) {
+                    picker.set_items(mention_items(&members));
+                }
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.members = Some(members);
+                }
+            }
+            ApiEvent::LoadedContacts(contacts) => {
+                if let Some(Popup::ShareContact(picker)) = &mut self.popup {
+                    let items = contacts
+                        .into_iter()
+                        .map(|c| (format!("{} {}", c.name(), c.phone), c))
+                        .collect();
+                    picker.set_items(items);
+                }
+            }
+            ApiEvent::LinkedChat { chat, linked } => {
+                let Some(linked) = linked else {
+                    self.status = Some("no linked discussion group or channel".into());
+                    return;
+                };
+                let Some(linked) = self.chat_states.iter().map(|c| c.chat).find(|c| c.id == linked) else {
+                    self.status = Some("the linked chat isn't in your dialogs, join it first".into());
+                    return;
+                };
+                // the answer may arrive after moving on to another chat
+                if self.current_chat().is_some_and(|c| c.chat == chat) && self.open_chat(linked) {
+                    self.push_jump(linked);
+                }
+            }
+            ApiEvent::LoadedComments { chat, message_id, comments } => {
+                if let Some(thread @ None) = self
+                    .chat_state_mut(chat)
+                    .and_then(|chat_state| chat_state.threads.get_mut(&message_id))
+                {
+                    *thread = Some(comments.clone());
+                }
+                // the replies may be for an expanded thread, not the popup
+                // which was already filled
+                if let Some(Popup::Comments(picker)) = &mut self.popup {
+                    if !picker.loading {
+                        return;
+                    }
+                    let items = comments
+                        .into_iter()
+                        .map(|c| (format!("{}: {}", c.sender, c.text.replace('\n', " ")), c))
+                        .collect();
+                    picker.set_items(items);
+                }
+            }
+            ApiEvent::SlowMode { chat, interval, wait } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    if interval.is_some() {
+                        chat_state.slow_mode = interval;
+                    }
+                    chat_state.slow_mode_until = wait.map(|wait| Instant::now() + wait);
+                }
+            }
+            ApiEvent::LoadedAdminLog(entries) => {
+                if let Some(Popup::AdminLog(picker)) = &mut self.popup {
+                    let items = entries
+                        .into_iter()
+                        .map(|e| {
+                            let label = format!(
+                                "{} {}: {}",
+                                render::time(e.date),
+                                e.user,
+                                render::admin_log_action(&e.action)
+                            );
+                            (label, e)
+                        })
+                        .collect();
+                    picker.set_items(items);
+                }
+            }
+            ApiEvent::LoadedJoinRequests { chat, requests } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.join_requests = requests.len() as i32;
+                }
+                let Some(Popup::JoinRequests(popup)) = &mut self.popup else {
+                    return;
+                };
+                if popup.chat != chat {
+                    return;
+                }
+                let selected = popup.picker.selected;
+                let items = requests
+                    .into_iter()
+                    .map(|request| (render::join_request(&request), request))
+                    .collect();
+                popup.picker.set_items(items);
+                popup.picker.selected = selected.min(popup.picker.items.len().saturating_sub(1));
+                if popup.picker.items.is_empty() {
+                    self.status = Some("no one is asking to join".into());
+                }
+            }
+            ApiEvent::JoinRequestHidden { chat, user_id, approve } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.join_requests = (chat_state.join_requests - 1).max(0);
+                }
+                let Some(Popup::JoinRequests(popup)) = &mut self.popup else {
+                    return;
+                };
+                let name = popup
+                    .picker
+                    .items
+                    .iter()
+                    .find(|(_, request)| request.user.user_id == user_id)
+                    .map(|(_, request)| request.name.clone())
+                    .unwrap_or_default();
+                popup.picker.items.retain(|(_, request)| request.user.user_id != user_id);
+                popup.picker.selected = popup.picker.selected.min(popup.picker.matches().len().saturating_sub(1));
+                self.status = Some(format!("{} {}", if approve { "approved" } else { "declined" }, name));
+            }
+            ApiEvent::PendingJoinRequests { chat_id, count } => {
+                let Some(chat_state) = self.chat_states.iter_mut().find(|c| c.chat.id == chat_id) else {
+                    return;
+                };
+                chat_state.join_requests = count;
+                let chat = chat_state.chat;
+                // someone else asked, or an admin elsewhere answered
+                if matches!(&self.popup, Some(Popup::JoinRequests(popup)) if popup.chat == chat) {
+                    self.send_job(ApiJob::LoadJoinRequests(chat));
+                }
+            }
+            ApiEvent::LeftChat(chat) => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    self.status = Some(format!("left {}", chat_state.dialog.chat().name()));
+                }
+                // it's gone from the dialogs, and its tab with it
+                self.send_job(ApiJob::RefreshDialogs);
+            }
+            ApiEvent::TtlPeriod { chat_id, period } => {
+                let Some(chat_state) = self.chat_states.iter_mut().find(|c| c.chat.id == chat_id) else {
+                    return;
+                };
+                if chat_state.ttl_period == period {
+                    return;
+                }
+                chat_state.ttl_period = period;
+                let name = chat_state.dialog.chat().name();
+                self.status = Some(match period {
+                    Some(period) => format!("messages in {} are deleted after {}", name, render::auto_delete(period)),
+                    None => format!("auto-delete is off in {}", name),
+                });
+            }
+            ApiEvent::StoryPosted(peer_id) => {
+                if let Some(chat_state) = self.chat_states.iter_mut().find(|c| c.chat.id == peer_id) {
+                    chat_state.new_story = true;
+                }
+            }
+            ApiEvent::Downloaded { path, then } => match then {
+                AfterDownload::Saved { chat, message_id } => {
+                    self.status = Some(format!("saved {} (O to open)", path.display()));
+                    self.saved_files.insert((chat, message_id), path);
+                }
+                AfterDownload::Play | AfterDownload::PlayInTerminal => self.play(&path, then),
+                AfterDownload::Cache => {
+                    self.pending_downloads.remove(&path);
+                    if let Err(e) = self.cache.evict() {
+                        self.show_error("clean the media cache", e);
+                    }
+                }
+                AfterDownload::CopyImage => {
+                    self.pending_downloads.remove(&path);
+                    self.copy_image(&path);
+                }
+                AfterDownload::ApplyPatch(target) => {
+                    self.pending_downloads.remove(&path);
+                    self.apply_patch(&path, &target);
+                }
+                AfterDownload::ExportVideoNote(target) => {
+                    self.pending_downloads.remove(&path);
+                    self.export_video_note(&path, &target);
+                }
+            },
+            ApiEvent::TransferProgress { id, done } => {
+                if let Some(transfer) = self.transfers.iter_mut().find(|t| t.id == id) {
+                    transfer.done = done;
+                }
+            }
+            ApiEvent::TransferDone(id) => {
+                // a failed download has to be retried, so it isn't pending anymore
+                if let Some(path) = self.transfers.iter().find(|t| t.id == id).and_then(|t| t.path.as_ref()) {
+                    self.pending_downloads.remove(path);
+                }
+                self.transfers.retain(|t| t.id != id);
+                // failures give the message back first, so it's sent
+                if let Some(idx) = self.outbox.messages.iter().position(|m| m.sending == Some(id)) {
+                    self.outbox.messages.remove(idx);
+                    self.save_outbox();
+                }
+            }
+            ApiEvent::SendFailed { id, chat, message, error } => {
+                let retry = matches!(error, Tg9Error::Network(_));
+                if let Some(queued) = self.outbox.sending(id) {
+                    queued.sending = None;
+                    queued.error = error.to_string();
+                    queued.retry = retry;
+                } else if retry {
+                    self.outbox.push(chat, message, error.to_string(), retry);
+                    self.status = Some("no connection, the message is queued and will be sent later".into());
+                } else {
+                    self.show_error("send", error);
+                    return;
+                }
+                self.save_outbox();
+            }
+            ApiEvent::ChatUnavailable { chat, reason } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.unavailable = Some(reason);
+                }
+            }
+            ApiEvent::HistoryCleared { chat, scope } => {
+                let cutoff = scope.cutoff();
+                let Some(chat_state) = self.chat_state_mut(chat) else {
+                    return;
+                };
+                let removed = chat_state.remove_messages(|m| cutoff.is_none_or(|cutoff| m.date() < cutoff));
+                if chat_state.messages.is_empty() {
+                    chat_state.dialog.last_message = None;
+                    chat_state.unread = 0;
+                    chat_state.mentions = 0;
+                }
+                self.status = Some(format!("deleted {} loaded messages", removed.len()));
+                self.uncache_media(&removed);
+            }
+            ApiEvent::MessagesDeleted { chat, ids } => {
+                let Some(chat_state) = self.chat_state_mut(chat) else {
+                    return;
+                };
+                let removed = chat_state.remove_messages(|m| ids.contains(&m.id()));
+                for id in &ids {
+                    if let Some(reply) = chat_state.replies.get_mut(id) {
+                        *reply = None;
+                    }
+                }
+                if chat_state.dialog.last_message.as_ref().is_some_and(|m| ids.contains(&m.id())) {
+                    chat_state.dialog.last_message = chat_state.messages.front().cloned();
+                }
+                self.status = Some(format!("deleted {} messages", ids.len()));
+                self.uncache_media(&removed);
+            }
+            ApiEvent::ChatEdited { chat, what } => {
+                let name = self.chat_state_mut(chat).map(|c| c.dialog.chat().name().to_string());
+                self.status = Some(match name {
+                    Some(name) => format!("changed the {} of {}", what, name),
+                    None => format!("changed the {}", what),
+                });
+                // the title and permissions are kept with the dialog
+                self.send_job(ApiJob::RefreshDialogs);
+            }
+            ApiEvent::Banned { chat, user_id } => {
+                let Some(chat_state) = self.chat_state_mut(chat) else {
+                    return;
+                };
+                let removed = chat_state.remove_messages(|m| m.sender().is_some_and(|s| s.id() == user_id));
+                self.status = Some(format!("banned, deleted {} loaded messages", removed.len()));
+                self.uncache_media(&removed);
+            }
+            ApiEvent::MarkedUnread { chat, unread } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.marked_unread = unread;
+                }
+            }
+            ApiEvent::LoadedPinned { chat, message } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.pinned = message;
+                }
+            }
+            ApiEvent::Pinned { chat, message_id, unpin } => {
+                let Some(chat_state) = self.chat_state_mut(chat) else {
+                    return;
+                };
+                if !unpin {
+                    chat_state.pinned = chat_state.messages.iter().find(|m| m.id() == message_id).cloned();
+                } else if chat_state.pinned.as_ref().is_some_and(|m| m.id() == message_id) {
+                    // the one pinned before it is shown again
+                    chat_state.pinned = None;
+                    self.send_job(ApiJob::LoadPinned(chat));
+                }
+            }
+            ApiEvent::LoadedViews { chat, views } => {
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.views.extend(views);
+                }
+            }
+            ApiEvent::Translated { chat, message_id, text } => {
+                self.status = None;
+                if let Some(chat_state) = self.chat_state_mut(chat) {
+                    chat_state.translations.insert(message_id, text);
+                }
+            }
+            ApiEvent::MessageDeleted(_deleted) => {}
+            ApiEvent::MessageEdited(message) => {
+                if let Some(chat_state) = self.chat_state_mut(message.chat().into()) {
+                    if let Some(old) = chat_state.messages.iter_mut().find(|m| m.id() == message.id()) {
+                        *old = message.clone();
+                    }
+                    if let Some(Some(old)) = chat_state.replies.get_mut(&message.id()) {
+                        *old = message.clone();
+                    }
+                    if chat_state.dialog.last_message.as_ref().is_some_and(|m| m.id() == message.id()) {
+                        chat_state.dialog.last_message = Some(message);
+                    }
+                }
+            }
+            ApiEvent::Notice(notice) => self.status = Some(notice),
+            ApiEvent::Error { doing, error } => self.show_error(doing, error),
+        }
+    }
+
+    /// Download media of the message in the background if the config says so.
+    fn auto_download(&mut self, message: &Message) {
+        let Some(media) = message.media() else {
+            return;
+        };
+        let chat = message.chat().pack();
+        if !download::should_auto_download(&self.config.auto_download, &chat, &media) {
+            return;
+        }
+        let Some(key) = cache::media_key(&media) else {
+            return;
+        };
+        if self.cache.get(CacheKind::File, &key).is_some() {
+            return;
+        }
+        let path = self.cache.path(CacheKind::File, &key);
+        if !self.pending_downloads.insert(path.clone()) {
+            return;
+        }
+        let id = self.start_transfer(
+            Direction::Download,
+            key,
+            download::media_size(&media),
+            Some(path.clone()),
+        );
+        self.send_job(ApiJob::Download {
+            id,
+            media,
+            path,
+            then: AfterDownload::Cache,
+        });
+    }
+
+    /// Desktop notification for an incoming message, unless its chat is
+    /// open, and the bell if it's configured to ring for it.
+    fn notify(&mut self, message: &Message) {
+        if self.dnd().is_some() || message.outgoing() {
+            return;
+        }
+        if self.config.rings(message.chat().id(), message.mentioned()) {
+            self.bell();
+        }
+        if self.current_chat().is_some_and(|c| c.chat.id == message.chat().id()) {
+            return;
+        }
+        self.play_sound(message.chat().id());
+        if !self.config.notifications.enabled {
+            return;
+        }
+        if let Err(e) = notify::message(&self.config, message, self.redact) {
+            self.status = Some(format!("failed to notify: {}", e));
+        }
+    }
+
+    /// Status bar segments of the plugins.
+    pub fn plugin_status(&self) -> Vec<String> {
+        self.plugins.iter().filter_map(|p| p.status()).collect()
+    }
+
+    /// Run the hooks for a new message: every one fires the message hook,
+    /// incoming ones mentioning me the mention hook too.
+    fn fire_hooks(&mut self, message: &Message) {
+        let hook_message = HookMessage::new(message);
+        let mut events = vec![HookEvent::Message(hook_message.clone())];
+        if message.mentioned() && !message.outgoing() {
+            events.push(HookEvent::Mention(hook_message));
+        }
+        for event in events {
+            if let Err(e) = hooks::fire(&self.config.hooks, &event) {
+                self.status = Some(format!("failed to run hook: {}", e));
+            }
+        }
+    }
+
+    /// Open the completion popup of group members after typing an @ at
+    /// the start of a word.
+    fn complete_mention(&mut self) {
+        let Some(chat_state) = self.current_chat() else {
+            return;
+        };
+        if !is_group(chat_state.dialog.chat()) {
+            return;
+        }
+        let text = &chat_state.draft.input.text[..chat_state.draft.input.cursor];
+        let at_word_start = text
+            .strip_suffix('@')
+            .is_some_and(|before| before.chars().next_back().is_none_or(char::is_whitespace));
+        if !at_word_start {
+            return;
+        }
+        let picker = match &chat_state.members {
+            Some(members) => Picker::new("Mention", mention_items(members)),
+            None => {
+                self.send_job(ApiJob::LoadMembers(chat_state.chat));
+                Picker::loading("Mention")
+            }
+        };
+        self.popup = Some(Popup::Mention(picker));
+    }
+
+    /// Read the config file again and use it from now on, or keep the
+    /// current one if the file is invalid.
+    pub fn reload_config(&mut self) {
+        match Config::load() {
+            Ok(config) => {
+                self.cache = MediaCache::new(config.media_cache_size);
+                self.config = config;
+                self.status = Some("config reloaded".into());
+            }
+            Err(e) => self.status = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Show an error in the status bar, with a hint on what to do about it.
+    fn show_error(&mut self, doing: &str, error: Tg9Error) {
+        self.status = Some(match error.hint() {
+            Some(hint) => format!("failed to {}: {} ({})", doing, error, hint),
+            None => format!("failed to {}: {}", doing, error),
+        });
+    }
+
+    fn bell(&mut self) {
+        match self.config.bell {
+            Bell::Off => {}
+            Bell::Audible => {
+                if let Err(e) = notify::bell() {
+                    self.status = Some(format!("failed to ring the bell: {}", e));
+                }
+            }
+            Bell::Visual => self.flash_until = Some(Instant::now() + Duration::from_millis(300)),
+        }
+    }
+
+    /// Remember the sender and chat of a message to reach them later.
+    fn record_peers(&mut self, message: &Message) {
+        self.peers.record(&message.chat());
+        if let Some(sender) = message.sender() {
+            self.peers.record(&sender);
+        }
+    }
+
+    /// Ask the next field of a 2FA change, or make it once all are answered.
+    fn fill_password_form(&mut self, form: PasswordForm) {
+        if form.field().is_some() {
+            self.popup = Some(Popup::PasswordForm(form));
+            return;
+        }
+        match form.edit() {
+            Ok(edit) => self.dispatch(Action::EditPassword(edit)),
+            Err(e) => {
+                self.popup = None;
+                self.status = Some(e);
+            }
+        }
+    }
+
+    /// Delete the cached media of deleted messages, which isn't reachable
+    /// from tg9 anymore.
+    fn uncache_media(&mut self, messages: &VecDeque<Message>) {
+        for media in messages.iter().filter_map(|m| m.media()) {
+            if let Some(key) = cache::media_key(&media) {
+                if let Err(e) = self.cache.remove(CacheKind::File, &key) {
+                    self.show_error("clear the media cache", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Play the notification sound of a chat, unless one was played too
+    /// recently.
+    fn play_sound(&mut self, chat_id: i64) {
+        let Some(command) = self.config.sound(chat_id) else {
+            return;
+        };
+        if self.sound_played.is_some_and(|played| played.elapsed() < SOUND_INTERVAL) {
+            return;
+        }
+        self.sound_played = Some(Instant::now());
+        if let Err(e) = notify::sound(command) {
+            self.status = Some(format!("failed to play the sound: {}", e));
+        }
+    }
+
+    /// Where the file of a message was downloaded to: saved with `s`,
+    /// into the media cache, or into its chat's download directory before.
+    fn downloaded_file(&self, message: &Message) -> Option<PathBuf> {
+        let chat = message.chat().id();
+        if let Some(path) = self.saved_files.get(&(chat, message.id())).filter(|p| p.exists()) {
+            return Some(path.clone());
+        }
+        let media = message.media()?;
+        if let Some(path) = cache::media_key(&media).and_then(|key| self.cache.get(CacheKind::File, &key)) {
+            return Some(path);
+        }
+        let (_, name) = saved_file(message)?;
+        let path = command::expand_tilde(&self.config.download_dir(chat)).join(name);
+        path.exists().then_some(path)
+    }
+
+    /// Copy an image file to the clipboard as an image, not its path.
+    fn copy_image(&mut self, path: &std::path::Path) {
+        let mime = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("webp") => "image/webp",
+            Some("gif") => "image/gif",
+            _ => {
+                self.status = Some("can't copy this kind of image".into());
+                return;
+            }
+        };
+        let Some(command) = self.config.copy_image_command(mime) else {
+            self.status = Some("copying images needs wl-copy or xclip".into());
+            return;
+        };
+        let result = std::fs::read(path).and_then(|image| external::spawn_with_input(&command, image));
+        match result {
+            Ok(()) => self.status = Some("copied the image".into()),
+            Err(e) => self.show_error("copy the image", e.into()),
+        }
+    }
+
+    /// Offer to attach text pasted into the draft as a file, when it's too
+    /// long for a message.
+    fn offer_snippet(&mut self, text: &str) {
+        let dir = config::temp_dir();
+        let name = format!("paste-{}.txt", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"));
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(dir.join(&name), text)) {
+            self.show_error("paste", e.into());
+            return;
+        }
+        self.popup = Some(Popup::Confirm(Confirm {
+            prompt: format!(
+                "The {} lines pasted are longer than a message can be, attach them as {} instead?",
+                text.lines().count(),
+                name
+            ),
+            action: Action::Attach(dir.join(name)),
+        }));
+    }
+
+    /// Write the image in the clipboard to a temporary file, if there's
+    /// one of a type which can be sent as a photo.
+    fn clipboard_image(&self) -> std::io::Result<Option<PathBuf>> {
+        let Some((list, paste)) = self.config.paste_commands() else {
+            return Err(std::io::Error::other("pasting images needs wl-paste or xclip"));
+        };
+        let types = external::output(list, std::iter::empty::<&str>())?;
+        let Some((mime, extension)) = [("image/png", "png"), ("image/jpeg", "jpg"), ("image/webp", "webp")]
+            .into_iter()
+            .find(|(mime, _)| types.lines().any(|line| line.trim() == *mime))
+        else {
+            return Ok(None);
+        };
+        let image = external::output_bytes(paste, [mime])?;
+        let dir = config::temp_dir();
+        std::fs::create_dir_all(&dir)?;
+        let name = format!("clipboard-{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"), extension);
+        std::fs::write(dir.join(&name), image)?;
+        Ok(Some(dir.join(name)))
+    }
+
+    fn apply_patch(&mut self, patch: &std::path::Path, target: &std::path::Path) {
+        match diff::apply(patch, target) {
+            // patch says which files it changed, the last is enough
+            Ok(printed) => {
+                self.status = Some(match printed.lines().last() {
+                    Some(line) => line.to_string(),
+                    None => format!("applied the patch to {}", target.display()),
+                })
+            }
+            Err(e) => self.show_error("apply the patch", e.into()),
+        }
+    }
+
+    fn export_video_note(&mut self, video_note: &std::path::Path, target: &std::path::Path) {
+        match video_note::export(video_note, target) {
+            Ok(true) => self.status = Some(format!("exported {}", target.display())),
+            Ok(false) => self.status = Some(format!("copied {} as it is, ffmpeg isn't installed", target.display())),
+            Err(e) => self.show_error("export the video note", e.into()),
+        }
+    }
+
+    /// Open a downloaded video with the player, or play a GIF inside the
+    /// terminal.
+    fn play(&mut self, path: &std::path::Path, then: AfterDownload) {
+        let result = match then {
+            AfterDownload::PlayInTerminal => {
+                external::command(&self.config.terminal_player, [path]).map(|command| {
+                    self.foreground = Some(command);
+                })
+            }
+            _ => external::spawn(&self.config.player, path),
+        };
+        self.status = match result {
+            Ok(()) => None,
+            Err(e) => Some(format!("failed to play {}: {}", path.display(), e)),
+        };
+    }
+}
+
+/// Media of a message which can be saved as a file, with its file name.
+fn saved_file(message: &Message) -> Option<(Media, String)> {
+    let media = message.media()?;
+    let name = match &media {
+        Media::Document(document) => match file_name(document.name()) {
+            Some(name) => name,
+            None if video_note::is_video_note(document) => video_note::file_name(document),
+            None => cache::media_key(&media)?,
+        },
+        Media::Photo(_) => cache::media_key(&media)?,
+        _ => return None,
+    };
+    Some((media, name))
+}
+
+/// Last part of a file name the sender chose, so it can't point outside
+/// the directory it's saved to; none if nothing is left, like for `..`.
+fn file_name(name: &str) -> Option<String> {
+    std::path::Path::new(name).file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Members labelled by name and username for the mention popup.
+fn mention_items(members: &[Member]) -> Vec<(String, Member)> {
+    members
+        .iter()
+        .map(|m| {
+            let label = match &m.username {
+                Some(username) => format!("{} @{}", m.name, username),
+                None => m.name.clone(),
+            };
+            (label, m.clone())
+        })
+        .collect()
+}
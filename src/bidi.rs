@@ -0,0 +1,88 @@
+use ratatui::text::{Span, Text};
+use std::borrow::Cow;
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level};
+
+/// Whether a letter is written right to left, like Arabic and Hebrew ones.
+fn is_rtl_char(c: char) -> bool {
+    matches!(bidi_class(c), BidiClass::R | BidiClass::AL)
+}
+
+pub fn has_rtl(text: &str) -> bool {
+    text.chars().any(is_rtl_char)
+}
+
+/// Whether text reads right to left, going by its first letter which has
+/// a direction.
+pub fn is_rtl(text: &str) -> bool {
+    text.chars()
+        .find_map(|c| match bidi_class(c) {
+            BidiClass::L => Some(false),
+            BidiClass::R | BidiClass::AL => Some(true),
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+fn level(rtl: Option<bool>) -> Option<Level> {
+    rtl.map(|rtl| if rtl { Level::rtl() } else { Level::ltr() })
+}
+
+/// A line in the order its characters are drawn: terminals draw them left
+/// to right as given, so the right-to-left runs are reversed as the
+/// Unicode bidi algorithm says. The direction of the line is found from
+/// its text unless given.
+pub fn visual(line: &str, rtl: Option<bool>) -> Cow<'_, str> {
+    if rtl != Some(true) && !has_rtl(line) {
+        return Cow::Borrowed(line);
+    }
+    let info = BidiInfo::new(line, level(rtl));
+    let reordered: String = info
+        .paragraphs
+        .iter()
+        .map(|para| info.reorder_line(para, para.range.clone()))
+        .collect();
+    Cow::Owned(reordered)
+}
+
+/// Every span of the text reordered by [`visual`], keeping their styles.
+pub fn text(mut text: Text<'static>) -> Text<'static> {
+    for span in text.lines.iter_mut().flat_map(|line| line.spans.iter_mut()) {
+        if has_rtl(&span.content) {
+            span.content = visual(&span.content, None).into_owned().into();
+        }
+    }
+    text
+}
+
+/// Cell of a line reordered by [`visual`] that the cursor at byte `cursor`
+/// is drawn on, from the left end of the line. At the end of a right to
+/// left line it's -1, just left of it.
+pub fn cursor_column(line: &str, cursor: usize, rtl: Option<bool>) -> isize {
+    let width = |s: &str| Span::raw(s).width() as isize;
+    if rtl != Some(true) && !has_rtl(line) {
+        return width(&line[..cursor]);
+    }
+    let info = BidiInfo::new(line, level(rtl));
+    let Some(para) = info.paragraphs.first() else {
+        return 0;
+    };
+    if cursor >= line.len() {
+        return if para.level.is_rtl() { -1 } else { width(line) };
+    }
+    let (levels, runs) = info.visual_runs(para, para.range.clone());
+    let mut column = 0;
+    for run in runs {
+        if run.contains(&cursor) {
+            // a right to left run is drawn from its end
+            let next = cursor + line[cursor..].chars().next().map_or(0, char::len_utf8);
+            return column
+                + if levels[run.start].is_rtl() {
+                    width(&line[next..run.end])
+                } else {
+                    width(&line[run.start..cursor])
+                };
+        }
+        column += width(&line[run]);
+    }
+    column
+}
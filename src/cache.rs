@@ -0,0 +1,169 @@
+use crate::error::{Result, Tg9Error};
+use grammers_client::types::Media;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What a cached file is used for, each kind has its own subdirectory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    /// Media of messages
+    File,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 1] = [CacheKind::File];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            CacheKind::File => "files",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KindStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheStats {
+    pub files: KindStats,
+}
+
+impl CacheStats {
+    pub fn total(&self) -> KindStats {
+        KindStats {
+            files: self.files.files,
+            bytes: self.files.bytes,
+        }
+    }
+}
+
+/// Media cache on disk, addressed by Telegram's media ids. Total size is
+/// kept under `max_size` by evicting the least recently used files, where
+/// use is tracked by touching the modification time.
+#[derive(Debug, Clone)]
+pub struct MediaCache {
+    root: PathBuf,
+    max_size: u64,
+}
+
+impl MediaCache {
+    pub fn new(max_size: u64) -> Self {
+        let root = dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("tg9")
+            .join("media");
+        MediaCache { root, max_size }
+    }
+
+    /// Where a file with the key is (or will be) stored.
+    pub fn path(&self, kind: CacheKind, key: &str) -> PathBuf {
+        self.root.join(kind.dir_name()).join(key)
+    }
+
+    /// Path of a cached file, marking it as recently used.
+    pub fn get(&self, kind: CacheKind, key: &str) -> Option<PathBuf> {
+        let path = self.path(kind, key);
+        let file = File::options().append(true).open(&path).ok()?;
+        let _ = file.set_modified(SystemTime::now());
+        Some(path)
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let mut stats = CacheStats::default();
+        for kind in CacheKind::ALL {
+            let kind_stats = match kind {
+                CacheKind::File => &mut stats.files,
+            };
+            for (_, len, _) in self.files(kind).map_err(Tg9Error::Cache)? {
+                kind_stats.files += 1;
+                kind_stats.bytes += len;
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_dir_all(&self.root) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Tg9Error::Cache(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete a cached file, if there is one.
+    pub fn remove(&self, kind: CacheKind, key: &str) -> Result<()> {
+        match fs::remove_file(self.path(kind, key)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Tg9Error::Cache(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete the least recently used files until the cache fits in its size.
+    pub fn evict(&self) -> Result<()> {
+        let mut files = Vec::new();
+        for kind in CacheKind::ALL {
+            files.extend(self.files(kind).map_err(Tg9Error::Cache)?);
+        }
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort();
+        for (_, len, path) in files {
+            if total <= self.max_size {
+                break;
+            }
+            fs::remove_file(path).map_err(Tg9Error::Cache)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    /// Files of a kind with their last use time and size.
+    fn files(&self, kind: CacheKind) -> io::Result<Vec<(SystemTime, u64, PathBuf)>> {
+        read_files(&self.root.join(kind.dir_name()))
+    }
+}
+
+fn read_files(dir: &Path) -> io::Result<Vec<(SystemTime, u64, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_file() {
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, meta.len(), entry.path()));
+        }
+    }
+    Ok(files)
+}
+
+/// Cache key of message media: its id, keeping the extension so the file
+/// can be opened by other programs.
+pub fn media_key(media: &Media) -> Option<String> {
+    match media {
+        Media::Photo(photo) => Some(photo_key(photo.id())),
+        Media::Document(document) => Some(document_key(document.id(), document.name())),
+        _ => None,
+    }
+}
+
+pub fn photo_key(id: i64) -> String {
+    format!("{}.jpg", id)
+}
+
+/// Cache key of a document by its id and file name.
+pub fn document_key(id: i64, name: &str) -> String {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| format!(".{}", e))
+        .unwrap_or_default();
+    format!("{}{}", id, ext)
+}
@@ -0,0 +1,348 @@
+use crate::api;
+use crate::command;
+use crate::config::Config;
+use crate::hooks::HookMessage;
+use crate::render;
+use crate::session;
+use crate::usernames::UsernameCache;
+use anyhow::{bail, Context, Result};
+use chrono::{Local, Utc};
+use grammers_client::types::Message;
+use grammers_client::{Client, InputMessage, Update};
+use grammers_session::PackedChat;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Messages printed by `tg9 tail` without `--since`
+const TAIL_DEFAULT: usize = 10;
+
+/// Matches printed by `tg9 search` without `--limit`
+const SEARCH_DEFAULT: usize = 20;
+
+/// Options of `tg9 send`.
+#[derive(Debug, Default)]
+pub struct SendArgs {
+    /// `@username`, or `me` for Saved Messages
+    pub peer: String,
+    /// File to send, `-` to read it from standard input
+    pub file: Option<PathBuf>,
+    /// Name of the file, for one read from standard input
+    pub name: Option<String>,
+    pub caption: Option<String>,
+    /// Text of the message, read from standard input if there's neither
+    /// text nor a file
+    pub text: Vec<String>,
+}
+
+impl SendArgs {
+    pub const USAGE: &'static str =
+        "usage: tg9 send <@username|me> [--file <path|->] [--name <name>] [--caption <text>] [text]";
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<SendArgs> {
+        let mut send = SendArgs {
+            peer: args.next().context(Self::USAGE)?,
+            ..SendArgs::default()
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--file" => send.file = Some(args.next().context(Self::USAGE)?.into()),
+                "--name" => send.name = Some(args.next().context(Self::USAGE)?),
+                "--caption" => send.caption = Some(args.next().context(Self::USAGE)?),
+                _ => send.text.push(arg),
+            }
+        }
+        Ok(send)
+    }
+}
+
+/// Send a message or a file from the command line, e.g. to pipe a
+/// screenshot or a log into a chat. Refused in read-only mode, by
+/// `--read-only` or the config.
+pub async fn send(args: SendArgs, read_only: bool) -> Result<()> {
+    if read_only {
+        bail!("read-only mode is on (--read-only)");
+    }
+    if Config::load()?.read_only {
+        bail!("read-only mode is on in the config");
+    }
+    let client = connect().await?;
+    let chat = resolve(&client, &args.peer).await?;
+    let text = args.text.join(" ");
+
+    let message = match args.file {
+        None => {
+            let text = if text.is_empty() {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                text
+            };
+            if text.trim().is_empty() {
+                bail!("nothing to send");
+            }
+            InputMessage::text(text)
+        }
+        Some(path) => {
+            let (bytes, name) = if path.as_os_str() == "-" {
+                let mut bytes = Vec::new();
+                std::io::stdin().read_to_end(&mut bytes)?;
+                (bytes, args.name)
+            } else {
+                let bytes = std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+                let name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+                (bytes, args.name.or(name))
+            };
+            if bytes.is_empty() {
+                bail!("the file is empty");
+            }
+            let file_type = FileType::of(&bytes);
+            let name = name.unwrap_or_else(|| format!("stdin.{}", file_type.extension));
+            let size = bytes.len();
+            let file = client.upload_stream(&mut Cursor::new(bytes), size, name).await?;
+            let message = InputMessage::text(args.caption.unwrap_or(text)).mime_type(file_type.mime_type);
+            if file_type.is_photo() {
+                message.photo(file)
+            } else {
+                message.document(file)
+            }
+        }
+    };
+    client.send_message(chat, message).await?;
+    Ok(())
+}
+
+/// Options of `tg9 tail`.
+#[derive(Debug, Default)]
+pub struct TailArgs {
+    /// `@username`, or `me` for Saved Messages
+    pub peer: String,
+    /// Print the messages of this long ago until now, instead of the last
+    /// few
+    pub since: Option<Duration>,
+    /// Keep printing new messages as they arrive
+    pub follow: bool,
+    /// Print a JSON object per message instead of a line of text
+    pub json: bool,
+}
+
+impl TailArgs {
+    pub const USAGE: &'static str = "usage: tg9 tail <@username|me> [--since <duration>] [--follow] [--json]";
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<TailArgs> {
+        let mut tail = TailArgs {
+            peer: args.next().context(Self::USAGE)?,
+            ..TailArgs::default()
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--since" => {
+                    let since = args.next().context(Self::USAGE)?;
+                    let since = command::parse_duration(&since).context("invalid duration, e.g. 30m or 1h30m")?;
+                    tail.since = Some(since);
+                }
+                "-f" | "--follow" => tail.follow = true,
+                "--json" => tail.json = true,
+                _ => bail!("{}", Self::USAGE),
+            }
+        }
+        Ok(tail)
+    }
+}
+
+/// Print the recent messages of a chat, and with `--follow` the new ones
+/// until interrupted, for piping into other tools.
+pub async fn tail(args: TailArgs) -> Result<()> {
+    let client = connect().await?;
+    let chat = resolve(&client, &args.peer).await?;
+
+    let mut messages = Vec::new();
+    match args.since {
+        Some(since) => {
+            let cutoff = Utc::now() - chrono::Duration::from_std(since)?;
+            let mut iter = client.iter_messages(chat);
+            while let Some(message) = iter.next().await? {
+                if message.date() < cutoff {
+                    break;
+                }
+                messages.push(message);
+            }
+        }
+        None => {
+            let mut iter = client.iter_messages(chat).limit(TAIL_DEFAULT);
+            while let Some(message) = iter.next().await? {
+                messages.push(message);
+            }
+        }
+    }
+    // newest first from the API
+    for message in messages.iter().rev() {
+        print_message(message, args.json, false)?;
+    }
+
+    if !args.follow {
+        return Ok(());
+    }
+    while let Some(update) = client.next_update().await? {
+        if let Update::NewMessage(message) = update {
+            if message.chat().id() == chat.id {
+                print_message(&message, args.json, false)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Options of `tg9 search`.
+#[derive(Debug, Default)]
+pub struct SearchArgs {
+    /// `@username`, or `me` for Saved Messages
+    pub peer: String,
+    pub query: String,
+    /// Most matches printed
+    pub limit: Option<usize>,
+    /// Print a JSON object per match instead of a line of text
+    pub json: bool,
+}
+
+impl SearchArgs {
+    pub const USAGE: &'static str = "usage: tg9 search <@username|me> <query> [--limit <n>] [--json]";
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<SearchArgs> {
+        let mut search = SearchArgs {
+            peer: args.next().context(Self::USAGE)?,
+            ..SearchArgs::default()
+        };
+        let mut query = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--limit" => {
+                    let limit = args.next().and_then(|n| n.parse().ok()).context(Self::USAGE)?;
+                    search.limit = Some(limit);
+                }
+                "--json" => search.json = true,
+                _ => query.push(arg),
+            }
+        }
+        if query.is_empty() {
+            bail!("{}", Self::USAGE);
+        }
+        search.query = query.join(" ");
+        Ok(search)
+    }
+}
+
+/// Print the messages of a chat matching a query, newest first, with
+/// their ids.
+pub async fn search(args: SearchArgs) -> Result<()> {
+    let client = connect().await?;
+    let chat = resolve(&client, &args.peer).await?;
+    let limit = args.limit.unwrap_or(SEARCH_DEFAULT);
+    for message in api::search(&client, chat, &args.query, limit).await? {
+        print_message(&message, args.json, true)?;
+    }
+    Ok(())
+}
+
+/// A message as a line like `2024-01-31 18:04:12 Ann: hi`, optionally
+/// starting with its id, or as JSON like the input of hooks.
+fn print_message(message: &Message, json: bool, with_id: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string(&HookMessage::new(message))?);
+        return Ok(());
+    }
+    let sender = message.sender().unwrap_or_else(|| message.chat());
+    let line = format!(
+        "{} {}: {}",
+        message.date().with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        sender.name(),
+        render::summary(message)
+    );
+    if with_id {
+        println!("{} {}", message.id(), line);
+    } else {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Connect with the session saved by logging in with the UI.
+pub async fn connect() -> Result<Client> {
+    if !api::has_session() {
+        bail!("not logged in, run tg9 to log in first");
+    }
+    let path = &api::session_path();
+    let passphrase = if session::is_encrypted(path) {
+        Some(session::passphrase("Passphrase: ")?)
+    } else {
+        None
+    };
+    let client = api::connect(session::load(path, passphrase.as_deref())?).await?;
+    if !client.is_authorized().await? {
+        bail!("the saved session was logged out, run tg9 to log in again");
+    }
+    Ok(client)
+}
+
+async fn resolve(client: &Client, peer: &str) -> Result<PackedChat> {
+    if peer == "me" {
+        return Ok(client.get_me().await?.pack());
+    }
+    let Some(username) = peer.strip_prefix('@') else {
+        bail!("{}", SendArgs::USAGE);
+    };
+    let mut usernames = UsernameCache::load();
+    if let Some(chat) = usernames.get(username) {
+        return Ok(chat);
+    }
+    match client.resolve_username(username).await? {
+        Some(chat) => {
+            usernames.insert(username, chat.pack());
+            let _ = usernames.save();
+            Ok(chat.pack())
+        }
+        None => bail!("@{} doesn't exist", username),
+    }
+}
+
+/// Type of a file guessed from its first bytes, since there's no name to
+/// go by when it's piped in.
+struct FileType {
+    mime_type: &'static str,
+    extension: &'static str,
+}
+
+impl FileType {
+    fn of(bytes: &[u8]) -> FileType {
+        const MAGIC: &[(&[u8], &str, &str)] = &[
+            (b"\x89PNG\r\n\x1a\n", "image/png", "png"),
+            (b"\xff\xd8\xff", "image/jpeg", "jpg"),
+            (b"GIF8", "image/gif", "gif"),
+            (b"%PDF-", "application/pdf", "pdf"),
+            (b"PK\x03\x04", "application/zip", "zip"),
+            (b"\x1f\x8b", "application/gzip", "gz"),
+            (b"OggS", "audio/ogg", "ogg"),
+            (b"ID3", "audio/mpeg", "mp3"),
+        ];
+        let (mime_type, extension) = if let Some(&(_, mime_type, extension)) =
+            MAGIC.iter().find(|(magic, _, _)| bytes.starts_with(magic))
+        {
+            (mime_type, extension)
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            ("image/webp", "webp")
+        } else if bytes.get(4..8) == Some(b"ftyp") {
+            ("video/mp4", "mp4")
+        } else if std::str::from_utf8(bytes).is_ok() {
+            ("text/plain", "txt")
+        } else {
+            ("application/octet-stream", "bin")
+        };
+        FileType { mime_type, extension }
+    }
+
+    /// Whether it can be sent as a compressed photo.
+    fn is_photo(&self) -> bool {
+        matches!(self.mime_type, "image/png" | "image/jpeg")
+    }
+}
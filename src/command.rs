@@ -0,0 +1,399 @@
+use crate::action::Action;
+use crate::api::{ChatEdit, ChatRight, ClearHistory, NewPoll, PrivacyKey, PrivacyValue, ProfileEdit, SESSION_TTL_DAYS};
+use crate::compose::PostOption;
+use crate::help::{self, Topic};
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Parse a `:` command line into an action.
+pub fn parse(line: &str) -> Result<Action, String> {
+    let line = line.trim();
+    let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+    let args = args.trim();
+    // the built-in commands are those `:help` lists, others may be a plugin's
+    if !name.is_empty() && !help::is_command(name) {
+        return Ok(Action::PluginCommand {
+            name: name.to_string(),
+            args: args.to_string(),
+        });
+    }
+    match name {
+        "q" | "quit" => Ok(Action::Quit),
+        "help" if args.is_empty() => Ok(Action::Help(Topic::Commands)),
+        "help" => match Topic::parse(args) {
+            Some(topic) => Ok(Action::Help(topic)),
+            None => {
+                let topics: Vec<_> = Topic::ALL.iter().map(|topic| topic.name()).collect();
+                Err(format!("the help topics are {}", topics.join(", ")))
+            }
+        },
+        "send" => Ok(Action::Send),
+        "preview" => Ok(Action::ToggleLinkPreview),
+        "post" => {
+            let usage = || "usage: post [silent|sign|schedule <+1h|18:00|2024-05-01 18:00>|now|clear]".to_string();
+            let (option, when) = args.split_once(' ').unwrap_or((args, ""));
+            match option {
+                "" => Ok(Action::PostOption(PostOption::Show)),
+                "silent" => Ok(Action::PostOption(PostOption::Silent)),
+                "sign" => Ok(Action::PostOption(PostOption::Signed)),
+                "schedule" => Ok(Action::PostOption(PostOption::Schedule(Some(
+                    parse_schedule(when.trim()).ok_or_else(usage)?,
+                )))),
+                "now" => Ok(Action::PostOption(PostOption::Schedule(None))),
+                "clear" => Ok(Action::PostOption(PostOption::Clear)),
+                _ => Err(usage()),
+            }
+        }
+        "contact" => Ok(Action::ShareContact),
+        "emoji" => Ok(Action::EmojiPicker),
+        "switch" => Ok(Action::SwitchChat),
+        "split" | "vsplit" => Ok(Action::ToggleSplit),
+        "only" => Ok(Action::CloseSplit),
+        "tab" | "tabnew" => Ok(Action::OpenTab),
+        "tabclose" => Ok(Action::CloseTab),
+        "undo" => Ok(Action::Undo),
+        "hide" => Ok(Action::HideDialog),
+        "unhide" => Ok(Action::UnhideDialogs),
+        "tabnext" => Ok(Action::NextTab),
+        "tabprev" => Ok(Action::PrevTab),
+        "dismiss" => Ok(Action::DismissStory),
+        "play" => Ok(Action::Play),
+        "translate" => Ok(Action::Translate(Some(args.to_string()).filter(|a| !a.is_empty()))),
+        "cancel" => Ok(Action::CancelTransfer),
+        "refresh" => Ok(Action::RefreshDialogs),
+        "save" => Ok(Action::SaveFile(Some(args).filter(|a| !a.is_empty()).map(expand_tilde))),
+        "copyimage" => Ok(Action::CopyImage),
+        "copycode" => Ok(Action::CopyCode),
+        "apply" => {
+            if args.is_empty() {
+                return Err("usage: apply <file or directory>".into());
+            }
+            Ok(Action::ApplyPatchTo(expand_tilde(args)))
+        }
+        "mp4" => Ok(Action::ExportVideoNote((!args.is_empty()).then(|| expand_tilde(args)))),
+        "reveal" => Ok(Action::OpenFile { reveal: true }),
+        "export" => {
+            if args.is_empty() {
+                return Err("usage: export <path>".into());
+            }
+            Ok(Action::Export(expand_tilde(args)))
+        }
+        "filter" => Ok(Action::Filter(Some(args.to_string()).filter(|a| !a.is_empty()))),
+        "unread" => Ok(Action::ToggleUnreadOnly),
+        "catchup" => Ok(Action::CatchUp),
+        "redact" => Ok(Action::ToggleRedact),
+        "metrics" => Ok(Action::ToggleMetrics),
+        "discussion" => Ok(Action::LinkedChat),
+        "comments" | "replies" => Ok(Action::OpenComments),
+        "thread" => Ok(Action::ToggleThread),
+        "markunread" => Ok(Action::ToggleUnreadMark),
+        "pin" => match args {
+            "" => Ok(Action::Pin { notify: false }),
+            "notify" => Ok(Action::Pin { notify: true }),
+            _ => Err("usage: pin [notify]".into()),
+        },
+        "unpin" => Ok(Action::Unpin),
+        "report" => Ok(Action::ReportSpam),
+        "whois" => Ok(Action::Whois),
+        "open" => match args.trim().strip_prefix('@') {
+            Some(username) if !username.is_empty() => Ok(Action::OpenUsername(username.to_string())),
+            // the file of the selected message
+            None if args.trim().is_empty() => Ok(Action::OpenFile { reveal: false }),
+            _ => Err("usage: open [@<username>]".into()),
+        },
+        "password" | "2fa" => Ok(Action::Password),
+        "sessions" => match args.split_once(' ') {
+            None if args.is_empty() => Ok(Action::Sessions),
+            Some(("ttl", days)) => match days.trim().parse() {
+                Ok(days) if SESSION_TTL_DAYS.contains(&days) => Ok(Action::SetSessionTtl(days)),
+                _ => Err(format!("the TTL is one of {:?} days", SESSION_TTL_DAYS)),
+            },
+            _ => Err("usage: sessions [ttl <days>]".into()),
+        },
+        "privacy" => {
+            if args.is_empty() {
+                return Ok(Action::Privacy);
+            }
+            let usage = || "usage: privacy [lastseen|photo|forwards|calls everybody|contacts|nobody]".to_string();
+            let (key, value) = args.split_once(' ').ok_or_else(usage)?;
+            let key = PrivacyKey::parse(key).ok_or_else(usage)?;
+            let value = PrivacyValue::parse(value.trim()).ok_or_else(usage)?;
+            Ok(Action::SetPrivacy { key, value })
+        }
+        "profile" => {
+            let (field, value) = args.split_once(' ').unwrap_or((args, ""));
+            let value = value.trim();
+            match field {
+                "" => Ok(Action::Profile),
+                "name" => {
+                    let (first, last) = value.split_once(' ').unwrap_or((value, ""));
+                    Ok(Action::EditProfile(ProfileEdit::Name {
+                        first: first.to_string(),
+                        last: last.trim().to_string(),
+                    }))
+                }
+                "bio" => Ok(Action::EditProfile(ProfileEdit::Bio(value.to_string()))),
+                "username" => Ok(Action::EditProfile(ProfileEdit::Username(
+                    value.trim_start_matches('@').to_string(),
+                ))),
+                _ => Err("usage: profile [name <first> [last]|bio [text]|username <name>]".into()),
+            }
+        }
+        "ban" => Ok(Action::Ban),
+        "requests" => Ok(Action::JoinRequests),
+        "leave" => Ok(Action::LeaveChat),
+        "autodelete" => match args {
+            "off" => Ok(Action::SetAutoDelete(None)),
+            _ => match parse_duration(args) {
+                Some(period) => Ok(Action::SetAutoDelete(Some(period))),
+                None => Err("usage: autodelete off|<period>, e.g. autodelete 1d or 7d".into()),
+            },
+        },
+        "chat" => {
+            let usage = || "usage: chat [title <text>|about [text]|photo <path>|allow|deny <permission>]".to_string();
+            let (field, value) = args.split_once(' ').unwrap_or((args, ""));
+            let value = value.trim();
+            match field {
+                "" | "permissions" => Ok(Action::ChatPermissions),
+                "title" if !value.is_empty() => Ok(Action::EditChat(ChatEdit::Title(value.to_string()))),
+                "about" => Ok(Action::EditChat(ChatEdit::About(value.to_string()))),
+                "photo" if !value.is_empty() => Ok(Action::SetChatPhoto(expand_tilde(value))),
+                "allow" | "deny" => match ChatRight::parse(value) {
+                    Some(right) => Ok(Action::SetChatRight { right, allowed: field == "allow" }),
+                    None => {
+                        let rights: Vec<_> = ChatRight::ALL.iter().map(|right| right.label()).collect();
+                        Err(format!("the permissions are {}", rights.join(", ")))
+                    }
+                },
+                _ => Err(usage()),
+            }
+        }
+        "info" => Ok(Action::MessageInfo),
+        "stats" => Ok(Action::ChatStats),
+        "clearhistory" => Ok(Action::ClearHistory(parse_clear_history(args)?)),
+        "log" | "adminlog" => Ok(Action::AdminLog),
+        "cache" => match args {
+            "stats" => Ok(Action::CacheStats),
+            "clear" => Ok(Action::CacheClear),
+            _ => Err("usage: cache stats|clear".into()),
+        },
+        "attach" => {
+            if args.is_empty() {
+                return Err("usage: attach <path>".into());
+            }
+            Ok(Action::Attach(expand_tilde(args)))
+        }
+        "location" => {
+            let usage = || "usage: location <lat> <lon>".to_string();
+            let (lat, lon) = args.split_once(' ').ok_or_else(usage)?;
+            let lat: f64 = lat.trim().parse().map_err(|_| usage())?;
+            let lon: f64 = lon.trim().parse().map_err(|_| usage())?;
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                return Err("coordinates out of range".into());
+            }
+            Ok(Action::SendLocation { lat, lon })
+        }
+        "poll" => Ok(Action::SendPoll(parse_poll(args)?)),
+        "dnd" => match args {
+            "" => Ok(Action::ToggleDnd),
+            "off" => Ok(Action::DndOff),
+            _ => match parse_duration(args) {
+                Some(duration) => Ok(Action::Dnd(Some(duration))),
+                None => Err("usage: dnd [off|<duration>], e.g. dnd 1h30m".into()),
+            },
+        },
+        "detach" => Ok(Action::Detach(parse_index(args)?)),
+        "retry" => Ok(Action::RetryQueued(parse_optional_index(args)?)),
+        "discard" => Ok(Action::DiscardQueued(parse_optional_index(args)?)),
+        "caption" => {
+            let (idx, caption) = args.split_once(' ').unwrap_or((args, ""));
+            Ok(Action::Caption(parse_index(idx)?, caption.trim().to_string()))
+        }
+        "" => Err("empty command".into()),
+        _ => Err(format!("{} is in the help but not built in", name)),
+    }
+}
+
+/// `[me|all] [keep <days>]`: for me only by default, and all messages
+/// unless the recent days are kept.
+fn parse_clear_history(args: &str) -> Result<ClearHistory, String> {
+    let usage = || "usage: clearhistory [me|all] [keep <days>]".to_string();
+    let mut scope = ClearHistory {
+        for_everyone: false,
+        keep_days: None,
+    };
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "me" => scope.for_everyone = false,
+            "all" => scope.for_everyone = true,
+            "keep" => {
+                let days = words.next().ok_or_else(usage)?.trim_end_matches('d');
+                scope.keep_days = Some(days.parse().ok().filter(|&d| d > 0).ok_or_else(usage)?);
+            }
+            _ => return Err(usage()),
+        }
+    }
+    Ok(scope)
+}
+
+/// `[--public] [--multi] <question> | <option> | <option>...`
+fn parse_poll(args: &str) -> Result<NewPoll, String> {
+    let usage = || "usage: poll [--public] [--multi] <question> | <option> | <option>...".to_string();
+    let mut poll = NewPoll {
+        question: String::new(),
+        options: Vec::new(),
+        public_voters: false,
+        multiple_choice: false,
+    };
+    let mut rest = args.trim_start();
+    loop {
+        if let Some(r) = rest.strip_prefix("--public") {
+            poll.public_voters = true;
+            rest = r.trim_start();
+        } else if let Some(r) = rest.strip_prefix("--multi") {
+            poll.multiple_choice = true;
+            rest = r.trim_start();
+        } else {
+            break;
+        }
+    }
+    let mut parts = rest.split('|').map(str::trim);
+    poll.question = parts.next().filter(|q| !q.is_empty()).ok_or_else(usage)?.to_string();
+    for option in parts {
+        if option.is_empty() {
+            return Err("empty poll option".into());
+        }
+        poll.options.push(option.to_string());
+    }
+    Ok(poll)
+}
+
+/// Like `parse_index`, none if empty.
+fn parse_optional_index(s: &str) -> Result<Option<usize>, String> {
+    match s {
+        "" => Ok(None),
+        s => parse_index(s).map(Some),
+    }
+}
+
+/// Attachment indices are shown and typed 1-based.
+fn parse_index(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("invalid index: {:?}", s)),
+    }
+}
+
+/// Durations like `45s`, `30m`, `1h30m` or `2d`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let mut secs = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: u64 = number.parse().ok()?;
+        number.clear();
+        secs += n * match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() || secs == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+/// Unix time of a time to schedule a post at: in a while like `+1h30m`, at
+/// a time of the day like `18:00`, tomorrow if it's past, or at a date and
+/// time like `2024-05-01 18:00`.
+pub fn parse_schedule(s: &str) -> Option<u64> {
+    let now = Local::now();
+    if let Some(duration) = s.strip_prefix('+') {
+        return Some(now.timestamp() as u64 + parse_duration(duration)?.as_secs());
+    }
+    let at = match NaiveTime::parse_from_str(s, "%H:%M") {
+        Ok(time) => {
+            let today = now.date_naive().and_time(time);
+            if today > now.naive_local() {
+                today
+            } else {
+                today + chrono::Duration::days(1)
+            }
+        }
+        Err(_) => NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").ok()?,
+    };
+    Some(at.and_local_timezone(Local).earliest()?.timestamp() as u64)
+}
+
+/// Complete the last part of a typed path, to the only file or directory
+/// it can be or as far as all the candidates agree, and the names of the
+/// candidates. Hidden files are left out unless asked for with a dot.
+pub fn complete_path(typed: &str) -> (String, Vec<String>) {
+    let (dir, prefix) = match typed.rfind('/') {
+        Some(i) => typed.split_at(i + 1),
+        None => ("", typed),
+    };
+    let read_dir = if dir.is_empty() { PathBuf::from(".") } else { expand_tilde(dir) };
+    let Ok(entries) = std::fs::read_dir(read_dir) else {
+        return (typed.to_string(), Vec::new());
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            Some(if entry.path().is_dir() { name + "/" } else { name })
+        })
+        .collect();
+    candidates.sort();
+    let Some(first) = candidates.first() else {
+        return (typed.to_string(), candidates);
+    };
+    let common = candidates.iter().fold(first.as_str(), |common, name| common_prefix(common, name));
+    (format!("{}{}", dir, common), candidates)
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .take_while(|((_, x), y)| x == y)
+        .last()
+        .map_or(0, |((i, c), _)| i + c.len_utf8());
+    &a[..len]
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    // Windows has no `HOME`, and takes either slash
+    let rest = path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\").filter(|_| cfg!(windows)));
+    match (rest, dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_command_in_the_help_is_built_in() {
+        for name in help::COMMANDS.iter().flat_map(|command| command.names) {
+            match parse(name) {
+                Ok(Action::PluginCommand { .. }) => panic!(":{} went to the plugins", name),
+                Err(e) => assert!(!e.ends_with("not built in"), "{}", e),
+                Ok(_) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use chrono::{DateTime, Local};
+use grammers_client::grammers_tl_types as tl;
+use grammers_session::PackedChat;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Single-line text input with a cursor, used by the compose box and the command line.
+#[derive(Debug, Default, Clone)]
+pub struct Input {
+    pub text: String,
+    /// Cursor position as a byte offset into `text`, always on a char boundary
+    pub cursor: usize,
+}
+
+impl Input {
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    pub fn delete_back(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+        }
+    }
+
+    pub fn right(&mut self) {
+        if let Some(c) = self.text[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Take the text out, leaving the input empty.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+}
+
+/// A file staged for sending in the attachment tray.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub path: PathBuf,
+    pub caption: String,
+}
+
+impl Attachment {
+    pub fn new(path: PathBuf) -> Self {
+        Attachment {
+            path,
+            caption: String::new(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    /// Whether the file can be sent as a compressed photo rather than a document.
+    pub fn is_photo(&self) -> bool {
+        is_photo_path(&self.path)
+    }
+}
+
+fn is_photo_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| matches!(e.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png" | "webp"))
+        .unwrap_or(false)
+}
+
+/// Member mentioned by name rather than by `@username`, picked from the
+/// completion popup. It's found again in the text by its name when sending.
+#[derive(Debug, Clone)]
+pub struct Mention {
+    pub name: String,
+    pub user: PackedChat,
+}
+
+/// Message a draft replies to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplyTo {
+    pub message_id: i32,
+    /// Part of its text shown quoted above the reply, if one was selected.
+    /// Not saved with the outbox, the formatting couldn't be.
+    #[serde(skip)]
+    pub quote: Option<Quote>,
+}
+
+/// Excerpt of the text of a message replied to.
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub text: String,
+    /// Formatting of the excerpt, relative to its start
+    pub entities: Vec<tl::enums::MessageEntity>,
+    /// Where the excerpt starts in the message text, in UTF-16 code units
+    pub offset: i32,
+}
+
+impl Quote {
+    /// The byte range `range` of a message text with the formatting
+    /// `entities`, of which quotes keep only the styles.
+    pub fn new(text: &str, entities: &[tl::enums::MessageEntity], range: Range<usize>) -> Self {
+        let offset = utf16_len(&text[..range.start]);
+        let length = utf16_len(&text[range.clone()]);
+        Quote {
+            text: text[range].to_string(),
+            entities: entities.iter().filter_map(|e| quoted_entity(e, offset, length)).collect(),
+            offset,
+        }
+    }
+}
+
+fn utf16_len(s: &str) -> i32 {
+    s.encode_utf16().count() as i32
+}
+
+/// An entity clipped to the quoted part of the text and made relative to
+/// it, if it's one of the styles a quote may have.
+fn quoted_entity(entity: &tl::enums::MessageEntity, start: i32, len: i32) -> Option<tl::enums::MessageEntity> {
+    use tl::enums::MessageEntity as E;
+
+    let mut entity = entity.clone();
+    // a custom emoji is dropped rather than cut
+    let whole = matches!(entity, E::CustomEmoji(_));
+    let (offset, length) = match &mut entity {
+        E::Bold(e) => (&mut e.offset, &mut e.length),
+        E::Italic(e) => (&mut e.offset, &mut e.length),
+        E::Underline(e) => (&mut e.offset, &mut e.length),
+        E::Strike(e) => (&mut e.offset, &mut e.length),
+        E::Spoiler(e) => (&mut e.offset, &mut e.length),
+        E::CustomEmoji(e) => (&mut e.offset, &mut e.length),
+        _ => return None,
+    };
+    let from = cmp::max(*offset, start);
+    let to = cmp::min(*offset + *length, start + len);
+    if from >= to || (whole && (from, to) != (*offset, *offset + *length)) {
+        return None;
+    }
+    *offset = from - start;
+    *length = to - from;
+    Some(entity)
+}
+
+/// Unsent message of a chat: the compose text and the attachment tray.
+#[derive(Debug)]
+pub struct Draft {
+    pub input: Input,
+    pub attachments: Vec<Attachment>,
+    pub reply: Option<ReplyTo>,
+    /// Mentions by name, in the order they were inserted
+    pub mentions: Vec<Mention>,
+    /// Whether Telegram should generate a preview for the first link in the text
+    pub link_preview: bool,
+    /// How the post goes out, in channels I post to
+    pub post: PostOptions,
+}
+
+impl Default for Draft {
+    fn default() -> Self {
+        Draft {
+            input: Input::default(),
+            attachments: Vec::new(),
+            reply: None,
+            mentions: Vec::new(),
+            link_preview: true,
+            post: PostOptions::default(),
+        }
+    }
+}
+
+/// How a post to a channel is broadcast, set for each post.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostOptions {
+    /// Delivered to subscribers without a sound
+    pub silent: bool,
+    /// Unix time it's published at, instead of right away
+    pub schedule: Option<u64>,
+    /// Signed with my name, although the channel doesn't sign its posts
+    pub signed: bool,
+}
+
+impl PostOptions {
+    pub fn schedule_date(&self) -> Option<SystemTime> {
+        self.schedule.map(|at| UNIX_EPOCH + Duration::from_secs(at))
+    }
+
+    /// Like `silent · at 05-01 18:00 · signed`, empty if none is set.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.silent {
+            parts.push("silent".to_string());
+        }
+        if let Some(at) = self.schedule.and_then(|at| DateTime::from_timestamp(at as i64, 0)) {
+            parts.push(format!("at {}", at.with_timezone(&Local).format("%m-%d %H:%M")));
+        }
+        if self.signed {
+            parts.push("signed".to_string());
+        }
+        parts.join(" · ")
+    }
+}
+
+/// A change of the options of a channel post, see `:post`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostOption {
+    /// Only tell which are set
+    Show,
+    Silent,
+    Signed,
+    /// Publish at the Unix time, or right away
+    Schedule(Option<u64>),
+    Clear,
+}
+
+impl Draft {
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty() && self.attachments.is_empty()
+    }
+
+    /// Take the text and attachments out, leaving the draft empty.
+    pub fn take(&mut self) -> (String, Vec<Attachment>) {
+        self.mentions.clear();
+        (self.input.take(), std::mem::take(&mut self.attachments))
+    }
+
+    /// Byte ranges of the mentions by name still in the text. Each mention
+    /// takes the next occurrence of its name after those already taken.
+    pub fn mention_ranges(&self) -> Vec<(Range<usize>, PackedChat)> {
+        let text = &self.input.text;
+        let mut taken: HashMap<&str, usize> = HashMap::new();
+        let mut ranges = Vec::new();
+        for mention in &self.mentions {
+            let from = taken.get(mention.name.as_str()).copied().unwrap_or(0);
+            let Some(start) = text[from..].find(&mention.name).map(|i| from + i) else {
+                continue;
+            };
+            let end = start + mention.name.len();
+            taken.insert(&mention.name, end);
+            ranges.push((start..end, mention.user));
+        }
+        ranges
+    }
+
+    /// Entities for the mentions by name. Telegram finds `@username`
+    /// mentions in the text by itself.
+    pub fn entities(&self) -> Vec<tl::enums::MessageEntity> {
+        let text = &self.input.text;
+        self.mention_ranges()
+            .into_iter()
+            .filter_map(|(range, user)| {
+                Some(
+                    tl::types::InputMessageEntityMentionName {
+                        offset: utf16_len(&text[..range.start]),
+                        length: utf16_len(&text[range]),
+                        user_id: user.try_to_input_user()?,
+                    }
+                    .into(),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,412 @@
+use crate::download::{AutoDownloadRule, ChatKind, MediaKind};
+use crate::external;
+use anyhow::{bail, Context, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// User configuration read from `config.toml` in the tg9 config directory.
+/// Every option has a default, so the file and any of its keys may be omitted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Command to play videos with, the file path is appended to it
+    pub player: String,
+    /// Command to play GIFs with inside the terminal if it's installed,
+    /// the file path is appended to it. Empty to use `player` instead
+    pub terminal_player: String,
+    /// Command to copy text to the clipboard with, which gets it on its
+    /// standard input. Empty to use wl-copy, xclip, pbcopy or clip, whichever
+    /// is installed
+    pub copy_command: String,
+    /// Directory files are saved to, `~` for the home directory. Empty for
+    /// the system's downloads directory
+    pub download_dir: String,
+    /// Media matching any of these is downloaded automatically
+    pub auto_download: Vec<AutoDownloadRule>,
+    /// Total size in bytes of the media cache
+    pub media_cache_size: u64,
+    /// Messages kept in memory per chat, the oldest are dropped and loaded
+    /// again when scrolled back to. 0 to keep them all
+    pub history_max: usize,
+    pub notifications: Notifications,
+    /// Alert when a message mentions me
+    pub bell: Bell,
+    /// Whether others see me online
+    pub presence: Presence,
+    pub translate: Translate,
+    pub hooks: Hooks,
+    pub confirm: Confirmations,
+    /// Refuse everything that changes the account, like sending messages
+    pub read_only: bool,
+    /// Reorder right-to-left text like Arabic and Hebrew for display, off
+    /// for terminals which do it themselves
+    pub bidi: bool,
+    /// Show a bar at the top with the unread messages and mentions in all
+    /// chats and in each chat folder
+    pub summary_bar: bool,
+    /// Print what changes as lines of text instead of drawing panes, for
+    /// screen readers and braille displays
+    pub plain: bool,
+    /// Commands to open files with by MIME type, like `application/pdf`
+    /// or `image/*`, the file path is appended to them. Others are opened
+    /// with xdg-open, open or explorer
+    pub open: BTreeMap<String, String>,
+    /// Options overriding the global ones for specific chats
+    #[serde(rename = "chat")]
+    pub chats: Vec<ChatConfig>,
+}
+
+/// Desktop notifications for new messages in chats other than the open one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notifications {
+    pub enabled: bool,
+    /// Command to show a notification, the title and body are appended
+    pub command: String,
+    /// Include the sender and text of the message, instead of only the chat
+    pub preview: bool,
+    /// Command to play a sound with for incoming messages, like
+    /// `paplay ding.ogg`; none if empty
+    pub sound: String,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Notifications {
+            enabled: true,
+            command: "notify-send".into(),
+            preview: true,
+            sound: String::new(),
+        }
+    }
+}
+
+/// Translation of messages, by Telegram unless a command is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Translate {
+    /// Language code to translate to
+    pub to: String,
+    /// External translator, the language and text are appended and the
+    /// translation is read from its output
+    pub command: Option<String>,
+}
+
+impl Default for Translate {
+    fn default() -> Self {
+        Translate {
+            to: "en".into(),
+            command: None,
+        }
+    }
+}
+
+/// Commands run on events, which get the event as JSON on their standard
+/// input. See [`crate::hooks::HookEvent`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// A message arrived or was sent, in any chat
+    pub message: Option<String>,
+    /// An incoming message mentions me
+    pub mention: Option<String>,
+    /// A chat was opened
+    pub chat_opened: Option<String>,
+}
+
+/// Which actions that are hard to take back are asked about first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Confirmations {
+    /// Deleting messages for everyone
+    pub delete_for_everyone: bool,
+    /// Leaving a group or channel
+    pub leave_chat: bool,
+    /// Deleting the history of a chat
+    pub clear_history: bool,
+    /// Marking every listed chat as read
+    pub mark_all_read: bool,
+}
+
+impl Default for Confirmations {
+    fn default() -> Self {
+        Confirmations {
+            delete_for_everyone: true,
+            leave_chat: true,
+            clear_history: true,
+            mark_all_read: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bell {
+    Off,
+    /// Ring the terminal bell
+    Audible,
+    /// Flash the status bar
+    Visual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Presence {
+    /// Online while connected, as Telegram decides
+    Auto,
+    /// Online only while the terminal has the focus
+    Focus,
+    /// Always online while tg9 runs
+    Online,
+    /// Never online
+    Offline,
+}
+
+/// Options for a single chat, unset ones fall back to the global options.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// Bare id of the user, group or channel
+    pub id: i64,
+    pub preview: Option<bool>,
+    /// Ring the bell for every message (or for none, not even mentions)
+    pub bell: Option<bool>,
+    /// Accent color of the chat's name, like `red` or `#ff8800`
+    #[serde(default, deserialize_with = "deserialize_color", serialize_with = "serialize_color")]
+    pub color: Option<Color>,
+    /// Shown before the chat's name, like `★`
+    pub marker: Option<String>,
+    /// Sound command for its messages, empty for none
+    pub sound: Option<String>,
+    /// Write right to left in its compose box, rather than going by the
+    /// first letter typed
+    pub rtl: Option<bool>,
+    /// Directory its files are saved to
+    pub download_dir: Option<String>,
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<Color>, D::Error> {
+    let Some(color) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    color
+        .parse()
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid color {:?}", color)))
+}
+
+fn serialize_color<S: Serializer>(color: &Option<Color>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match color {
+        Some(color) => serializer.serialize_some(&color.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            player: "mpv".into(),
+            terminal_player: "chafa --duration 5".into(),
+            copy_command: String::new(),
+            download_dir: String::new(),
+            auto_download: vec![AutoDownloadRule {
+                media: Some(MediaKind::Photo),
+                chats: Some(ChatKind::Private),
+                max_size: Some(1024 * 1024),
+            }],
+            media_cache_size: 512 * 1024 * 1024,
+            history_max: 2000,
+            notifications: Notifications::default(),
+            bell: Bell::Off,
+            presence: Presence::Auto,
+            translate: Translate::default(),
+            hooks: Hooks::default(),
+            confirm: Confirmations::default(),
+            read_only: false,
+            bidi: true,
+            summary_bar: false,
+            plain: false,
+            open: BTreeMap::new(),
+            chats: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("tg9").join("config.toml"))
+    }
+
+    pub fn load() -> Result<Config> {
+        let Some(path) = Config::path().filter(|p| p.exists()) else {
+            return Ok(Config::default());
+        };
+        Config::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Config> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))
+    }
+
+    pub fn chat(&self, id: i64) -> Option<&ChatConfig> {
+        self.chats.iter().find(|c| c.id == id)
+    }
+
+    /// Directory files of the chat are saved to, as configured, so maybe
+    /// starting with `~`.
+    pub fn download_dir(&self, id: i64) -> String {
+        let dir = self.chat(id).and_then(|c| c.download_dir.as_ref()).unwrap_or(&self.download_dir);
+        if !dir.is_empty() {
+            return dir.clone();
+        }
+        dirs::download_dir()
+            .or_else(dirs::home_dir)
+            .map_or_else(|| ".".into(), |dir| dir.display().to_string())
+    }
+
+    /// Whether a new message in the chat rings the bell.
+    pub fn rings(&self, id: i64, mentioned: bool) -> bool {
+        self.bell != Bell::Off && self.chat(id).and_then(|c| c.bell).unwrap_or(mentioned)
+    }
+
+    /// Command to play the sound for a message in the chat with, if any.
+    pub fn sound(&self, id: i64) -> Option<&str> {
+        let sound = self.chat(id).and_then(|c| c.sound.as_deref());
+        Some(sound.unwrap_or(&self.notifications.sound)).filter(|s| !s.is_empty())
+    }
+
+    /// Command to copy text to the clipboard with, if there's one.
+    pub fn copy_command(&self) -> Option<&str> {
+        if !self.copy_command.is_empty() {
+            return Some(&self.copy_command);
+        }
+        ["wl-copy", "xclip -selection clipboard", "pbcopy", "clip"]
+            .into_iter()
+            .find(|command| external::is_installed(command))
+    }
+
+    /// Command to copy an image of the MIME type to the clipboard with, if
+    /// there's one; `copy_command` only takes text.
+    pub fn copy_image_command(&self, mime: &str) -> Option<String> {
+        [
+            ("wl-copy", format!("wl-copy --type {}", mime)),
+            ("xclip", format!("xclip -selection clipboard -t {}", mime)),
+        ]
+        .into_iter()
+        .find(|(program, _)| external::is_installed(program))
+        .map(|(_, command)| command)
+    }
+
+    /// Commands listing the MIME types in the clipboard and, with one of
+    /// them appended, printing the clipboard as that type, if there are.
+    pub fn paste_commands(&self) -> Option<(&'static str, &'static str)> {
+        [
+            ("wl-paste", "wl-paste --list-types", "wl-paste --no-newline --type"),
+            ("xclip", "xclip -selection clipboard -o -t TARGETS", "xclip -selection clipboard -o -t"),
+        ]
+        .into_iter()
+        .find(|(program, _, _)| external::is_installed(program))
+        .map(|(_, list, paste)| (list, paste))
+    }
+
+    /// Command to open a file of the MIME type with, if there's one.
+    pub fn opener(&self, mime: Option<&str>) -> Option<&str> {
+        let configured = mime.and_then(|mime| {
+            let wildcard = mime.split_once('/').map(|(kind, _)| format!("{}/*", kind));
+            self.open.get(mime).or_else(|| self.open.get(&wildcard?))
+        });
+        configured.map(String::as_str).or_else(|| {
+            ["xdg-open", "open", "explorer"]
+                .into_iter()
+                .find(|command| external::is_installed(command))
+        })
+    }
+
+    /// Whether notifications for the chat show the message.
+    pub fn preview(&self, id: i64) -> bool {
+        self.chat(id)
+            .and_then(|c| c.preview)
+            .unwrap_or(self.notifications.preview)
+    }
+}
+
+/// `tg9 config` subcommands, for debugging and sharing configurations.
+pub enum ConfigCommand {
+    /// Print the effective configuration, with the defaults filled in
+    Dump,
+    /// Validate a config file, the usual one unless given
+    Check(Option<PathBuf>),
+}
+
+impl ConfigCommand {
+    pub fn run(self) -> Result<()> {
+        match self {
+            ConfigCommand::Dump => {
+                let config = Config::load()?;
+                print!("{}", toml::to_string_pretty(&config)?);
+                Ok(())
+            }
+            ConfigCommand::Check(path) => check(path),
+        }
+    }
+}
+
+/// Fail on a config file which can't be loaded, or which has options tg9
+/// doesn't know: those are otherwise silently ignored, so a typo looks
+/// like an option having no effect.
+fn check(path: Option<PathBuf>) -> Result<()> {
+    let Some(path) = path.or_else(Config::path) else {
+        bail!("no config directory on this system");
+    };
+    let unknown = validate(&path)?;
+    if !unknown.is_empty() {
+        bail!("unknown options in {}: {}", path.display(), unknown.join(", "));
+    }
+    println!("{} is valid", path.display());
+    Ok(())
+}
+
+/// Load a config file, failing if it can't be, and give the options in it
+/// which tg9 doesn't know, as dotted paths.
+pub fn validate(path: &Path) -> Result<Vec<String>> {
+    let config = Config::load_from(path)?;
+    let text = std::fs::read_to_string(path)?;
+    let given: toml::Value = toml::from_str(&text)?;
+    let mut unknown = Vec::new();
+    unknown_keys(&given, &toml::Value::try_from(&config)?, "", &mut unknown);
+    Ok(unknown)
+}
+
+/// Keys set in `given` which the parsed configuration `known` doesn't
+/// have, as dotted paths.
+fn unknown_keys(given: &toml::Value, known: &toml::Value, prefix: &str, unknown: &mut Vec<String>) {
+    match (given, known) {
+        (toml::Value::Table(given), toml::Value::Table(known)) => {
+            for (key, value) in given {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match known.get(key) {
+                    Some(known) => unknown_keys(value, known, &path, unknown),
+                    None => unknown.push(path),
+                }
+            }
+        }
+        (toml::Value::Array(given), toml::Value::Array(known)) => {
+            for (i, (value, known)) in given.iter().zip(known).enumerate() {
+                unknown_keys(value, known, &format!("{}[{}]", prefix, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Directory for temporary files of this process, removed on exit.
+pub fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("tg9-{}", std::process::id()))
+}
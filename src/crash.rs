@@ -0,0 +1,77 @@
+use chrono::Local;
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::io;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Lines of the log kept for a crash report
+const LOG_LINES_MAX: usize = 100;
+
+/// What happened lately, oldest first: statuses, errors, connection changes.
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// What the UI was doing, kept up to date by the event loop.
+static STATE: Mutex<String> = Mutex::new(String::new());
+
+/// Add a line to the log kept for crash reports.
+pub fn log(line: impl Into<String>) {
+    let Ok(mut log) = LOG.lock() else {
+        return;
+    };
+    if log.len() == LOG_LINES_MAX {
+        log.pop_front();
+    }
+    log.push_back(format!("{} {}", Local::now().format("%H:%M:%S%.3f"), line.into()));
+}
+
+/// Replace the summary of the UI state in crash reports.
+pub fn set_state(state: String) {
+    if let Ok(mut current) = STATE.lock() {
+        *current = state;
+    }
+}
+
+fn dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("tg9").join("crashes"))
+}
+
+/// Write a report of a panic for a bug report: where it happened, the
+/// backtrace, the log and the UI state, which has no message contents.
+pub fn report(info: &PanicHookInfo) -> io::Result<PathBuf> {
+    let Some(dir) = dir() else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no data directory on this system"));
+    };
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crash-{}.txt", Local::now().format("%Y%m%d-%H%M%S")));
+    // the panic may have happened while holding these, which would then
+    // never be released
+    let log = match LOG.try_lock() {
+        Ok(log) => log.iter().cloned().collect::<Vec<_>>().join("\n"),
+        Err(_) => "(unavailable)".into(),
+    };
+    let state = match STATE.try_lock() {
+        Ok(state) if !state.is_empty() => state.clone(),
+        Ok(_) => "(not started)".into(),
+        Err(_) => "(unavailable)".into(),
+    };
+    let thread = std::thread::current();
+    let report = format!(
+        "tg9 {} on {} {}\n\n\
+         thread '{}' {}\n\n\
+         backtrace:\n{}\n\n\
+         state:\n{}\n\n\
+         log:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        thread.name().unwrap_or("<unnamed>"),
+        info,
+        Backtrace::force_capture(),
+        state,
+        log,
+    );
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
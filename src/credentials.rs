@@ -0,0 +1,45 @@
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// API credentials of the app typed into the setup wizard, for builds
+/// without them built in. Kept next to the config, readable only by me.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Credentials {
+    pub api_id: Option<i32>,
+    /// Only if it couldn't be stored in the keyring
+    pub api_hash: Option<String>,
+}
+
+impl Credentials {
+    fn path() -> Option<PathBuf> {
+        Some(Config::path()?.with_file_name("credentials.toml"))
+    }
+
+    /// The saved credentials, or none if they can't be read.
+    pub fn load() -> Credentials {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no config directory on this system"));
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options.open(path)?.write_all(text.as_bytes())
+    }
+}
@@ -0,0 +1,100 @@
+use grammers_client::types::media::Document;
+use ratatui::prelude::*;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Lines of a patch file shown under its message, the rest is left out
+const PREVIEW_LINES_MAX: usize = 20;
+
+/// Patch files kept previewed, past which they're all read again
+const PREVIEW_CACHE_MAX: usize = 64;
+
+/// Previews of downloaded patch files by path, since messages are rendered
+/// on every frame.
+static PREVIEWS: Mutex<BTreeMap<PathBuf, Vec<Line<'static>>>> = Mutex::new(BTreeMap::new());
+
+/// Whether text is a unified diff, like `git diff` or `diff -u` print: a
+/// hunk header after the file headers.
+pub fn looks_like_diff(text: &str) -> bool {
+    let mut lines = text.lines().skip_while(|line| !line.starts_with("--- "));
+    lines.next().is_some()
+        && lines.next().is_some_and(|line| line.starts_with("+++ "))
+        && lines.next().is_some_and(|line| line.starts_with("@@ "))
+}
+
+/// Whether a document is a patch file, by its type or name.
+pub fn is_patch(document: &Document) -> bool {
+    document.mime_type().is_some_and(|mime| matches!(mime, "text/x-diff" | "text/x-patch"))
+        || [".diff", ".patch"].iter().any(|ext| document.name().to_ascii_lowercase().ends_with(ext))
+}
+
+/// Lines of a diff with additions in green, removals in red and the
+/// headers of files and hunks set apart.
+pub fn colorize(text: &str) -> Vec<Line<'static>> {
+    text.lines()
+        .map(|line| {
+            let style = if ["diff ", "index ", "--- ", "+++ "].iter().any(|p| line.starts_with(p)) {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else if line.starts_with("@@") {
+                Style::default().fg(Color::Cyan)
+            } else if line.starts_with('+') {
+                Style::default().fg(Color::Green)
+            } else if line.starts_with('-') {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            Line::from(Span::styled(line.to_string(), style))
+        })
+        .collect()
+}
+
+/// The start of a downloaded patch file, colorized, if it can be read.
+pub fn preview(path: &Path) -> Option<Vec<Line<'static>>> {
+    if let Some(lines) = PREVIEWS.lock().unwrap().get(path) {
+        return Some(lines.clone());
+    }
+    let text = std::fs::read(path).ok()?;
+    let text = String::from_utf8_lossy(&text);
+    let mut lines = colorize(&text);
+    if lines.len() > PREVIEW_LINES_MAX {
+        let more = lines.len() - PREVIEW_LINES_MAX;
+        lines.truncate(PREVIEW_LINES_MAX);
+        lines.push(Line::from(Span::styled(
+            format!("… {} more lines", more),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    let mut previews = PREVIEWS.lock().unwrap();
+    if previews.len() >= PREVIEW_CACHE_MAX {
+        previews.clear();
+    }
+    previews.insert(path.to_path_buf(), lines.clone());
+    Some(lines)
+}
+
+/// Apply the patch file to a file, or to the files under a directory with
+/// patch(1). Diffs by git have paths starting with `a/` and `b/`, which
+/// are stripped. Gives what patch printed.
+pub fn apply(patch: &Path, target: &Path) -> io::Result<String> {
+    let mut command = Command::new("patch");
+    command.args(["--forward", "--batch", "--input"]).arg(patch);
+    if target.is_dir() {
+        let text = String::from_utf8_lossy(&std::fs::read(patch)?).into_owned();
+        let git = text.lines().any(|line| line.starts_with("--- a/") || line.starts_with("diff --git "));
+        command.arg(if git { "-p1" } else { "-p0" }).arg("--directory").arg(target);
+    } else {
+        command.arg(target);
+    }
+    let output = command.stdin(Stdio::null()).output()?;
+    let printed = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let reason = [error, printed].into_iter().find(|s| !s.is_empty()).unwrap_or_default();
+        return Err(io::Error::other(format!("patch exited with {}: {}", output.status, reason)));
+    }
+    Ok(printed)
+}
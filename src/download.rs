@@ -0,0 +1,86 @@
+use grammers_client::types::Media;
+use grammers_session::{PackedChat, PackedType};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Photo,
+    Video,
+    Document,
+}
+
+impl MediaKind {
+    pub fn of(media: &Media) -> Option<MediaKind> {
+        match media {
+            Media::Photo(_) => Some(MediaKind::Photo),
+            Media::Document(d) if d.mime_type().is_some_and(|m| m.starts_with("video/")) => {
+                Some(MediaKind::Video)
+            }
+            Media::Document(_) => Some(MediaKind::Document),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatKind {
+    Private,
+    Group,
+    Channel,
+}
+
+impl ChatKind {
+    pub fn of(chat: &PackedChat) -> ChatKind {
+        match chat.ty {
+            PackedType::User | PackedType::Bot => ChatKind::Private,
+            PackedType::Chat | PackedType::Megagroup | PackedType::Gigagroup => ChatKind::Group,
+            PackedType::Broadcast => ChatKind::Channel,
+        }
+    }
+}
+
+/// Media matching any rule is downloaded in the background as soon as its
+/// message arrives. Omitted fields match anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoDownloadRule {
+    pub media: Option<MediaKind>,
+    pub chats: Option<ChatKind>,
+    /// Largest file size in bytes
+    pub max_size: Option<u64>,
+}
+
+impl AutoDownloadRule {
+    fn matches(&self, media: MediaKind, chat: ChatKind, size: u64) -> bool {
+        self.media.is_none_or(|m| m == media)
+            && self.chats.is_none_or(|c| c == chat)
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+}
+
+pub fn should_auto_download(rules: &[AutoDownloadRule], chat: &PackedChat, media: &Media) -> bool {
+    let Some(kind) = MediaKind::of(media) else {
+        return false;
+    };
+    let size = media_size(media);
+    let chat = ChatKind::of(chat);
+    rules.iter().any(|r| r.matches(kind, chat, size))
+}
+
+/// MIME type of a photo or file, if known.
+pub fn media_mime(media: &Media) -> Option<String> {
+    match media {
+        Media::Photo(_) => Some("image/jpeg".into()),
+        Media::Document(document) => document.mime_type().map(str::to_string),
+        _ => None,
+    }
+}
+
+pub fn media_size(media: &Media) -> u64 {
+    match media {
+        Media::Photo(photo) => photo.size() as u64,
+        Media::Document(document) => document.size() as u64,
+        _ => 0,
+    }
+}
@@ -0,0 +1,325 @@
+/// Groups the emoji picker shows, also searchable by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Smileys,
+    People,
+    Animals,
+    Food,
+    Activities,
+    Travel,
+    Objects,
+    Symbols,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Smileys => "smileys",
+            Category::People => "people",
+            Category::Animals => "animals & nature",
+            Category::Food => "food & drink",
+            Category::Activities => "activities",
+            Category::Travel => "travel & places",
+            Category::Objects => "objects",
+            Category::Symbols => "symbols",
+        }
+    }
+}
+
+/// How many recently used emoji are remembered
+pub const RECENT_MAX: usize = 24;
+
+/// Emoji with their names, in the order the picker lists them. Not every
+/// emoji there is, only those commonly used in chats.
+pub const EMOJI: &[(&str, &str, Category)] = &[
+    ("😀", "grinning face", Category::Smileys),
+    ("😃", "grinning face with big eyes", Category::Smileys),
+    ("😄", "grinning face with smiling eyes", Category::Smileys),
+    ("😁", "beaming face", Category::Smileys),
+    ("😆", "grinning squinting face", Category::Smileys),
+    ("😅", "grinning face with sweat", Category::Smileys),
+    ("🤣", "rolling on the floor laughing", Category::Smileys),
+    ("😂", "face with tears of joy", Category::Smileys),
+    ("🙂", "slightly smiling face", Category::Smileys),
+    ("🙃", "upside-down face", Category::Smileys),
+    ("😉", "winking face", Category::Smileys),
+    ("😊", "smiling face with smiling eyes", Category::Smileys),
+    ("😇", "smiling face with halo", Category::Smileys),
+    ("🥰", "smiling face with hearts", Category::Smileys),
+    ("😍", "heart eyes", Category::Smileys),
+    ("🤩", "star-struck", Category::Smileys),
+    ("😘", "face blowing a kiss", Category::Smileys),
+    ("😋", "face savoring food, yum", Category::Smileys),
+    ("😛", "face with tongue", Category::Smileys),
+    ("😜", "winking face with tongue", Category::Smileys),
+    ("🤪", "zany face", Category::Smileys),
+    ("🤑", "money-mouth face", Category::Smileys),
+    ("🤗", "hugging face", Category::Smileys),
+    ("🤭", "face with hand over mouth", Category::Smileys),
+    ("🤫", "shushing face", Category::Smileys),
+    ("🤔", "thinking face", Category::Smileys),
+    ("🤐", "zipper-mouth face", Category::Smileys),
+    ("🤨", "face with raised eyebrow", Category::Smileys),
+    ("😐", "neutral face", Category::Smileys),
+    ("😑", "expressionless face", Category::Smileys),
+    ("😶", "face without mouth", Category::Smileys),
+    ("😏", "smirking face", Category::Smileys),
+    ("😒", "unamused face", Category::Smileys),
+    ("🙄", "face with rolling eyes", Category::Smileys),
+    ("😬", "grimacing face", Category::Smileys),
+    ("😌", "relieved face", Category::Smileys),
+    ("😔", "pensive face", Category::Smileys),
+    ("😪", "sleepy face", Category::Smileys),
+    ("😴", "sleeping face", Category::Smileys),
+    ("😷", "face with medical mask", Category::Smileys),
+    ("🤒", "face with thermometer, sick", Category::Smileys),
+    ("🤢", "nauseated face", Category::Smileys),
+    ("🤮", "face vomiting", Category::Smileys),
+    ("🥵", "hot face", Category::Smileys),
+    ("🥶", "cold face", Category::Smileys),
+    ("🥴", "woozy face", Category::Smileys),
+    ("😵", "dizzy face", Category::Smileys),
+    ("🤯", "exploding head, mind blown", Category::Smileys),
+    ("🥳", "partying face", Category::Smileys),
+    ("😎", "smiling face with sunglasses, cool", Category::Smileys),
+    ("🤓", "nerd face", Category::Smileys),
+    ("😕", "confused face", Category::Smileys),
+    ("😟", "worried face", Category::Smileys),
+    ("🙁", "slightly frowning face", Category::Smileys),
+    ("😮", "face with open mouth", Category::Smileys),
+    ("😲", "astonished face", Category::Smileys),
+    ("😳", "flushed face", Category::Smileys),
+    ("🥺", "pleading face", Category::Smileys),
+    ("😢", "crying face", Category::Smileys),
+    ("😭", "loudly crying face", Category::Smileys),
+    ("😱", "face screaming in fear", Category::Smileys),
+    ("😖", "confounded face", Category::Smileys),
+    ("😞", "disappointed face", Category::Smileys),
+    ("😓", "downcast face with sweat", Category::Smileys),
+    ("😩", "weary face", Category::Smileys),
+    ("😫", "tired face", Category::Smileys),
+    ("🥱", "yawning face", Category::Smileys),
+    ("😤", "face with steam from nose", Category::Smileys),
+    ("😡", "pouting face, angry", Category::Smileys),
+    ("😠", "angry face", Category::Smileys),
+    ("🤬", "face with symbols on mouth, swearing", Category::Smileys),
+    ("😈", "smiling face with horns, devil", Category::Smileys),
+    ("💀", "skull", Category::Smileys),
+    ("💩", "pile of poo", Category::Smileys),
+    ("🤡", "clown face", Category::Smileys),
+    ("👻", "ghost", Category::Smileys),
+    ("👽", "alien", Category::Smileys),
+    ("🤖", "robot", Category::Smileys),
+    ("😺", "grinning cat", Category::Smileys),
+    ("🙈", "see-no-evil monkey", Category::Smileys),
+    ("👋", "waving hand", Category::People),
+    ("🤚", "raised back of hand", Category::People),
+    ("✋", "raised hand", Category::People),
+    ("👌", "ok hand", Category::People),
+    ("🤌", "pinched fingers", Category::People),
+    ("✌️", "victory hand", Category::People),
+    ("🤞", "crossed fingers", Category::People),
+    ("🤟", "love-you gesture", Category::People),
+    ("🤘", "sign of the horns", Category::People),
+    ("👈", "backhand index pointing left", Category::People),
+    ("👉", "backhand index pointing right", Category::People),
+    ("👆", "backhand index pointing up", Category::People),
+    ("👇", "backhand index pointing down", Category::People),
+    ("👍", "thumbs up", Category::People),
+    ("👎", "thumbs down", Category::People),
+    ("✊", "raised fist", Category::People),
+    ("👊", "oncoming fist", Category::People),
+    ("👏", "clapping hands", Category::People),
+    ("🙌", "raising hands", Category::People),
+    ("👐", "open hands", Category::People),
+    ("🤝", "handshake", Category::People),
+    ("🙏", "folded hands, please, thanks", Category::People),
+    ("💪", "flexed biceps, strong", Category::People),
+    ("🫡", "saluting face", Category::People),
+    ("👀", "eyes", Category::People),
+    ("🧠", "brain", Category::People),
+    ("🤷", "person shrugging", Category::People),
+    ("🤦", "person facepalming", Category::People),
+    ("🙋", "person raising hand", Category::People),
+    ("🐶", "dog face", Category::Animals),
+    ("🐱", "cat face", Category::Animals),
+    ("🐭", "mouse face", Category::Animals),
+    ("🐰", "rabbit face", Category::Animals),
+    ("🦊", "fox", Category::Animals),
+    ("🐻", "bear", Category::Animals),
+    ("🐼", "panda", Category::Animals),
+    ("🐨", "koala", Category::Animals),
+    ("🐯", "tiger face", Category::Animals),
+    ("🦁", "lion", Category::Animals),
+    ("🐮", "cow face", Category::Animals),
+    ("🐷", "pig face", Category::Animals),
+    ("🐸", "frog", Category::Animals),
+    ("🐵", "monkey face", Category::Animals),
+    ("🐔", "chicken", Category::Animals),
+    ("🐧", "penguin", Category::Animals),
+    ("🦆", "duck", Category::Animals),
+    ("🦉", "owl", Category::Animals),
+    ("🐝", "honeybee", Category::Animals),
+    ("🦋", "butterfly", Category::Animals),
+    ("🐢", "turtle", Category::Animals),
+    ("🐍", "snake", Category::Animals),
+    ("🐙", "octopus", Category::Animals),
+    ("🐳", "spouting whale", Category::Animals),
+    ("🦀", "crab, rust", Category::Animals),
+    ("🌸", "cherry blossom", Category::Animals),
+    ("🌹", "rose", Category::Animals),
+    ("🌻", "sunflower", Category::Animals),
+    ("🌳", "deciduous tree", Category::Animals),
+    ("🌵", "cactus", Category::Animals),
+    ("🍀", "four leaf clover, luck", Category::Animals),
+    ("🍁", "maple leaf", Category::Animals),
+    ("🌈", "rainbow", Category::Animals),
+    ("☀️", "sun", Category::Animals),
+    ("🌙", "crescent moon", Category::Animals),
+    ("⭐", "star", Category::Animals),
+    ("⚡", "high voltage, lightning", Category::Animals),
+    ("🔥", "fire", Category::Animals),
+    ("❄️", "snowflake", Category::Animals),
+    ("🌊", "water wave", Category::Animals),
+    ("🍏", "green apple", Category::Food),
+    ("🍎", "red apple", Category::Food),
+    ("🍌", "banana", Category::Food),
+    ("🍉", "watermelon", Category::Food),
+    ("🍓", "strawberry", Category::Food),
+    ("🍒", "cherries", Category::Food),
+    ("🍑", "peach", Category::Food),
+    ("🥑", "avocado", Category::Food),
+    ("🌶️", "hot pepper", Category::Food),
+    ("🥐", "croissant", Category::Food),
+    ("🍞", "bread", Category::Food),
+    ("🧀", "cheese", Category::Food),
+    ("🍔", "hamburger", Category::Food),
+    ("🍟", "french fries", Category::Food),
+    ("🍕", "pizza", Category::Food),
+    ("🌮", "taco", Category::Food),
+    ("🍣", "sushi", Category::Food),
+    ("🍜", "steaming bowl, ramen", Category::Food),
+    ("🍿", "popcorn", Category::Food),
+    ("🍩", "doughnut", Category::Food),
+    ("🍪", "cookie", Category::Food),
+    ("🎂", "birthday cake", Category::Food),
+    ("🍫", "chocolate bar", Category::Food),
+    ("☕", "hot beverage, coffee", Category::Food),
+    ("🍵", "teacup", Category::Food),
+    ("🍺", "beer mug", Category::Food),
+    ("🍻", "clinking beer mugs, cheers", Category::Food),
+    ("🍷", "wine glass", Category::Food),
+    ("🥂", "clinking glasses", Category::Food),
+    ("⚽", "soccer ball, football", Category::Activities),
+    ("🏀", "basketball", Category::Activities),
+    ("🎾", "tennis", Category::Activities),
+    ("🎮", "video game", Category::Activities),
+    ("🎲", "game die", Category::Activities),
+    ("♟️", "chess pawn", Category::Activities),
+    ("🎯", "bullseye, direct hit", Category::Activities),
+    ("🎳", "bowling", Category::Activities),
+    ("🏆", "trophy", Category::Activities),
+    ("🥇", "first place medal", Category::Activities),
+    ("🎉", "party popper, tada", Category::Activities),
+    ("🎊", "confetti ball", Category::Activities),
+    ("🎁", "wrapped gift", Category::Activities),
+    ("🎈", "balloon", Category::Activities),
+    ("🎄", "christmas tree", Category::Activities),
+    ("🎃", "jack-o-lantern", Category::Activities),
+    ("🎵", "musical note", Category::Activities),
+    ("🎸", "guitar", Category::Activities),
+    ("🎨", "artist palette", Category::Activities),
+    ("🎬", "clapper board", Category::Activities),
+    ("🚗", "automobile, car", Category::Travel),
+    ("🚕", "taxi", Category::Travel),
+    ("🚌", "bus", Category::Travel),
+    ("🚲", "bicycle", Category::Travel),
+    ("🚂", "locomotive, train", Category::Travel),
+    ("✈️", "airplane", Category::Travel),
+    ("🚀", "rocket", Category::Travel),
+    ("⛵", "sailboat", Category::Travel),
+    ("🏠", "house, home", Category::Travel),
+    ("🏢", "office building", Category::Travel),
+    ("🏖️", "beach with umbrella", Category::Travel),
+    ("⛰️", "mountain", Category::Travel),
+    ("🗺️", "world map", Category::Travel),
+    ("🌍", "globe showing europe-africa", Category::Travel),
+    ("📱", "mobile phone", Category::Objects),
+    ("💻", "laptop", Category::Objects),
+    ("⌨️", "keyboard", Category::Objects),
+    ("🖥️", "desktop computer", Category::Objects),
+    ("📷", "camera", Category::Objects),
+    ("📺", "television", Category::Objects),
+    ("⏰", "alarm clock", Category::Objects),
+    ("⌛", "hourglass done", Category::Objects),
+    ("💡", "light bulb, idea", Category::Objects),
+    ("🔋", "battery", Category::Objects),
+    ("💰", "money bag", Category::Objects),
+    ("💸", "money with wings", Category::Objects),
+    ("📦", "package", Category::Objects),
+    ("📎", "paperclip", Category::Objects),
+    ("📌", "pushpin", Category::Objects),
+    ("✏️", "pencil", Category::Objects),
+    ("📝", "memo", Category::Objects),
+    ("📚", "books", Category::Objects),
+    ("📅", "calendar", Category::Objects),
+    ("🔑", "key", Category::Objects),
+    ("🔒", "locked", Category::Objects),
+    ("🔨", "hammer", Category::Objects),
+    ("🔧", "wrench", Category::Objects),
+    ("⚙️", "gear", Category::Objects),
+    ("🧪", "test tube", Category::Objects),
+    ("💊", "pill", Category::Objects),
+    ("🛒", "shopping cart", Category::Objects),
+    ("❤️", "red heart", Category::Symbols),
+    ("🧡", "orange heart", Category::Symbols),
+    ("💛", "yellow heart", Category::Symbols),
+    ("💚", "green heart", Category::Symbols),
+    ("💙", "blue heart", Category::Symbols),
+    ("💜", "purple heart", Category::Symbols),
+    ("🖤", "black heart", Category::Symbols),
+    ("🤍", "white heart", Category::Symbols),
+    ("💔", "broken heart", Category::Symbols),
+    ("💕", "two hearts", Category::Symbols),
+    ("💯", "hundred points", Category::Symbols),
+    ("💥", "collision, boom", Category::Symbols),
+    ("💫", "dizzy", Category::Symbols),
+    ("💬", "speech balloon", Category::Symbols),
+    ("💤", "zzz", Category::Symbols),
+    ("✅", "check mark button", Category::Symbols),
+    ("✔️", "check mark", Category::Symbols),
+    ("❌", "cross mark", Category::Symbols),
+    ("❓", "question mark", Category::Symbols),
+    ("❗", "exclamation mark", Category::Symbols),
+    ("⚠️", "warning", Category::Symbols),
+    ("🚫", "prohibited", Category::Symbols),
+    ("♻️", "recycling symbol", Category::Symbols),
+    ("➕", "plus", Category::Symbols),
+    ("➖", "minus", Category::Symbols),
+    ("➡️", "right arrow", Category::Symbols),
+    ("⬅️", "left arrow", Category::Symbols),
+    ("🔝", "top arrow", Category::Symbols),
+    ("🆗", "ok button", Category::Symbols),
+    ("🆕", "new button", Category::Symbols),
+    ("🔴", "red circle", Category::Symbols),
+    ("🟢", "green circle", Category::Symbols),
+    ("🔵", "blue circle", Category::Symbols),
+];
+
+/// Name of an emoji from the table, if it's there.
+pub fn name(emoji: &str) -> Option<&'static str> {
+    EMOJI.iter().find(|(e, _, _)| *e == emoji).map(|(_, name, _)| *name)
+}
+
+/// Picker items: the recently used emoji first, then all of them by
+/// category. Labels include the category so typing it filters by it.
+pub fn items(recent: &[String]) -> Vec<(String, String)> {
+    let recent = recent.iter().map(|emoji| {
+        let label = format!("{} {} (recent)", emoji, name(emoji).unwrap_or_default());
+        (label, emoji.clone())
+    });
+    let all = EMOJI.iter().map(|(emoji, name, category)| {
+        (format!("{} {} ({})", emoji, name, category.name()), emoji.to_string())
+    });
+    recent.chain(all).collect()
+}
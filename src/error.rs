@@ -0,0 +1,119 @@
+use grammers_client::InvocationError;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, Tg9Error>;
+
+/// Errors of the API worker, transfers, the media cache and the terminal.
+/// Kinds the user can do something about come with a hint.
+#[derive(Debug)]
+pub enum Tg9Error {
+    /// Logging in failed
+    Auth(String),
+    /// The passphrase doesn't decrypt the session
+    WrongPassphrase,
+    /// Connection to Telegram failed
+    Network(String),
+    /// Telegram asked to wait this long before trying again
+    FloodWait(Duration),
+    /// Slow mode of the group allows sending again after this long
+    SlowMode(Duration),
+    /// Telegram refused the request, `name` is like `CHAT_WRITE_FORBIDDEN`
+    Rpc { name: String, message: String },
+    Terminal(io::Error),
+    /// Reading or writing the media cache failed
+    Cache(io::Error),
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// A configured command failed
+    External(String),
+    Other(String),
+}
+
+impl Tg9Error {
+    /// What the user can do about the error.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Tg9Error::Auth(_) => Some("delete the session file and log in again"),
+            Tg9Error::WrongPassphrase => None,
+            Tg9Error::Network(_) => Some("check your connection"),
+            Tg9Error::FloodWait(_) => Some("Telegram limits how fast requests can be made"),
+            Tg9Error::SlowMode(_) => Some("this group limits how often members can send"),
+            Tg9Error::Rpc { name, .. } => rpc_hint(name),
+            Tg9Error::Terminal(_) => Some("tg9 needs an interactive terminal"),
+            Tg9Error::Cache(_) => Some("try :cache clear"),
+            Tg9Error::Io(_) | Tg9Error::External(_) | Tg9Error::Other(_) => None,
+        }
+    }
+}
+
+fn rpc_hint(name: &str) -> Option<&'static str> {
+    match name {
+        "MEDIA_CAPTION_TOO_LONG" => Some("the caption is too long"),
+        "MESSAGE_TOO_LONG" => Some("the message is too long"),
+        "FILE_PARTS_INVALID" => Some("the file is too large"),
+        "PREMIUM_ACCOUNT_REQUIRED" => Some("this needs Telegram Premium"),
+        "REACTION_INVALID" => Some("this reaction isn't available"),
+        "CHAT_WRITE_FORBIDDEN" | "CHAT_SEND_PLAIN_FORBIDDEN" => Some("you can't write to this chat"),
+        "CHAT_ADMIN_REQUIRED" => Some("only admins can do this"),
+        "AUTH_KEY_UNREGISTERED" | "SESSION_REVOKED" => Some("the session ended, log in again"),
+        "USERNAME_OCCUPIED" => Some("the username is taken"),
+        "USERNAME_INVALID" => Some("usernames are 5-32 letters, digits and underscores"),
+        "USERNAME_NOT_MODIFIED" => Some("that's already the username"),
+        "ABOUT_TOO_LONG" => Some("the bio is too long"),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Tg9Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tg9Error::Auth(e) => write!(f, "login failed: {}", e),
+            Tg9Error::WrongPassphrase => write!(f, "wrong passphrase"),
+            Tg9Error::Network(e) => write!(f, "network error: {}", e),
+            Tg9Error::FloodWait(wait) => write!(f, "too many requests, wait {}s", wait.as_secs()),
+            Tg9Error::SlowMode(wait) => write!(f, "slow mode, wait {}s", wait.as_secs()),
+            Tg9Error::Rpc { message, .. } => write!(f, "{}", message),
+            Tg9Error::Terminal(e) => write!(f, "terminal error: {}", e),
+            Tg9Error::Cache(e) => write!(f, "media cache error: {}", e),
+            Tg9Error::Io(e) => write!(f, "{}", e),
+            Tg9Error::External(e) | Tg9Error::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Tg9Error {}
+
+impl From<InvocationError> for Tg9Error {
+    fn from(error: InvocationError) -> Self {
+        match error {
+            InvocationError::Rpc(rpc) if rpc.name == "FLOOD_WAIT" => {
+                Tg9Error::FloodWait(Duration::from_secs(rpc.value.unwrap_or(1).into()))
+            }
+            InvocationError::Rpc(rpc) if rpc.name == "SLOWMODE_WAIT" => {
+                Tg9Error::SlowMode(Duration::from_secs(rpc.value.unwrap_or(1).into()))
+            }
+            InvocationError::Rpc(rpc) => Tg9Error::Rpc {
+                name: rpc.name.clone(),
+                message: rpc.to_string(),
+            },
+            InvocationError::Io(_) | InvocationError::Transport(_) | InvocationError::Dropped => {
+                Tg9Error::Network(error.to_string())
+            }
+            _ => Tg9Error::Other(error.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for Tg9Error {
+    fn from(error: io::Error) -> Self {
+        Tg9Error::Io(error)
+    }
+}
+
+impl From<tokio::task::JoinError> for Tg9Error {
+    fn from(error: tokio::task::JoinError) -> Self {
+        Tg9Error::Other(error.to_string())
+    }
+}
@@ -0,0 +1,290 @@
+use crate::api::ApiEvent;
+use crate::metrics::JobSpan;
+use grammers_client::types::Message;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Events waiting for the UI past which updates, dialogs and history stop
+/// being taken from Telegram until it catches up
+const EVENTS_MAX: usize = 512;
+
+/// Channel of events from the API worker to the UI.
+///
+/// Sending never waits, so events can be sent from anywhere, but an event
+/// which makes a queued one redundant replaces it: edits of the same
+/// message, progress of the same transfer, the same dialog loaded again.
+/// Whatever floods the UI, like updates or history, waits on
+/// `EventSender::ready` before taking more, so the queue stays around
+/// `EVENTS_MAX` events however far behind the UI is.
+pub fn channel() -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            events: VecDeque::new(),
+            senders: 1,
+            receiver_gone: false,
+        }),
+        queued: Notify::new(),
+        room: Notify::new(),
+    });
+    (
+        EventSender {
+            shared: shared.clone(),
+            span: None,
+        },
+        EventReceiver { shared },
+    )
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Woken when an event is queued or the last sender is gone
+    queued: Notify,
+    /// Woken when there's room for more events
+    room: Notify,
+}
+
+struct Queue {
+    events: VecDeque<ApiEvent>,
+    senders: usize,
+    receiver_gone: bool,
+}
+
+/// The UI stopped receiving events.
+#[derive(Debug)]
+pub struct Closed;
+
+pub struct EventSender {
+    shared: Arc<Shared>,
+    /// The API job whose events these are, timed until it's done
+    span: Option<Arc<JobSpan>>,
+}
+
+impl EventSender {
+    /// Queue an event for the UI, or merge it into a queued one it makes
+    /// redundant.
+    pub fn send(&self, event: ApiEvent) -> Result<(), Closed> {
+        if let (Some(span), ApiEvent::Error { .. } | ApiEvent::SendFailed { .. }) = (&self.span, &event) {
+            span.fail();
+        }
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.receiver_gone {
+            return Err(Closed);
+        }
+        if let Some(event) = coalesce(&mut queue.events, event) {
+            queue.events.push_back(event);
+        }
+        drop(queue);
+        self.shared.queued.notify_one();
+        Ok(())
+    }
+
+    /// A sender for the events of an API job, which times the job until
+    /// it and its clones are dropped.
+    pub fn traced(&self, job: &'static str) -> EventSender {
+        let mut tx = self.clone();
+        tx.span = Some(Arc::new(JobSpan::new(job)));
+        tx
+    }
+
+    /// A sender which doesn't keep the job of this one timed, for tasks
+    /// outliving it.
+    pub fn untraced(&self) -> EventSender {
+        let mut tx = self.clone();
+        tx.span = None;
+        tx
+    }
+
+    /// Wait until the UI has room for more events.
+    pub async fn ready(&self) {
+        loop {
+            // created before looking, so room made meanwhile isn't missed
+            let room = self.shared.room.notified();
+            {
+                let queue = self.shared.queue.lock().unwrap();
+                if queue.events.len() < EVENTS_MAX || queue.receiver_gone {
+                    return;
+                }
+            }
+            room.await;
+        }
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.shared.queue.lock().unwrap().senders += 1;
+        EventSender {
+            shared: self.shared.clone(),
+            span: self.span.clone(),
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.senders -= 1;
+        if queue.senders == 0 {
+            drop(queue);
+            self.shared.queued.notify_one();
+        }
+    }
+}
+
+impl fmt::Debug for EventSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventSender").finish_non_exhaustive()
+    }
+}
+
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// Events waiting to be received.
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().events.len()
+    }
+
+    /// The next event, none once every sender is gone.
+    pub async fn recv(&mut self) -> Option<ApiEvent> {
+        loop {
+            let queued = self.shared.queued.notified();
+            {
+                let mut queue = self.shared.queue.lock().unwrap();
+                if let Some(event) = queue.events.pop_front() {
+                    if queue.events.len() < EVENTS_MAX {
+                        self.shared.room.notify_waiters();
+                    }
+                    return Some(event);
+                }
+                if queue.senders == 0 {
+                    return None;
+                }
+            }
+            queued.await;
+        }
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().receiver_gone = true;
+        self.shared.room.notify_waiters();
+    }
+}
+
+/// Merge an event into the newest queued one it makes redundant, or give
+/// it back if there's none.
+fn coalesce(events: &mut VecDeque<ApiEvent>, event: ApiEvent) -> Option<ApiEvent> {
+    let Some(queued) = events.iter_mut().rev().find(|queued| supersedes(&event, queued)) else {
+        return Some(event);
+    };
+    use ApiEvent as E;
+    match (queued, event) {
+        // a message edited before the UI saw it is shown edited from the start
+        (
+            E::MessageNew(message) | E::MessageEdited(message) | E::LoadedMessages(message),
+            E::MessageEdited(edited),
+        ) => *message = edited,
+        (queued, event) => *queued = event,
+    }
+    None
+}
+
+/// Whether `event` makes `queued` redundant.
+fn supersedes(event: &ApiEvent, queued: &ApiEvent) -> bool {
+    use ApiEvent as E;
+    match (event, queued) {
+        (
+            E::MessageEdited(edited),
+            E::MessageNew(message) | E::MessageEdited(message) | E::LoadedMessages(message),
+        ) => same_message(edited, message),
+        (E::TransferProgress { id, .. }, E::TransferProgress { id: queued, .. }) => id == queued,
+        (E::LoadedDialog(dialog), E::LoadedDialog(queued)) => dialog.chat().id() == queued.chat().id(),
+        (E::LoadedPinned { chat, .. }, E::LoadedPinned { chat: queued, .. }) => chat.id == queued.id,
+        (E::MarkedUnread { chat, .. }, E::MarkedUnread { chat: queued, .. }) => chat.id == queued.id,
+        (E::LoadedAccount { .. }, E::LoadedAccount { .. })
+        | (E::LoadedProfile(_), E::LoadedProfile(_))
+        | (E::LoadedFolders(_), E::LoadedFolders(_))
+        | (E::FoldersChanged, E::FoldersChanged)
+        | (E::LoadedPrivacy(_), E::LoadedPrivacy(_))
+        | (E::LoadedPassword(_), E::LoadedPassword(_))
+        | (E::LoadedSessions(_), E::LoadedSessions(_))
+        | (E::RefreshedDialogs(_), E::RefreshedDialogs(_)) => true,
+        _ => false,
+    }
+}
+
+fn same_message(a: &Message, b: &Message) -> bool {
+    a.id() == b.id() && a.chat().id() == b.chat().id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grammers_session::{PackedChat, PackedType};
+
+    fn queue(events: impl IntoIterator<Item = ApiEvent>) -> VecDeque<ApiEvent> {
+        let mut queue = VecDeque::new();
+        for event in events {
+            if let Some(event) = coalesce(&mut queue, event) {
+                queue.push_back(event);
+            }
+        }
+        queue
+    }
+
+    fn chat(id: i64) -> PackedChat {
+        PackedChat { ty: PackedType::Chat, id, access_hash: None }
+    }
+
+    #[test]
+    fn progress_replaces_progress_of_the_same_transfer() {
+        let events = queue([
+            ApiEvent::TransferProgress { id: 1, done: 10 },
+            ApiEvent::TransferProgress { id: 2, done: 5 },
+            ApiEvent::TransferProgress { id: 1, done: 20 },
+        ]);
+        let done: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                ApiEvent::TransferProgress { id, done } => (*id, *done),
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        assert_eq!(done, [(1, 20), (2, 5)]);
+    }
+
+    #[test]
+    fn unread_mark_replaces_the_mark_of_the_same_chat() {
+        let events = queue([
+            ApiEvent::MarkedUnread { chat: chat(1), unread: true },
+            ApiEvent::MarkedUnread { chat: chat(2), unread: true },
+            ApiEvent::MarkedUnread { chat: chat(1), unread: false },
+        ]);
+        let marks: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                ApiEvent::MarkedUnread { chat, unread } => (chat.id, *unread),
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        assert_eq!(marks, [(1, false), (2, true)]);
+    }
+
+    #[test]
+    fn folders_are_loaded_once_past_other_events() {
+        let events = queue([
+            ApiEvent::LoadedFolders(Vec::new()),
+            ApiEvent::Notice("between".into()),
+            ApiEvent::LoadedFolders(Vec::new()),
+        ]);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], ApiEvent::LoadedFolders(_)));
+        assert!(matches!(events[1], ApiEvent::Notice(_)));
+    }
+}
@@ -0,0 +1,127 @@
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Launch a configured command line (program and arguments separated by
+/// whitespace) with `path` appended, detached from the terminal.
+pub fn spawn(command: &str, path: &Path) -> io::Result<()> {
+    spawn_with_args(command, [path])
+}
+
+/// Like [`spawn`], with any number of arguments appended.
+pub fn spawn_with_args<I, S>(command: &str, args: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    };
+    Command::new(program)
+        .args(parts)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Like [`spawn`], writing `input` to the standard input of the command.
+/// It's written and waited for on a thread of its own, so a slow command
+/// doesn't hold up the caller.
+pub fn spawn_with_input(command: &str, input: Vec<u8>) -> io::Result<()> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    };
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take();
+    std::thread::spawn(move || {
+        if let Some(stdin) = &mut stdin {
+            // the command may not read its input at all
+            let _ = stdin.write_all(&input);
+        }
+        drop(stdin);
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Command for a configured command line with the arguments appended, to
+/// run in the foreground.
+pub fn command<I, S>(command: &str, args: I) -> io::Result<Command>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    };
+    let mut command = Command::new(program);
+    command.args(parts).args(args);
+    Ok(command)
+}
+
+/// Whether the program of a configured command line is installed.
+pub fn is_installed(command: &str) -> bool {
+    let Some(program) = command.split_whitespace().next() else {
+        return false;
+    };
+    if Path::new(program).components().count() > 1 {
+        return Path::new(program).is_file();
+    }
+    // on Windows the program is found with any of the extensions in
+    // `PATHEXT`, like `clip.exe` for `clip`
+    let extensions: Vec<String> = match std::env::var("PATHEXT") {
+        Ok(extensions) if cfg!(windows) => extensions.split(';').map(str::to_lowercase).collect(),
+        _ => Vec::new(),
+    };
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            dir.join(program).is_file()
+                || extensions.iter().any(|ext| dir.join(format!("{}{}", program, ext)).is_file())
+        })
+    })
+}
+
+/// Run a configured command line with the arguments appended and wait for
+/// its standard output.
+pub fn output<I, S>(command: &str, args: I) -> io::Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = output_bytes(command, args)?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Like [`output`], for commands printing something other than text.
+pub fn output_bytes<I, S>(command: &str, args: I) -> io::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    };
+    let output = Command::new(program)
+        .args(parts)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("{} exited with {}", program, output.status)));
+    }
+    Ok(output.stdout)
+}
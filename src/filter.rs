@@ -0,0 +1,35 @@
+use grammers_client::types::Message;
+use regex::{Regex, RegexBuilder};
+
+/// Hides the loaded messages of a chat whose text doesn't match, until
+/// it's cleared.
+#[derive(Debug, Clone)]
+pub struct MessageFilter {
+    /// As typed
+    pub pattern: String,
+    regex: Regex,
+}
+
+impl MessageFilter {
+    /// A regex between slashes like `/deploy(ed)?/`, or else text to find,
+    /// both ignoring case.
+    pub fn new(pattern: &str) -> Result<MessageFilter, String> {
+        let source = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) if !regex.is_empty() => regex.to_string(),
+            _ => regex::escape(pattern),
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(true)
+            .build()
+            // the last line says what's wrong, the others point at where
+            .map_err(|e| format!("invalid regex: {}", e.to_string().lines().last().unwrap_or_default()))?;
+        Ok(MessageFilter {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        self.regex.is_match(message.text())
+    }
+}
@@ -0,0 +1,143 @@
+use crate::app::ChatState;
+use grammers_client::grammers_tl_types as tl;
+use grammers_client::grammers_tl_types::enums::Dialog as RawDialog;
+use grammers_client::types::Chat;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Id folders give Saved Messages by, since they name it as myself
+const SAVED_MESSAGES: i64 = 0;
+
+/// Id of the archive, where chats are put away
+const ARCHIVE_FOLDER_ID: i32 = 1;
+
+/// A chat folder as set up in the official apps: the kinds of chats it
+/// takes, the chats it always or never takes, and what it leaves out.
+#[derive(Debug, Clone)]
+pub struct Folder {
+    pub title: String,
+    /// Chats always in the folder, by bare id
+    include: Vec<i64>,
+    /// Chats never in the folder, by bare id
+    exclude: Vec<i64>,
+    contacts: bool,
+    non_contacts: bool,
+    groups: bool,
+    broadcasts: bool,
+    bots: bool,
+    exclude_muted: bool,
+    exclude_read: bool,
+    exclude_archived: bool,
+}
+
+impl Folder {
+    /// The folder from how Telegram sends it, none for the folder of all
+    /// chats.
+    pub fn from_raw(filter: tl::enums::DialogFilter) -> Option<Folder> {
+        match filter {
+            tl::enums::DialogFilter::Filter(f) => Some(Folder {
+                title: f.title,
+                include: f.pinned_peers.iter().chain(&f.include_peers).filter_map(input_peer_id).collect(),
+                exclude: f.exclude_peers.iter().filter_map(input_peer_id).collect(),
+                contacts: f.contacts,
+                non_contacts: f.non_contacts,
+                groups: f.groups,
+                broadcasts: f.broadcasts,
+                bots: f.bots,
+                exclude_muted: f.exclude_muted,
+                exclude_read: f.exclude_read,
+                exclude_archived: f.exclude_archived,
+            }),
+            // shared folders are only ever the chats in them
+            tl::enums::DialogFilter::Chatlist(f) => Some(Folder {
+                title: f.title,
+                include: f.pinned_peers.iter().chain(&f.include_peers).filter_map(input_peer_id).collect(),
+                exclude: Vec::new(),
+                contacts: false,
+                non_contacts: false,
+                groups: false,
+                broadcasts: false,
+                bots: false,
+                exclude_muted: false,
+                exclude_read: false,
+                exclude_archived: false,
+            }),
+            tl::enums::DialogFilter::Default => None,
+        }
+    }
+
+    pub fn contains(&self, chat_state: &ChatState) -> bool {
+        let chat = chat_state.dialog.chat();
+        let id = match chat {
+            Chat::User(user) if user.is_self() => SAVED_MESSAGES,
+            _ => chat.id(),
+        };
+        if self.exclude.contains(&id) {
+            return false;
+        }
+        if self.include.contains(&id) {
+            return true;
+        }
+        if (self.exclude_read && !chat_state.is_unread())
+            || (self.exclude_muted && is_muted(chat_state))
+            || (self.exclude_archived && is_archived(chat_state))
+        {
+            return false;
+        }
+        match chat {
+            Chat::User(user) if user.is_bot() => self.bots,
+            Chat::User(user) if user.raw.contact || user.is_self() => self.contacts,
+            Chat::User(_) => self.non_contacts,
+            Chat::Group(_) => self.groups,
+            Chat::Channel(_) => self.broadcasts,
+        }
+    }
+}
+
+/// Unread messages and mentions across some chats.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Counts {
+    pub unread: i32,
+    /// Chats with unread messages or marked as unread
+    pub chats: usize,
+    pub mentions: i32,
+}
+
+impl Counts {
+    pub fn of<'a>(chat_states: impl IntoIterator<Item = &'a ChatState>) -> Counts {
+        chat_states.into_iter().fold(Counts::default(), |counts, c| Counts {
+            unread: counts.unread + c.unread,
+            chats: counts.chats + c.is_unread() as usize,
+            mentions: counts.mentions + c.mentions,
+        })
+    }
+}
+
+/// Bare id of the chat an input peer points to.
+fn input_peer_id(peer: &tl::enums::InputPeer) -> Option<i64> {
+    match peer {
+        tl::enums::InputPeer::Chat(p) => Some(p.chat_id),
+        tl::enums::InputPeer::User(p) => Some(p.user_id),
+        tl::enums::InputPeer::Channel(p) => Some(p.channel_id),
+        tl::enums::InputPeer::UserFromMessage(p) => Some(p.user_id),
+        tl::enums::InputPeer::ChannelFromMessage(p) => Some(p.channel_id),
+        tl::enums::InputPeer::PeerSelf => Some(SAVED_MESSAGES),
+        tl::enums::InputPeer::Empty => None,
+    }
+}
+
+/// Whether notifications of a chat are muted for now.
+fn is_muted(chat_state: &ChatState) -> bool {
+    let RawDialog::Dialog(dialog) = &chat_state.dialog.dialog else {
+        return false;
+    };
+    let tl::enums::PeerNotifySettings::Settings(settings) = &dialog.notify_settings;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    settings.mute_until.is_some_and(|until| until as i64 > now)
+}
+
+fn is_archived(chat_state: &ChatState) -> bool {
+    match &chat_state.dialog.dialog {
+        RawDialog::Dialog(dialog) => dialog.folder_id == Some(ARCHIVE_FOLDER_ID),
+        RawDialog::Folder(_) => false,
+    }
+}
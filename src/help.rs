@@ -0,0 +1,240 @@
+use crate::action::BINDINGS;
+use crate::app::Mode;
+use crate::config::Config;
+use std::collections::BTreeMap;
+
+/// A built-in `:` command. `command::parse` takes only the commands listed
+/// in [`COMMANDS`], the others go to plugins, so `:help commands` lists
+/// every one there is.
+pub struct CommandHelp {
+    /// The name and its aliases
+    pub names: &'static [&'static str],
+    /// What may follow the name, `[optional]` and `a|b` for one of them
+    pub args: &'static str,
+    pub about: &'static str,
+}
+
+pub const COMMANDS: &[CommandHelp] = &[
+    CommandHelp { names: &["q", "quit"], args: "", about: "quit tg9" },
+    CommandHelp { names: &["help"], args: "[commands|modes|config]", about: "show a page of this help" },
+    CommandHelp { names: &["send"], args: "", about: "send the draft" },
+    CommandHelp { names: &["preview"], args: "", about: "turn the link preview of the draft off or on" },
+    CommandHelp {
+        names: &["post"],
+        args: "[silent|sign|schedule <+1h|18:00|2024-05-01 18:00>|now|clear]",
+        about: "how the next post to a channel goes out",
+    },
+    CommandHelp { names: &["contact"], args: "", about: "share a contact in the current chat" },
+    CommandHelp { names: &["emoji"], args: "", about: "pick an emoji to type" },
+    CommandHelp { names: &["switch"], args: "", about: "jump to a chat by typing part of its name" },
+    CommandHelp { names: &["split", "vsplit"], args: "", about: "show another chat next to the current one, or stop" },
+    CommandHelp { names: &["only"], args: "", about: "close the other window" },
+    CommandHelp { names: &["tab", "tabnew"], args: "", about: "open the current chat in a tab" },
+    CommandHelp { names: &["tabclose"], args: "", about: "close the current tab" },
+    CommandHelp { names: &["tabnext"], args: "", about: "go to the next tab" },
+    CommandHelp { names: &["tabprev"], args: "", about: "go to the previous tab" },
+    CommandHelp { names: &["undo"], args: "", about: "take back the last local change, like `u`" },
+    CommandHelp { names: &["hide"], args: "", about: "leave the selected dialog out of the list" },
+    CommandHelp { names: &["unhide"], args: "", about: "list the hidden dialogs again" },
+    CommandHelp { names: &["dismiss"], args: "", about: "dismiss the story of the selected chat" },
+    CommandHelp { names: &["play"], args: "", about: "play the video of the selected message" },
+    CommandHelp { names: &["translate"], args: "[language]", about: "translate the selected message" },
+    CommandHelp { names: &["cancel"], args: "", about: "cancel the newest upload or download" },
+    CommandHelp { names: &["refresh"], args: "", about: "load the dialogs again" },
+    CommandHelp { names: &["save"], args: "[path]", about: "save the file of the selected message" },
+    CommandHelp { names: &["copyimage"], args: "", about: "copy the photo of the selected message" },
+    CommandHelp { names: &["copycode"], args: "", about: "copy the code blocks of the selected message" },
+    CommandHelp {
+        names: &["apply"],
+        args: "<file or directory>",
+        about: "apply the diff of the selected message with patch(1)",
+    },
+    CommandHelp { names: &["mp4"], args: "[file or directory]", about: "export the selected video note as MP4" },
+    CommandHelp { names: &["open"], args: "[@username]", about: "open the selected file, or a chat by username" },
+    CommandHelp { names: &["reveal"], args: "", about: "open the directory of the selected file" },
+    CommandHelp { names: &["export"], args: "<path>", about: "write the messages selected in visual mode to a file" },
+    CommandHelp { names: &["filter"], args: "[pattern]", about: "show only the messages matching, or all again" },
+    CommandHelp { names: &["unread"], args: "", about: "list only the dialogs with unread messages, or all" },
+    CommandHelp { names: &["catchup"], args: "", about: "mark every listed dialog as read" },
+    CommandHelp { names: &["redact"], args: "", about: "hide the text of messages, or show it again" },
+    CommandHelp { names: &["metrics"], args: "", about: "show the debug overlay, or hide it" },
+    CommandHelp { names: &["discussion"], args: "", about: "open the discussion group of the channel" },
+    CommandHelp { names: &["comments", "replies"], args: "", about: "show the comments on the selected post" },
+    CommandHelp { names: &["thread"], args: "", about: "show the replies to the selected message, or hide them" },
+    CommandHelp { names: &["markunread"], args: "", about: "set or clear the unread mark of the dialog" },
+    CommandHelp { names: &["pin"], args: "[notify]", about: "pin the selected message, telling the members" },
+    CommandHelp { names: &["unpin"], args: "", about: "unpin the pinned message" },
+    CommandHelp { names: &["report"], args: "", about: "report the selected message, or the user, as spam" },
+    CommandHelp { names: &["whois"], args: "", about: "show who the sender of the selected message is" },
+    CommandHelp { names: &["password", "2fa"], args: "", about: "change the two-step verification" },
+    CommandHelp { names: &["sessions"], args: "[ttl <days>]", about: "list my sessions, or when inactive ones end" },
+    CommandHelp {
+        names: &["privacy"],
+        args: "[lastseen|photo|forwards|calls everybody|contacts|nobody]",
+        about: "show or change who sees what of me",
+    },
+    CommandHelp {
+        names: &["profile"],
+        args: "[name <first> [last]|bio [text]|username <name>]",
+        about: "show or change my profile",
+    },
+    CommandHelp { names: &["ban"], args: "", about: "ban the sender of the selected message, deleting their messages" },
+    CommandHelp { names: &["requests"], args: "", about: "review the requests to join the chat" },
+    CommandHelp { names: &["leave"], args: "", about: "leave the current group or channel" },
+    CommandHelp { names: &["autodelete"], args: "off|<period>", about: "delete new messages after a while, like 1d" },
+    CommandHelp {
+        names: &["chat"],
+        args: "[title <text>|about [text]|photo <path>|allow|deny <permission>|permissions]",
+        about: "show or change the group or channel",
+    },
+    CommandHelp { names: &["info"], args: "", about: "show the details of the selected message" },
+    CommandHelp { names: &["stats"], args: "", about: "chart the loaded messages of the chat" },
+    CommandHelp { names: &["clearhistory"], args: "[me|all] [keep <days>]", about: "delete the history of the chat" },
+    CommandHelp { names: &["log", "adminlog"], args: "", about: "show the recent actions of the admins" },
+    CommandHelp { names: &["cache"], args: "stats|clear", about: "show the size of the media cache, or empty it" },
+    CommandHelp { names: &["attach"], args: "<path>", about: "attach a file to the draft" },
+    CommandHelp { names: &["location"], args: "<lat> <lon>", about: "send a location" },
+    CommandHelp {
+        names: &["poll"],
+        args: "[--public] [--multi] <question> | <option> | <option>...",
+        about: "send a poll",
+    },
+    CommandHelp { names: &["dnd"], args: "[off|<duration>]", about: "don't disturb, for a while like 1h30m" },
+    CommandHelp { names: &["detach"], args: "<n>", about: "remove the nth attachment of the draft" },
+    CommandHelp { names: &["retry"], args: "[n]", about: "send the queued messages of the chat again, or the nth" },
+    CommandHelp { names: &["discard"], args: "[n]", about: "drop the queued messages of the chat, or the nth" },
+    CommandHelp { names: &["caption"], args: "<n> [text]", about: "caption the nth attachment of the draft" },
+];
+
+/// Whether a command is built in, under any of its names.
+pub fn is_command(name: &str) -> bool {
+    COMMANDS.iter().any(|command| command.names.contains(&name))
+}
+
+/// What `:help` shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topic {
+    Commands,
+    Modes,
+    Config,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 3] = [Topic::Commands, Topic::Modes, Topic::Config];
+
+    pub fn parse(s: &str) -> Option<Topic> {
+        Topic::ALL.into_iter().find(|topic| topic.name() == s)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::Commands => "commands",
+            Topic::Modes => "modes",
+            Topic::Config => "config",
+        }
+    }
+}
+
+/// Every mode, in the order `:help modes` lists them.
+const MODES: [Mode; 4] = [Mode::Normal, Mode::Insert, Mode::Command, Mode::Visual];
+
+/// What a mode is for, its keys come from [`BINDINGS`].
+fn mode_about(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Normal => "moving around the dialogs and messages",
+        Mode::Insert => "writing the draft of the current chat",
+        Mode::Command => "typing a `:` command",
+        Mode::Visual => "selecting messages in the chat view",
+    }
+}
+
+/// Keys of the config file and what they do. Keys are listed with their
+/// defaults from [`Config::default`], and any left out here are still
+/// listed after these.
+const CONFIG: &[(&str, &str)] = &[
+    ("player", "command to play videos with, the path is appended"),
+    ("terminal_player", "command to play GIFs with inside the terminal, \"\" for the player"),
+    ("copy_command", "command to copy text with, \"\" for wl-copy, xclip, pbcopy or clip"),
+    ("download_dir", "where files are saved, \"\" for the downloads directory"),
+    ("auto_download", "media downloaded in the background, by media, chats and max_size"),
+    ("media_cache_size", "total size in bytes of the media cache"),
+    ("history_max", "messages kept in memory per chat, 0 keeps all"),
+    ("bell", "alert when a message mentions me: off, audible or visual"),
+    ("presence", "whether others see me online: auto, focus, online or offline"),
+    ("read_only", "refuse everything that changes the account"),
+    ("bidi", "reorder right-to-left text for display"),
+    ("summary_bar", "bar with the unread messages and mentions per chat folder"),
+    ("plain", "print what changes as lines of text, for screen readers"),
+    ("open", "commands to open files with by MIME type, like \"image/*\" = \"imv\""),
+    ("notifications.enabled", "desktop notifications of messages in other chats"),
+    ("notifications.command", "command to show a notification, the title and body are appended"),
+    ("notifications.preview", "show the sender and text in notifications"),
+    ("notifications.sound", "command to play a sound for incoming messages, \"\" for none"),
+    ("translate.to", "language to translate messages to"),
+    ("translate.command", "translator to use instead of Telegram, the language and text are appended"),
+    ("hooks.message", "command run for every new message, with the event as JSON on its input"),
+    ("hooks.mention", "command run for incoming messages mentioning me"),
+    ("hooks.chat_opened", "command run when a chat is opened"),
+    ("confirm.delete_for_everyone", "ask before deleting messages for everyone"),
+    ("confirm.leave_chat", "ask before leaving a group or channel"),
+    ("confirm.clear_history", "ask before deleting the history of a chat"),
+    ("confirm.mark_all_read", "ask before marking every listed chat as read"),
+    (
+        "chat",
+        "[[chat]] tables with an id and options for that chat: preview, bell, color, marker, sound, rtl, download_dir",
+    ),
+];
+
+/// Rows of a help page: a term and what it means.
+pub fn page(topic: Topic) -> Vec<(String, String)> {
+    match topic {
+        Topic::Commands => COMMANDS
+            .iter()
+            .map(|command| {
+                let names = command.names.iter().map(|name| format!(":{}", name)).collect::<Vec<_>>().join(", ");
+                let usage = if command.args.is_empty() { names } else { format!("{} {}", names, command.args) };
+                (usage, command.about.to_string())
+            })
+            .collect(),
+        Topic::Modes => MODES
+            .into_iter()
+            .flat_map(|mode| {
+                let keys = BINDINGS
+                    .iter()
+                    .filter(move |binding| binding.modes.contains(&mode))
+                    .map(|binding| (format!("  {}", binding.key_names()), binding.about.to_string()));
+                std::iter::once((format!("{:?}", mode).to_lowercase(), mode_about(mode).to_string())).chain(keys)
+            })
+            .collect(),
+        Topic::Config => {
+            let mut defaults = BTreeMap::new();
+            if let Ok(config) = toml::Value::try_from(Config::default()) {
+                flatten(&config, "", &mut defaults);
+            }
+            let mut rows: Vec<_> = CONFIG
+                .iter()
+                .map(|&(key, about)| match defaults.remove(key) {
+                    Some(default) => (key.to_string(), format!("{} (default {})", about, default)),
+                    None => (key.to_string(), about.to_string()),
+                })
+                .collect();
+            rows.extend(defaults.into_iter().map(|(key, default)| (key, format!("(default {})", default))));
+            rows
+        }
+    }
+}
+
+/// Keys of the tables in a config value, dotted, with their values.
+fn flatten(value: &toml::Value, prefix: &str, keys: &mut BTreeMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(value, &key, keys);
+            }
+        }
+        _ => {
+            keys.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
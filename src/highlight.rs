@@ -0,0 +1,118 @@
+use grammers_client::grammers_tl_types as tl;
+use ratatui::prelude::*;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::{Mutex, OnceLock};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{self, FontStyle, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Theme of the highlighted code, one of those built into syntect
+const THEME: &str = "base16-ocean.dark";
+
+/// Code blocks whose highlighting is kept, past which it's all dropped
+const CACHE_MAX: usize = 256;
+
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+
+static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+
+/// Highlighted lines by language and code, since messages are rendered on
+/// every frame.
+static CACHE: Mutex<BTreeMap<(String, String), Vec<Line<'static>>>> = Mutex::new(BTreeMap::new());
+
+/// A `pre` block in the text of a message.
+#[derive(Debug, Clone)]
+pub struct CodeBlock {
+    /// Byte range of the code in the text
+    pub range: Range<usize>,
+    /// As given by the sender, empty if none was
+    pub language: String,
+}
+
+/// The code blocks of a message text with the formatting `entities`, in
+/// the order they appear.
+pub fn code_blocks(text: &str, entities: &[tl::enums::MessageEntity]) -> Vec<CodeBlock> {
+    let mut blocks: Vec<_> = entities
+        .iter()
+        .filter_map(|entity| match entity {
+            tl::enums::MessageEntity::Pre(pre) => Some(CodeBlock {
+                range: byte_offset(text, pre.offset)..byte_offset(text, pre.offset + pre.length),
+                language: pre.language.clone(),
+            }),
+            _ => None,
+        })
+        .collect();
+    blocks.sort_by_key(|block| block.range.start);
+    blocks
+}
+
+/// Byte offset in `text` of an offset in UTF-16 code units, which entities
+/// are measured in.
+fn byte_offset(text: &str, offset: i32) -> usize {
+    let mut units = 0;
+    for (i, c) in text.char_indices() {
+        if units >= offset as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Lines of a code block highlighted for its language, or without one for
+/// the language its first line tells, like `#!/bin/sh`. Code in languages
+/// syntect doesn't know is left plain.
+pub fn highlight(code: &str, language: &str) -> Vec<Line<'static>> {
+    let key = (language.to_string(), code.to_string());
+    if let Some(lines) = CACHE.lock().unwrap().get(&key) {
+        return lines.clone();
+    }
+    let lines = highlight_lines(code, language)
+        .unwrap_or_else(|| code.lines().map(|line| Line::from(line.to_string())).collect());
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= CACHE_MAX {
+        cache.clear();
+    }
+    cache.insert(key, lines.clone());
+    lines
+}
+
+fn highlight_lines(code: &str, language: &str) -> Option<Vec<Line<'static>>> {
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let syntax = if language.is_empty() {
+        syntaxes.find_syntax_by_first_line(code)
+    } else {
+        syntaxes.find_syntax_by_token(language)
+    }?;
+    let theme = THEMES.get_or_init(ThemeSet::load_defaults).themes.get(THEME)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    LinesWithEndings::from(code)
+        .map(|line| {
+            let ranges = highlighter.highlight_line(line, syntaxes).ok()?;
+            let spans: Vec<_> = ranges
+                .into_iter()
+                .map(|(style, text)| Span::styled(text.trim_end_matches(['\r', '\n']).to_string(), style_of(style)))
+                .collect();
+            Some(Line::from(spans))
+        })
+        .collect()
+}
+
+/// The foreground and font style of a theme's style; its background is
+/// left out for the terminal's own.
+fn style_of(style: highlighting::Style) -> Style {
+    let color = style.foreground;
+    let mut result = Style::default().fg(Color::Rgb(color.r, color.g, color.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        result = result.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        result = result.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        result = result.add_modifier(Modifier::UNDERLINED);
+    }
+    result
+}
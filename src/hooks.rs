@@ -0,0 +1,72 @@
+use crate::config::Hooks;
+use crate::external;
+use crate::render;
+use grammers_client::types::{Chat, Message};
+use serde::Serialize;
+use std::io;
+
+/// What a hook gets on its standard input, like
+/// `{"event":"mention","chat_id":123,"chat":"Friends",...}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent {
+    Message(HookMessage),
+    Mention(HookMessage),
+    ChatOpened { chat_id: i64, chat: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookMessage {
+    pub chat_id: i64,
+    pub chat: String,
+    pub message_id: i32,
+    pub sender_id: Option<i64>,
+    pub sender: Option<String>,
+    /// Text of the message, or a description of its media
+    pub text: String,
+    pub outgoing: bool,
+    /// Unix timestamp
+    pub date: i64,
+}
+
+impl HookMessage {
+    pub fn new(message: &Message) -> Self {
+        let chat = message.chat();
+        let sender = message.sender();
+        HookMessage {
+            chat_id: chat.id(),
+            chat: chat.name().to_string(),
+            message_id: message.id(),
+            sender_id: sender.as_ref().map(Chat::id),
+            sender: sender.as_ref().map(|s| s.name().to_string()),
+            text: render::summary(message),
+            outgoing: message.outgoing(),
+            date: message.date().timestamp(),
+        }
+    }
+}
+
+impl HookEvent {
+    pub fn chat_opened(chat: &Chat) -> Self {
+        HookEvent::ChatOpened {
+            chat_id: chat.id(),
+            chat: chat.name().to_string(),
+        }
+    }
+
+    fn command<'a>(&self, hooks: &'a Hooks) -> Option<&'a str> {
+        match self {
+            HookEvent::Message(_) => hooks.message.as_deref(),
+            HookEvent::Mention(_) => hooks.mention.as_deref(),
+            HookEvent::ChatOpened { .. } => hooks.chat_opened.as_deref(),
+        }
+    }
+}
+
+/// Run the hook configured for the event, if any, without waiting for it.
+pub fn fire(hooks: &Hooks, event: &HookEvent) -> io::Result<()> {
+    let Some(command) = event.command(hooks) else {
+        return Ok(());
+    };
+    external::spawn_with_input(command, serde_json::to_vec(event)?)
+}
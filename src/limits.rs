@@ -0,0 +1,190 @@
+use crate::api::{ChatEdit, NewPoll};
+use crate::compose::Draft;
+use crate::render;
+
+/// Longest text of a message, with or without Premium
+pub const MESSAGE_LENGTH: usize = 4096;
+
+/// Longest question of a poll
+pub const POLL_QUESTION_LENGTH: usize = 255;
+
+/// Longest option of a poll
+pub const POLL_OPTION_LENGTH: usize = 100;
+
+/// Fewest and most options of a poll
+pub const POLL_OPTIONS: std::ops::RangeInclusive<usize> = 2..=10;
+
+/// Longest first and last name
+pub const NAME_LENGTH: usize = 64;
+
+/// Longest title of a group or channel
+pub const CHAT_TITLE_LENGTH: usize = 128;
+
+/// Longest description of a group or channel
+pub const CHAT_ABOUT_LENGTH: usize = 255;
+
+/// Largest photo of a group or channel, the most uploaded in one go
+pub const CHAT_PHOTO_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Check an edit of a group or channel, the same for every account.
+pub fn check_chat_edit(edit: &ChatEdit) -> Result<(), String> {
+    match edit {
+        ChatEdit::Title(title) if title.trim().is_empty() => Err("the title can't be empty".into()),
+        ChatEdit::Title(title) if title.chars().count() > CHAT_TITLE_LENGTH => {
+            Err(format!("the title is longer than {} characters", CHAT_TITLE_LENGTH))
+        }
+        ChatEdit::About(about) if about.chars().count() > CHAT_ABOUT_LENGTH => {
+            Err(format!("the description is longer than {} characters", CHAT_ABOUT_LENGTH))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check an edit of my name, the same for every account.
+pub fn check_name(first: &str, last: &str) -> Result<(), String> {
+    if first.is_empty() {
+        return Err("the first name can't be empty".into());
+    }
+    if let Some(name) = [first, last].into_iter().find(|n| n.chars().count() > NAME_LENGTH) {
+        return Err(format!("{:?} is longer than {} characters", name, NAME_LENGTH));
+    }
+    Ok(())
+}
+
+/// Usernames are 5 to 32 letters, digits and underscores, starting with
+/// a letter.
+pub fn check_username(username: &str) -> Result<(), String> {
+    let valid = (5..=32).contains(&username.len())
+        && username.starts_with(|c: char| c.is_ascii_alphabetic())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err("usernames are 5-32 letters, digits and underscores, starting with a letter".into());
+    }
+    Ok(())
+}
+
+/// Check a poll before sending, the same for every account.
+pub fn check_poll(poll: &NewPoll) -> Result<(), String> {
+    let length = poll.question.chars().count();
+    if length > POLL_QUESTION_LENGTH {
+        return Err(format!(
+            "question is {} characters, the limit is {}",
+            length, POLL_QUESTION_LENGTH
+        ));
+    }
+    if !POLL_OPTIONS.contains(&poll.options.len()) {
+        return Err(format!(
+            "a poll needs {} to {} options, not {}",
+            POLL_OPTIONS.start(),
+            POLL_OPTIONS.end(),
+            poll.options.len()
+        ));
+    }
+    if let Some(option) = poll.options.iter().find(|o| o.chars().count() > POLL_OPTION_LENGTH) {
+        return Err(format!(
+            "option {:?} is {} characters, the limit is {}",
+            option,
+            option.chars().count(),
+            POLL_OPTION_LENGTH
+        ));
+    }
+    if let Some((i, option)) = poll.options.iter().enumerate().find(|(i, o)| poll.options[..*i].contains(o)) {
+        return Err(format!("option {} repeats {:?}", i + 1, option));
+    }
+    Ok(())
+}
+
+/// Limits on what the account can send, which are higher with Telegram
+/// Premium. Drafts are checked before sending to explain what's over the
+/// limit instead of failing with an API error.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub premium: bool,
+}
+
+impl Limits {
+    pub fn caption_length(&self) -> usize {
+        if self.premium {
+            4096
+        } else {
+            1024
+        }
+    }
+
+    pub fn bio_length(&self) -> usize {
+        if self.premium {
+            140
+        } else {
+            70
+        }
+    }
+
+    /// Check a bio before setting it.
+    pub fn check_bio(&self, bio: &str) -> Result<(), String> {
+        let length = bio.chars().count();
+        if length > self.bio_length() {
+            return Err(self.over_limit(
+                format!("bio is {} characters", length),
+                self.bio_length().to_string(),
+                Limits { premium: true }.bio_length().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn upload_size(&self) -> u64 {
+        if self.premium {
+            4000 * 1024 * 1024
+        } else {
+            2000 * 1024 * 1024
+        }
+    }
+
+    pub fn check(&self, draft: &Draft) -> Result<(), String> {
+        let text_length = draft.input.text.chars().count();
+        // the text is sent as the caption of the first attachment, unless it
+        // has its own; then it's a message of its own before them
+        let text_is_caption = draft.attachments.first().is_some_and(|a| a.caption.is_empty());
+        if !text_is_caption && text_length > MESSAGE_LENGTH {
+            return Err(format!(
+                "message is {} characters, the limit is {}",
+                text_length, MESSAGE_LENGTH
+            ));
+        }
+        if draft.attachments.is_empty() {
+            return Ok(());
+        }
+
+        let captions = text_is_caption
+            .then_some(text_length)
+            .into_iter()
+            .chain(draft.attachments.iter().map(|a| a.caption.chars().count()));
+        if let Some(length) = captions.max().filter(|&l| l > self.caption_length()) {
+            return Err(self.over_limit(
+                format!("caption is {} characters", length),
+                self.caption_length().to_string(),
+                Limits { premium: true }.caption_length().to_string(),
+            ));
+        }
+
+        for attachment in &draft.attachments {
+            let size = attachment.path.metadata().map(|m| m.len()).unwrap_or(0);
+            if size > self.upload_size() {
+                return Err(self.over_limit(
+                    format!("{} is {}", attachment.name(), render::human_size(size)),
+                    render::human_size(self.upload_size()),
+                    render::human_size(Limits { premium: true }.upload_size()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn over_limit(&self, what: String, limit: String, premium_limit: String) -> String {
+        if self.premium {
+            format!("{}, the limit is {}", what, limit)
+        } else {
+            format!("{}, the limit is {} ({} with Telegram Premium)", what, limit, premium_limit)
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::action::Action;
+use std::collections::HashMap;
+
+/// What the key after `q` or `@` names the register for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Awaiting {
+    Record,
+    Replay,
+}
+
+/// Keyboard macros: the actions of keys pressed while recording into a
+/// register with `q`, replayed with `@`. Kept until tg9 quits.
+#[derive(Debug, Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<Action>>,
+    /// Register being recorded into, with the actions so far
+    recording: Option<(char, Vec<Action>)>,
+    /// `q` or `@` was pressed, the next key names the register
+    pub awaiting: Option<Awaiting>,
+    /// Register replayed last, for `@@`
+    last: Option<char>,
+}
+
+impl Macros {
+    /// Registers are letters and digits, like in vim.
+    pub fn is_register(c: char) -> bool {
+        c.is_ascii_alphanumeric()
+    }
+
+    /// Register being recorded into, if any.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn start(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    /// Stop recording, keeping what was recorded in its register. Gives
+    /// the register and how many actions it holds.
+    pub fn stop(&mut self) -> Option<(char, usize)> {
+        let (register, actions) = self.recording.take()?;
+        let len = actions.len();
+        self.registers.insert(register, actions);
+        Some((register, len))
+    }
+
+    /// Add an action to the recording, unless it's about macros itself.
+    pub fn record(&mut self, action: &Action) {
+        let Some((_, actions)) = &mut self.recording else {
+            return;
+        };
+        if !matches!(action, Action::RecordMacro | Action::ReplayMacro | Action::MacroRegister(_)) {
+            actions.push(action.clone());
+        }
+    }
+
+    /// Actions to replay from a register, `@` for the one replayed last.
+    pub fn replay(&mut self, register: char) -> Option<Vec<Action>> {
+        let register = if register == '@' { self.last? } else { register };
+        let actions = self.registers.get(&register)?.clone();
+        self.last = Some(register);
+        Some(actions)
+    }
+}
@@ -1,281 +1,277 @@
+mod action;
 mod api;
+mod app;
+mod bidi;
+mod cache;
+mod cli;
+mod command;
+mod compose;
+mod config;
+mod credentials;
+mod crash;
+mod diff;
+mod doctor;
+mod download;
+mod emoji;
+mod error;
+mod events;
+mod external;
+mod filter;
+mod folders;
+mod help;
+mod highlight;
+mod hooks;
+mod limits;
+mod macros;
+mod metrics;
+mod notify;
+mod outbox;
+mod peers;
+mod plain;
+mod plugin;
+mod popup;
+mod render;
 mod screen;
+mod secrets;
+mod session;
+mod state;
+mod stats;
+mod status;
+mod takeout;
+mod transfer;
+mod two_factor;
 mod ui;
+mod usage;
+mod usernames;
+mod video_note;
+mod watch;
+mod wizard;
 
 use screen::ScreenEvent;
 use anyhow::Result;
-use crossterm::event::{KeyCode, KeyModifiers};
-use grammers_client::types::iter_buffer::InvocationError;
-use grammers_client::types::{Chat, Dialog, Message, MessageDeletion};
-use grammers_client::{Client, Update};
-use grammers_session::PackedChat;
-use ratatui::{prelude::*, widgets::*};
+use api::ApiJob;
+use app::{App, Mode};
+use config::{Config, ConfigCommand};
+use secrets::SecretCommand;
+use session::SessionCommand;
+use state::State;
+use std::path::PathBuf;
+use std::time::Instant;
 use tokio::sync::mpsc;
-use std::cmp;
-use std::collections::{VecDeque, HashMap};
 
-
-struct ChatState {
-    chat: PackedChat,
-    dialog: Dialog,
-    messages: VecDeque<Message>,
-}
-
-impl ChatState {
-    fn new(dialog: Dialog) -> ChatState {
-        let chat = dialog.chat().pack();
-        ChatState {
-            dialog,
-            messages: VecDeque::new(),
-            chat,
-        }
-    }
+/// Command line options.
+#[derive(Default)]
+struct Args {
+    read_only: bool,
+    /// Linear output for screen readers, see `plain.rs`
+    plain: bool,
+    /// Print JSON, for subcommands with output
+    json: bool,
+    /// Run instead of the client
+    command: Option<Subcommand>,
 }
 
-struct App {
-    quit: bool,
-    chat_states: VecDeque<ChatState>,
-    dialog_idx: Option<usize>,
-    // chat_idxs: HashMap<usize, Option<usize>>,
-}
-
-impl App {
-    fn new() -> Self {
-        App {
-            quit: false,
-            chat_states: VecDeque::new(),
-            dialog_idx: None,
-        }
-    }
-}
-
-fn ui(frame: &mut Frame, app: &mut App) {
-    let area = frame.size();
-
-    // TODO: scroll and stuff
-    //
-    let layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Min(0), Constraint::Max(3)])
-        .split(area);
-
-    let view_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(vec![Constraint::Percentage(33), Constraint::Percentage(67)])
-        .split(layout[0]);
-
-    let dialogs_widget = List::new(
-        app.chat_states
-            .iter()
-            .enumerate()
-            .map(|(i, c)| {
-                format!(
-                    "{}[{}]: {}",
-                    if app.dialog_idx == Some(i) { "*" } else { " " },
-                    c.dialog.chat().name(),
-                    c.dialog
-                        .last_message
-                        .as_ref()
-                        .map(|m| m.text())
-                        .unwrap_or("")
-                )
-            })
-            .map(Line::from),
-    )
-    .block(Block::default().borders(Borders::ALL));
-
-    let active_chat_widget = if let Some(idx) = app.dialog_idx {
-        List::new(
-            app.chat_states
-                .get(idx)
-                .unwrap()
-                .messages
-                .iter()
-                .map(|m| m.text())
-                .map(Line::from),
-        )
-    } else {
-        List::default()
-    }
-    .direction(ListDirection::BottomToTop)
-    .block(Block::default().borders(Borders::ALL));
-
-    let status_view =
-        Paragraph::new(Line::from("tg9 v0.1")).block(Block::new().borders(Borders::ALL));
-
-    frame.render_widget(dialogs_widget, view_layout[0]);
-    frame.render_widget(active_chat_widget, view_layout[1]);
-    frame.render_widget(status_view, layout[1]);
+enum Subcommand {
+    Config(ConfigCommand),
+    Session(SessionCommand),
+    Secret(SecretCommand),
+    /// Print the unread counts of the running tg9
+    Status,
+    Send(cli::SendArgs),
+    Tail(cli::TailArgs),
+    Search(cli::SearchArgs),
+    Takeout(takeout::TakeoutArgs),
+    /// Check the setup and say what's wrong with it
+    Doctor,
 }
 
-/// Jobs for api client worker to perform
-#[derive(Debug)]
-enum ApiJob {
-    /// Load a part of chat messages
-    LoadMessages(PackedChat),
-
-    /// Initial loading of all dialogs
-    LoadDialogs,
-}
-
-/// Perform API calls and receive updates.
-async fn api_worker(
-    client: Client,
-    mut rx: mpsc::UnboundedReceiver<ApiJob>,
-    tx: mpsc::UnboundedSender<ApiEvent>,
-) {
-    loop {
-        tokio::select! {
-        job = rx.recv() => {
-            let Some(job) = job else { break; };
-            let tx = tx.clone();
-            let client = client.clone();
-            match job {
-                ApiJob::LoadDialogs => {
-                    let mut dialogs = client.iter_dialogs();
-                    while let Some(dialog) = dialogs.next().await.unwrap() {
-                        tx.send(ApiEvent::LoadedDialog(dialog)).unwrap();
-                    }
+impl Args {
+    fn parse() -> Result<Args> {
+        let mut args = Args::default();
+        let mut argv = std::env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "--read-only" => args.read_only = true,
+                "--plain" => args.plain = true,
+                "--json" => args.json = true,
+                "config" => {
+                    let command = match argv.next().as_deref() {
+                        Some("dump") => ConfigCommand::Dump,
+                        Some("check") => ConfigCommand::Check(argv.next().map(PathBuf::from)),
+                        _ => anyhow::bail!("usage: tg9 config dump|check [path]"),
+                    };
+                    args.command = Some(Subcommand::Config(command));
                 }
-                ApiJob::LoadMessages(c) => {
-                    // TODO: when scrolling up, load necessary messages. For now this is
-                    // just for initial loading of chats (and the view is not scrollable)
-                    let mut message_iter = client.iter_messages(c).limit(30);
-
-                    // let mut messages = Vec::new();
-                    while let Some(message) = message_iter.next().await.unwrap() {
-                        // messages.push(message);
-                        tx.send(ApiEvent::LoadedMessages(message)).unwrap();
-                    }
+                "session" => {
+                    let command = match argv.next().as_deref() {
+                        Some("encrypt") => SessionCommand::Encrypt,
+                        Some("decrypt") => SessionCommand::Decrypt,
+                        _ => anyhow::bail!("usage: tg9 session encrypt|decrypt"),
+                    };
+                    args.command = Some(Subcommand::Session(command));
                 }
-            }
-        }
-            update = client.next_update() => {
-                let Ok(update) = update else {
-                    tx.send(ApiEvent::Error()).unwrap();
-                    break;
-                };
-                let Some(update) = update else { break; };
-                match update {
-                    Update::NewMessage(message) if !message.outgoing() => {
-                        tx.send(ApiEvent::MessageNew(message)).unwrap();
-                    }
-                    Update::MessageDeleted(_message_del) => {}
-                    Update::MessageEdited(_message) => {}
-                    _ => {}
+                "secret" => {
+                    let command = SecretCommand::parse(argv.next().as_deref(), argv.next().as_deref())?;
+                    args.command = Some(Subcommand::Secret(command));
+                }
+                "status" => args.command = Some(Subcommand::Status),
+                "doctor" => args.command = Some(Subcommand::Doctor),
+                // the rest of the arguments are its own
+                "send" => {
+                    args.command = Some(Subcommand::Send(cli::SendArgs::parse(argv.by_ref())?));
+                }
+                "tail" => {
+                    args.command = Some(Subcommand::Tail(cli::TailArgs::parse(argv.by_ref())?));
+                }
+                "search" => {
+                    args.command = Some(Subcommand::Search(cli::SearchArgs::parse(argv.by_ref())?));
                 }
+                "takeout" => {
+                    args.command = Some(Subcommand::Takeout(takeout::TakeoutArgs::parse(argv.by_ref())?));
+                }
+                _ => anyhow::bail!("unknown argument: {}", arg),
             }
         }
+        Ok(args)
     }
 }
 
-/// Events that update state from API messages
-#[derive(Debug)]
-enum ApiEvent {
-    /// new message
-    MessageNew(Message),
-
-    MessageDeleted(MessageDeletion),
-
-    MessageEdited(Message),
-
-    /// load a part of messages in chat
-    LoadedMessages(Message),
-
-    /// initial loading of dialogs
-    LoadedDialog(Dialog),
-
-    /// error invoking API
-    Error(),
-}
-
 async fn run() -> Result<()> {
-    // TODO: provide login data from tui
-    let client = api::login().await?;
+    let args = Args::parse()?;
+    match args.command {
+        Some(Subcommand::Config(command)) => return command.run(),
+        Some(Subcommand::Session(command)) => return command.run(&api::session_path()),
+        Some(Subcommand::Secret(command)) => return command.run(),
+        Some(Subcommand::Status) => return status::print(args.json),
+        Some(Subcommand::Send(send)) => return cli::send(send, args.read_only).await,
+        // `--json` goes for them before their name too
+        Some(Subcommand::Tail(tail)) => return cli::tail(cli::TailArgs { json: tail.json || args.json, ..tail }).await,
+        Some(Subcommand::Search(search)) => {
+            return cli::search(cli::SearchArgs { json: search.json || args.json, ..search }).await
+        }
+        Some(Subcommand::Takeout(takeout)) => return takeout::takeout(takeout).await,
+        Some(Subcommand::Doctor) => return doctor::doctor().await,
+        None => {}
+    }
+    let config = Config::load()?;
+
+    // with a saved session, connect while the UI is already up
+    let client = if api::has_session() {
+        None
+    } else {
+        Some(wizard::run().await?)
+    };
 
-    let (api_tx, mut api_rx) = mpsc::unbounded_channel();
+    let (api_tx, mut api_rx) = events::channel();
 
     let (api_job_tx, api_job_rx) = mpsc::unbounded_channel();
 
+    let (passphrase_tx, passphrase_rx) = mpsc::unbounded_channel();
+
     tokio::spawn({
         let api_tx = api_tx.clone();
-        let client = client.clone();
         async move {
-            api_worker(client, api_job_rx, api_tx).await;
+            let client = match client {
+                Some(client) => client,
+                None => match api::connect_saved(&api_tx, passphrase_rx).await {
+                    Some(client) => client,
+                    None => return,
+                },
+            };
+            api_tx.send(api::ApiEvent::Connected).unwrap();
+            api::api_worker(client, api_job_rx, api_tx).await;
         }
     });
 
     let (screen_tx, mut screen_rx) = mpsc::unbounded_channel();
     let mut screen = screen::Screen::new(screen_tx).unwrap();
+    screen.plain = args.plain || config.plain;
     screen.enter()?;
+    let mut plain = screen.plain.then(plain::Plain::default);
+
+    let mut app = App::new(config, State::load(), api_job_tx);
+    app.read_only = args.read_only;
+    app.passphrases = Some(passphrase_tx);
+
+    let (config_tx, mut config_rx) = mpsc::unbounded_channel();
+    let _config_watcher = match watch::config(config_tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            app.status = Some(format!("config won't be reloaded: {}", e));
+            None
+        }
+    };
 
-    let mut app = App::new();
-
-    api_job_tx.send(ApiJob::LoadDialogs).unwrap();
+    app.send_job(ApiJob::LoadAccount);
+    app.send_job(ApiJob::LoadFolders);
+    app.send_job(ApiJob::LoadDialogs);
 
+    // statuses go into the log of crash reports as they change
+    let mut last_status = None;
     loop {
         tokio::select! {
         Some(e) = screen_rx.recv() => {
             match e {
-                ScreenEvent::Tick => {},
+                ScreenEvent::Tick => app.tick(),
                 ScreenEvent::Render => {},
 
                 ScreenEvent::Key(e) => {
-                    match (e.modifiers, e.code) {
-                        (KeyModifiers::NONE, KeyCode::Char('q')) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
-                            app.quit = true;
-                        }
-                        (KeyModifiers::NONE, KeyCode::Char('j')) => {
-                            if app.chat_states.is_empty() { continue; } 
-                            let idx = cmp::min(app.dialog_idx.map(|i| i+1).unwrap_or(0), app.chat_states.len()-1);
-                            app.dialog_idx = Some(idx);
-                            let c = app.chat_states[idx].chat;
-                            api_job_tx.send(ApiJob::LoadMessages(c)).unwrap();
+                    let action = match &app.popup {
+                        _ if app.macros.awaiting.is_some() => action::from_register_key(e),
+                        Some(popup) => action::from_popup_key(popup, e),
+                        None => action::from_key(app.mode, e),
+                    };
+                    if let Some(action) = action {
+                        // passwords typed into macros would be typed again on replay
+                        if !app.popup.as_ref().is_some_and(popup::Popup::is_secret) {
+                            app.macros.record(&action);
                         }
-                        (KeyModifiers::NONE, KeyCode::Char('k')) => {
-                            if app.chat_states.is_empty() { continue; } 
-                            let idx = cmp::max(app.dialog_idx.map(|i| i-1).unwrap_or(0), 0);
-                            app.dialog_idx = Some(idx);
-                            let chat = app.chat_states[idx].chat;
-                            api_job_tx.send(ApiJob::LoadMessages(chat)).unwrap();
-                        }
-                        _ => {}
+                        app.dispatch(action);
                     }
                 },
+                ScreenEvent::Paste(text) => app.dispatch(action::Action::Paste(text)),
+                ScreenEvent::FocusGained | ScreenEvent::FocusLost => {
+                    app.focused = matches!(e, ScreenEvent::FocusGained);
+                    app.update_presence();
+                }
                 ScreenEvent::Quit => app.quit = true,
                 _ => {}
             }
         }
 
             Some(api_event) = api_rx.recv() => {
-                match api_event {
-                    ApiEvent::LoadedDialog(dialog) => {
-                        let chat_state = ChatState::new(dialog);
-                        app.chat_states.push_back(chat_state);
-                    }
-                    ApiEvent::LoadedMessages(message) => {
-                        for v in app.chat_states.iter_mut() {
-                            if v.chat == message.chat().into() {
-                                v.messages.push_back(message);
-                                break;
-                            }
-                        }
-                    }
-                    ApiEvent::MessageNew(_message) => {
-                    }
-                    ApiEvent::MessageDeleted(_deleted) => {
-                    }
-                    ApiEvent::MessageEdited(_message) => {
-                    }
-                    ApiEvent::Error() => {
-                    }
-                }
+                app.handle_api_event(api_event);
+            }
+
+            Some(()) = config_rx.recv() => {
+                app.reload_config();
+            }
+        }
+
+        if app.status != last_status {
+            last_status = app.status.clone();
+            crash::log(last_status.as_deref().unwrap_or("(status cleared)"));
+        }
+        crash::set_state(app.summary());
+        app.metrics.events_queued = api_rx.len();
+
+        if let Some(mut command) = app.foreground.take() {
+            if let Err(e) = screen.run_foreground(&mut command) {
+                app.status = Some(format!("failed to play: {}", e));
             }
         }
 
-        screen.terminal.draw(|f| {
-            ui(f, &mut app);
-        })?;
+        match &mut plain {
+            Some(plain) => plain.render(&app)?,
+            None => {
+                let started = Instant::now();
+                screen.set_cursor_bar(matches!(app.mode, Mode::Insert | Mode::Command))?;
+                screen.terminal.draw(|f| {
+                    ui::ui(f, &mut app);
+                })?;
+                app.metrics.frame(started);
+            }
+        }
 
         if app.quit {
             break;
@@ -284,6 +280,20 @@ async fn run() -> Result<()> {
 
     screen.exit()?;
 
+    if let Err(e) = app.state().save() {
+        eprintln!("failed to save state: {}", e);
+    }
+    if let Err(e) = app.peers.save() {
+        eprintln!("failed to save the seen peers: {}", e);
+    }
+    if let Err(e) = app.usage.save() {
+        eprintln!("failed to save the chat usage: {}", e);
+    }
+    let _ = status::UnreadStatus::remove();
+
+    // downloads for the external player
+    let _ = std::fs::remove_dir_all(config::temp_dir());
+
     Ok(())
 }
 
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Frames whose times are kept
+const FRAMES_MAX: usize = 120;
+
+/// Latencies kept per kind of API job
+const LATENCIES_MAX: usize = 200;
+
+/// Jobs sent to the API worker which it hasn't taken yet.
+static JOBS_QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn job_sent() {
+    JOBS_QUEUED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn job_taken() {
+    JOBS_QUEUED.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn jobs_queued() -> usize {
+    JOBS_QUEUED.load(Ordering::Relaxed)
+}
+
+/// Timings of the jobs done lately, by kind.
+static JOB_TIMES: Mutex<BTreeMap<&'static str, JobTimes>> = Mutex::new(BTreeMap::new());
+
+/// How long the recent jobs of a kind took and whether they failed, oldest
+/// first.
+type JobTimes = VecDeque<(Duration, bool)>;
+
+/// An API job being timed, from when the worker takes it until every
+/// task doing it is done, that is when the last sender of its events is
+/// dropped.
+pub struct JobSpan {
+    job: &'static str,
+    started: Instant,
+    failed: AtomicBool,
+}
+
+impl JobSpan {
+    pub fn new(job: &'static str) -> JobSpan {
+        JobSpan {
+            job,
+            started: Instant::now(),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// The job reported an error.
+    pub fn fail(&self) {
+        self.failed.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for JobSpan {
+    fn drop(&mut self) {
+        let Ok(mut times) = JOB_TIMES.lock() else {
+            return;
+        };
+        let times = times.entry(self.job).or_default();
+        if times.len() == LATENCIES_MAX {
+            times.pop_front();
+        }
+        times.push_back((self.started.elapsed(), *self.failed.get_mut()));
+    }
+}
+
+/// Latency of a kind of API job over its recent runs, `LATENCIES_MAX` at
+/// most.
+#[derive(Debug, Clone)]
+pub struct JobStats {
+    pub job: &'static str,
+    pub runs: usize,
+    pub failed: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+}
+
+/// Latencies of the jobs done so far, slowest first.
+pub fn job_stats() -> Vec<JobStats> {
+    let times = JOB_TIMES.lock().unwrap();
+    let mut stats: Vec<_> = times
+        .iter()
+        .map(|(&job, times)| {
+            let mut latencies: Vec<_> = times.iter().map(|&(latency, _)| latency).collect();
+            latencies.sort();
+            let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+            JobStats {
+                job,
+                runs: latencies.len(),
+                failed: times.iter().filter(|&&(_, failed)| failed).count(),
+                p50: percentile(50),
+                p95: percentile(95),
+            }
+        })
+        .collect();
+    stats.sort_by_key(|stats| std::cmp::Reverse(stats.p95));
+    stats
+}
+
+/// How the event loop keeps up, shown in the debug overlay.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// When the recent frames were drawn and how long each took, oldest
+    /// first
+    frames: VecDeque<(Instant, Duration)>,
+    /// Events from the API worker waiting for the UI, as of the last frame
+    pub events_queued: usize,
+    /// Size of the media cache on disk, measured when the overlay opens
+    pub media_cache: Option<u64>,
+}
+
+impl Metrics {
+    pub fn frame(&mut self, started: Instant) {
+        if self.frames.len() == FRAMES_MAX {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((started, started.elapsed()));
+    }
+
+    /// Frames drawn in the last second.
+    pub fn fps(&self) -> usize {
+        self.frames.iter().filter(|(at, _)| at.elapsed() <= Duration::from_secs(1)).count()
+    }
+
+    /// Mean and longest time the recent frames took to draw.
+    pub fn frame_times(&self) -> (Duration, Duration) {
+        let total: Duration = self.frames.iter().map(|(_, took)| *took).sum();
+        let mean = total / self.frames.len().max(1) as u32;
+        let max = self.frames.iter().map(|(_, took)| *took).max().unwrap_or_default();
+        (mean, max)
+    }
+}
+
+/// Memory the process takes up, where the system tells.
+pub fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kb: u64 = kb.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kb * 1024)
+}
@@ -0,0 +1,35 @@
+use crate::config::Config;
+use crate::external;
+use crate::render;
+use grammers_client::types::Message;
+use std::io::{self, Write};
+
+/// Show a desktop notification for an incoming message. Whether it
+/// includes the sender and text depends on the `preview` options, it never
+/// does while text is redacted.
+pub fn message(config: &Config, message: &Message, redact: bool) -> io::Result<()> {
+    let chat = message.chat();
+    let (title, body) = if config.preview(chat.id()) && !redact {
+        let text = render::summary(message);
+        let body = match message.sender() {
+            Some(sender) if sender.id() != chat.id() => format!("{}: {}", sender.name(), text),
+            _ => text,
+        };
+        (chat.name().to_string(), body)
+    } else {
+        ("tg9".to_string(), format!("New message in {}", chat.name()))
+    };
+    external::spawn_with_args(&config.notifications.command, [title, body])
+}
+
+/// Play a notification sound with the configured command.
+pub fn sound(command: &str) -> io::Result<()> {
+    external::spawn_with_args(command, std::iter::empty::<&str>())
+}
+
+/// Ring the terminal bell.
+pub fn bell() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()
+}
@@ -0,0 +1,112 @@
+use crate::compose::{Attachment, PostOptions, ReplyTo};
+use crate::peers::{now, StoredChat};
+use crate::state::State;
+use crate::transfer::TransferId;
+use grammers_client::grammers_tl_types as tl;
+use grammers_session::PackedChat;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that makes up a message to send, kept to send it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutgoingMessage {
+    pub text: String,
+    pub link_preview: bool,
+    /// Formatting of the text, like mentions of users without a username.
+    /// Not saved, a message sent after a restart goes without.
+    #[serde(skip)]
+    pub entities: Vec<tl::enums::MessageEntity>,
+    pub attachments: Vec<Attachment>,
+    pub reply: Option<ReplyTo>,
+    /// Options of a channel post
+    #[serde(default)]
+    pub post: PostOptions,
+}
+
+/// Message that failed to send.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    #[serde(flatten)]
+    chat: StoredChat,
+    /// Why the last try failed
+    pub error: String,
+    /// Unix time it was queued
+    pub queued: u64,
+    /// Sent again on its own once the connection is back, otherwise only
+    /// when asked to
+    pub retry: bool,
+    /// The transfer sending it again right now
+    #[serde(skip)]
+    pub sending: Option<TransferId>,
+    pub message: OutgoingMessage,
+}
+
+impl QueuedMessage {
+    pub fn chat(&self) -> Option<PackedChat> {
+        self.chat.pack()
+    }
+}
+
+/// Messages that couldn't be sent for lack of a connection, shown as
+/// queued under their chat and sent again once it's back. Saved next to
+/// the UI state so they survive a restart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Outbox {
+    pub messages: Vec<QueuedMessage>,
+}
+
+impl Outbox {
+    fn path() -> Option<PathBuf> {
+        Some(State::path()?.with_file_name("outbox.toml"))
+    }
+
+    /// The saved outbox, or an empty one if it can't be read.
+    pub fn load() -> Outbox {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if self.messages.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text)
+    }
+
+    pub fn push(&mut self, chat: PackedChat, message: OutgoingMessage, error: String, retry: bool) {
+        self.messages.push(QueuedMessage {
+            chat: chat.into(),
+            error,
+            queued: now(),
+            retry,
+            sending: None,
+            message,
+        });
+    }
+
+    /// Indices of the messages queued for a chat, oldest first.
+    pub fn of_chat(&self, chat: PackedChat) -> Vec<usize> {
+        (0..self.messages.len())
+            .filter(|&i| self.messages[i].chat().is_some_and(|c| c.id == chat.id))
+            .collect()
+    }
+
+    /// The message being sent again by a transfer.
+    pub fn sending(&mut self, id: TransferId) -> Option<&mut QueuedMessage> {
+        self.messages.iter_mut().find(|m| m.sending == Some(id))
+    }
+}
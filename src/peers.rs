@@ -0,0 +1,153 @@
+use crate::state::State;
+use grammers_client::types::Chat;
+use grammers_session::{PackedChat, PackedType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most peers remembered, the ones seen longest ago are forgotten first
+const PEERS_MAX: usize = 20_000;
+
+/// Unix time now, in seconds.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// A `PackedChat` as it's saved to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredChat {
+    kind: String,
+    id: i64,
+    access_hash: Option<i64>,
+}
+
+impl StoredChat {
+    pub fn pack(&self) -> Option<PackedChat> {
+        let ty = match self.kind.as_str() {
+            "user" => PackedType::User,
+            "bot" => PackedType::Bot,
+            "chat" => PackedType::Chat,
+            "megagroup" => PackedType::Megagroup,
+            "broadcast" => PackedType::Broadcast,
+            "gigagroup" => PackedType::Gigagroup,
+            _ => return None,
+        };
+        Some(PackedChat {
+            ty,
+            id: self.id,
+            access_hash: self.access_hash,
+        })
+    }
+}
+
+impl From<PackedChat> for StoredChat {
+    fn from(chat: PackedChat) -> Self {
+        let kind = match chat.ty {
+            PackedType::User => "user",
+            PackedType::Bot => "bot",
+            PackedType::Chat => "chat",
+            PackedType::Megagroup => "megagroup",
+            PackedType::Broadcast => "broadcast",
+            PackedType::Gigagroup => "gigagroup",
+        };
+        StoredChat {
+            kind: kind.into(),
+            id: chat.id,
+            access_hash: chat.access_hash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SeenPeer {
+    #[serde(flatten)]
+    chat: StoredChat,
+    name: String,
+    /// Unix time it was last seen
+    seen: u64,
+}
+
+/// Every user, group and channel seen in dialogs, messages and member
+/// lists, with the access hash needed to reach it again. Telegram only
+/// sends those along with the peers, so a sender seen once in a group can
+/// otherwise only be reached by finding a message of theirs again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerStore {
+    /// By bare id
+    peers: HashMap<String, SeenPeer>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl PeerStore {
+    fn path() -> Option<PathBuf> {
+        Some(State::path()?.with_file_name("peers.toml"))
+    }
+
+    /// The saved peers, or none if they can't be read.
+    pub fn load() -> PeerStore {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Written only if a peer was seen since the last time.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if !self.changed {
+            return Ok(());
+        }
+        if self.peers.len() > PEERS_MAX {
+            let mut seen: Vec<_> = self.peers.values().map(|p| p.seen).collect();
+            seen.sort_unstable();
+            let oldest_kept = seen[seen.len() - PEERS_MAX];
+            self.peers.retain(|_, p| p.seen >= oldest_kept);
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    pub fn record(&mut self, chat: &Chat) {
+        self.record_packed(chat.pack(), chat.name());
+    }
+
+    pub fn record_packed(&mut self, chat: PackedChat, name: &str) {
+        // a min peer seen without its access hash doesn't replace a full one
+        let key = chat.id.to_string();
+        if chat.access_hash.is_none() && self.peers.get(&key).is_some_and(|p| p.chat.access_hash.is_some()) {
+            return;
+        }
+        self.peers.insert(
+            key,
+            SeenPeer {
+                chat: chat.into(),
+                name: name.to_string(),
+                seen: now(),
+            },
+        );
+        self.changed = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn get(&self, id: i64) -> Option<PackedChat> {
+        self.peers.get(&id.to_string())?.chat.pack()
+    }
+
+    pub fn name(&self, id: i64) -> Option<&str> {
+        Some(&self.peers.get(&id.to_string())?.name)
+    }
+}
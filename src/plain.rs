@@ -0,0 +1,289 @@
+use crate::app::{App, ChatState, Focus, Mode};
+use crate::popup::{Picker, Popup};
+use crate::render;
+use grammers_client::types::Message;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// Messages printed when a chat is opened
+const BACKLOG: usize = 10;
+
+/// Items of a popup printed when it opens, the rest are counted
+const POPUP_ITEMS_MAX: usize = 20;
+
+/// What a popup shows, as text.
+#[derive(PartialEq)]
+struct PopupView {
+    title: String,
+    items: Vec<String>,
+    /// The selected item, or what the popup is asking for
+    current: Option<String>,
+}
+
+impl PopupView {
+    /// With `redact`, what the panes hide is hidden here too.
+    fn new(popup: &Popup, redact: bool) -> PopupView {
+        let text = |title: &str, items: Vec<String>| PopupView {
+            title: title.to_string(),
+            items,
+            current: None,
+        };
+        let hide = |s: &str| if redact { render::redact_str(s) } else { s.to_string() };
+        match popup {
+            Popup::ShareContact(picker) => PopupView::picker(picker, false),
+            Popup::Comments(picker) => PopupView::picker(picker, redact),
+            Popup::AdminLog(picker) => PopupView::picker(picker, redact),
+            Popup::Mention(picker) => PopupView::picker(picker, false),
+            Popup::Emoji(picker) => PopupView::picker(picker, false),
+            Popup::Privacy(picker) => PopupView::picker(picker, false),
+            Popup::Password(picker) => PopupView::picker(picker, false),
+            Popup::Sessions(picker) => PopupView::picker(picker, redact),
+            Popup::JoinRequests(requests) => PopupView::picker(&requests.picker, redact),
+            Popup::Forward(forward) => PopupView::picker(&forward.picker, redact),
+            Popup::Switch(picker) => PopupView::picker(picker, redact),
+            Popup::Info(info) => text(
+                &info.title,
+                info.fields.iter().map(|(name, value)| format!("{}: {}", name, hide(value))).collect(),
+            ),
+            Popup::Help(pager) => text(
+                &pager.title,
+                pager.rows.iter().map(|(term, about)| format!("{}: {}", term, about)).collect(),
+            ),
+            Popup::Stats(stats) => {
+                let mut items = vec![format!("{} messages", stats.messages)];
+                items.extend(stats.senders.iter().map(|(name, n)| format!("{}: {}", hide(name), n)));
+                items.extend(stats.media.iter().map(|(kind, n)| format!("{}: {}", kind, n)));
+                text(&stats.title, items)
+            }
+            Popup::Confirm(confirm) => text(&format!("{} (y/n)", confirm.prompt), Vec::new()),
+            Popup::Passphrase(_) => text("Session passphrase:", Vec::new()),
+            Popup::PasswordForm(form) => text(&format!("{}:", form.field().unwrap_or_default().0), Vec::new()),
+            Popup::Quote(selection) => PopupView {
+                title: "Quote: h/l w/b 0/$ to move, o for the other end, Enter to reply".into(),
+                items: Vec::new(),
+                current: Some(format!("“{}”", hide(&selection.message.text()[selection.range()]))),
+            },
+        }
+    }
+
+    fn picker<T>(picker: &Picker<T>, redact: bool) -> PopupView {
+        let matches = picker.matches();
+        let title = if picker.loading {
+            format!("{} (loading…)", picker.title)
+        } else {
+            picker.title.clone()
+        };
+        let label = |i: usize| {
+            let label = &picker.items[i].0;
+            if redact {
+                render::redact_str(label)
+            } else {
+                label.clone()
+            }
+        };
+        PopupView {
+            title,
+            items: matches.iter().map(|&i| label(i)).collect(),
+            current: matches.get(picker.selected).map(|&i| label(i)),
+        }
+    }
+}
+
+/// Linear output for screen readers and braille displays, in place of the
+/// panes: nothing is redrawn, what changed since the last time is printed
+/// as new lines at the end.
+#[derive(Default)]
+pub struct Plain {
+    mode: Option<Mode>,
+    status: Option<String>,
+    /// Chat whose messages are being printed, by id
+    chat: Option<i64>,
+    /// Messages printed of that chat, by id
+    printed: HashSet<i32>,
+    /// Dialog announced while browsing the list, by id
+    dialog: Option<i64>,
+    selected: Option<i32>,
+    popup: Option<PopupView>,
+    /// Prompt and text of the line being typed, echoed as it changes
+    input: Option<(&'static str, String)>,
+    /// The typed line is printed without its line break yet
+    echo_open: bool,
+}
+
+impl Plain {
+    /// Print what changed since the last time.
+    pub fn render(&mut self, app: &App) -> io::Result<()> {
+        let mut lines = Vec::new();
+        if self.mode != Some(app.mode) {
+            match app.mode {
+                Mode::Normal if self.mode.is_some() => lines.push("-- NORMAL --".to_string()),
+                Mode::Visual => lines.push("-- VISUAL --".to_string()),
+                _ => {}
+            }
+            self.mode = Some(app.mode);
+        }
+        if app.status != self.status {
+            self.status = app.status.clone();
+            lines.extend(self.status.clone());
+        }
+        self.chat(app, &mut lines);
+        self.popup(app, &mut lines);
+
+        let mut out = io::stdout().lock();
+        if !lines.is_empty() && self.echo_open {
+            // announcements go on lines of their own, then the typed line
+            // is printed again
+            write!(out, "\r\n")?;
+            self.echo_open = false;
+            self.input = None;
+        }
+        for line in lines {
+            write!(out, "{}\r\n", line.replace('\n', "\r\n"))?;
+        }
+        self.echo(app, &mut out)?;
+        out.flush()
+    }
+
+    /// The current chat, its new messages and the selected one.
+    fn chat(&mut self, app: &App, lines: &mut Vec<String>) {
+        let Some(chat_state) = app.current_chat() else {
+            return;
+        };
+        let id = chat_state.dialog.chat().id();
+        if app.focus == Focus::Dialogs {
+            if self.dialog != Some(id) {
+                self.dialog = Some(id);
+                lines.push(dialog_line(chat_state, app.redact));
+            }
+            return;
+        }
+        self.dialog = None;
+        if self.chat != Some(id) {
+            self.chat = Some(id);
+            self.printed.clear();
+            self.selected = None;
+            lines.push(format!("── {} ──", chat_state.dialog.chat().name()));
+        }
+        // messages newer than those printed, and older ones until the
+        // backlog is full, also when the history loads after a message
+        // which came in before the chat was opened
+        let newest = self.printed.iter().max().copied();
+        let mut backlog = BACKLOG.saturating_sub(self.printed.len());
+        let mut new: Vec<_> = chat_state
+            .messages
+            .iter()
+            .filter(|m| chat_state.shown(m) && !self.printed.contains(&m.id()))
+            .filter(|m| {
+                if newest.is_some_and(|newest| m.id() > newest) {
+                    return true;
+                }
+                let fits = backlog > 0;
+                backlog = backlog.saturating_sub(1);
+                fits
+            })
+            .collect();
+        new.sort_by_key(|m| m.id());
+        self.printed.extend(new.iter().map(|m| m.id()));
+        lines.extend(new.iter().map(|m| plain_message(m, app.redact)));
+
+        let selected = chat_state.selected_message();
+        if selected.map(|m| m.id()) != self.selected {
+            self.selected = selected.map(|m| m.id());
+            if let Some(message) = selected {
+                let mut line = format!("▸ {}", plain_message(message, app.redact));
+                if app.mode == Mode::Visual {
+                    line.push_str(&format!(" ({} selected)", chat_state.visual_selection().len()));
+                }
+                lines.push(line);
+            }
+        }
+    }
+
+    fn popup(&mut self, app: &App, lines: &mut Vec<String>) {
+        let view = app.popup.as_ref().map(|popup| PopupView::new(popup, app.redact));
+        match (&view, &self.popup) {
+            (Some(view), Some(last)) if (&view.title, &view.items) == (&last.title, &last.items) => {
+                if view.current != last.current {
+                    lines.extend(view.current.clone());
+                }
+            }
+            (Some(view), _) => {
+                lines.push(view.title.clone());
+                for (i, item) in view.items.iter().take(POPUP_ITEMS_MAX).enumerate() {
+                    lines.push(format!("{}. {}", i + 1, item));
+                }
+                if view.items.len() > POPUP_ITEMS_MAX {
+                    lines.push(format!("and {} more", view.items.len() - POPUP_ITEMS_MAX));
+                }
+                lines.extend(view.current.clone());
+            }
+            (None, Some(_)) => lines.push("closed".into()),
+            (None, None) => {}
+        }
+        self.popup = view;
+    }
+
+    /// Echo the compose text or command line as it's typed.
+    fn echo(&mut self, app: &App, out: &mut impl Write) -> io::Result<()> {
+        let input = match app.mode {
+            _ if app.popup.is_some() => None,
+            Mode::Insert => app.current_chat().map(|c| ("> ", c.draft.input.text.clone())),
+            Mode::Command => Some((":", app.command.text.clone())),
+            Mode::Normal | Mode::Visual => None,
+        };
+        let same_prompt = matches!((&self.input, &input), (Some((a, _)), Some((b, _))) if a == b);
+        match (&self.input, &input) {
+            (Some(last), Some(input)) if last == input => {}
+            // typed or erased at the end
+            (Some((_, last)), Some((_, text))) if same_prompt && text.starts_with(last.as_str()) => {
+                // raw mode doesn't return the carriage on new lines
+                write!(out, "{}", text[last.len()..].replace('\n', "\r\n"))?;
+            }
+            (Some((_, last)), Some((_, text))) if same_prompt && last.starts_with(text.as_str()) => {
+                let erased = last[text.len()..].chars().count();
+                write!(out, "{}", "\x08 \x08".repeat(erased))?;
+            }
+            (last, Some((prompt, text))) => {
+                if last.is_some() {
+                    write!(out, "\r\n")?;
+                }
+                write!(out, "{}{}", prompt, text.replace('\n', "\r\n"))?;
+                self.echo_open = true;
+            }
+            (Some(_), None) => {
+                write!(out, "\r\n")?;
+                self.echo_open = false;
+            }
+            (None, None) => {}
+        }
+        self.input = input;
+        Ok(())
+    }
+}
+
+/// A message as a line, unreadable with `redact` like in the panes.
+fn plain_message(message: &Message, redact: bool) -> String {
+    let line = render::plain(message);
+    if redact {
+        render::redact_str(&line)
+    } else {
+        line
+    }
+}
+
+/// A dialog as announced while browsing the list, like
+/// `Friends, 3 unread: see you`.
+fn dialog_line(chat_state: &ChatState, redact: bool) -> String {
+    let mut line = chat_state.dialog.chat().name().to_string();
+    if chat_state.unread > 0 {
+        line.push_str(&format!(", {} unread", chat_state.unread));
+    } else if chat_state.marked_unread {
+        line.push_str(", marked unread");
+    }
+    if let Some(message) = &chat_state.dialog.last_message {
+        let summary = render::summary(message);
+        let summary = if redact { render::redact_str(&summary) } else { summary };
+        line.push_str(&format!(": {}", summary));
+    }
+    line
+}
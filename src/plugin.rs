@@ -0,0 +1,37 @@
+use crate::action::Action;
+use crate::api::ApiEvent;
+
+/// Extension compiled into tg9, adding `:` commands, a segment of the
+/// status bar or reactions to events from Telegram. Plugins change the app
+/// only through the actions they return, so those go through the same
+/// checks as keys and commands, like read-only mode.
+///
+/// To add one, implement this trait and list it in [`plugins`].
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    /// Names of the `:` commands it adds
+    fn commands(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Run one of its commands, returning the action to apply, if any.
+    fn run(&mut self, _command: &str, _args: &str) -> Result<Option<Action>, String> {
+        Ok(None)
+    }
+
+    /// Text shown in the status bar, if any
+    fn status(&self) -> Option<String> {
+        None
+    }
+
+    /// Called for every event of the API worker, before tg9 handles it.
+    fn on_event(&mut self, _event: &ApiEvent) -> Option<Action> {
+        None
+    }
+}
+
+/// Plugins loaded on startup.
+pub fn plugins() -> Vec<Box<dyn Plugin>> {
+    Vec::new()
+}
@@ -0,0 +1,436 @@
+use crate::action::Action;
+use crate::api::{
+    AdminLogEntry, JoinRequest, Member, PasswordEdit, PasswordOption, PrivacySetting, SessionItem, SharedContact,
+    ThreadMessage,
+};
+use crate::compose::{Input, Quote, ReplyTo};
+use crate::stats::Stats;
+use grammers_client::types::Message;
+use grammers_session::PackedChat;
+use std::cmp;
+use std::ops::Range;
+
+/// Filterable list the user picks one item from.
+pub struct Picker<T> {
+    pub title: String,
+    /// Items with the labels they are displayed and filtered by
+    pub items: Vec<(String, T)>,
+    pub filter: Input,
+    /// Index into `matches()`
+    pub selected: usize,
+    /// Items are still being fetched
+    pub loading: bool,
+    /// The filter also matches its letters in order with others between
+    /// them, see `fuzzy_rank`
+    pub fuzzy: bool,
+}
+
+impl<T> Picker<T> {
+    pub fn new(title: impl Into<String>, items: Vec<(String, T)>) -> Self {
+        Picker {
+            title: title.into(),
+            items,
+            filter: Input::default(),
+            selected: 0,
+            loading: false,
+            fuzzy: false,
+        }
+    }
+
+    /// Picker with fuzzy matching, whose items come in the order of their
+    /// ranks, then their own.
+    pub fn fuzzy(title: impl Into<String>, items: Vec<(String, T)>) -> Self {
+        Picker {
+            fuzzy: true,
+            ..Picker::new(title, items)
+        }
+    }
+
+    /// Picker waiting for its items to arrive from the API.
+    pub fn loading(title: impl Into<String>) -> Self {
+        Picker {
+            loading: true,
+            ..Picker::new(title, Vec::new())
+        }
+    }
+
+    pub fn set_items(&mut self, items: Vec<(String, T)>) {
+        self.items = items;
+        self.loading = false;
+        self.selected = 0;
+    }
+
+    /// Indices of items whose label contains the filter, ignoring case.
+    pub fn matches(&self) -> Vec<usize> {
+        let filter = self.filter.text.to_lowercase();
+        if !self.fuzzy {
+            return self
+                .items
+                .iter()
+                .enumerate()
+                .filter(|(_, (label, _))| label.to_lowercase().contains(&filter))
+                .map(|(i, _)| i)
+                .collect();
+        }
+        let mut ranked: Vec<_> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (label, _))| Some((i, fuzzy_rank(&label.to_lowercase(), &filter)?)))
+            .collect();
+        // stable, so items keep their order within a rank
+        ranked.sort_by_key(|&(_, rank)| rank);
+        ranked.into_iter().map(|(i, _)| i).collect()
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        let len = self.matches().len();
+        if self.selected + 1 < len {
+            self.selected += 1;
+        }
+    }
+
+
+    pub fn selected_item(&self) -> Option<&T> {
+        let idx = *self.matches().get(self.selected)?;
+        self.items.get(idx).map(|(_, item)| item)
+    }
+}
+
+/// How closely a label matches a filter, lower is closer: starting with
+/// it, a word starting with it, containing it, or containing its letters
+/// in order. `None` if it doesn't match at all.
+fn fuzzy_rank(label: &str, filter: &str) -> Option<u8> {
+    if label.starts_with(filter) {
+        return Some(0);
+    }
+    if label.split_whitespace().any(|word| word.starts_with(filter)) {
+        return Some(1);
+    }
+    if label.contains(filter) {
+        return Some(2);
+    }
+    let mut letters = label.chars();
+    filter.chars().all(|c| letters.any(|l| l == c)).then_some(3)
+}
+
+/// Read-only list of details, like the raw fields of a message.
+pub struct Info {
+    pub title: String,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Text to scroll through, like the pages of `:help`.
+pub struct Pager {
+    pub title: String,
+    /// A term and what it means, one row each
+    pub rows: Vec<(String, String)>,
+    /// Rows scrolled past
+    pub scroll: usize,
+}
+
+impl Pager {
+    pub fn scroll_by(&mut self, lines: i32) {
+        let scroll = (self.scroll as i64).saturating_add(lines as i64);
+        self.scroll = scroll.clamp(0, self.rows.len().saturating_sub(1) as i64) as usize;
+    }
+}
+
+/// Messages to forward, with the chat to forward them to being picked.
+pub struct Forward {
+    pub from: PackedChat,
+    pub ids: Vec<i32>,
+    pub picker: Picker<PackedChat>,
+}
+
+/// Pending requests to join a chat I administer, to approve or decline.
+pub struct JoinRequests {
+    pub chat: PackedChat,
+    pub picker: Picker<JoinRequest>,
+}
+
+/// Movement of the cursor when selecting a quote.
+#[derive(Debug, Clone, Copy)]
+pub enum Motion {
+    Left,
+    Right,
+    /// To the start of the previous/next word
+    WordLeft,
+    WordRight,
+    /// To the first/last character of the line
+    LineStart,
+    LineEnd,
+    /// Move the other end of the selection instead
+    SwapEnds,
+}
+
+/// Part of a message's text being selected to quote in a reply: the
+/// characters from `anchor` to `cursor`, both included.
+pub struct QuoteSelection {
+    pub message: Message,
+    pub anchor: usize,
+    pub cursor: usize,
+}
+
+impl QuoteSelection {
+    /// All of the text selected, with the cursor at its end. `None` if
+    /// the message has no text.
+    pub fn new(message: Message) -> Option<Self> {
+        let last = message.text().chars().count().checked_sub(1)?;
+        Some(QuoteSelection {
+            message,
+            anchor: 0,
+            cursor: last,
+        })
+    }
+
+    pub fn motion(&mut self, motion: Motion) {
+        let chars: Vec<char> = self.message.text().chars().collect();
+        let last = chars.len().saturating_sub(1);
+        let mut i = self.cursor;
+        match motion {
+            Motion::Left => i = i.saturating_sub(1),
+            Motion::Right => i = cmp::min(i + 1, last),
+            Motion::WordLeft => {
+                while i > 0 && chars[i - 1].is_whitespace() {
+                    i -= 1;
+                }
+                while i > 0 && !chars[i - 1].is_whitespace() {
+                    i -= 1;
+                }
+            }
+            Motion::WordRight => {
+                while i < last && !chars[i].is_whitespace() {
+                    i += 1;
+                }
+                while i < last && chars[i].is_whitespace() {
+                    i += 1;
+                }
+            }
+            Motion::LineStart => {
+                while i > 0 && chars[i - 1] != '\n' {
+                    i -= 1;
+                }
+            }
+            Motion::LineEnd => {
+                while i < last && chars[i + 1] != '\n' {
+                    i += 1;
+                }
+            }
+            Motion::SwapEnds => std::mem::swap(&mut self.anchor, &mut i),
+        }
+        self.cursor = i;
+    }
+
+    /// Byte range of the selected text.
+    pub fn range(&self) -> Range<usize> {
+        let text = self.message.text();
+        let byte = |i: usize| text.char_indices().nth(i).map_or(text.len(), |(b, _)| b);
+        byte(cmp::min(self.anchor, self.cursor))..byte(cmp::max(self.anchor, self.cursor) + 1)
+    }
+
+    pub fn reply(&self) -> ReplyTo {
+        let entities = self.message.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+        ReplyTo {
+            message_id: self.message.id(),
+            quote: Some(Quote::new(self.message.text(), entities, self.range())),
+        }
+    }
+}
+
+/// Question to answer before doing something that's hard to undo.
+pub struct Confirm {
+    pub prompt: String,
+    /// Done if the answer is yes
+    pub action: Action,
+}
+
+/// Answers for a change of the 2FA settings, asked one at a time with
+/// the passwords masked.
+pub struct PasswordForm {
+    pub option: PasswordOption,
+    /// Prompt of each field, and whether what's typed is masked
+    pub fields: Vec<(&'static str, bool)>,
+    pub answers: Vec<String>,
+    pub input: Input,
+}
+
+impl PasswordForm {
+    pub fn new(option: PasswordOption) -> Self {
+        let fields = match option {
+            PasswordOption::Enable => vec![
+                ("New password", true),
+                ("Repeat the password", true),
+                ("Hint (optional)", false),
+                ("Recovery email (optional)", false),
+            ],
+            PasswordOption::Change => vec![
+                ("Current password", true),
+                ("New password", true),
+                ("Repeat the password", true),
+                ("Hint (optional)", false),
+            ],
+            PasswordOption::Disable => vec![("Current password", true)],
+            PasswordOption::Email => vec![("Current password", true), ("Recovery email", false)],
+            PasswordOption::ConfirmEmail => vec![("Code from the email", false)],
+            PasswordOption::ResendEmail | PasswordOption::CancelEmail => Vec::new(),
+        };
+        PasswordForm {
+            option,
+            fields,
+            answers: Vec::new(),
+            input: Input::default(),
+        }
+    }
+
+    /// The field being typed, none once all are answered.
+    pub fn field(&self) -> Option<(&'static str, bool)> {
+        self.fields.get(self.answers.len()).copied()
+    }
+
+    /// The change the answers ask for.
+    pub fn edit(&self) -> Result<PasswordEdit, String> {
+        let answer = |i: usize| self.answers.get(i).cloned().unwrap_or_default();
+        let optional = |i: usize| Some(answer(i)).filter(|a| !a.is_empty());
+        let new_password = |new: usize| {
+            if answer(new).is_empty() {
+                Err("the password can't be empty".to_string())
+            } else if answer(new) != answer(new + 1) {
+                Err("the passwords don't match".to_string())
+            } else {
+                Ok(answer(new))
+            }
+        };
+        match self.option {
+            PasswordOption::Enable => Ok(PasswordEdit::Set {
+                current: None,
+                new: new_password(0)?,
+                hint: answer(2),
+                email: optional(3),
+            }),
+            PasswordOption::Change => Ok(PasswordEdit::Set {
+                current: Some(answer(0)),
+                new: new_password(1)?,
+                hint: answer(3),
+                email: None,
+            }),
+            PasswordOption::Disable => Ok(PasswordEdit::Disable { current: answer(0) }),
+            PasswordOption::Email => match optional(1) {
+                Some(email) => Ok(PasswordEdit::Email { current: answer(0), email }),
+                None => Err("the email can't be empty".into()),
+            },
+            PasswordOption::ConfirmEmail => Ok(PasswordEdit::ConfirmEmail { code: answer(0).trim().to_string() }),
+            PasswordOption::ResendEmail => Ok(PasswordEdit::ResendEmail),
+            PasswordOption::CancelEmail => Ok(PasswordEdit::CancelEmail),
+        }
+    }
+}
+
+/// Overlay drawn on top of the chat view which receives all keys while open.
+pub enum Popup {
+    /// Pick one of my contacts to share into the current chat
+    ShareContact(Picker<SharedContact>),
+    /// Comments on a channel post, read only
+    Comments(Picker<ThreadMessage>),
+    /// Recent admin log of a channel, read only
+    AdminLog(Picker<AdminLogEntry>),
+    /// Complete a mention being typed in the compose box
+    Mention(Picker<Member>),
+    /// Insert an emoji into the compose box
+    Emoji(Picker<String>),
+    /// My privacy settings, selecting one changes it to the next value
+    Privacy(Picker<PrivacySetting>),
+    /// My 2FA settings, what can be changed about them
+    Password(Picker<PasswordOption>),
+    PasswordForm(PasswordForm),
+    /// My active sessions and their policy
+    Sessions(Picker<SessionItem>),
+    JoinRequests(JoinRequests),
+    /// Pick the chat to forward the messages selected in visual mode to
+    Forward(Forward),
+    /// Pick a chat to open, the most used first
+    Switch(Picker<PackedChat>),
+    /// Select the part of a message to quote in a reply
+    Quote(QuoteSelection),
+    Confirm(Confirm),
+    Info(Info),
+    Help(Pager),
+    Stats(Stats),
+    /// Passphrase of the encrypted session, typed on startup
+    Passphrase(Input),
+}
+
+impl Popup {
+    /// Whether what's typed into it is a secret, a passphrase or password.
+    pub fn is_secret(&self) -> bool {
+        matches!(self, Popup::Passphrase(_) | Popup::PasswordForm(_))
+    }
+
+    /// Which popup it is, for crash reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Popup::ShareContact(_) => "share contact",
+            Popup::Comments(_) => "comments",
+            Popup::AdminLog(_) => "admin log",
+            Popup::Mention(_) => "mention",
+            Popup::Emoji(_) => "emoji",
+            Popup::Privacy(_) => "privacy",
+            Popup::Password(_) => "password",
+            Popup::PasswordForm(_) => "password form",
+            Popup::Sessions(_) => "sessions",
+            Popup::JoinRequests(_) => "join requests",
+            Popup::Forward(_) => "forward",
+            Popup::Switch(_) => "switch",
+            Popup::Quote(_) => "quote",
+            Popup::Confirm(_) => "confirm",
+            Popup::Info(_) => "info",
+            Popup::Help(_) => "help",
+            Popup::Stats(_) => "stats",
+            Popup::Passphrase(_) => "passphrase",
+        }
+    }
+
+    pub fn filter_mut(&mut self) -> Option<&mut Input> {
+        match self {
+            Popup::ShareContact(picker) => Some(&mut picker.filter),
+            Popup::Comments(picker) => Some(&mut picker.filter),
+            Popup::AdminLog(picker) => Some(&mut picker.filter),
+            Popup::Mention(picker) => Some(&mut picker.filter),
+            Popup::Emoji(picker) => Some(&mut picker.filter),
+            Popup::Forward(forward) => Some(&mut forward.picker.filter),
+            Popup::Switch(picker) => Some(&mut picker.filter),
+            Popup::Privacy(_) | Popup::Password(_) | Popup::Sessions(_) | Popup::JoinRequests(_) => None,
+            Popup::Passphrase(input) => Some(input),
+            Popup::PasswordForm(form) => Some(&mut form.input),
+            Popup::Confirm(_) | Popup::Info(_) | Popup::Help(_) | Popup::Stats(_) | Popup::Quote(_) => None,
+        }
+    }
+
+    /// Keep the selection within the matches after the filter changed.
+    pub fn filter_changed(&mut self) {
+        match self {
+            Popup::ShareContact(picker) => picker.selected = 0,
+            Popup::Comments(picker) => picker.selected = 0,
+            Popup::AdminLog(picker) => picker.selected = 0,
+            Popup::Mention(picker) => picker.selected = 0,
+            Popup::Emoji(picker) => picker.selected = 0,
+            Popup::Privacy(picker) => picker.selected = 0,
+            Popup::Password(picker) => picker.selected = 0,
+            Popup::Sessions(picker) => picker.selected = 0,
+            Popup::JoinRequests(requests) => requests.picker.selected = 0,
+            Popup::Forward(forward) => forward.picker.selected = 0,
+            Popup::Switch(picker) => picker.selected = 0,
+            Popup::Confirm(_)
+            | Popup::Info(_)
+            | Popup::Help(_)
+            | Popup::Stats(_)
+            | Popup::Passphrase(_)
+            | Popup::PasswordForm(_)
+            | Popup::Quote(_) => {}
+        }
+    }
+}
@@ -0,0 +1,770 @@
+use crate::api::{
+    JoinRequest, PasswordOption, PasswordState, PrivacySetting, Profile, SessionItem, ThreadMessage, Views,
+};
+use crate::diff;
+use crate::highlight;
+use crate::peers::PeerStore;
+use crate::video_note;
+use chrono::{DateTime, Local, Utc};
+use grammers_client::grammers_tl_types as tl;
+use grammers_client::types::media::{Geo, WebPage};
+use grammers_client::types::{Media, Message};
+use ratatui::prelude::*;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Longest description of a link preview shown before it's cut off
+const PREVIEW_DESCRIPTION_MAX: usize = 200;
+
+/// Longest quote of a message replied to shown before it's cut off
+const REPLY_PREVIEW_MAX: usize = 60;
+
+/// Render a message into lines for the chat view. Always returns at least
+/// one line, so every message takes up a row even if its content can't be
+/// shown.
+pub fn message(message: &Message) -> Text<'static> {
+    if let Some(action) = message.action() {
+        return Text::from(Line::from(Span::styled(
+            service_action(action),
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        )));
+    }
+
+    let media = message.media();
+    let mut text = Text::default();
+    if let Some(descriptor) = media.as_ref().and_then(media_descriptor) {
+        text.extend([Line::from(Span::styled(
+            descriptor,
+            Style::default().fg(Color::Cyan),
+        ))]);
+    }
+    text.extend(body(message));
+
+    match media {
+        Some(Media::WebPage(page)) => text.extend(web_page(&page)),
+        Some(Media::Geo(geo)) => text.extend(location("Location", Some(&geo), None)),
+        Some(Media::GeoLive(live)) => {
+            text.extend(location("Live location", live.geo.as_ref(), None))
+        }
+        Some(Media::Venue(venue)) => text.extend(location(
+            venue.title(),
+            venue.geo.as_ref(),
+            Some(venue.address()),
+        )),
+        Some(Media::Contact(contact)) => {
+            let name = format!("{} {}", contact.first_name(), contact.last_name());
+            text.extend([
+                Line::from(Span::styled(
+                    format!("👤 {}", name.trim()),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Line::from(contact.phone_number().to_string()),
+            ]);
+        }
+        _ => {}
+    }
+
+    if let Some(comments) = comments(message) {
+        let label = match comments {
+            1 => "💬 1 comment".to_string(),
+            n => format!("💬 {} comments", n),
+        };
+        text.extend([Line::from(Span::styled(label, Style::default().fg(Color::Blue)))]);
+    } else if let Some(replies) = replies(message).filter(|&n| n > 0) {
+        let label = match replies {
+            1 => "↩ 1 reply".to_string(),
+            n => format!("↩ {} replies", n),
+        };
+        text.extend([Line::from(Span::styled(label, Style::default().fg(Color::Blue)))]);
+    }
+
+    if text.lines.is_empty() {
+        // e.g. giveaways and stories shared to chat, which aren't exposed as media
+        text.extend([Line::from(Span::styled(
+            "[unsupported message, open in the official app]",
+            Style::default().fg(Color::DarkGray),
+        ))]);
+    }
+    text
+}
+
+/// The text of a message, with its code blocks highlighted and set off by
+/// a bar, and diffs colorized.
+fn body(message: &Message) -> Text<'static> {
+    let text = message.text();
+    let entities = message.fmt_entities().map(Vec::as_slice).unwrap_or_default();
+    if diff::looks_like_diff(text) && !entities.iter().any(|e| matches!(e, tl::enums::MessageEntity::Pre(_))) {
+        return Text::from(diff::colorize(text));
+    }
+    let bar = Style::default().fg(Color::DarkGray);
+    let mut body = Text::default();
+    let mut end = 0;
+    for block in highlight::code_blocks(text, entities) {
+        if block.range.start < end {
+            continue;
+        }
+        // the line breaks around a block are the block's own lines
+        let before = text[end..block.range.start].strip_suffix('\n');
+        body.extend(Text::from(before.unwrap_or(&text[end..block.range.start]).to_string()));
+        if !block.language.is_empty() {
+            let label = Span::styled(format!("▏{}", block.language), bar.add_modifier(Modifier::ITALIC));
+            body.extend([Line::from(label)]);
+        }
+        let code = &text[block.range.clone()];
+        let code = match block.language.as_str() {
+            "diff" | "patch" => diff::colorize(code),
+            "" if diff::looks_like_diff(code) => diff::colorize(code),
+            language => highlight::highlight(code, language),
+        };
+        body.extend(code.into_iter().map(|mut line| {
+            line.spans.insert(0, Span::styled("▏", bar));
+            line
+        }));
+        end = block.range.end;
+        if text[end..].starts_with('\n') {
+            end += 1;
+        }
+    }
+    body.extend(Text::from(text[end..].to_string()));
+    body
+}
+
+/// Header of a forwarded message, like `↪ Forwarded from Alice · 2024-01-05 10:12`.
+pub fn forward_header(message: &Message, peers: &PeerStore) -> Option<Line<'static>> {
+    let tl::enums::MessageFwdHeader::Header(header) = message.forward_header()?;
+    let from = match (&header.from_id, &header.from_name) {
+        (Some(peer), _) => {
+            let id = crate::api::peer_id(peer);
+            peers.name(id).map_or_else(|| format!("#{}", id), String::from)
+        }
+        (None, Some(name)) => name.clone(),
+        (None, None) => "a hidden account".into(),
+    };
+    let from = match &header.post_author {
+        Some(author) => format!("{} ({})", from, author),
+        None => from,
+    };
+    let date = DateTime::from_timestamp(header.date.into(), 0).unwrap_or_default();
+    Some(Line::from(Span::styled(
+        format!("↪ Forwarded from {} · {}", from, time(date)),
+        Style::default().fg(Color::Green).add_modifier(Modifier::ITALIC),
+    )))
+}
+
+/// Quote of the message replied to above a reply, like `▎ Alice: see you`.
+pub fn reply_preview(replied: Option<Option<&Message>>) -> Line<'static> {
+    let quote = match replied {
+        Some(Some(message)) => {
+            let summary: String = summary(message).chars().take(REPLY_PREVIEW_MAX).collect();
+            match message.sender() {
+                Some(sender) => format!("▎ {}: {}", sender.name(), summary),
+                None => format!("▎ {}", summary),
+            }
+        }
+        Some(None) => "▎ deleted message".into(),
+        None => "▎ …".into(),
+    };
+    Line::from(Span::styled(quote, Style::default().fg(Color::DarkGray)))
+}
+
+/// Number of comments on a channel post which has a discussion group.
+pub fn comments(message: &Message) -> Option<i32> {
+    match message.replies()? {
+        tl::enums::MessageReplies::Replies(replies) if replies.comments => Some(replies.replies),
+        _ => None,
+    }
+}
+
+/// View and forward counters under a channel post, like `👁 1.2K  ↪ 15`.
+pub fn views(views: Views) -> Option<Line<'static>> {
+    let mut counters = Vec::new();
+    if let Some(views) = views.views {
+        counters.push(format!("👁 {}", human_count(views)));
+    }
+    if let Some(forwards) = views.forwards.filter(|&n| n > 0) {
+        counters.push(format!("↪ {}", human_count(forwards)));
+    }
+    if counters.is_empty() {
+        return None;
+    }
+    Some(Line::from(Span::styled(counters.join("  "), Style::default().fg(Color::DarkGray))))
+}
+
+/// Number of replies in the thread started by a message, including
+/// comments on channel posts.
+pub fn replies(message: &Message) -> Option<i32> {
+    let tl::enums::MessageReplies::Replies(replies) = message.replies()?;
+    Some(replies.replies)
+}
+
+/// Most replies of a thread shown expanded under its message
+const THREAD_INLINE_MAX: usize = 10;
+
+/// Replies of a thread expanded under its message, the newest ones if
+/// there are many. `None` while they're loading.
+pub fn thread(replies: Option<&[ThreadMessage]>) -> Text<'static> {
+    let style = Style::default().fg(Color::DarkGray);
+    let Some(replies) = replies else {
+        return Text::from(Line::from(Span::styled("│ loading replies…", style)));
+    };
+    if replies.is_empty() {
+        return Text::from(Line::from(Span::styled("│ no replies", style)));
+    }
+    let mut lines = Vec::new();
+    let hidden = replies.len().saturating_sub(THREAD_INLINE_MAX);
+    if hidden > 0 {
+        lines.push(Line::from(Span::styled(format!("│ … {} earlier, press c to see all", hidden), style)));
+    }
+    for reply in &replies[hidden..] {
+        lines.push(Line::from(vec![
+            Span::styled("│ ", style),
+            Span::styled(format!("{}: ", reply.sender), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(reply.text.replace('\n', " ")),
+        ]));
+    }
+    Text::from(lines)
+}
+
+/// When a message was last edited, shown under the selected message.
+pub fn edited(date: DateTime<Utc>) -> Line<'static> {
+    Line::from(Span::styled(
+        format!("✎ edited {}", time(date)),
+        Style::default().fg(Color::DarkGray),
+    ))
+}
+
+/// Local time of day, with the date unless it's today.
+pub fn time(date: DateTime<Utc>) -> String {
+    let date = date.with_timezone(&Local);
+    if date.date_naive() == Local::now().date_naive() {
+        date.format("%H:%M").to_string()
+    } else {
+        date.format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+/// One-line description of an admin log entry.
+pub fn admin_log_action(action: &tl::enums::ChannelAdminLogEventAction) -> String {
+    use tl::enums::ChannelAdminLogEventAction as A;
+    let text = |message: &tl::enums::Message| match message {
+        tl::enums::Message::Message(message) => truncate(&message.message.replace('\n', " "), 60),
+        _ => String::new(),
+    };
+    match action {
+        A::EditMessage(edit) => format!(
+            "edited \"{}\" to \"{}\"",
+            text(&edit.prev_message),
+            text(&edit.new_message)
+        ),
+        A::DeleteMessage(delete) => format!("deleted \"{}\"", text(&delete.message)),
+        A::UpdatePinned(pin) => format!("pinned \"{}\"", text(&pin.message)),
+        A::ChangeTitle(title) => format!("changed the title to \"{}\"", title.new_value),
+        A::ParticipantJoin => "joined".into(),
+        A::ParticipantLeave => "left".into(),
+        A::ParticipantInvite(_) => "invited a member".into(),
+        A::ParticipantToggleBan(_) => "changed the restrictions of a member".into(),
+        _ => "[other action]".into(),
+    }
+}
+
+/// Text with every visible character replaced, keeping its layout and
+/// styles so the chat looks the same apart from being unreadable.
+pub fn redact(text: Text<'static>) -> Text<'static> {
+    Text::from(
+        text.lines
+            .into_iter()
+            .map(|line| {
+                Line::from(
+                    line.spans
+                        .into_iter()
+                        .map(|span| Span::styled(redact_str(&span.content), span.style))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+pub fn redact_str(s: &str) -> String {
+    s.chars().map(|c| if c.is_whitespace() { c } else { '░' }).collect()
+}
+
+/// Raw details of a message for the info popup, as name and value pairs.
+pub fn message_info(message: &Message, peers: &PeerStore) -> Vec<(&'static str, String)> {
+    let mut info = vec![
+        ("id", message.id().to_string()),
+        ("chat id", message.chat().id().to_string()),
+        ("date", message.date().to_rfc3339()),
+    ];
+    if let Some(sender) = message.sender() {
+        info.push(("sender", format!("{} ({})", sender.name(), sender.id())));
+    }
+    if let Some(author) = message.post_author() {
+        info.push(("post author", author.to_string()));
+    }
+    if let Some(tl::enums::MessageFwdHeader::Header(header)) = message.forward_header() {
+        let from = match (&header.from_id, &header.from_name) {
+            (Some(peer), _) => {
+                let id = crate::api::peer_id(peer);
+                match peers.name(id) {
+                    Some(name) => format!("{} ({})", name, id),
+                    None => id.to_string(),
+                }
+            }
+            (None, Some(name)) => name.clone(),
+            (None, None) => "hidden".into(),
+        };
+        let date = DateTime::from_timestamp(header.date.into(), 0).unwrap_or_default();
+        info.push(("forwarded from", format!("{} at {}", from, date.to_rfc3339())));
+        if let Some(post) = header.channel_post {
+            info.push(("original post id", post.to_string()));
+        }
+    }
+    if let Some(reply_to) = message.reply_to_message_id() {
+        info.push(("reply to", reply_to.to_string()));
+    }
+    if let Some(bot) = message.via_bot_id() {
+        info.push(("via bot", bot.to_string()));
+    }
+    if let Some(views) = message.view_count() {
+        info.push(("views", views.to_string()));
+    }
+    if let Some(forwards) = message.forward_count() {
+        info.push(("forwards", forwards.to_string()));
+    }
+    if let Some(edited) = message.edit_date() {
+        info.push(("edited", edited.to_rfc3339()));
+    }
+    if let Some(grouped_id) = message.grouped_id() {
+        info.push(("album id", grouped_id.to_string()));
+    }
+    if let Some(ttl) = message.ttl_period() {
+        info.push(("deleted after", auto_delete(ttl)));
+    }
+    match message.media() {
+        Some(Media::Photo(photo)) => {
+            info.push(("media", "photo".into()));
+            info.push(("photo id", photo.id().to_string()));
+            info.push(("size", human_size(photo.size() as u64)));
+        }
+        Some(Media::Document(document)) => {
+            let kind = if video_note::is_video_note(&document) { "video note" } else { "document" };
+            info.push(("media", kind.into()));
+            info.push(("document id", document.id().to_string()));
+            info.push(("name", document.name().to_string()));
+            if let Some(mime_type) = document.mime_type() {
+                info.push(("mime type", mime_type.to_string()));
+            }
+            info.push(("size", human_size(document.size() as u64)));
+            if let Some(duration) = document.duration() {
+                info.push(("duration", format!("{:.1}s", duration)));
+            }
+            if let Some((width, height)) = document.resolution() {
+                info.push(("resolution", format!("{}x{}", width, height)));
+            }
+        }
+        Some(media) => {
+            if let Some(descriptor) = media_descriptor(&media) {
+                info.push(("media", descriptor));
+            }
+        }
+        None => {}
+    }
+    info
+}
+
+/// Translation shown under the original message.
+pub fn translation(text: &str) -> Text<'static> {
+    let style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC);
+    Text::from(
+        text.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let prefix = if i == 0 { "🌐 " } else { "   " };
+                Line::from(Span::styled(format!("{}{}", prefix, line), style))
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Plain one-line summary of a message, for places without room for
+/// [`message`], like notifications.
+pub fn summary(message: &Message) -> String {
+    if let Some(action) = message.action() {
+        return service_action(action);
+    }
+    let descriptor = message.media().as_ref().and_then(media_descriptor);
+    let text = message.text().lines().next().unwrap_or_default();
+    match (descriptor, text) {
+        (Some(descriptor), "") => descriptor,
+        (Some(descriptor), text) => format!("{} {}", descriptor, text),
+        (None, "") => "[message]".into(),
+        (None, text) => text.to_string(),
+    }
+}
+
+/// A message as plain text for copying, like `[18:04] Ann: hi`, with all
+/// of its lines.
+pub fn plain(message: &Message) -> String {
+    format!("[{}] {}: {}", time(message.date()), sender_name(message), plain_body(message))
+}
+
+fn sender_name(message: &Message) -> String {
+    message.sender().unwrap_or_else(|| message.chat()).name().to_string()
+}
+
+/// Text of a message, after what kind of media it has if any.
+fn plain_body(message: &Message) -> String {
+    match (message.media().as_ref().and_then(media_descriptor), message.text()) {
+        _ if message.action().is_some() => summary(message),
+        (Some(descriptor), "") => descriptor,
+        (Some(descriptor), text) => format!("{} {}", descriptor, text),
+        (None, text) => text.to_string(),
+    }
+}
+
+/// How messages are written out to quote them elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Plain,
+    Markdown,
+}
+
+/// Messages, oldest first, to quote elsewhere: a line like
+/// `[2024-01-02 18:04] Ann: hi` each in plain text, or a blockquote under
+/// the sender in Markdown. Dates are always written in full.
+pub fn transcript(messages: &[&Message], format: TextFormat) -> String {
+    let mut text = String::new();
+    for message in messages {
+        let date = message.date().with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        let body = plain_body(message);
+        match format {
+            TextFormat::Plain => text.push_str(&format!("[{}] {}: {}\n", date, sender_name(message), body)),
+            TextFormat::Markdown => {
+                text.push_str(&format!("**{}** · {}\n", sender_name(message), date));
+                for line in body.lines() {
+                    text.push_str(format!("> {}", line).trim_end());
+                    text.push('\n');
+                }
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+/// Fields of my profile, with how to edit them.
+pub fn profile(profile: &Profile) -> Vec<(&'static str, String)> {
+    let name = match &profile.last_name {
+        Some(last) if !last.is_empty() => format!("{} {}", profile.first_name, last),
+        _ => profile.first_name.clone(),
+    };
+    let none = || "none".to_string();
+    vec![
+        ("Name", name),
+        ("Username", profile.username.as_ref().map_or_else(none, |u| format!("@{}", u))),
+        ("Bio", profile.bio.clone().unwrap_or_else(none)),
+        ("Phone", profile.phone.as_ref().map_or_else(none, |p| format!("+{}", p))),
+    ]
+}
+
+/// A privacy setting as a line of `:privacy`, like `Calls: my contacts (+2)`.
+pub fn privacy_setting(setting: &PrivacySetting) -> String {
+    let line = format!("{}: {}", setting.key.label(), setting.value.label());
+    if setting.exceptions > 0 {
+        format!("{} (+{} exceptions)", line, setting.exceptions)
+    } else {
+        line
+    }
+}
+
+/// My 2FA settings as the title of `:password`.
+pub fn password_state(state: &PasswordState) -> String {
+    if !state.has_password {
+        return "Two-step verification: off".into();
+    }
+    let mut title = "Two-step verification: on".to_string();
+    if let Some(hint) = &state.hint {
+        title.push_str(&format!(", hint \"{}\"", hint));
+    }
+    if !state.has_recovery {
+        title.push_str(", no recovery email");
+    }
+    title
+}
+
+pub fn password_option(option: PasswordOption, state: &PasswordState) -> String {
+    match option {
+        PasswordOption::Enable => "Enable a password".into(),
+        PasswordOption::Change => "Change the password".into(),
+        PasswordOption::Disable => "Disable the password".into(),
+        PasswordOption::Email if state.has_recovery => "Change the recovery email".into(),
+        PasswordOption::Email => "Set a recovery email".into(),
+        PasswordOption::ConfirmEmail => format!(
+            "Enter the code sent to {}",
+            state.unconfirmed_email.as_deref().unwrap_or("the email")
+        ),
+        PasswordOption::ResendEmail => "Send the code again".into(),
+        PasswordOption::CancelEmail => "Cancel the new recovery email".into(),
+    }
+}
+
+/// An entry of `:sessions`, like `Firefox 120 on Linux, active 12:04`.
+pub fn session_item(item: &SessionItem) -> String {
+    match item {
+        SessionItem::Ttl(days) => format!("Terminate sessions inactive for: {}", ttl(*days)),
+        SessionItem::Password(true) => "Password for new sessions: on".into(),
+        SessionItem::Password(false) => "Password for new sessions: off (enable 2FA)".into(),
+        SessionItem::Session(session) if session.current => {
+            format!("{} on {}, {} (this session)", session.app, session.device, session.location)
+        }
+        SessionItem::Session(session) => format!(
+            "{} on {}, {}, active {}",
+            session.app,
+            session.device,
+            session.location,
+            time(session.active)
+        ),
+    }
+}
+
+/// A request of `:requests`, like `12:04 Jane Doe @jane: I'm a colleague of Bob's`.
+pub fn join_request(request: &JoinRequest) -> String {
+    let mut label = format!("{} {}", time(request.date), request.name);
+    if let Some(username) = &request.username {
+        label.push_str(&format!(" @{}", username));
+    }
+    if let Some(about) = &request.about {
+        label.push_str(&format!(": {}", about));
+    }
+    label
+}
+
+fn ttl(days: i32) -> String {
+    match days {
+        7 => "1 week".into(),
+        30 => "1 month".into(),
+        90 => "3 months".into(),
+        183 => "6 months".into(),
+        365 => "1 year".into(),
+        _ => format!("{} days", days),
+    }
+}
+
+/// One-line description of media without a richer rendering.
+fn media_descriptor(media: &Media) -> Option<String> {
+    match media {
+        Media::Photo(_) => Some("[Photo]".into()),
+        Media::Document(document) if document.is_animated() => {
+            Some(format!("[GIF {}]", human_size(document.size() as u64)))
+        }
+        Media::Document(document) if video_note::is_video_note(document) => {
+            Some(format!("[Video note {}]", video_note::duration(document)))
+        }
+        Media::Document(document) => Some(format!(
+            "[File {} {}]",
+            document.name(),
+            human_size(document.size() as u64)
+        )),
+        Media::Sticker(sticker) => Some(format!("[Sticker {}]", sticker.emoji())),
+        Media::Poll(poll) => Some(format!("[Poll] {}", poll.question())),
+        Media::Dice(dice) => Some(format!("[{} {}]", dice.emoticon(), dice.value())),
+        _ => None,
+    }
+}
+
+/// Text shown in place of a service message.
+fn service_action(action: &tl::enums::MessageAction) -> String {
+    use tl::enums::MessageAction as A;
+    match action {
+        A::GiftPremium(gift) => {
+            format!("🎁 Gifted Telegram Premium for {} months", gift.months)
+        }
+        A::GiftCode(code) if code.via_giveaway => {
+            format!("🎉 Won Telegram Premium for {} months in a giveaway", code.months)
+        }
+        A::GiftCode(code) => format!("🎁 Telegram Premium gift code for {} months", code.months),
+        A::GiveawayLaunch => "🎉 Started a giveaway".into(),
+        A::GiveawayResults(results) => format!(
+            "🎉 Giveaway finished: {} winners",
+            results.winners_count
+        ),
+        A::PinMessage => "📌 Pinned a message".into(),
+        A::ChatEditTitle(edit) => format!("Changed the title to \"{}\"", edit.title),
+        A::ChatEditPhoto(_) => "Changed the chat photo".into(),
+        A::ChatDeletePhoto => "Removed the chat photo".into(),
+        A::ChatCreate(create) => format!("Created the group \"{}\"", create.title),
+        A::ChannelCreate(create) => format!("Created the channel \"{}\"", create.title),
+        A::ChatAddUser(_) => "Added members".into(),
+        A::ChatDeleteUser(_) => "Removed a member".into(),
+        A::ChatJoinedByLink(_) | A::ChatJoinedByRequest => "Joined the chat".into(),
+        A::ContactSignUp => "Joined Telegram".into(),
+        A::ScreenshotTaken => "Took a screenshot".into(),
+        A::HistoryClear => "History was cleared".into(),
+        A::PhoneCall(call) => match call.duration {
+            Some(secs) => format!("📞 Call ({}:{:02})", secs / 60, secs % 60),
+            None => "📞 Missed call".into(),
+        },
+        A::CustomAction(custom) => custom.message.clone(),
+        _ => "[service message]".into(),
+    }
+}
+
+/// Text with the given byte ranges styled, the ranges sorted and not
+/// overlapping.
+pub fn highlighted(text: &str, ranges: &[Range<usize>], style: Style) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for range in ranges {
+        if range.start < end {
+            continue;
+        }
+        spans.push(Span::raw(text[end..range.start].to_string()));
+        spans.push(Span::styled(text[range.clone()].to_string(), style));
+        end = range.end;
+    }
+    spans.push(Span::raw(text[end..].to_string()));
+    Line::from(spans)
+}
+
+/// A line with newlines in its spans broken into lines, keeping the styles.
+pub fn split_lines(line: Line<'static>) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::default()];
+    for span in line.spans {
+        for (i, part) in span.content.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Line::default());
+            }
+            if !part.is_empty() {
+                lines.last_mut().unwrap().spans.push(Span::styled(part.to_string(), span.style));
+            }
+        }
+    }
+    lines
+}
+
+/// Short count, like `950`, `1.2K` or `3.4M`.
+pub fn human_count(n: i32) -> String {
+    match n {
+        ..=999 => n.to_string(),
+        1000..=999_999 => format!("{:.1}K", n as f64 / 1e3),
+        _ => format!("{:.1}M", n as f64 / 1e6),
+    }
+}
+
+/// Period of a chat's auto-delete timer, like `1 week` or `12h00m`.
+pub fn auto_delete(period: i32) -> String {
+    const DAY: i32 = 24 * 60 * 60;
+    match period {
+        DAY => "1 day".into(),
+        _ if period == 7 * DAY => "1 week".into(),
+        _ if period == 31 * DAY => "1 month".into(),
+        _ if period % DAY == 0 => format!("{} days", period / DAY),
+        _ => human_duration(Duration::from_secs(period.max(0) as u64)),
+    }
+}
+
+/// How soon a message is deleted by the auto-delete timer, once that's
+/// within a tenth of the timer's period.
+pub fn expiry(message: &Message) -> Option<Line<'static>> {
+    let period = message.ttl_period()?;
+    let left = message.date().timestamp() + period as i64 - Utc::now().timestamp();
+    if left >= period as i64 / 10 {
+        return None;
+    }
+    Some(Line::from(Span::styled(
+        format!("⏲ deleted in {}", human_duration(Duration::from_secs(left.max(0) as u64))),
+        Style::default().fg(Color::DarkGray),
+    )))
+}
+
+/// Coarse duration, like `1h05m`, `42m` or `30s`.
+pub fn human_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// File size with a binary unit, like `2.1MB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, UNITS[0])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+/// Location with its coordinates and a link to open it on a map.
+fn location(title: &str, geo: Option<&Geo>, address: Option<&str>) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(Span::styled(
+        format!("📍 {}", title),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    if let Some(address) = address.filter(|a| !a.is_empty()) {
+        lines.push(Line::from(address.to_string()));
+    }
+    if let Some(geo) = geo {
+        let (lat, lon) = (geo.latitue(), geo.longitude());
+        lines.push(Line::from(format!("{:.5}, {:.5}", lat, lon)));
+        lines.push(Line::from(Span::styled(
+            map_link(lat, lon),
+            Style::default().fg(Color::Blue).add_modifier(Modifier::UNDERLINED),
+        )));
+    }
+    lines
+}
+
+fn map_link(lat: f64, lon: f64) -> String {
+    format!(
+        "https://www.openstreetmap.org/?mlat={lat:.5}&mlon={lon:.5}#map=16/{lat:.5}/{lon:.5}"
+    )
+}
+
+/// Link preview as a quote-like block: site name, title and description.
+fn web_page(page: &WebPage) -> Vec<Line<'static>> {
+    let tl::enums::WebPage::Page(page) = &page.raw.webpage else {
+        return Vec::new();
+    };
+    let bar = || Span::styled("▎", Style::default().fg(Color::Blue));
+
+    let mut lines = Vec::new();
+    if let Some(site_name) = &page.site_name {
+        lines.push(Line::from(vec![
+            bar(),
+            Span::styled(site_name.clone(), Style::default().fg(Color::Blue)),
+        ]));
+    }
+    if let Some(title) = &page.title {
+        lines.push(Line::from(vec![
+            bar(),
+            Span::styled(title.clone(), Style::default().add_modifier(Modifier::BOLD)),
+        ]));
+    }
+    if let Some(description) = &page.description {
+        let description = truncate(description, PREVIEW_DESCRIPTION_MAX);
+        for l in description.lines() {
+            lines.push(Line::from(vec![
+                bar(),
+                Span::styled(l.to_string(), Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+    }
+    lines
+}
+
+pub fn truncate(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((i, _)) => format!("{}…", &s[..i]),
+        None => s.to_string(),
+    }
+}
@@ -1,14 +1,14 @@
-use anyhow::Result;
+use crate::error::{Result, Tg9Error};
 
 use futures::{future::FutureExt, StreamExt};
 
 use std::io::{stdout, Stdout};
 use crossterm;
 use crossterm::{
-    cursor::{Hide, Show},
+    cursor::{Hide, SetCursorStyle, Show},
     event::{
-        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
-        Event, EventStream, KeyEvent, KeyEventKind, MouseEvent,
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste, EnableFocusChange,
+        EnableMouseCapture, Event, EventStream, KeyEvent, KeyEventKind, MouseEvent,
     },
     terminal::{disable_raw_mode, is_raw_mode_enabled, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -39,12 +39,19 @@ pub struct Screen {
     pub task: JoinHandle<()>,
     pub tx: mpsc::UnboundedSender<ScreenEvent>,
     pub mouse: bool,
+    /// Whether the terminal took bracketed paste, which the legacy Windows
+    /// console doesn't
     pub paste: bool,
+    /// Stay in the normal screen for the plain output, see `plain.rs`
+    pub plain: bool,
+    /// Whether the cursor was last set to a bar rather than a block, none
+    /// while it has the terminal's own shape
+    cursor_bar: Option<bool>,
 }
 
 impl Screen {
     pub fn new(tx: mpsc::UnboundedSender<ScreenEvent>) -> Result<Self> {
-        let terminal = ratatui::Terminal::new(Backend::new(stdout()))?;
+        let terminal = ratatui::Terminal::new(Backend::new(stdout())).map_err(Tg9Error::Terminal)?;
         let task = tokio::spawn(async {});
         let mouse = true;
         let paste = true;
@@ -54,6 +61,8 @@ impl Screen {
             tx,
             mouse,
             paste,
+            plain: false,
+            cursor_bar: None,
         })
     }
 
@@ -65,13 +74,18 @@ impl Screen {
             let mut reader = EventStream::new();
             let mut tick_interval = tokio::time::interval(tick_delay);
             let mut render_interval = tokio::time::interval(render_delay);
+            let mut quit = Box::pin(quit_signal().fuse());
             // event_tx.send(TermEvent::Init).unwrap();
             loop {
                 let tick_delay = tick_interval.tick();
                 let render_delay = render_interval.tick();
                 let crossterm_event = reader.next().fuse();
                 tokio::select! {
-                    // TODO: signals
+                    result = &mut quit => {
+                        if result.is_ok() {
+                            event_tx.send(ScreenEvent::Quit).unwrap();
+                        }
+                    },
                     maybe_event = crossterm_event => {
                         match maybe_event {
                             Some(Ok(evt)) => {
@@ -116,25 +130,74 @@ impl Screen {
     }
 
     pub fn enter(&mut self) -> Result<()> {
-        enable_raw_mode()?;
-        crossterm::execute!(stdout(), EnterAlternateScreen, Hide, EnableMouseCapture, EnableBracketedPaste)?;
+        enable_raw_mode().map_err(Tg9Error::Terminal)?;
+        if self.plain {
+            self.start();
+            return Ok(());
+        }
+        crossterm::execute!(stdout(), EnterAlternateScreen, Hide, EnableMouseCapture).map_err(Tg9Error::Terminal)?;
+        self.paste = crossterm::execute!(stdout(), EnableBracketedPaste).is_ok();
+        // for the `focus` presence, terminals which can't tell just don't
+        let _ = crossterm::execute!(stdout(), EnableFocusChange);
         self.start();
         Ok(())
     }
 
     pub fn exit(&mut self) -> Result<()> {
-        if is_raw_mode_enabled()? {
-            self.terminal.flush()?;
-            crossterm::execute!(stdout(), LeaveAlternateScreen, Show, DisableMouseCapture, DisableBracketedPaste)?;
-            disable_raw_mode()?;
+        if is_raw_mode_enabled().map_err(Tg9Error::Terminal)? && self.plain {
+            disable_raw_mode().map_err(Tg9Error::Terminal)?;
+        } else if is_raw_mode_enabled().map_err(Tg9Error::Terminal)? {
+            self.terminal.flush().map_err(Tg9Error::Terminal)?;
+            crossterm::execute!(stdout(), LeaveAlternateScreen, Show, DisableMouseCapture)
+                .map_err(Tg9Error::Terminal)?;
+            if self.paste {
+                crossterm::execute!(stdout(), DisableBracketedPaste).map_err(Tg9Error::Terminal)?;
+            }
+            let _ = crossterm::execute!(stdout(), DisableFocusChange);
+            if self.cursor_bar.take().is_some() {
+                crossterm::execute!(stdout(), SetCursorStyle::DefaultUserShape).map_err(Tg9Error::Terminal)?;
+            }
+            disable_raw_mode().map_err(Tg9Error::Terminal)?;
         }
         Ok(())
     }
 
-    pub fn suspend(&mut self) -> Result<()> {
+    /// Leave the UI to run a command in the terminal, and come back once
+    /// it exits.
+    pub fn run_foreground(&mut self, command: &mut std::process::Command) -> Result<()> {
+        // the command owns the terminal's input while it runs
+        self.task.abort();
         self.exit()?;
-        #[cfg(not(windows))]
-        signal_hook::low_level::raise(signal_hook::consts::signal::SIGTSTP)?;
+        let status = command.status();
+        self.enter()?;
+        if !self.plain {
+            self.terminal.clear().map_err(Tg9Error::Terminal)?;
+        }
+        status?;
+        Ok(())
+    }
+
+    /// Shape the cursor as a bar while typing, like editors do in insert
+    /// mode, and as a block otherwise. Only sent to the terminal when it
+    /// changes.
+    pub fn set_cursor_bar(&mut self, bar: bool) -> Result<()> {
+        if self.plain || self.cursor_bar == Some(bar) {
+            return Ok(());
+        }
+        let style = if bar { SetCursorStyle::SteadyBar } else { SetCursorStyle::SteadyBlock };
+        crossterm::execute!(stdout(), style).map_err(Tg9Error::Terminal)?;
+        self.cursor_bar = Some(bar);
+        Ok(())
+    }
+
+    /// Stop tg9 in the background of the shell, like Ctrl-Z. Windows has no
+    /// job control, so there it does nothing.
+    pub fn suspend(&mut self) -> Result<()> {
+        #[cfg(unix)]
+        {
+            self.exit()?;
+            signal_hook::low_level::raise(signal_hook::consts::signal::SIGTSTP).map_err(Tg9Error::Terminal)?;
+        }
         Ok(())
     }
 
@@ -151,8 +214,50 @@ impl Screen {
 pub fn setup_panic_handler() {
     let original_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
-        crossterm::execute!(std::io::stdout(), LeaveAlternateScreen, DisableBracketedPaste, DisableMouseCapture).unwrap();
-        disable_raw_mode().unwrap();
+        // failing here would abort without the report, bracketed paste
+        // fails in the legacy Windows console
+        let _ = crossterm::execute!(
+            std::io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            SetCursorStyle::DefaultUserShape,
+            Show
+        );
+        let _ = crossterm::execute!(std::io::stdout(), DisableBracketedPaste, DisableFocusChange);
+        let _ = disable_raw_mode();
         original_hook(panic_info);
+        match crate::crash::report(panic_info) {
+            Ok(path) => eprintln!("crash report written to {}, please attach it to a bug report", path.display()),
+            Err(e) => eprintln!("failed to write a crash report: {}", e),
+        }
     }));
 }
+
+/// Wait for a signal asking tg9 to quit, which it then does the usual way,
+/// saving its state: SIGTERM or SIGHUP on Unix, and on Windows the console
+/// window closing, logging off or shutting down.
+#[cfg(unix)]
+async fn quit_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::select! {
+        _ = terminate.recv() => {},
+        _ = hangup.recv() => {},
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn quit_signal() -> std::io::Result<()> {
+    use tokio::signal::windows::{ctrl_close, ctrl_logoff, ctrl_shutdown};
+    let mut close = ctrl_close()?;
+    let mut logoff = ctrl_logoff()?;
+    let mut shutdown = ctrl_shutdown()?;
+    tokio::select! {
+        _ = close.recv() => {},
+        _ = logoff.recv() => {},
+        _ = shutdown.recv() => {},
+    }
+    Ok(())
+}
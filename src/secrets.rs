@@ -0,0 +1,126 @@
+use crate::session;
+
+/// Secrets that can be kept in the OS keyring (Secret Service, the macOS
+/// Keychain or the Windows credential store) instead of in plain text, when
+/// tg9 is built with the `keyring` feature.
+#[derive(Debug, Clone, Copy)]
+pub enum Secret {
+    /// API hash of the app, used instead of the one built in
+    ApiHash,
+    /// Passphrase of the encrypted session
+    Passphrase,
+    /// 2FA cloud password of the account, for logging in
+    Password,
+}
+
+impl Secret {
+    fn parse(name: &str) -> Option<Secret> {
+        match name {
+            "api-hash" => Some(Secret::ApiHash),
+            "passphrase" => Some(Secret::Passphrase),
+            "password" => Some(Secret::Password),
+            _ => None,
+        }
+    }
+
+    /// Name of the entry in the keyring, and in `tg9 secret`.
+    fn name(self) -> &'static str {
+        match self {
+            Secret::ApiHash => "api-hash",
+            Secret::Passphrase => "passphrase",
+            Secret::Password => "password",
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+mod store {
+    use super::Secret;
+    use keyring::Entry;
+
+    const SERVICE: &str = "tg9";
+
+    pub fn get(secret: Secret) -> Option<String> {
+        Entry::new(SERVICE, secret.name()).ok()?.get_password().ok()
+    }
+
+    pub fn set(secret: Secret, value: &str) -> anyhow::Result<()> {
+        Entry::new(SERVICE, secret.name())?.set_password(value)?;
+        Ok(())
+    }
+
+    pub fn delete(secret: Secret) -> anyhow::Result<()> {
+        match Entry::new(SERVICE, secret.name())?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+mod store {
+    use super::Secret;
+
+    pub fn get(_secret: Secret) -> Option<String> {
+        None
+    }
+
+    pub fn set(_secret: Secret, _value: &str) -> anyhow::Result<()> {
+        anyhow::bail!("tg9 was built without the keyring feature")
+    }
+
+    pub fn delete(secret: Secret) -> anyhow::Result<()> {
+        set(secret, "")
+    }
+}
+
+/// The secret stored in the keyring, if there's one and it can be read.
+/// Without the `keyring` feature there never is.
+pub fn get(secret: Secret) -> Option<String> {
+    store::get(secret)
+}
+
+/// Store a secret in the keyring, an error if there's none to store it in.
+pub fn set(secret: Secret, value: &str) -> anyhow::Result<()> {
+    store::set(secret, value)
+}
+
+/// `tg9 secret` subcommands.
+pub enum SecretCommand {
+    /// Store a secret typed into the terminal
+    Set(Secret),
+    Delete(Secret),
+}
+
+impl SecretCommand {
+    pub const USAGE: &'static str = "usage: tg9 secret set|delete api-hash|passphrase|password";
+
+    pub fn parse(action: Option<&str>, name: Option<&str>) -> anyhow::Result<SecretCommand> {
+        let Some(secret) = name.and_then(Secret::parse) else {
+            anyhow::bail!(Self::USAGE);
+        };
+        match action {
+            Some("set") => Ok(SecretCommand::Set(secret)),
+            Some("delete") => Ok(SecretCommand::Delete(secret)),
+            _ => anyhow::bail!(Self::USAGE),
+        }
+    }
+
+    pub fn run(self) -> anyhow::Result<()> {
+        match self {
+            SecretCommand::Set(secret) => {
+                let value = session::read_secret(&format!("{}: ", secret.name()))?;
+                if value.is_empty() {
+                    anyhow::bail!("the {} can't be empty", secret.name());
+                }
+                store::set(secret, &value)?;
+                println!("the {} is stored in the keyring", secret.name());
+            }
+            SecretCommand::Delete(secret) => {
+                store::delete(secret)?;
+                println!("the {} is no longer in the keyring", secret.name());
+            }
+        }
+        Ok(())
+    }
+}
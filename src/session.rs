@@ -0,0 +1,159 @@
+use crate::error::{Result, Tg9Error};
+use crate::secrets::{self, Secret};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use grammers_session::Session;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Start of an encrypted session file, followed by the salt of the key,
+/// the nonce and the encrypted session.
+const MAGIC: &[u8] = b"tg9-encrypted-session-v1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Whether the session file is encrypted with a passphrase.
+pub fn is_encrypted(path: &Path) -> bool {
+    std::fs::read(path).is_ok_and(|data| data.starts_with(MAGIC))
+}
+
+/// Read the session file, decrypting it if it's encrypted. A new session
+/// if there's no file yet.
+pub fn load(path: &Path, passphrase: Option<&str>) -> Result<Session> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Session::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let data = match (data.strip_prefix(MAGIC), passphrase) {
+        (Some(encrypted), Some(passphrase)) => decrypt(encrypted, passphrase)?,
+        (Some(_), None) => return Err(Tg9Error::Auth("the session is encrypted".into())),
+        (None, _) => data,
+    };
+    Session::load(&data).map_err(|_| Tg9Error::Auth("the session file is corrupted".into()))
+}
+
+/// Write the session file, encrypted if there's a passphrase.
+pub fn save(session: &Session, path: &Path, passphrase: Option<&str>) -> Result<()> {
+    let data = match passphrase {
+        Some(passphrase) => [MAGIC, &encrypt(&session.save(), passphrase)?].concat(),
+        None => session.save(),
+    };
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+/// Key derived from the passphrase with Argon2, so guessing it is slow.
+fn key(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Tg9Error::Other(format!("failed to derive the key: {}", e)))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn encrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let encrypted = key(passphrase, &salt)?
+        .encrypt(&nonce, data)
+        .map_err(|e| Tg9Error::Other(format!("failed to encrypt the session: {}", e)))?;
+    Ok([&salt[..], &nonce[..], &encrypted].concat())
+}
+
+fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err(Tg9Error::Auth("the session file is corrupted".into()));
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, encrypted) = rest.split_at(NONCE_LEN);
+    // a wrong passphrase and a tampered file can't be told apart
+    key(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), encrypted)
+        .map_err(|_| Tg9Error::WrongPassphrase)
+}
+
+/// Passphrase of the session for commands without the UI: from
+/// `TG9_PASSPHRASE` for scripts, the keyring, or typed into the terminal.
+pub fn passphrase(prompt: &str) -> io::Result<String> {
+    if let Ok(passphrase) = std::env::var("TG9_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    if let Some(passphrase) = secrets::get(Secret::Passphrase) {
+        return Ok(passphrase);
+    }
+    read_secret(prompt)
+}
+
+/// Read a line from the terminal without echoing it.
+pub fn read_secret(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    crossterm::terminal::enable_raw_mode()?;
+    let mut secret = String::new();
+    let result = loop {
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        };
+        match key.code {
+            KeyCode::Enter => break Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                break Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            KeyCode::Backspace => {
+                secret.pop();
+            }
+            KeyCode::Char(c) => secret.push(c),
+            _ => {}
+        }
+    };
+    crossterm::terminal::disable_raw_mode()?;
+    eprintln!();
+    result.map(|()| secret)
+}
+
+/// `tg9 session` subcommands.
+pub enum SessionCommand {
+    /// Encrypt the session file with a new passphrase
+    Encrypt,
+    /// Store the session file in plain again
+    Decrypt,
+}
+
+impl SessionCommand {
+    pub fn run(self, path: &Path) -> anyhow::Result<()> {
+        let encrypted = is_encrypted(path);
+        match self {
+            SessionCommand::Encrypt => {
+                if encrypted {
+                    anyhow::bail!("the session is already encrypted");
+                }
+                let session = load(path, None)?;
+                let passphrase = read_secret("New passphrase: ")?;
+                if passphrase.is_empty() {
+                    anyhow::bail!("the passphrase can't be empty");
+                }
+                if read_secret("Repeat the passphrase: ")? != passphrase {
+                    anyhow::bail!("the passphrases don't match");
+                }
+                save(&session, path, Some(&passphrase))?;
+                println!("the session is encrypted, tg9 will ask for the passphrase on startup");
+            }
+            SessionCommand::Decrypt => {
+                if !encrypted {
+                    anyhow::bail!("the session isn't encrypted");
+                }
+                let session = load(path, Some(&read_secret("Passphrase: ")?))?;
+                save(&session, path, None)?;
+                println!("the session is no longer encrypted");
+            }
+        }
+        Ok(())
+    }
+}
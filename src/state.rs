@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// UI state saved on exit and restored on startup, so tg9 reopens where
+/// it was left. Chats are stored by bare id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Chat open on exit
+    pub chat: Option<i64>,
+    /// Chat in the other window of the split view
+    pub split: Option<i64>,
+    pub tabs: Vec<i64>,
+    /// Width of the dialog list in percent of the screen
+    pub dialogs_width: u16,
+    /// Emoji picked last, most recent first
+    pub recent_emoji: Vec<String>,
+    /// Chats left out of the dialog list with `:hide`
+    pub hidden: Vec<i64>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            chat: None,
+            split: None,
+            tabs: Vec::new(),
+            dialogs_width: 33,
+            recent_emoji: Vec::new(),
+            hidden: Vec::new(),
+        }
+    }
+}
+
+impl State {
+    pub fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|d| d.join("tg9").join("state.toml"))
+    }
+
+    /// The saved state, or the default one if there's none or it can't be
+    /// read: losing it isn't worth failing to start.
+    pub fn load() -> State {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text)
+    }
+}
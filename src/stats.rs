@@ -0,0 +1,68 @@
+use chrono::{Local, Timelike};
+use grammers_client::types::{Media, Message};
+use std::collections::HashMap;
+
+/// Most senders listed, the rest are summed up as "others"
+const SENDERS_MAX: usize = 10;
+
+/// Statistics of the loaded messages of a chat, shown by `:stats`.
+pub struct Stats {
+    pub title: String,
+    pub messages: usize,
+    /// Messages by sender, most first
+    pub senders: Vec<(String, u64)>,
+    /// Messages by hour of the day, in local time
+    pub hours: [u64; 24],
+    /// Messages by kind of content, most first
+    pub media: Vec<(&'static str, u64)>,
+}
+
+impl Stats {
+    pub fn new<'a>(chat: &str, messages: impl IntoIterator<Item = &'a Message>) -> Stats {
+        let mut senders: HashMap<String, u64> = HashMap::new();
+        let mut hours = [0; 24];
+        let mut media: HashMap<&'static str, u64> = HashMap::new();
+        let mut count = 0;
+        for message in messages {
+            count += 1;
+            let sender = message.sender().unwrap_or_else(|| message.chat());
+            *senders.entry(sender.name().to_string()).or_default() += 1;
+            hours[message.date().with_timezone(&Local).hour() as usize] += 1;
+            *media.entry(kind(message)).or_default() += 1;
+        }
+
+        let mut senders: Vec<_> = senders.into_iter().collect();
+        senders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        if senders.len() > SENDERS_MAX {
+            let others = senders.split_off(SENDERS_MAX - 1).iter().map(|(_, n)| n).sum();
+            senders.push(("others".into(), others));
+        }
+        let mut media: Vec<_> = media.into_iter().collect();
+        media.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        Stats {
+            title: format!("Stats of {} ({} loaded messages)", chat, count),
+            messages: count,
+            senders,
+            hours,
+            media,
+        }
+    }
+}
+
+/// Kind of content of a message, for the media breakdown.
+fn kind(message: &Message) -> &'static str {
+    match message.media() {
+        None => "text",
+        Some(Media::Photo(_)) => "photo",
+        Some(Media::Sticker(_)) => "sticker",
+        Some(Media::Poll(_)) => "poll",
+        Some(Media::Document(document)) if document.is_animated() => "gif",
+        Some(Media::Document(document)) => match document.mime_type() {
+            Some(mime) if mime.starts_with("video/") => "video",
+            Some(mime) if mime.starts_with("audio/") => "audio",
+            _ => "file",
+        },
+        Some(_) => "other",
+    }
+}
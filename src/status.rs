@@ -0,0 +1,78 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// Unread counts which the running tg9 keeps written for `tg9 status`, so
+/// status bars can show them without connecting to Telegram. The file is
+/// removed on exit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UnreadStatus {
+    /// Unread messages in all chats
+    pub unread: i32,
+    /// Chats with unread messages or marked as unread
+    pub chats: usize,
+    /// Unread messages mentioning me
+    pub mentions: i32,
+}
+
+impl UnreadStatus {
+    pub fn path() -> Option<PathBuf> {
+        dirs::runtime_dir()
+            .or_else(dirs::state_dir)
+            .or_else(dirs::data_local_dir)
+            .map(|d| d.join("tg9").join("status.toml"))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text)
+    }
+
+    pub fn remove() -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What `tg9 status` prints.
+#[derive(Debug, Serialize)]
+struct Output {
+    /// tg9 is running, otherwise the counts are zero
+    running: bool,
+    #[serde(flatten)]
+    status: UnreadStatus,
+}
+
+/// Print the unread counts of the running tg9, as JSON for status bars or
+/// as a line of text.
+pub fn print(json: bool) -> Result<()> {
+    let text = UnreadStatus::path().and_then(|path| std::fs::read_to_string(path).ok());
+    let output = Output {
+        running: text.is_some(),
+        status: text.map(|text| toml::from_str(&text)).transpose()?.unwrap_or_default(),
+    };
+    if json {
+        println!("{}", serde_json::to_string(&output)?);
+    } else if !output.running {
+        println!("tg9 isn't running");
+    } else {
+        let status = output.status;
+        println!(
+            "{} unread in {} chats, {} mentions",
+            status.unread, status.chats, status.mentions
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,511 @@
+use crate::api;
+use crate::cache;
+use crate::cli;
+use crate::error::Tg9Error;
+use anyhow::{bail, Context, Result};
+use grammers_client::grammers_tl_types::{self as tl, RemoteCall};
+use grammers_client::{Client, InvocationError};
+use grammers_session::{PackedChat, PackedType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Messages and dialogs requested at once, the most Telegram returns
+const PAGE_SIZE: i32 = 100;
+
+/// Bytes of a file requested at once, a multiple of 4 KiB which divides
+/// 1 MiB as Telegram wants
+const PART_SIZE: i32 = 512 * 1024;
+
+/// Largest file downloaded with `--media`
+const FILE_MAX_SIZE: i64 = 2 * 1024 * 1024 * 1024;
+
+/// Options of `tg9 takeout`.
+#[derive(Debug, Default)]
+pub struct TakeoutArgs {
+    /// Directory the export is written to, and resumed from
+    pub dir: PathBuf,
+    /// Also download the photos and files of the messages
+    pub media: bool,
+}
+
+impl TakeoutArgs {
+    pub const USAGE: &'static str = "usage: tg9 takeout <dir> [--media]";
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<TakeoutArgs> {
+        let mut takeout = TakeoutArgs::default();
+        let mut dir = None;
+        for arg in args {
+            match arg.as_str() {
+                "--media" => takeout.media = true,
+                _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+                _ => bail!("{}", Self::USAGE),
+            }
+        }
+        takeout.dir = dir.context(Self::USAGE)?;
+        Ok(takeout)
+    }
+}
+
+/// Progress of an export, saved after every page of messages so an
+/// interrupted one continues where it stopped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Checkpoint {
+    /// By chat id
+    chats: BTreeMap<String, ChatCheckpoint>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ChatCheckpoint {
+    name: String,
+    /// Newest message exported, the next run starts after it
+    last_id: i32,
+    /// Length of the export with the messages up to `last_id`, anything
+    /// past it was appended by an interrupted run and is written again
+    len: Option<u64>,
+}
+
+impl Checkpoint {
+    fn path(dir: &Path) -> PathBuf {
+        dir.join("takeout.toml")
+    }
+
+    fn load(dir: &Path) -> Result<Checkpoint> {
+        match std::fs::read_to_string(Self::path(dir)) {
+            Ok(text) => toml::from_str(&text).context("the checkpoint of the export is corrupted"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Checkpoint::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Written to a temporary file first, so an interruption never leaves
+    /// half a checkpoint.
+    fn save(&self, dir: &Path) -> Result<()> {
+        let path = Self::path(dir);
+        let temp = path.with_extension("toml.tmp");
+        std::fs::write(&temp, toml::to_string(self)?)?;
+        std::fs::rename(temp, path)?;
+        Ok(())
+    }
+}
+
+/// A message as a line of the export.
+#[derive(Debug, Serialize)]
+struct ExportedMessage {
+    id: i32,
+    /// Unix time
+    date: i32,
+    from_id: Option<i64>,
+    reply_to: Option<i32>,
+    text: String,
+    /// Service messages: what happened, like a member joining
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<String>,
+    media: bool,
+    /// Downloaded photo or file, relative to the export directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+}
+
+impl ExportedMessage {
+    fn new(message: tl::enums::Message) -> Option<ExportedMessage> {
+        match message {
+            tl::enums::Message::Empty(_) => None,
+            tl::enums::Message::Message(message) => Some(ExportedMessage {
+                id: message.id,
+                date: message.date,
+                from_id: message.from_id.as_ref().map(api::peer_id),
+                reply_to: match message.reply_to {
+                    Some(tl::enums::MessageReplyHeader::Header(header)) => header.reply_to_msg_id,
+                    _ => None,
+                },
+                text: message.message,
+                action: None,
+                media: message.media.is_some(),
+                file: None,
+            }),
+            tl::enums::Message::Service(message) => Some(ExportedMessage {
+                id: message.id,
+                date: message.date,
+                from_id: message.from_id.as_ref().map(api::peer_id),
+                reply_to: None,
+                text: String::new(),
+                action: Some(format!("{:?}", message.action)),
+                media: false,
+                file: None,
+            }),
+        }
+    }
+}
+
+/// Export the history of every chat as JSON lines (`<chat id>.jsonl`),
+/// and with `--media` their files (`media/<chat id>/`), through a takeout
+/// session: Telegram's export mode with laxer rate limits. Running it
+/// again into the same directory resumes it, and later adds new messages.
+pub async fn takeout(args: TakeoutArgs) -> Result<()> {
+    std::fs::create_dir_all(&args.dir)?;
+    let mut checkpoint = Checkpoint::load(&args.dir)?;
+    let client = cli::connect().await?;
+
+    let tl::enums::account::Takeout::Takeout(session) = client
+        .invoke(&tl::functions::account::InitTakeoutSession {
+            contacts: false,
+            message_users: true,
+            message_chats: true,
+            message_megagroups: true,
+            message_channels: true,
+            files: args.media,
+            file_max_size: args.media.then_some(FILE_MAX_SIZE),
+        })
+        .await
+        .map_err(|e| match Tg9Error::from(e) {
+            Tg9Error::Rpc { name, .. } if name == "TAKEOUT_INIT_DELAY" => anyhow::anyhow!(
+                "Telegram delays exports from new sessions, allow it in an official app and try again"
+            ),
+            e => e.into(),
+        })?;
+    let takeout = Takeout { client: &client, id: session.id };
+
+    let mut chats = Vec::new();
+    let result = async {
+        chats = load_chats(&takeout).await?;
+        for (i, (chat, name)) in chats.iter().enumerate() {
+            let progress = format!("[{}/{}] {}", i + 1, chats.len(), name);
+            export_chat(&takeout, &args, &mut checkpoint, *chat, name, &progress).await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    // ends the takeout session either way, a failed one is resumed with
+    // a new session
+    let finished = takeout
+        .invoke(tl::functions::account::FinishTakeoutSession { success: result.is_ok() })
+        .await;
+    result?;
+    finished?;
+    eprintln!("exported {} chats into {}", chats.len(), args.dir.display());
+    Ok(())
+}
+
+/// Append the messages of a chat newer than its checkpoint to its export,
+/// oldest first, a page at a time.
+async fn export_chat(
+    takeout: &Takeout<'_>,
+    args: &TakeoutArgs,
+    checkpoint: &mut Checkpoint,
+    chat: PackedChat,
+    name: &str,
+    progress: &str,
+) -> Result<()> {
+    let key = chat.id.to_string();
+    let saved = checkpoint.chats.get(&key);
+    let mut last_id = saved.map_or(0, |c| c.last_id);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(args.dir.join(format!("{}.jsonl", chat.id)))?;
+    if let Some(len) = saved.and_then(|c| c.len) {
+        file.set_len(len)?;
+    }
+    let mut file = BufWriter::new(file);
+    let mut exported = 0;
+    loop {
+        eprint!("\r{}: {} messages", progress, exported);
+        // with a negative offset, the page after `offset_id`, newest first
+        let request = tl::functions::messages::GetHistory {
+            peer: chat.to_input_peer(),
+            offset_id: last_id + 1,
+            offset_date: 0,
+            add_offset: -PAGE_SIZE,
+            limit: PAGE_SIZE,
+            max_id: 0,
+            min_id: last_id,
+            hash: 0,
+        };
+        let messages = match takeout.invoke(request).await? {
+            tl::enums::messages::Messages::Messages(messages) => messages.messages,
+            tl::enums::messages::Messages::Slice(messages) => messages.messages,
+            tl::enums::messages::Messages::ChannelMessages(messages) => messages.messages,
+            tl::enums::messages::Messages::NotModified(_) => break,
+        };
+        let mut page: Vec<_> = messages
+            .into_iter()
+            .filter_map(|message| {
+                let media = match &message {
+                    tl::enums::Message::Message(message) => message.media.clone(),
+                    _ => None,
+                };
+                Some((ExportedMessage::new(message)?, media))
+            })
+            .filter(|(m, _)| m.id > last_id)
+            .collect();
+        if page.is_empty() {
+            break;
+        }
+        page.sort_by_key(|(m, _)| m.id);
+        if args.media {
+            download_media(takeout, &args.dir, chat, &mut page).await?;
+        }
+        for (message, _) in &page {
+            serde_json::to_writer(&mut file, message)?;
+            file.write_all(b"\n")?;
+        }
+        file.flush()?;
+
+        exported += page.len();
+        last_id = page.last().map_or(last_id, |(m, _)| m.id);
+        checkpoint.chats.insert(
+            key.clone(),
+            ChatCheckpoint {
+                name: name.to_string(),
+                last_id,
+                len: Some(file.get_ref().metadata()?.len()),
+            },
+        );
+        checkpoint.save(&args.dir)?;
+    }
+    eprintln!("\r{}: {} messages", progress, exported);
+    Ok(())
+}
+
+/// Every chat with its name, a page of dialogs at a time.
+async fn load_chats(takeout: &Takeout<'_>) -> Result<Vec<(PackedChat, String)>> {
+    let mut chats = Vec::new();
+    let mut seen = HashSet::new();
+    let (mut offset_date, mut offset_id, mut offset_peer) = (0, 0, tl::enums::InputPeer::Empty);
+    loop {
+        let request = tl::functions::messages::GetDialogs {
+            exclude_pinned: false,
+            folder_id: None,
+            offset_date,
+            offset_id,
+            offset_peer: offset_peer.clone(),
+            limit: PAGE_SIZE,
+            hash: 0,
+        };
+        let (dialogs, messages, raw_chats, users) = match takeout.invoke(request).await? {
+            tl::enums::messages::Dialogs::Dialogs(d) => (d.dialogs, d.messages, d.chats, d.users),
+            tl::enums::messages::Dialogs::Slice(d) => (d.dialogs, d.messages, d.chats, d.users),
+            tl::enums::messages::Dialogs::NotModified(_) => break,
+        };
+        let mut peers: HashMap<i64, (PackedChat, String)> = users
+            .into_iter()
+            .filter_map(packed_user)
+            .chain(raw_chats.into_iter().filter_map(packed_chat))
+            .map(|(chat, name)| (chat.id, (chat, name)))
+            .collect();
+        let mut last = None;
+        for dialog in &dialogs {
+            let tl::enums::Dialog::Dialog(dialog) = dialog else {
+                continue;
+            };
+            let id = api::peer_id(&dialog.peer);
+            if let Some((chat, name)) = peers.remove(&id) {
+                last = Some((chat, dialog.top_message));
+                if seen.insert(id) {
+                    chats.push((chat, name));
+                }
+            }
+        }
+        let Some((chat, top_message)) = last else {
+            break;
+        };
+        if dialogs.len() < PAGE_SIZE as usize {
+            break;
+        }
+        // the next page starts past the last message of the last dialog
+        let is_top = |id: i32, peer: &tl::enums::Peer| id == top_message && api::peer_id(peer) == chat.id;
+        offset_date = messages
+            .iter()
+            .find_map(|message| match message {
+                tl::enums::Message::Message(m) if is_top(m.id, &m.peer_id) => Some(m.date),
+                tl::enums::Message::Service(m) if is_top(m.id, &m.peer_id) => Some(m.date),
+                _ => None,
+            })
+            .unwrap_or(0);
+        offset_id = top_message;
+        offset_peer = chat.to_input_peer();
+    }
+    Ok(chats)
+}
+
+fn packed_user(user: tl::enums::User) -> Option<(PackedChat, String)> {
+    let tl::enums::User::User(user) = user else {
+        return None;
+    };
+    let chat = PackedChat {
+        ty: if user.bot { PackedType::Bot } else { PackedType::User },
+        id: user.id,
+        access_hash: user.access_hash,
+    };
+    let name = format!("{} {}", user.first_name.unwrap_or_default(), user.last_name.unwrap_or_default());
+    Some((chat, name.trim().to_string()))
+}
+
+fn packed_chat(chat: tl::enums::Chat) -> Option<(PackedChat, String)> {
+    let channel_type = |broadcast| if broadcast { PackedType::Broadcast } else { PackedType::Megagroup };
+    let (ty, id, access_hash, title) = match chat {
+        tl::enums::Chat::Empty(_) => return None,
+        tl::enums::Chat::Chat(chat) => (PackedType::Chat, chat.id, None, chat.title),
+        tl::enums::Chat::Forbidden(chat) => (PackedType::Chat, chat.id, None, chat.title),
+        tl::enums::Chat::Channel(c) => (channel_type(c.broadcast), c.id, c.access_hash, c.title),
+        tl::enums::Chat::ChannelForbidden(c) => (channel_type(c.broadcast), c.id, Some(c.access_hash), c.title),
+    };
+    Some((PackedChat { ty, id, access_hash }, title))
+}
+
+/// Download the photos and files of a page of messages into
+/// `media/<chat id>/`, skipping those an earlier run got.
+async fn download_media(
+    takeout: &Takeout<'_>,
+    dir: &Path,
+    chat: PackedChat,
+    page: &mut [(ExportedMessage, Option<tl::enums::MessageMedia>)],
+) -> Result<()> {
+    let media_dir = dir.join("media").join(chat.id.to_string());
+    for (message, media) in page.iter_mut() {
+        let Some((key, location)) = media.as_ref().and_then(media_file) else {
+            continue;
+        };
+        std::fs::create_dir_all(&media_dir)?;
+        let path = media_dir.join(&key);
+        if !path.exists() {
+            download(takeout, location, &path).await?;
+        }
+        message.file = Some(format!("media/{}/{}", chat.id, key));
+    }
+    Ok(())
+}
+
+/// Name of the photo or file of a message in the export, the same as in
+/// the media cache, and where to download it from. None for other media
+/// and files larger than the export takes.
+fn media_file(media: &tl::enums::MessageMedia) -> Option<(String, tl::enums::InputFileLocation)> {
+    match media {
+        tl::enums::MessageMedia::Photo(tl::types::MessageMediaPhoto {
+            photo: Some(tl::enums::Photo::Photo(photo)),
+            ..
+        }) => {
+            // the largest size, the original
+            let (_, thumb_size) = photo
+                .sizes
+                .iter()
+                .filter_map(|size| match size {
+                    tl::enums::PhotoSize::Size(s) => Some((s.size, &s.r#type)),
+                    tl::enums::PhotoSize::Progressive(s) => Some((*s.sizes.iter().max()?, &s.r#type)),
+                    _ => None,
+                })
+                .max_by_key(|(size, _)| *size)?;
+            let location = tl::types::InputPhotoFileLocation {
+                id: photo.id,
+                access_hash: photo.access_hash,
+                file_reference: photo.file_reference.clone(),
+                thumb_size: thumb_size.clone(),
+            };
+            Some((cache::photo_key(photo.id), tl::enums::InputFileLocation::InputPhotoFileLocation(location)))
+        }
+        tl::enums::MessageMedia::Document(tl::types::MessageMediaDocument {
+            document: Some(tl::enums::Document::Document(document)),
+            ..
+        }) if document.size <= FILE_MAX_SIZE => {
+            let name = document
+                .attributes
+                .iter()
+                .find_map(|attribute| match attribute {
+                    tl::enums::DocumentAttribute::Filename(f) => Some(f.file_name.as_str()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            let location = tl::types::InputDocumentFileLocation {
+                id: document.id,
+                access_hash: document.access_hash,
+                file_reference: document.file_reference.clone(),
+                thumb_size: String::new(),
+            };
+            Some((
+                cache::document_key(document.id, name),
+                tl::enums::InputFileLocation::InputDocumentFileLocation(location),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Download a file a part at a time in the takeout session, into a
+/// `.part` file renamed once it's whole.
+async fn download(takeout: &Takeout<'_>, location: tl::enums::InputFileLocation, path: &Path) -> Result<()> {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    let mut file = BufWriter::new(File::create(&part)?);
+    let mut dc_id = None;
+    let mut offset = 0;
+    loop {
+        let request = tl::functions::upload::GetFile {
+            precise: false,
+            cdn_supported: false,
+            location: location.clone(),
+            offset,
+            limit: PART_SIZE,
+        };
+        let bytes = match takeout.invoke_in(request, &mut dc_id).await? {
+            tl::enums::upload::File::File(file) => file.bytes,
+            // only sent to clients which ask for it
+            tl::enums::upload::File::CdnRedirect(_) => bail!("Telegram redirected a download to a CDN"),
+        };
+        file.write_all(&bytes)?;
+        if bytes.len() < PART_SIZE as usize {
+            break;
+        }
+        offset += PART_SIZE as i64;
+    }
+    file.into_inner().map_err(|e| e.into_error())?;
+    std::fs::rename(&part, path)?;
+    Ok(())
+}
+
+/// Requests made inside a takeout session.
+struct Takeout<'a> {
+    client: &'a Client,
+    id: i64,
+}
+
+impl Takeout<'_> {
+    /// Invoke a request in the session, waiting out the rate limits
+    /// Telegram asks for.
+    async fn invoke<R: RemoteCall>(&self, query: R) -> Result<R::Return> {
+        self.invoke_in(query, &mut None).await
+    }
+
+    /// Invoke a request in the session in a data center, the home one if
+    /// none. Files kept in another one are asked for there, which `dc_id`
+    /// remembers for the next parts.
+    async fn invoke_in<R: RemoteCall>(&self, query: R, dc_id: &mut Option<i32>) -> Result<R::Return> {
+        let request = tl::functions::InvokeWithTakeout {
+            takeout_id: self.id,
+            query,
+        };
+        loop {
+            let result = match *dc_id {
+                Some(dc_id) => self.client.invoke_in_dc(&request, dc_id).await,
+                None => self.client.invoke(&request).await,
+            };
+            match result {
+                Ok(result) => return Ok(result),
+                Err(InvocationError::Rpc(rpc)) if rpc.name == "FILE_MIGRATE" && rpc.value.is_some() => {
+                    *dc_id = rpc.value.map(|dc| dc as i32);
+                }
+                Err(e) => match Tg9Error::from(e) {
+                    Tg9Error::FloodWait(wait) => {
+                        eprint!(" (waiting {}s for the rate limit)", wait.as_secs());
+                        tokio::time::sleep(wait).await;
+                    }
+                    e => return Err(e.into()),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::api::ApiEvent;
+use crate::events::EventSender;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+pub type TransferId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+/// Upload or download in flight, as tracked by the app.
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub id: TransferId,
+    pub direction: Direction,
+    pub name: String,
+    pub done: u64,
+    pub total: u64,
+    /// Destination of a download
+    pub path: Option<PathBuf>,
+}
+
+impl Transfer {
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.done as f64 / self.total as f64).min(1.0)
+        }
+    }
+}
+
+/// Reports progress of a transfer to the app, at most once per percent.
+pub struct Progress {
+    id: TransferId,
+    done: u64,
+    total: u64,
+    percent: u64,
+    tx: EventSender,
+}
+
+impl Progress {
+    pub fn new(id: TransferId, total: u64, tx: EventSender) -> Self {
+        Progress {
+            id,
+            done: 0,
+            total,
+            percent: 0,
+            tx,
+        }
+    }
+
+    pub fn add(&mut self, bytes: u64) {
+        self.done += bytes;
+        let percent = (self.done * 100).checked_div(self.total).unwrap_or(0);
+        if percent != self.percent {
+            self.percent = percent;
+            let _ = self.tx.send(ApiEvent::TransferProgress {
+                id: self.id,
+                done: self.done,
+            });
+        }
+    }
+}
+
+/// Reader that reports how much of the inner reader was consumed.
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a mut Progress,
+}
+
+impl<'a, R> ProgressReader<'a, R> {
+    pub fn new(inner: R, progress: &'a mut Progress) -> Self {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            let read = buf.filled().len() - before;
+            self.progress.add(read as u64);
+        }
+        poll
+    }
+}
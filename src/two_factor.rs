@@ -0,0 +1,90 @@
+use crate::error::{Result, Tg9Error};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use grammers_client::grammers_tl_types as tl;
+use grammers_crypto::two_factor_auth::calculate_2fa;
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256, Sha512};
+
+/// Bytes of the modulus, and of the password hash sent to Telegram
+const HASH_LEN: usize = 256;
+
+type Algo = tl::types::PasswordKdfAlgoSha256Sha256Pbkdf2Hmacsha512iter100000Sha256ModPow;
+
+fn supported(algo: Option<&tl::enums::PasswordKdfAlgo>) -> Result<&Algo> {
+    match algo {
+        Some(tl::enums::PasswordKdfAlgo::Sha256Sha256Pbkdf2Hmacsha512iter100000Sha256ModPow(algo)) => Ok(algo),
+        _ => Err(Tg9Error::Other(
+            "the password uses an algorithm tg9 doesn't know, update tg9".into(),
+        )),
+    }
+}
+
+/// Proof of the current password for changing the 2FA settings, or none
+/// if there's no password yet.
+pub fn check(info: &tl::types::account::Password, password: Option<&str>) -> Result<tl::enums::InputCheckPasswordSrp> {
+    if !info.has_password {
+        return Ok(tl::enums::InputCheckPasswordSrp::InputCheckPasswordEmpty);
+    }
+    let Some(password) = password else {
+        return Err(Tg9Error::Other("the current password is needed".into()));
+    };
+    let algo = supported(info.current_algo.as_ref())?;
+    let (Some(srp_b), Some(srp_id)) = (&info.srp_b, info.srp_id) else {
+        return Err(Tg9Error::Other("Telegram didn't send what's needed to check the password".into()));
+    };
+    let (m1, g_a) = calculate_2fa(
+        &algo.salt1,
+        &algo.salt2,
+        &algo.g,
+        &algo.p,
+        srp_b.clone(),
+        info.secure_random.clone(),
+        password,
+    );
+    Ok(tl::types::InputCheckPasswordSrp {
+        srp_id,
+        a: g_a.to_vec(),
+        m1: m1.to_vec(),
+    }
+    .into())
+}
+
+/// Algorithm and hash of a new password, with the salt Telegram suggests
+/// made longer with random bytes as the protocol asks.
+pub fn new_password(info: &tl::types::account::Password, password: &str) -> Result<(tl::enums::PasswordKdfAlgo, Vec<u8>)> {
+    let mut algo = supported(Some(&info.new_algo))?.clone();
+    let mut random = [0; 32];
+    OsRng.fill_bytes(&mut random);
+    algo.salt1.extend_from_slice(&random);
+
+    let x = BigUint::from_bytes_be(&ph2(password.as_bytes(), &algo.salt1, &algo.salt2));
+    let g = BigUint::from(algo.g as u32);
+    let p = BigUint::from_bytes_be(&algo.p);
+    let hash = g.modpow(&x, &p).to_bytes_be();
+    // left padded to the length of the modulus
+    let mut padded = vec![0; HASH_LEN.saturating_sub(hash.len())];
+    padded.extend(hash);
+    Ok((
+        tl::enums::PasswordKdfAlgo::Sha256Sha256Pbkdf2Hmacsha512iter100000Sha256ModPow(algo),
+        padded,
+    ))
+}
+
+/// `SH(data, salt) = SHA256(salt | data | salt)`
+fn sh(data: &[u8], salt: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update(salt)
+        .chain_update(data)
+        .chain_update(salt)
+        .finalize()
+        .to_vec()
+}
+
+/// The password stretched as Telegram's SRP expects.
+fn ph2(password: &[u8], salt1: &[u8], salt2: &[u8]) -> Vec<u8> {
+    let ph1 = sh(&sh(password, salt1), salt2);
+    let mut stretched = [0; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(&ph1, salt1, 100_000, &mut stretched);
+    sh(&stretched, salt2)
+}
@@ -0,0 +1,874 @@
+use crate::app::{App, ChatState, Focus, Mode};
+use crate::bidi;
+use crate::cache::{self, CacheKind};
+use crate::diff;
+use crate::folders::Counts;
+use crate::metrics;
+use crate::popup::{Info, Pager, Picker, Popup, QuoteSelection};
+use crate::render;
+use crate::stats::Stats;
+use crate::transfer::Direction as TransferDirection;
+use grammers_client::types::{Media, Message};
+use ratatui::{prelude::*, widgets::*};
+use std::cmp;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Queued messages shown under a chat, the older ones are left out
+const OUTBOX_LINES_MAX: usize = 3;
+
+/// Lines of the draft shown at once, longer ones scroll with the cursor
+const COMPOSE_LINES_MAX: usize = 8;
+
+/// Kinds of API jobs whose latency the debug overlay shows, the faster
+/// ones are left out
+const JOB_LINES_MAX: usize = 8;
+
+pub fn ui(frame: &mut Frame, app: &mut App) {
+    let area = frame.size();
+
+    // TODO: scroll and stuff
+    //
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Length(app.config.summary_bar as u16),
+            Constraint::Min(0),
+            Constraint::Max(3),
+        ])
+        .split(area);
+
+    if app.config.summary_bar {
+        draw_summary(frame, app, layout[0]);
+    }
+
+    let view_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Percentage(app.dialogs_width),
+            Constraint::Percentage(100 - app.dialogs_width),
+        ])
+        .split(layout[1]);
+
+    let visible = app.visible_dialogs();
+    let dialogs_widget = List::new(
+        visible
+            .iter()
+            .map(|&i| (i, &app.chat_states[i]))
+            .map(|(i, c)| {
+                let marker = if app.dialog_idx == Some(i) {
+                    "*"
+                } else if app.split.is_some_and(|s| s.dialog_idx == i) {
+                    "+"
+                } else {
+                    " "
+                };
+                let mut line = vec![Span::raw(marker)];
+                if c.new_story {
+                    line.push(Span::styled("◉", Style::default().fg(Color::Magenta)));
+                }
+                let preview = c.dialog.last_message.as_ref().map(|m| m.text()).unwrap_or("");
+                let preview = if app.redact {
+                    render::redact_str(preview)
+                } else if app.config.bidi {
+                    bidi::visual(preview, None).into_owned()
+                } else {
+                    preview.to_string()
+                };
+                line.push(Span::raw("["));
+                line.push(chat_name(app, c));
+                line.push(Span::raw("]"));
+                if c.unread > 0 {
+                    line.push(Span::styled(format!(" ({})", c.unread), Style::default().fg(Color::Yellow)));
+                } else if c.marked_unread {
+                    line.push(Span::styled(" (•)", Style::default().fg(Color::Yellow)));
+                }
+                if c.mentions > 0 {
+                    line.push(Span::styled(format!(" @{}", c.mentions), Style::default().fg(Color::Red)));
+                }
+                line.push(Span::raw(format!(": {}", preview)));
+                Line::from(line)
+            }),
+    )
+    .block(Block::default().borders(Borders::ALL).title(if app.unread_only { "Unread" } else { "" }));
+
+    let stories: Vec<_> = app
+        .chat_states
+        .iter()
+        .filter(|c| c.new_story)
+        .map(|c| c.dialog.chat().name())
+        .collect();
+    let dialogs_area = if stories.is_empty() {
+        view_layout[0]
+    } else {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(3), Constraint::Min(0)])
+            .split(view_layout[0]);
+        let strip = Paragraph::new(Line::from(vec![
+            Span::styled("◉ ", Style::default().fg(Color::Magenta)),
+            Span::raw(stories.join(", ")),
+        ]))
+        .block(Block::default().borders(Borders::ALL).title("Stories"));
+        frame.render_widget(strip, layout[0]);
+        layout[1]
+    };
+
+    if visible.is_empty() {
+        let placeholder = if app.unread_only && !app.chat_states.is_empty() {
+            "no unread dialogs"
+        } else if app.connected {
+            "loading dialogs…"
+        } else {
+            "connecting…"
+        };
+        let block = Block::default().borders(Borders::ALL);
+        let inner = block.inner(dialogs_area);
+        frame.render_widget(block, dialogs_area);
+        frame.render_widget(
+            Paragraph::new(placeholder).style(Style::default().fg(Color::DarkGray)),
+            inner,
+        );
+    } else {
+        frame.render_widget(dialogs_widget, dialogs_area);
+    }
+
+    let chat_area = if app.tabs.is_empty() {
+        view_layout[1]
+    } else {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1), Constraint::Min(0)])
+            .split(view_layout[1]);
+        draw_tabs(frame, app, layout[0]);
+        layout[1]
+    };
+    match app.split {
+        None => draw_chat(frame, app, app.current_chat(), true, chat_area),
+        Some(split) => {
+            let windows = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(chat_area);
+            let (current, other) = if split.current_right {
+                (windows[1], windows[0])
+            } else {
+                (windows[0], windows[1])
+            };
+            draw_chat(frame, app, app.current_chat(), true, current);
+            draw_chat(frame, app, app.chat_states.get(split.dialog_idx), false, other);
+        }
+    }
+    draw_status(frame, app, layout[2]);
+
+    if let Some(Popup::Confirm(confirm)) = &app.popup {
+        let width = confirm.prompt.chars().count() as u16 + 4;
+        let area = Rect {
+            x: area.width.saturating_sub(width) / 2,
+            y: area.height.saturating_sub(4) / 2,
+            width: width.min(area.width),
+            height: 4.min(area.height),
+        };
+        frame.render_widget(Clear, area);
+        let text = vec![Line::from(confirm.prompt.as_str()), Line::from("y/n")];
+        frame.render_widget(
+            Paragraph::new(text)
+                .alignment(Alignment::Center)
+                .block(Block::default().borders(Borders::ALL)),
+            area,
+        );
+    } else if let Some(popup @ (Popup::Passphrase(_) | Popup::PasswordForm(_))) = &app.popup {
+        let area = Rect {
+            x: area.width.saturating_sub(40) / 2,
+            y: area.height.saturating_sub(3) / 2,
+            width: 40.min(area.width),
+            height: 3.min(area.height),
+        };
+        frame.render_widget(Clear, area);
+        match popup {
+            Popup::Passphrase(input) => draw_secret(frame, "Session passphrase", &input.text, true, area),
+            Popup::PasswordForm(form) => {
+                let (prompt, masked) = form.field().unwrap_or(("", false));
+                draw_secret(frame, prompt, &form.input.text, masked, area);
+            }
+            _ => {}
+        }
+    } else if let Some(popup) = &app.popup {
+        let area = centered_rect(60, 60, area);
+        frame.render_widget(Clear, area);
+        match popup {
+            Popup::ShareContact(picker) => draw_picker(frame, picker, false, area),
+            Popup::Comments(picker) => draw_picker(frame, picker, app.redact, area),
+            Popup::AdminLog(picker) => draw_picker(frame, picker, app.redact, area),
+            Popup::Mention(picker) => draw_picker(frame, picker, false, area),
+            Popup::Emoji(picker) => draw_picker(frame, picker, false, area),
+            Popup::Privacy(picker) => draw_picker(frame, picker, false, area),
+            Popup::Password(picker) => draw_picker(frame, picker, false, area),
+            Popup::Sessions(picker) => draw_picker(frame, picker, app.redact, area),
+            Popup::JoinRequests(requests) => draw_picker(frame, &requests.picker, app.redact, area),
+            Popup::Forward(forward) => draw_picker(frame, &forward.picker, app.redact, area),
+            Popup::Switch(picker) => draw_picker(frame, picker, app.redact, area),
+            Popup::Quote(selection) => draw_quote(frame, selection, app.redact, area),
+            Popup::Info(info) => draw_info(frame, info, app.redact, area),
+            Popup::Help(pager) => draw_pager(frame, pager, area),
+            Popup::Stats(stats) => draw_stats(frame, stats, app.redact, area),
+            Popup::Confirm(_) | Popup::Passphrase(_) | Popup::PasswordForm(_) => {}
+        }
+    }
+    if app.show_metrics {
+        draw_metrics(frame, app, area);
+    }
+}
+
+/// Debug overlay in the top right corner: how fast frames are drawn, what's
+/// waiting in the queues, how much is kept in memory and how long API jobs
+/// take.
+fn draw_metrics(frame: &mut Frame, app: &App, area: Rect) {
+    let metrics = &app.metrics;
+    let (mean, max) = metrics.frame_times();
+    let messages: usize = app.chat_states.iter().map(|c| c.messages.len()).sum();
+    let size = |bytes: Option<u64>| bytes.map_or_else(|| "?".to_string(), render::human_size);
+    let mut lines = vec![
+        format!("{} fps, frame {:.1?} mean, {:.1?} max", metrics.fps(), mean, max),
+        format!("{} events, {} jobs queued", metrics.events_queued, metrics::jobs_queued()),
+        format!("{} transfers, {} messages to send", app.transfers.len(), app.outbox.messages.len()),
+        format!("{} messages loaded in {} chats", messages, app.chat_states.len()),
+        format!("{} peers known", app.peers.len()),
+        format!("media cache {}", size(metrics.media_cache)),
+        format!("memory {}", size(metrics::resident_memory())),
+    ];
+    let jobs = metrics::job_stats();
+    if !jobs.is_empty() {
+        lines.push("API jobs, slowest first:".into());
+    }
+    for stats in jobs.iter().take(JOB_LINES_MAX) {
+        let mut line = format!("{} {}× p50 {:.0?} p95 {:.0?}", stats.job, stats.runs, stats.p50, stats.p95);
+        if stats.failed > 0 {
+            line.push_str(&format!(", {} failed", stats.failed));
+        }
+        lines.push(line);
+    }
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16 + 2;
+    let height = lines.len() as u16 + 2;
+    let area = Rect {
+        x: area.width.saturating_sub(width),
+        y: 0,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    };
+    frame.render_widget(Clear, area);
+    let lines: Vec<_> = lines.into_iter().map(Line::from).collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Metrics")),
+        area,
+    );
+}
+
+/// Name of a chat with the accent color and marker configured for it.
+fn chat_name(app: &App, chat_state: &ChatState) -> Span<'static> {
+    let chat = chat_state.dialog.chat();
+    let chat_config = app.config.chat(chat.id());
+    let name = if app.config.bidi {
+        bidi::visual(chat.name(), None)
+    } else {
+        chat.name().into()
+    };
+    let name = match chat_config.and_then(|c| c.marker.as_ref()) {
+        Some(marker) => format!("{} {}", marker, name),
+        None => name.into_owned(),
+    };
+    match chat_config.and_then(|c| c.color) {
+        Some(color) => Span::styled(name, Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        None => Span::raw(name),
+    }
+}
+
+/// Tabline of the chats in the working set.
+fn draw_tabs(frame: &mut Frame, app: &App, area: Rect) {
+    let titles: Vec<_> = app
+        .tabs
+        .iter()
+        .map(|&chat| {
+            app.chat_states
+                .iter()
+                .find(|c| c.chat == chat)
+                .map(|c| render::truncate(c.dialog.chat().name(), 20))
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut tabs = Tabs::new(titles).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    if let Some(idx) = app.current_tab() {
+        tabs = tabs.select(idx);
+    }
+    frame.render_widget(tabs, area);
+}
+
+/// Unread messages and mentions in all chats, then in each chat folder.
+fn draw_summary(frame: &mut Frame, app: &App, area: Rect) {
+    let mut spans = badge("All", Counts::of(&app.chat_states));
+    for folder in &app.folders {
+        let counts = Counts::of(app.chat_states.iter().filter(|c| folder.contains(c)));
+        spans.push(Span::styled(" │ ", Style::default().fg(Color::DarkGray)));
+        spans.extend(badge(&folder.title, counts));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// A title with its unread messages, `•` if it only has chats marked as
+/// unread, and its mentions. Dimmed if there's nothing unread.
+fn badge(title: &str, counts: Counts) -> Vec<Span<'static>> {
+    if counts.chats == 0 {
+        return vec![Span::styled(title.to_string(), Style::default().fg(Color::DarkGray))];
+    }
+    let unread = if counts.unread > 0 { format!(" {}", counts.unread) } else { " •".into() };
+    let mut spans = vec![Span::raw(title.to_string()), Span::styled(unread, Style::default().fg(Color::Yellow))];
+    if counts.mentions > 0 {
+        spans.push(Span::styled(format!(" @{}", counts.mentions), Style::default().fg(Color::Red)));
+    }
+    spans
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+fn draw_picker<T>(frame: &mut Frame, picker: &Picker<T>, redact: bool, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(picker.title.as_str());
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    frame.render_widget(Paragraph::new(format!("> {}", picker.filter.text)), layout[0]);
+
+    if picker.loading {
+        frame.render_widget(Paragraph::new("loading…"), layout[1]);
+        return;
+    }
+    let items: Vec<_> = picker
+        .matches()
+        .into_iter()
+        .map(|i| {
+            let label = &picker.items[i].0;
+            ListItem::new(if redact { render::redact_str(label) } else { label.clone() })
+        })
+        .collect();
+    let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(picker.selected));
+    frame.render_stateful_widget(list, layout[1], &mut state);
+}
+
+fn draw_info(frame: &mut Frame, info: &Info, redact: bool, area: Rect) {
+    let lines: Vec<_> = info
+        .fields
+        .iter()
+        .map(|(name, value)| {
+            let value = if redact { render::redact_str(value) } else { value.clone() };
+            Line::from(vec![
+                Span::styled(format!("{}: ", name), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(info.title.as_str()));
+    frame.render_widget(paragraph, area);
+}
+
+/// Rows of a pager with their terms in bold, scrolled as far as asked.
+fn draw_pager(frame: &mut Frame, pager: &Pager, area: Rect) {
+    let lines: Vec<_> = pager
+        .rows
+        .iter()
+        .skip(pager.scroll)
+        .map(|(term, about)| {
+            Line::from(vec![
+                Span::styled(term.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(format!("  {}", about), Style::default().fg(Color::Gray)),
+            ])
+        })
+        .collect();
+    let title = format!("{} {}/{}", pager.title, (pager.scroll + 1).min(pager.rows.len()), pager.rows.len());
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(paragraph, area);
+}
+
+/// Bar charts of the messages by sender, hour of the day and kind.
+fn draw_stats(frame: &mut Frame, stats: &Stats, redact: bool, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(stats.title.as_str());
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if stats.messages == 0 {
+        frame.render_widget(Paragraph::new("no messages loaded"), inner);
+        return;
+    }
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(stats.senders.len() as u16 + 2),
+            Constraint::Min(6),
+            Constraint::Length(stats.media.len() as u16 + 2),
+        ])
+        .split(inner);
+
+    let senders: Vec<_> = stats
+        .senders
+        .iter()
+        .map(|(name, count)| {
+            let name = if redact { render::redact_str(name) } else { name.clone() };
+            Bar::default().label(name.into()).value(*count)
+        })
+        .collect();
+    let senders = BarChart::default()
+        .block(Block::default().title("Senders"))
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .data(BarGroup::default().bars(&senders));
+    frame.render_widget(senders, layout[0]);
+
+    let hours: Vec<_> = stats
+        .hours
+        .iter()
+        .enumerate()
+        .map(|(hour, count)| Bar::default().label(format!("{:02}", hour).into()).value(*count))
+        .collect();
+    let hours = BarChart::default()
+        .block(Block::default().title("Hours"))
+        .bar_width(2)
+        .bar_gap(1)
+        .data(BarGroup::default().bars(&hours));
+    frame.render_widget(hours, layout[1]);
+
+    let media: Vec<_> = stats
+        .media
+        .iter()
+        .map(|(kind, count)| Bar::default().label((*kind).into()).value(*count))
+        .collect();
+    let media = BarChart::default()
+        .block(Block::default().title("Kinds"))
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .data(BarGroup::default().bars(&media));
+    frame.render_widget(media, layout[2]);
+}
+
+/// Text of the message being quoted from, with the selection highlighted.
+fn draw_quote(frame: &mut Frame, selection: &QuoteSelection, redact: bool, area: Rect) {
+    let range = selection.range();
+    let mut start = 0;
+    let lines: Vec<_> = selection
+        .message
+        .text()
+        .split('\n')
+        .map(|line| {
+            let end = start + line.len();
+            let selected = range.start.clamp(start, end) - start..range.end.clamp(start, end) - start;
+            start = end + 1;
+            let ranges = if selected.is_empty() { vec![] } else { vec![selected] };
+            render::highlighted(line, &ranges, Style::default().add_modifier(Modifier::REVERSED))
+        })
+        .collect();
+    let mut text = Text::from(lines);
+    if redact {
+        text = render::redact(text);
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Quote: h/l w/b 0/$ to move, o for the other end, Enter to reply");
+    frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: false }).block(block), area);
+}
+
+/// One-line prompt for a passphrase or a 2FA field, passwords masked.
+fn draw_secret(frame: &mut Frame, title: &str, text: &str, masked: bool, area: Rect) {
+    let text = if masked {
+        "•".repeat(text.chars().count())
+    } else {
+        text.to_string()
+    };
+    let paragraph = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(paragraph, area);
+}
+
+/// Messages of a chat, those queued to send, its attachment tray and
+/// compose box. Only the current window is highlighted as focused.
+fn draw_chat(frame: &mut Frame, app: &App, chat_state: Option<&ChatState>, current: bool, area: Rect) {
+    let Some(chat_state) = chat_state else {
+        frame.render_widget(Block::default().borders(Borders::ALL), area);
+        return;
+    };
+
+    let attachments = &chat_state.draft.attachments;
+    let tray_height = if attachments.is_empty() {
+        0
+    } else {
+        attachments.len() as u16 + 2
+    };
+    let queued = app.outbox.of_chat(chat_state.chat);
+    let outbox_height = if queued.is_empty() {
+        0
+    } else {
+        cmp::min(queued.len(), OUTBOX_LINES_MAX) as u16 + 2
+    };
+    let input = &chat_state.draft.input;
+    let compose_lines = input.text.split('\n').count();
+    let compose_height = cmp::min(compose_lines, COMPOSE_LINES_MAX) as u16 + 2;
+    let chat_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Min(0),
+            Constraint::Length(outbox_height),
+            Constraint::Length(tray_height),
+            Constraint::Length(compose_height),
+        ])
+        .split(area);
+
+    let messages_border = if current && app.focus == Focus::Chat {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let visual = if current && app.mode == Mode::Visual {
+        chat_state.visual_selection()
+    } else {
+        Vec::new()
+    };
+    let shown = chat_state.shown_messages();
+    let messages_widget = List::new(shown.iter().map(|&i| {
+        let message = &chat_state.messages[i];
+        let mut text = render::message(message);
+        if let Some(path) = patch_file(app, message) {
+            text.extend(diff::preview(&path).unwrap_or_default());
+        }
+        if let Some(id) = message.reply_to_message_id().filter(|_| message.action().is_none()) {
+            text.lines.insert(0, render::reply_preview(chat_state.replied(id)));
+        }
+        if let Some(header) = render::forward_header(message, &app.peers) {
+            text.lines.insert(0, header);
+        }
+        if let Some(views) = render::views(chat_state.views(message)) {
+            text.extend([views]);
+        }
+        if let Some(expiry) = render::expiry(message) {
+            text.extend([expiry]);
+        }
+        if chat_state.selected == Some(i) {
+            if let Some(date) = message.edit_date() {
+                text.extend([render::edited(date)]);
+            }
+        }
+        if let Some(thread) = chat_state.threads.get(&message.id()) {
+            text.extend(render::thread(thread.as_deref()));
+        }
+        if let Some(translation) = chat_state.translations.get(&message.id()) {
+            text.extend(render::translation(translation));
+        }
+        if app.config.bidi {
+            text = bidi::text(text);
+        }
+        if app.redact {
+            text = render::redact(text);
+        }
+        let item = ListItem::new(text);
+        if visual.contains(&message.id()) {
+            item.style(Style::default().bg(Color::DarkGray))
+        } else {
+            item
+        }
+    }));
+    let mut messages_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(messages_border)
+        .title(chat_name(app, chat_state));
+    if chat_state.join_requests > 0 {
+        messages_block = messages_block.title(Span::styled(
+            format!("{} asking to join (:requests)", chat_state.join_requests),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if let Some(period) = chat_state.ttl_period {
+        messages_block = messages_block.title(Span::styled(
+            format!("⏲ {}", render::auto_delete(period)),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if let Some(filter) = &chat_state.filter {
+        messages_block = messages_block.title(
+            block::Title::from(Span::styled(
+                format!("/{}: {} of {} (:filter to clear)", filter.pattern, shown.len(), chat_state.messages.len()),
+                Style::default().fg(Color::Yellow),
+            ))
+            .alignment(Alignment::Right),
+        );
+    }
+    let messages_widget = messages_widget
+        .direction(ListDirection::BottomToTop)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(messages_block);
+    let selected = chat_state.selected.and_then(|i| shown.iter().position(|&s| s == i));
+    let mut messages_state = ListState::default().with_selected(selected);
+    let messages_area = match &chat_state.unavailable {
+        Some(reason) => {
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Length(1), Constraint::Min(0)])
+                .split(chat_layout[0]);
+            let banner = Paragraph::new(format!("⚠ {}", reason))
+                .style(Style::default().fg(Color::White).bg(Color::Red));
+            frame.render_widget(banner, layout[0]);
+            layout[1]
+        }
+        None => chat_layout[0],
+    };
+    let messages_area = match &chat_state.pinned {
+        Some(pinned) => {
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Length(1), Constraint::Min(0)])
+                .split(messages_area);
+            let summary = render::summary(pinned);
+            let summary = if app.redact { render::redact_str(&summary) } else { summary };
+            let bar = Paragraph::new(format!("📌 {}", summary)).style(Style::default().bg(Color::DarkGray));
+            frame.render_widget(bar, layout[0]);
+            layout[1]
+        }
+        None => messages_area,
+    };
+    frame.render_stateful_widget(messages_widget, messages_area, &mut messages_state);
+
+    if !queued.is_empty() {
+        // the newest ones, right below the messages
+        let skip = queued.len().saturating_sub(OUTBOX_LINES_MAX);
+        let outbox_widget = List::new(queued.iter().enumerate().skip(skip).map(|(i, &idx)| {
+            let queued = &app.outbox.messages[idx];
+            let (marker, color) = match (queued.sending, queued.retry) {
+                (Some(_), _) => ("sending", Color::Yellow),
+                (None, true) => ("queued", Color::DarkGray),
+                (None, false) => ("failed", Color::Red),
+            };
+            let message = &queued.message;
+            let text = match (message.text.as_str(), message.attachments.len()) {
+                ("", 1) => message.attachments[0].name(),
+                ("", n) => format!("{} files", n),
+                (text, _) => text.to_string(),
+            };
+            Line::from(vec![
+                Span::styled(format!("{}. ⏳ {} ", i + 1, marker), Style::default().fg(color)),
+                Span::raw(render::truncate(&text, 60)),
+                Span::styled(format!(" — {}", queued.error), Style::default().fg(Color::DarkGray)),
+            ])
+        }))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Outbox (:retry, :discard)"),
+        );
+        frame.render_widget(outbox_widget, chat_layout[1]);
+    }
+
+    if !attachments.is_empty() {
+        let tray_widget = List::new(attachments.iter().enumerate().map(|(i, a)| {
+            let mut line = vec![Span::raw(format!("{}. {}", i + 1, a.name()))];
+            if !a.caption.is_empty() {
+                line.push(Span::styled(
+                    format!(" — {}", a.caption),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            Line::from(line)
+        }))
+        .block(Block::default().borders(Borders::ALL).title("Attachments"));
+        frame.render_widget(tray_widget, chat_layout[2]);
+    }
+
+    let compose_style = if current && app.mode == Mode::Insert {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let mut compose_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(compose_style);
+    if chat_state.unavailable.is_some() {
+        compose_block = compose_block
+            .title("can't write to this chat")
+            .border_style(Style::default().fg(Color::DarkGray));
+    } else if !chat_state.draft.link_preview {
+        compose_block = compose_block.title("no link preview");
+    }
+    if let Some(reply) = &chat_state.draft.reply {
+        let to = match chat_state.replied(reply.message_id).flatten().and_then(|m| m.sender()) {
+            Some(sender) => format!("↩ {}", sender.name()),
+            None => "↩ reply".into(),
+        };
+        let title = match &reply.quote {
+            Some(quote) => format!("{}: “{}” (^x cancel)", to, render::truncate(&quote.text, 30)),
+            None => format!("{} (^x cancel)", to),
+        };
+        compose_block = compose_block.title(Span::styled(title, Style::default().fg(Color::Green)));
+    }
+    let post = chat_state.draft.post.summary();
+    if !post.is_empty() {
+        compose_block = compose_block.title(Span::styled(post, Style::default().fg(Color::Magenta)));
+    }
+    if let Some(wait) = chat_state.slow_mode_wait() {
+        compose_block = compose_block.title(
+            block::Title::from(Span::styled(
+                format!("slow mode {}", render::human_duration(wait)),
+                Style::default().fg(Color::Yellow),
+            ))
+            .alignment(Alignment::Right),
+        );
+    }
+    let rtl = chat_state.compose_rtl(&app.config);
+    let reordered = app.config.bidi && (rtl || bidi::has_rtl(&input.text));
+    // mentions can't be found again in reordered text
+    let compose_text = if reordered {
+        let lines = input.text.split('\n').map(|line| Line::from(bidi::visual(line, Some(rtl)).into_owned()));
+        lines.collect::<Vec<_>>()
+    } else {
+        render::split_lines(render::highlighted(
+            &input.text,
+            &chat_state.mention_highlights(),
+            Style::default().fg(Color::Blue),
+        ))
+    };
+    // the line with the cursor, and where it starts in the text
+    let line_start = input.text[..input.cursor].rfind('\n').map_or(0, |i| i + 1);
+    let row = input.text[..input.cursor].matches('\n').count();
+    let scroll = (row + 1).saturating_sub(COMPOSE_LINES_MAX);
+    let compose_inner = compose_block.inner(chat_layout[3]);
+    let compose_widget = Paragraph::new(compose_text)
+        .alignment(if rtl { Alignment::Right } else { Alignment::Left })
+        .scroll((scroll as u16, 0))
+        .block(compose_block);
+    frame.render_widget(compose_widget, chat_layout[3]);
+
+    if current && app.mode == Mode::Insert && app.popup.is_none() && compose_inner.width > 0 {
+        let line = input.text[line_start..].split('\n').next().unwrap_or_default();
+        let column = if reordered {
+            bidi::cursor_column(line, input.cursor - line_start, Some(rtl))
+        } else {
+            Span::raw(&input.text[line_start..input.cursor]).width() as isize
+        };
+        let start = if rtl {
+            compose_inner.width as isize - Span::raw(line).width() as isize
+        } else {
+            0
+        };
+        let x = (start + column).clamp(0, compose_inner.width as isize - 1) as u16;
+        frame.set_cursor(compose_inner.x + x, compose_inner.y + (row - scroll) as u16);
+    }
+}
+
+/// The downloaded patch file of a message, to preview under it.
+fn patch_file(app: &App, message: &Message) -> Option<PathBuf> {
+    let media = message.media()?;
+    let Media::Document(document) = &media else {
+        return None;
+    };
+    if !diff::is_patch(document) {
+        return None;
+    }
+    let path = app.cache.path(CacheKind::File, &cache::media_key(&media)?);
+    path.exists().then_some(path)
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let mut line = match (app.mode, &app.status) {
+        (Mode::Command, _) => Line::from(format!(":{}", app.command.text)),
+        (Mode::Insert, _) => Line::from("-- INSERT --"),
+        (Mode::Visual, _) => {
+            let selected = app.current_chat().map_or(0, |c| c.visual_selection().len());
+            Line::from(format!("-- VISUAL -- {} selected", selected))
+        }
+        (Mode::Normal, Some(status)) => Line::from(status.as_str()),
+        (Mode::Normal, None) if !app.connected => Line::from("connecting…"),
+        (Mode::Normal, None) => Line::from("tg9 v0.1"),
+    };
+    if app.is_read_only() {
+        line.spans.insert(0, Span::styled("READ-ONLY ", Style::default().fg(Color::Cyan)));
+    }
+    if app.redact {
+        line.spans.insert(0, Span::styled("REDACTED ", Style::default().fg(Color::Magenta)));
+    }
+    if let Some(register) = app.macros.recording() {
+        line.spans.insert(0, Span::styled(format!("recording @{} ", register), Style::default().fg(Color::Yellow)));
+    }
+    if let Some(dnd) = app.dnd() {
+        let label = match dnd.remaining() {
+            Some(remaining) => format!("DND {} ", render::human_duration(remaining)),
+            None => "DND ".into(),
+        };
+        line.spans.insert(0, Span::styled(label, Style::default().fg(Color::Red)));
+    }
+    // past the labels before the command line
+    let command_cursor = (app.mode == Mode::Command && app.popup.is_none())
+        .then(|| line.width() - Span::raw(&app.command.text[app.command.cursor..]).width());
+    for status in app.plugin_status() {
+        line.spans.push(Span::styled(format!(" │ {}", status), Style::default().fg(Color::DarkGray)));
+    }
+    let mut block = Block::new().borders(Borders::ALL);
+    if app.flash_until.is_some_and(|until| Instant::now() < until) {
+        block = block.style(Style::default().add_modifier(Modifier::REVERSED));
+    }
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if let Some(x) = command_cursor.filter(|_| inner.width > 0) {
+        frame.set_cursor(inner.x + cmp::min(x as u16, inner.width - 1), inner.y);
+    }
+
+    let Some(transfer) = app.transfers.last() else {
+        frame.render_widget(Paragraph::new(line), inner);
+        return;
+    };
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(40)])
+        .split(inner);
+    frame.render_widget(Paragraph::new(line), layout[0]);
+
+    let arrow = match transfer.direction {
+        TransferDirection::Upload => "↑",
+        TransferDirection::Download => "↓",
+    };
+    let mut label = format!(
+        "{} {} {}%",
+        arrow,
+        render::truncate(&transfer.name, 16),
+        (transfer.ratio() * 100.0) as u64
+    );
+    if app.transfers.len() > 1 {
+        label.push_str(&format!(" +{}", app.transfers.len() - 1));
+    }
+    let gauge = LineGauge::default()
+        .label(label)
+        .gauge_style(Style::default().fg(Color::Yellow))
+        .ratio(transfer.ratio());
+    frame.render_widget(gauge, layout[1]);
+}
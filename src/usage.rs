@@ -0,0 +1,85 @@
+use crate::peers::now;
+use crate::state::State;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+/// Days after which the use of a chat counts half as much
+const HALF_LIFE_DAYS: f64 = 7.0;
+
+/// How often and how lately each chat was used, to rank the chat switcher
+/// like an editor's list of recent buffers. Saved next to the UI state.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatUsage {
+    /// By bare chat id
+    chats: BTreeMap<String, Usage>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Usage {
+    opened: u32,
+    /// Messages I sent
+    sent: u32,
+    /// Unix time it was last opened or sent to
+    used: u64,
+}
+
+impl ChatUsage {
+    fn path() -> Option<PathBuf> {
+        Some(State::path()?.with_file_name("usage.toml"))
+    }
+
+    /// The saved usage, or none if it can't be read.
+    pub fn load() -> ChatUsage {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Written only if a chat was used since the last time.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    fn used(&mut self, id: i64) -> &mut Usage {
+        self.changed = true;
+        let usage = self.chats.entry(id.to_string()).or_default();
+        usage.used = now();
+        usage
+    }
+
+    pub fn opened(&mut self, id: i64) {
+        self.used(id).opened += 1;
+    }
+
+    pub fn sent(&mut self, id: i64) {
+        self.used(id).sent += 1;
+    }
+
+    /// How much the chat is used: the times it was opened or sent to,
+    /// counting less the longer it wasn't.
+    pub fn score(&self, id: i64) -> f64 {
+        let Some(usage) = self.chats.get(&id.to_string()) else {
+            return 0.0;
+        };
+        let days = now().saturating_sub(usage.used) as f64 / (24.0 * 60.0 * 60.0);
+        f64::from(usage.opened + usage.sent) * 0.5f64.powf(days / HALF_LIFE_DAYS)
+    }
+}
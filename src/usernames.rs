@@ -0,0 +1,77 @@
+use crate::peers::{now, StoredChat};
+use crate::state::State;
+use grammers_session::PackedChat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long a resolved username is trusted, it may be given up and taken
+/// by someone else
+const TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Usernames resolved before, so resolving them again doesn't ask
+/// Telegram, which rate limits it hard. Saved next to the UI state.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsernameCache {
+    /// By lowercase username, without the @
+    usernames: BTreeMap<String, CachedChat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedChat {
+    #[serde(flatten)]
+    chat: StoredChat,
+    /// Unix time it was resolved
+    resolved: u64,
+}
+
+impl UsernameCache {
+    fn path() -> Option<PathBuf> {
+        Some(State::path()?.with_file_name("usernames.toml"))
+    }
+
+    /// The saved cache, or an empty one if it can't be read.
+    pub fn load() -> UsernameCache {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text)
+    }
+
+    /// The chat the username was resolved to, unless that's too long ago.
+    pub fn get(&self, username: &str) -> Option<PackedChat> {
+        let cached = self.usernames.get(&username.to_lowercase())?;
+        if now().saturating_sub(cached.resolved) > TTL.as_secs() {
+            return None;
+        }
+        cached.chat.pack()
+    }
+
+    /// Remember what a username resolved to, dropping the expired ones.
+    pub fn insert(&mut self, username: &str, chat: PackedChat) {
+        let now = now();
+        self.usernames
+            .retain(|_, cached| now.saturating_sub(cached.resolved) <= TTL.as_secs());
+        self.usernames.insert(
+            username.to_lowercase(),
+            CachedChat {
+                chat: chat.into(),
+                resolved: now,
+            },
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use crate::external;
+use grammers_client::grammers_tl_types as tl;
+use grammers_client::types::media::Document;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Command remuxing video notes on export, the input and output are
+/// appended to its options
+const FFMPEG: &str = "ffmpeg";
+
+/// Whether a document is a video note, the round videos recorded in the
+/// chat.
+pub fn is_video_note(document: &Document) -> bool {
+    let Some(tl::enums::Document::Document(raw)) = &document.raw.document else {
+        return false;
+    };
+    raw.attributes
+        .iter()
+        .any(|attribute| matches!(attribute, tl::enums::DocumentAttribute::Video(video) if video.round_message))
+}
+
+/// Name a video note is saved under, since it has none of its own.
+pub fn file_name(document: &Document) -> String {
+    format!("video-note-{}.mp4", document.id())
+}
+
+/// Length of a video note, like `0:42`.
+pub fn duration(document: &Document) -> String {
+    let secs = document.duration().unwrap_or_default().round() as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Write a downloaded video note to `to` as an MP4 file that plays
+/// anywhere, remuxed with ffmpeg if it's installed or else copied as it
+/// is. Gives whether it was remuxed.
+pub fn export(from: &Path, to: &Path) -> io::Result<bool> {
+    if let Some(dir) = to.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if !external::is_installed(FFMPEG) {
+        std::fs::copy(from, to)?;
+        return Ok(false);
+    }
+    // the moov atom up front, so players start before the whole file is read
+    let output = Command::new(FFMPEG)
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(from)
+        .args(["-c", "copy", "-movflags", "+faststart", "-f", "mp4"])
+        .arg(to)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(format!("ffmpeg exited with {}: {}", output.status, error)));
+    }
+    Ok(true)
+}
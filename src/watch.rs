@@ -0,0 +1,27 @@
+use crate::config::Config;
+use ::notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// Watch the config file, sending on `tx` whenever it changes. The directory
+/// is watched rather than the file, since editors often save by replacing
+/// it. Returns `None` if there's no config directory to watch; the watcher
+/// stops when dropped.
+pub fn config(tx: mpsc::UnboundedSender<()>) -> ::notify::Result<Option<RecommendedWatcher>> {
+    let Some(path) = Config::path() else {
+        return Ok(None);
+    };
+    let Some(dir) = path.parent().filter(|d| d.is_dir()).map(Path::to_path_buf) else {
+        return Ok(None);
+    };
+    let mut watcher = ::notify::recommended_watcher(move |event: ::notify::Result<Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if (event.kind.is_create() || event.kind.is_modify()) && event.paths.contains(&path) {
+            let _ = tx.send(());
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok(Some(watcher))
+}
@@ -0,0 +1,296 @@
+use crate::api;
+use crate::compose::Input;
+use crate::config::Config;
+use crate::credentials::Credentials;
+use crate::secrets::{self, Secret};
+use crate::session;
+use anyhow::{bail, Result};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use futures::StreamExt;
+use grammers_client::{Client, SignInError};
+use ratatui::backend::CrosstermBackend;
+use ratatui::{prelude::*, widgets::*};
+use std::io::{self, Stdout};
+use std::path::Path;
+
+/// Where apps get their API ID and hash
+const APPS_URL: &str = "https://my.telegram.org/apps";
+
+/// Width of the wizard's box, at most
+const WIDTH_MAX: u16 = 72;
+
+/// A question of the wizard.
+struct Prompt<'a> {
+    title: &'a str,
+    /// What it's for and where to find it
+    text: &'a [&'a str],
+    /// Typed in advance, to confirm or change
+    default: &'a str,
+    /// Shown as dots, like passwords
+    masked: bool,
+}
+
+/// Setup on the first launch, before the UI: the API credentials unless
+/// they're built in, where files go unless there's a config already, and
+/// logging in. Gives the logged in client.
+pub async fn run() -> Result<Client> {
+    let mut wizard = Wizard::enter()?;
+    let client = wizard.steps().await;
+    wizard.exit()?;
+    client
+}
+
+struct Wizard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    events: EventStream,
+    /// What's wrong with what was typed last, shown until it's typed again
+    error: Option<String>,
+}
+
+impl Wizard {
+    fn enter() -> Result<Wizard> {
+        enable_raw_mode()?;
+        crossterm::execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Wizard {
+            terminal: Terminal::new(CrosstermBackend::new(io::stdout()))?,
+            events: EventStream::new(),
+            error: None,
+        })
+    }
+
+    fn exit(&mut self) -> Result<()> {
+        crossterm::execute!(io::stdout(), LeaveAlternateScreen)?;
+        disable_raw_mode()?;
+        Ok(())
+    }
+
+    async fn steps(&mut self) -> Result<Client> {
+        if api::api_id().is_err() || api::api_hash().is_err() {
+            self.credentials().await?;
+        }
+        if let Some(path) = Config::path().filter(|path| !path.exists()) {
+            self.config(&path).await?;
+        }
+        self.login().await
+    }
+
+    async fn credentials(&mut self) -> Result<()> {
+        let text = [
+            "tg9 talks to Telegram as an app of your own. Log in at",
+            APPS_URL,
+            "with your phone number, create an app there (any name will do)",
+            "and copy its api_id and api_hash here.",
+        ];
+        let api_id = loop {
+            let prompt = Prompt {
+                title: "API ID",
+                text: &text,
+                default: "",
+                masked: false,
+            };
+            match self.ask(prompt).await?.trim().parse::<i32>() {
+                Ok(api_id) => break api_id,
+                Err(_) => self.error = Some("the API ID is a number, like 12345".into()),
+            }
+        };
+        let api_hash = loop {
+            let prompt = Prompt {
+                title: "API hash",
+                text: &text,
+                default: "",
+                masked: false,
+            };
+            let api_hash = self.ask(prompt).await?.trim().to_string();
+            if api_hash.len() == 32 && api_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                break api_hash;
+            }
+            self.error = Some("the API hash is 32 letters and digits".into());
+        };
+        // the file is only a fallback for the hash, when there's no keyring to keep it in
+        let api_hash = secrets::set(Secret::ApiHash, &api_hash).is_err().then_some(api_hash);
+        let credentials = Credentials {
+            api_id: Some(api_id),
+            api_hash,
+        };
+        credentials.save()?;
+        Ok(())
+    }
+
+    /// Start the config with where files are saved.
+    async fn config(&mut self, path: &Path) -> Result<()> {
+        let default = Config::default().download_dir(0);
+        let saved = format!("It's saved to {} with the other options,", path.display());
+        let prompt = Prompt {
+            title: "Download directory",
+            text: &[
+                "Files saved from messages with `s` go here, `~` is your home directory.",
+                &saved,
+                "see the README for what else can be set there.",
+            ],
+            default: &default,
+            masked: false,
+        };
+        let dir = self.ask(prompt).await?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, format!("download_dir = {}\n", toml::Value::String(dir.trim().into())))?;
+        Ok(())
+    }
+
+    async fn login(&mut self) -> Result<Client> {
+        self.show("Connecting to Telegram…")?;
+        let client = api::connect(session::load(&api::session_path(), None)?).await?;
+        if client.is_authorized().await? {
+            return Ok(client);
+        }
+        let token = loop {
+            let prompt = Prompt {
+                title: "Phone number",
+                text: &["The phone number of your Telegram account, with the country code like +44."],
+                default: "",
+                masked: false,
+            };
+            let phone = self.ask(prompt).await?;
+            self.show("Sending the code…")?;
+            match client.request_login_code(phone.trim()).await {
+                Ok(token) => break token,
+                Err(e) => self.error = Some(format!("failed to send the code: {}", e)),
+            }
+        };
+        let password_token = loop {
+            let prompt = Prompt {
+                title: "Code",
+                text: &["Telegram sent a code to the app on your other devices, or else by SMS."],
+                default: "",
+                masked: false,
+            };
+            let code = self.ask(prompt).await?;
+            self.show("Logging in…")?;
+            match client.sign_in(&token, code.trim()).await {
+                Ok(_) => {
+                    save_session(&client)?;
+                    return Ok(client);
+                }
+                Err(SignInError::PasswordRequired(token)) => break token,
+                Err(SignInError::InvalidCode) => self.error = Some("wrong code, check it and type it again".into()),
+                Err(SignInError::SignUpRequired { .. }) => {
+                    bail!("there's no account with this phone number, sign up in an official app first")
+                }
+                Err(e) => bail!("failed to log in: {}", e),
+            }
+        };
+        let password = match secrets::get(Secret::Password) {
+            Some(password) => password,
+            None => {
+                let hint = password_token.hint().map(|hint| format!("Its hint is: {}", hint));
+                let mut text = vec!["The account has two-step verification, type its password."];
+                text.extend(hint.as_deref());
+                let prompt = Prompt {
+                    title: "Password",
+                    text: &text,
+                    default: "",
+                    masked: true,
+                };
+                self.ask(prompt).await?
+            }
+        };
+        self.show("Checking the password…")?;
+        match client.check_password(password_token, password).await {
+            Ok(_) => {}
+            Err(SignInError::InvalidPassword) => bail!("wrong password, run tg9 again to retry"),
+            Err(e) => bail!("failed to log in: {}", e),
+        }
+        save_session(&client)?;
+        Ok(client)
+    }
+
+    /// Ask until something is typed.
+    async fn ask(&mut self, prompt: Prompt<'_>) -> Result<String> {
+        let mut input = Input::default();
+        prompt.default.chars().for_each(|c| input.insert(c));
+        loop {
+            self.draw(prompt.title, prompt.text, Some((&input, prompt.masked)))?;
+            let Some(event) = self.events.next().await else {
+                bail!("the terminal is gone");
+            };
+            let Event::Key(key) = event? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match (key.modifiers, key.code) {
+                (KeyModifiers::CONTROL, KeyCode::Char('c')) | (_, KeyCode::Esc) => bail!("setup cancelled"),
+                (_, KeyCode::Enter) if !input.text.trim().is_empty() => {
+                    self.error = None;
+                    return Ok(input.text);
+                }
+                (_, KeyCode::Backspace) => input.delete_back(),
+                (_, KeyCode::Left) => input.left(),
+                (_, KeyCode::Right) => input.right(),
+                (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => input.insert(c),
+                _ => {}
+            }
+        }
+    }
+
+    /// Say what's going on while waiting for Telegram.
+    fn show(&mut self, status: &str) -> io::Result<()> {
+        self.draw("tg9", &[status], None)
+    }
+
+    fn draw(&mut self, title: &str, text: &[&str], input: Option<(&Input, bool)>) -> io::Result<()> {
+        let mut lines: Vec<_> = text.iter().map(|&line| Line::from(line)).collect();
+        if let Some((input, masked)) = input {
+            let (before, after) = input.text.split_at(input.cursor);
+            let (before, after) = if masked {
+                ("•".repeat(before.chars().count()), "•".repeat(after.chars().count()))
+            } else {
+                (before.to_string(), after.to_string())
+            };
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                Span::raw("> "),
+                Span::styled(before, Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled("▏", Style::default().fg(Color::DarkGray)),
+                Span::styled(after, Style::default().add_modifier(Modifier::BOLD)),
+            ]));
+        }
+        if let Some(error) = &self.error {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))));
+        }
+        if input.is_some() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                "Enter to go on, Esc to quit",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        self.terminal.draw(|frame| {
+            let screen = frame.size();
+            let width = WIDTH_MAX.min(screen.width);
+            // room for lines wrapping
+            let height = (lines.len() as u16 + 4).min(screen.height);
+            let area = Rect {
+                x: (screen.width - width) / 2,
+                y: (screen.height - height) / 2,
+                width,
+                height,
+            };
+            let block = Block::default().borders(Borders::ALL).title(format!("tg9 setup: {}", title));
+            frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), area);
+        })?;
+        Ok(())
+    }
+}
+
+fn save_session(client: &Client) -> io::Result<()> {
+    let path = api::session_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    client.session().save_to_file(path)
+}